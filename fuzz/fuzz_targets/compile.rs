@@ -0,0 +1,38 @@
+#![no_main]
+
+// Whatever the compiler accepts must load, and must run without panicking.
+use arbitrary::Arbitrary;
+use brvm::compiler::{self, CompileOptions};
+use brvm::vm::VM;
+use libfuzzer_sys::fuzz_target;
+
+#[derive(Debug, Arbitrary)]
+struct Input<'a> {
+    source: &'a str,
+    strict: bool,
+    strict_math: bool,
+    no_optimize: bool,
+    version_4: bool,
+}
+
+fuzz_target!(|input: Input| {
+    let options = CompileOptions {
+        strict: input.strict,
+        strict_math: input.strict_math,
+        no_optimize: input.no_optimize,
+        version: input.version_4.then_some(4),
+        ..Default::default()
+    };
+    let Ok(bytecode) = compiler::compile_source(input.source, "fuzz.brainrot", &options) else {
+        return;
+    };
+    let mut vm = VM::builder()
+        .seed(0)
+        .max_instructions(10_000)
+        .max_string_bytes(1 << 20)
+        .stdin(std::io::empty())
+        .stdout(std::io::sink())
+        .build();
+    vm.load(&bytecode).expect("compiled bytecode should load");
+    let _ = vm.run();
+});
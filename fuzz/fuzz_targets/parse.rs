@@ -0,0 +1,10 @@
+#![no_main]
+
+use brvm::{lexer, parser};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|source: &str| {
+    if let Ok(tokens) = lexer::tokenize_spanned(source, "fuzz.brainrot") {
+        let _ = parser::parse(tokens, "fuzz.brainrot");
+    }
+});
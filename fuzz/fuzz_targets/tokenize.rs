@@ -0,0 +1,9 @@
+#![no_main]
+
+use brvm::lexer;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|source: &str| {
+    let _ = lexer::tokenize(source, "fuzz.brainrot");
+    let _ = lexer::tokenize_with_comments(source, "fuzz.brainrot");
+});
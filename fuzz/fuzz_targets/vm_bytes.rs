@@ -0,0 +1,18 @@
+#![no_main]
+
+// Raw bytes straight into the loader; most are rejected there.
+use brvm::vm::VM;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|bytecode: &[u8]| {
+    let mut vm = VM::builder()
+        .seed(0)
+        .max_instructions(10_000)
+        .max_string_bytes(1 << 20)
+        .stdin(std::io::empty())
+        .stdout(std::io::sink())
+        .build();
+    if vm.load(bytecode).is_ok() {
+        let _ = vm.run();
+    }
+});
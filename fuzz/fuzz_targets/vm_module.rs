@@ -0,0 +1,26 @@
+#![no_main]
+
+// A structured module with a header the VM accepts, so the fuzzer spends its
+// time in the interpreter rather than in header checks.
+use brvm::bytecode::{self, Module};
+use brvm::vm::VM;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|module: Module| {
+    let mut module = module;
+    module.version = module
+        .version
+        .clamp(bytecode::MIN_VERSION, bytecode::VERSION);
+    module.flags &= bytecode::KNOWN_FLAGS;
+    module.globals %= bytecode::MAX_GLOBALS + 1;
+    let mut vm = VM::builder()
+        .seed(0)
+        .max_instructions(10_000)
+        .max_string_bytes(1 << 20)
+        .stdin(std::io::empty())
+        .stdout(std::io::sink())
+        .build();
+    if vm.load(&bytecode::encode(&module)).is_ok() {
+        let _ = vm.run();
+    }
+});
@@ -0,0 +1,327 @@
+// `brvm analyze`: reports operations that are certain to fail at runtime
+// whenever they are reached, without running the program.
+//
+// The type checker already rejects mismatched types, and its errors are
+// reported here too. On top of that, this pass follows the values of names
+// through the program: a name holds a known value after it is assigned one,
+// and nothing once a loop, a branch or a call could have changed it. Known
+// values are combined with the VM's own operations, so a failure among them
+// is the failure the VM would report. Zero divisors and TRANSFORM of text
+// that is not a number are found this way. Calls to user functions with the
+// wrong number of arguments are reported as well, as the VM only notices
+// them when the stack runs short.
+use crate::diagnostic::Diagnostic;
+use crate::error::CompileError;
+use crate::lexer::{self, Span, BRAINCELLS};
+use crate::parser::{self, BinaryOp, Expr, Function, Program, SpannedStatement, Statement};
+use crate::typecheck;
+use crate::value::Value;
+use crate::vm::{self, ErrorKind};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+// Longer strings are not worth following; they become unknown.
+const MAX_KNOWN_STRING: usize = 64 * 1024;
+
+pub fn analyze(
+    source: &str,
+    filename: &str,
+    strict: bool,
+) -> Result<Vec<Diagnostic>, CompileError> {
+    let tokens = lexer::tokenize_spanned(source, filename)?;
+    let program = parser::parse(tokens, filename)?;
+
+    let mut found = Vec::new();
+    if let Err(e) = typecheck::check(&program, filename, strict) {
+        found.push(Diagnostic::from_error(&e));
+        found.extend(e.additional.iter().map(Diagnostic::from_error));
+    }
+
+    let mut analyzer = Analyzer {
+        filename,
+        strict,
+        consts: HashMap::new(),
+        functions: program
+            .functions
+            .iter()
+            .map(|f| (f.name.as_str(), f))
+            .collect(),
+        pokes: program.calls_builtin("POKE"),
+        found: Vec::new(),
+    };
+    analyzer.consts(&program);
+    for func in &program.functions {
+        analyzer.block(&func.body, &mut HashMap::new());
+    }
+    analyzer.block(&program.main_statements, &mut HashMap::new());
+
+    found.extend(analyzer.found);
+    found.sort_by_key(|d| (d.line, d.col));
+    found.dedup_by(|a, b| (a.line, a.col, &a.message) == (b.line, b.col, &b.message));
+    Ok(found)
+}
+
+// Names whose value is known at this point.
+type Env<'a> = HashMap<&'a str, Value>;
+
+struct Analyzer<'a> {
+    filename: &'a str,
+    strict: bool,
+    consts: HashMap<&'a str, Value>,
+    functions: HashMap<&'a str, &'a Function>,
+    pokes: bool, // POKE can change any braincell
+    found: Vec<Diagnostic>,
+}
+
+impl<'a> Analyzer<'a> {
+    fn report(&mut self, span: Span, message: String, help: Option<&str>) {
+        let mut error = CompileError::new(self.filename, span.line, span.col, &message);
+        error.help = help.map(str::to_string);
+        self.found.push(Diagnostic::from_error(&error));
+    }
+
+    fn consts(&mut self, program: &'a Program) {
+        for c in &program.consts {
+            if let Some(value) = self.expr(&c.value, &mut HashMap::new()) {
+                self.consts.insert(&c.name, value);
+            }
+        }
+    }
+
+    // False when the block always ends in RETREAT, YOUSHALLNOTPASS or
+    // CRASHOUT, so nothing after it runs.
+    fn block(&mut self, stmts: &'a [SpannedStatement], env: &mut Env<'a>) -> bool {
+        for stmt in stmts {
+            match &stmt.statement {
+                Statement::Assign(name, _, expr) => {
+                    let value = self.expr(expr, env);
+                    self.store(name, value, env);
+                }
+                Statement::Copy { dest, source } => {
+                    let value = self.expr(source, env);
+                    self.store(dest, value, env);
+                }
+                Statement::Print(expr)
+                | Statement::PrintInline(expr)
+                | Statement::PrintError(expr) => {
+                    self.expr(expr, env);
+                }
+                Statement::Return(expr) | Statement::Throw(expr) => {
+                    self.expr(expr, env);
+                    return false;
+                }
+                Statement::Halt => return false,
+                Statement::If {
+                    condition,
+                    then_block,
+                    else_block,
+                } => {
+                    let condition = self.expr(condition, env).map(|c| vm::is_truthy(&c));
+                    let mut then_env = env.clone();
+                    let then_falls =
+                        condition != Some(false) && self.block(then_block, &mut then_env);
+                    let mut else_env = env.clone();
+                    let else_falls = condition != Some(true)
+                        && else_block
+                            .as_ref()
+                            .is_none_or(|else_block| self.block(else_block, &mut else_env));
+                    *env = match (then_falls, else_falls) {
+                        (true, true) => {
+                            then_env.retain(|name, value| else_env.get(name) == Some(value));
+                            then_env
+                        }
+                        (true, false) => then_env,
+                        (false, true) => else_env,
+                        (false, false) => return false,
+                    };
+                }
+                Statement::While { condition, body } => {
+                    let mut assigned = Vec::new();
+                    typecheck::assigned_names(body, &mut assigned);
+                    for name in assigned {
+                        env.remove(name);
+                    }
+                    if expr_calls(condition) || block_calls(body) {
+                        self.forget_braincells(env);
+                    }
+                    let condition = self.expr(condition, env).map(|c| vm::is_truthy(&c));
+                    if condition != Some(false) {
+                        self.block(body, &mut env.clone());
+                    }
+                }
+            }
+        }
+        true
+    }
+
+    fn store(&mut self, name: &'a str, value: Option<Value>, env: &mut Env<'a>) {
+        match value {
+            Some(value) if !(self.pokes && is_braincell(name)) => env.insert(name, value),
+            _ => env.remove(name),
+        };
+    }
+
+    // A user function can assign any braincell.
+    fn forget_braincells(&self, env: &mut Env<'a>) {
+        env.retain(|name, _| !is_braincell(name));
+    }
+
+    fn expr(&mut self, expr: &'a Expr, env: &mut Env<'a>) -> Option<Value> {
+        match expr {
+            Expr::Number(n, _) => Some(Value::Number(*n)),
+            Expr::String(s, _) => Some(Value::String(Rc::new(s.clone()))),
+            Expr::Variable(name, _) => env
+                .get(name.as_str())
+                .or_else(|| self.consts.get(name.as_str()))
+                .cloned(),
+            Expr::Binary {
+                op,
+                left,
+                right,
+                span,
+            } => {
+                let left = self.expr(left, env);
+                let right = self.expr(right, env);
+                self.binary(*op, left, right, *span)
+            }
+            Expr::FunctionCall { name, args, span } => {
+                let args: Vec<_> = args.iter().map(|arg| self.expr(arg, env)).collect();
+                match (name.as_str(), args.as_slice()) {
+                    ("TRANSFORM", [Some(Value::String(s))]) => match vm::parse_number(s) {
+                        Some(n) => Some(Value::Number(n)),
+                        None => {
+                            let message = ErrorKind::BuiltinArgument {
+                                name: "TRANSFORM",
+                                message: "invalid number string",
+                            }
+                            .to_string();
+                            self.report(
+                                *span,
+                                format!("{}: {:?}", message, s.as_str()),
+                                Some("TRYTRANSFORM gives \"\" instead of failing"),
+                            );
+                            None
+                        }
+                    },
+                    _ => None,
+                }
+            }
+            Expr::UserFunctionCall { name, args, span } => {
+                for arg in args {
+                    self.expr(arg, env);
+                }
+                if let Some(func) = self.functions.get(name.as_str()) {
+                    let arity = func.params.len();
+                    if args.len() != arity {
+                        self.report(
+                            *span,
+                            format!(
+                                "{} takes {} argument{}, got {}",
+                                name,
+                                arity,
+                                if arity == 1 { "" } else { "s" },
+                                args.len()
+                            ),
+                            Some("the VM does not check argument counts, so the call takes the wrong values from the stack"),
+                        );
+                    }
+                }
+                self.forget_braincells(env);
+                None
+            }
+        }
+    }
+
+    fn binary(
+        &mut self,
+        op: BinaryOp,
+        left: Option<Value>,
+        right: Option<Value>,
+        span: Span,
+    ) -> Option<Value> {
+        // A zero divisor fails whatever is divided by it.
+        let zero = matches!(&right, Some(Value::Number(n)) if *n == 0.0)
+            || matches!(&right, Some(Value::Big(n)) if n.is_zero());
+        if op == BinaryOp::Divide && zero && !matches!(left, Some(Value::String(_))) {
+            self.report(span, ErrorKind::DivisionByZero.to_string(), None);
+            return None;
+        }
+
+        let (left, right) = (left?, right?);
+        if let (BinaryOp::Multiply, Value::String(s), Value::Number(n))
+        | (BinaryOp::Multiply, Value::Number(n), Value::String(s)) = (op, &left, &right)
+        {
+            if n.is_finite() && s.len() as f64 * n.trunc() > MAX_KNOWN_STRING as f64 {
+                return None;
+            }
+        }
+        if op == BinaryOp::Add {
+            if let (Value::String(a), Value::String(b)) = (&left, &right) {
+                if a.len() + b.len() > MAX_KNOWN_STRING {
+                    return None;
+                }
+            }
+        }
+
+        let (name, result) = match op {
+            BinaryOp::Add if self.strict => ("ADD", left.add_strict(&right)),
+            BinaryOp::Add => ("ADD", left.add(&right)),
+            BinaryOp::Subtract => ("SUB", left.sub(&right)),
+            BinaryOp::Multiply => ("MUL", left.mul(&right)),
+            BinaryOp::Divide => ("DIV", left.div(&right)),
+            BinaryOp::Equal => {
+                return Some(Value::Number(f64::from(u8::from(left.equals(&right)))))
+            }
+        };
+        match result {
+            Ok(Value::String(s)) if s.len() > MAX_KNOWN_STRING => None,
+            Ok(value) => Some(value),
+            Err(message) => {
+                // Type errors are the type checker's to report.
+                match vm::arithmetic_error(name, &left, &right, message) {
+                    ErrorKind::TypeError { .. } => {}
+                    kind => self.report(span, kind.to_string(), None),
+                }
+                None
+            }
+        }
+    }
+}
+
+fn is_braincell(name: &str) -> bool {
+    BRAINCELLS.iter().any(|(braincell, _)| *braincell == name)
+}
+
+// Whether the expression calls a user function.
+fn expr_calls(expr: &Expr) -> bool {
+    match expr {
+        Expr::UserFunctionCall { .. } => true,
+        Expr::FunctionCall { args, .. } => args.iter().any(expr_calls),
+        Expr::Binary { left, right, .. } => expr_calls(left) || expr_calls(right),
+        Expr::Number(..) | Expr::String(..) | Expr::Variable(..) => false,
+    }
+}
+
+// Whether any statement in the block calls a user function.
+fn block_calls(stmts: &[SpannedStatement]) -> bool {
+    stmts.iter().any(|stmt| match &stmt.statement {
+        Statement::Assign(_, _, expr)
+        | Statement::Copy { source: expr, .. }
+        | Statement::Print(expr)
+        | Statement::PrintInline(expr)
+        | Statement::PrintError(expr)
+        | Statement::Return(expr)
+        | Statement::Throw(expr) => expr_calls(expr),
+        Statement::Halt => false,
+        Statement::If {
+            condition,
+            then_block,
+            else_block,
+        } => {
+            expr_calls(condition)
+                || block_calls(then_block)
+                || else_block.as_deref().is_some_and(block_calls)
+        }
+        Statement::While { condition, body } => expr_calls(condition) || block_calls(body),
+    })
+}
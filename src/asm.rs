@@ -0,0 +1,922 @@
+// Textual assembler for hand-written BRBC (`brvm asm`). Instructions use the
+// names `disasm` prints, one per line:
+//
+//   .version 5             optional; the current version by default
+//   .flags strict          strict and strict-math, space separated
+//   .globals 9             declares the slot count
+//       LOAD_CONST "hi"    numbers and strings are interned; #N is a pool index
+//       LOAD_GLOBAL aura   braincells by name or slot
+//   top:
+//       JUMP_IF_FALSE top  jumps to labels
+//       HITMEUP double     functions by name, CALL_BUILTIN by built-in name
+//   .function double 1 1   name, arity and locals; the body follows
+//
+// `;` starts a comment. Numeric operands are expressions over numbers,
+// labels, `.set` names and `$` (the offset of the current instruction) with
+// + - * / and parentheses, so `JUMP top+1` lands mid-instruction. `.byte`
+// emits raw bytes. `.macro name a, b` ... `.endm` defines a macro whose body
+// uses its arguments as `\a` and `\b` and gets a fresh `\@` per expansion;
+// `.rept N` ... `.endr` repeats a block.
+//
+// `disasm` output assembles too. Its header, constant pool and function table
+// are taken as they stand instead of being rebuilt, the offset in front of
+// each instruction is checked, and function labels are skipped, so
+// `brvm disasm x.brbc | brvm asm -` gives back the same bytes.
+use crate::bigint::BigInt;
+use crate::builtins;
+use crate::bytecode::{self, FunctionEntry, Module};
+use crate::disasm;
+use crate::error::CompileError;
+use crate::opcode;
+use crate::value::Value;
+use std::collections::HashMap;
+
+const BRAINCELL_NAMES: [&str; 7] = ["aura", "peak", "goon", "mog", "npc", "sigma", "gyatt"];
+
+// Macros expanding macros stop here, which catches a macro that uses itself.
+const MAX_EXPANSION_DEPTH: usize = 64;
+
+pub fn assemble(source: &str, filename: &str) -> Result<Vec<u8>, CompileError> {
+    let lines: Vec<(usize, String)> = source
+        .lines()
+        .enumerate()
+        .map(|(i, text)| (i + 1, strip_comment(text).to_string()))
+        .collect();
+    let mut expander = Expander {
+        filename,
+        macros: HashMap::new(),
+        expansions: 0,
+    };
+    let mut expanded = Vec::new();
+    expander.expand(&lines, 0, &mut expanded)?;
+
+    let mut asm = Assembler {
+        filename,
+        line: 0,
+        version: bytecode::VERSION,
+        newest_op: None,
+        listing: false,
+        section: Section::Code,
+        module: Module {
+            version: bytecode::VERSION,
+            flags: 0,
+            globals: bytecode::DEFAULT_GLOBALS,
+            constants: Vec::new(),
+            functions: Vec::new(),
+            code: Vec::new(),
+        },
+        function_names: Vec::new(),
+        labels: HashMap::new(),
+        symbols: HashMap::new(),
+        fixups: Vec::new(),
+    };
+    for (line, text) in &expanded {
+        asm.line = *line;
+        asm.line(text)?;
+    }
+    asm.finish()
+}
+
+// Disassembles `bytecode` and assembles the listing again, for `brvm
+// roundtrip`. Err names the first byte that came back different.
+pub fn roundtrip(bytecode: &[u8]) -> Result<(), String> {
+    let listing = disasm::disassemble(bytecode)?;
+    let rebuilt = assemble(&listing, "<listing>").map_err(|e| e.to_string())?;
+    match bytecode.iter().zip(&rebuilt).position(|(a, b)| a != b) {
+        Some(at) => Err(format!(
+            "byte 0x{:04x} was 0x{:02x} but reassembles as 0x{:02x}",
+            at, bytecode[at], rebuilt[at]
+        )),
+        None if bytecode.len() != rebuilt.len() => Err(format!(
+            "{} bytes reassemble as {}",
+            bytecode.len(),
+            rebuilt.len()
+        )),
+        None => Ok(()),
+    }
+}
+
+struct Macro {
+    params: Vec<String>,
+    body: Vec<(usize, String)>,
+}
+
+// Rewrites `.macro` and `.rept` blocks into plain lines. Each output line
+// keeps the source line it came from, so errors point into macro bodies.
+struct Expander<'a> {
+    filename: &'a str,
+    macros: HashMap<String, Macro>,
+    expansions: usize, // numbers `\@`
+}
+
+impl Expander<'_> {
+    fn error(&self, line: usize, message: &str) -> CompileError {
+        CompileError::new(self.filename, line, 1, message)
+    }
+
+    fn expand(
+        &mut self,
+        lines: &[(usize, String)],
+        depth: usize,
+        out: &mut Vec<(usize, String)>,
+    ) -> Result<(), CompileError> {
+        let mut i = 0;
+        while i < lines.len() {
+            let (line, text) = &lines[i];
+            let (word, rest) = split_word(text.trim());
+            match word {
+                ".macro" => {
+                    let end = self.block_end(lines, i, ".macro", ".endm")?;
+                    let (name, params) = split_word(rest);
+                    if !is_name(name) {
+                        return Err(self.error(*line, ".macro needs a name"));
+                    }
+                    let params = split_args(params);
+                    if let Some(bad) = params.iter().find(|p| !is_name(p)) {
+                        return Err(
+                            self.error(*line, &format!("'{}' is not a valid parameter", bad))
+                        );
+                    }
+                    self.macros.insert(
+                        name.to_string(),
+                        Macro {
+                            params,
+                            body: lines[i + 1..end].to_vec(),
+                        },
+                    );
+                    i = end + 1;
+                }
+                ".rept" => {
+                    let end = self.block_end(lines, i, ".rept", ".endr")?;
+                    let count = evaluate(rest, &|_| None)
+                        .map_err(|e| self.error(*line, &format!(".rept: {}", e)))?;
+                    if !(0..=65536).contains(&count) {
+                        return Err(self.error(*line, ".rept takes a count from 0 to 65536"));
+                    }
+                    for _ in 0..count {
+                        self.expand(&lines[i + 1..end], depth, out)?;
+                    }
+                    i = end + 1;
+                }
+                ".endm" | ".endr" => {
+                    return Err(self.error(*line, &format!("{} without a block to end", word)));
+                }
+                _ if self.macros.contains_key(word) => {
+                    if depth >= MAX_EXPANSION_DEPTH {
+                        return Err(self.error(
+                            *line,
+                            &format!("macros nest more than {} deep", MAX_EXPANSION_DEPTH),
+                        ));
+                    }
+                    let body = self.substitute(word, rest, *line)?;
+                    self.expand(&body, depth + 1, out)?;
+                    i += 1;
+                }
+                _ => {
+                    out.push((*line, text.clone()));
+                    i += 1;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Index of the line that closes the block opened at `start`.
+    fn block_end(
+        &self,
+        lines: &[(usize, String)],
+        start: usize,
+        open: &str,
+        close: &str,
+    ) -> Result<usize, CompileError> {
+        let mut nesting = 0;
+        for (i, (_, text)) in lines.iter().enumerate().skip(start + 1) {
+            let (word, _) = split_word(text.trim());
+            if word == open {
+                nesting += 1;
+            } else if word == close {
+                if nesting == 0 {
+                    return Ok(i);
+                }
+                nesting -= 1;
+            }
+        }
+        Err(self.error(lines[start].0, &format!("{} without {}", open, close)))
+    }
+
+    fn substitute(
+        &mut self,
+        name: &str,
+        args: &str,
+        line: usize,
+    ) -> Result<Vec<(usize, String)>, CompileError> {
+        let mac = &self.macros[name];
+        let args = split_args(args);
+        if args.len() != mac.params.len() {
+            return Err(self.error(
+                line,
+                &format!(
+                    "macro '{}' takes {} argument(s), got {}",
+                    name,
+                    mac.params.len(),
+                    args.len()
+                ),
+            ));
+        }
+        self.expansions += 1;
+        let unique = self.expansions.to_string();
+        // Longest names first, so `\ab` is not taken for `\a` followed by `b`.
+        let mut pairs: Vec<(&String, &String)> = mac.params.iter().zip(&args).collect();
+        pairs.sort_by_key(|(param, _)| std::cmp::Reverse(param.len()));
+        Ok(mac
+            .body
+            .iter()
+            .map(|(body_line, text)| {
+                let mut text = text.replace("\\@", &unique);
+                for (param, arg) in &pairs {
+                    text = text.replace(&format!("\\{}", param), arg);
+                }
+                (*body_line, text)
+            })
+            .collect())
+    }
+}
+
+// An operand that is resolved once every label is known.
+struct Fixup {
+    at: usize,
+    width: usize,
+    expr: String,
+    here: u32, // offset of the instruction, for `$`
+    function: bool,
+    line: usize,
+    col: usize,
+}
+
+// Which part of a `disasm` listing the assembler is in.
+#[derive(Clone, Copy, PartialEq)]
+enum Section {
+    Constants,
+    Functions,
+    Code,
+}
+
+struct Assembler<'a> {
+    filename: &'a str,
+    line: usize,
+    version: u16,
+    listing: bool, // reading `disasm` output
+    section: Section,
+    // (version, name, line, col) of the opcode needing the newest version
+    newest_op: Option<(u16, &'static str, usize, usize)>,
+    module: Module,
+    function_names: Vec<String>,
+    labels: HashMap<String, u32>,
+    symbols: HashMap<String, i64>, // from `.set`
+    fixups: Vec<Fixup>,
+}
+
+impl Assembler<'_> {
+    fn error(&self, col: usize, message: &str) -> CompileError {
+        CompileError::new(self.filename, self.line, col, message)
+    }
+
+    fn line(&mut self, text: &str) -> Result<(), CompileError> {
+        let trimmed = text.trim_start();
+        if trimmed.trim_end().is_empty() {
+            return Ok(());
+        }
+        let mut col = text.len() - trimmed.len() + 1;
+        if (self.listing || trimmed.starts_with("BRBC ")) && self.listing_line(trimmed, col)? {
+            return Ok(());
+        }
+        let (mut word, mut rest, mut rest_col) = split_at(trimmed, col);
+        if let Some(hex) = word.strip_prefix("0x").filter(|_| !rest.is_empty()) {
+            let here = self.module.code.len();
+            if usize::from_str_radix(hex, 16).ok() != Some(here) {
+                return Err(
+                    self.error(col, &format!("this is offset 0x{:04x}, not {}", here, word))
+                );
+            }
+            col = rest_col;
+            (word, rest, rest_col) = split_at(rest, col);
+        }
+
+        if let Some(label) = word.strip_suffix(':') {
+            if !rest.is_empty() {
+                return Err(self.error(rest_col, "a label goes on a line of its own"));
+            }
+            let offset = self.module.code.len() as u32;
+            return self.define(label, col, |asm| &mut asm.labels, offset);
+        }
+        match word {
+            ".version" => {
+                let version = self.immediate(rest, rest_col)?;
+                if !(bytecode::MIN_VERSION as i64..=bytecode::VERSION as i64).contains(&version) {
+                    return Err(self.error(
+                        rest_col,
+                        &format!(
+                            "versions {} to {} can be assembled",
+                            bytecode::MIN_VERSION,
+                            bytecode::VERSION
+                        ),
+                    ));
+                }
+                self.version = version as u16;
+                Ok(())
+            }
+            ".flags" => {
+                for flag in rest.split_whitespace() {
+                    self.module.flags |= match flag {
+                        "strict" => bytecode::FLAG_STRICT,
+                        "strict-math" => bytecode::FLAG_STRICT_MATH,
+                        _ => {
+                            return Err(self.error(
+                                rest_col,
+                                &format!("unknown flag '{}' (strict, strict-math)", flag),
+                            ))
+                        }
+                    };
+                }
+                Ok(())
+            }
+            ".globals" => {
+                let globals = self.immediate(rest, rest_col)?;
+                self.module.globals = self.in_range(globals, u16::MAX as u32, rest_col)? as usize;
+                self.module.flags |= bytecode::FLAG_GLOBALS;
+                Ok(())
+            }
+            ".set" => {
+                let Some((name, expr)) = rest.split_once(char::is_whitespace) else {
+                    return Err(self.error(rest_col, ".set takes a name and a value"));
+                };
+                let value = self.immediate(expr, rest_col)?;
+                self.define(name, rest_col, |asm| &mut asm.symbols, value)
+            }
+            ".byte" => {
+                for expr in split_args(rest) {
+                    let here = self.module.code.len() as u32;
+                    self.defer(&expr, 1, false, rest_col, here);
+                }
+                Ok(())
+            }
+            ".function" => self.function(rest, rest_col),
+            _ => self.instruction(word, col, rest, rest_col),
+        }
+    }
+
+    // The header, constant pool, function table and function labels of a
+    // `disasm` listing; false for any other line.
+    fn listing_line(&mut self, text: &str, col: usize) -> Result<bool, CompileError> {
+        let text = text.trim_end();
+        let (word, rest, rest_col) = split_at(text, col);
+        let first = rest.split_whitespace().next().unwrap_or("");
+        match word {
+            "BRBC" => {
+                let Some(version) = rest.strip_prefix("version") else {
+                    return Ok(false);
+                };
+                let version = self.immediate(version, rest_col)?;
+                self.version = self.in_range(version, u16::MAX as u32, rest_col)? as u16;
+                self.listing = true;
+            }
+            "flags:" => {
+                let flags = self.immediate(first, rest_col)?;
+                self.module.flags = self.in_range(flags, u16::MAX as u32, rest_col)? as u16;
+            }
+            "globals:" => {
+                let globals = self.immediate(first, rest_col)?;
+                self.module.globals = self.in_range(globals, u16::MAX as u32, rest_col)? as usize;
+            }
+            "constant" if first == "pool:" => self.section = Section::Constants,
+            "functions:" => self.section = Section::Functions,
+            "code:" => self.section = Section::Code,
+            _ if self.section == Section::Constants && word.starts_with('#') => {
+                let index = self.immediate(&word[1..], col + 1)?;
+                if index != self.module.constants.len() as i64 {
+                    return Err(self.error(
+                        col,
+                        &format!("expected constant #{}", self.module.constants.len()),
+                    ));
+                }
+                let value = self.pool_entry(rest, rest_col)?;
+                self.module.constants.push(value);
+            }
+            _ if self.section == Section::Functions => self.function_entry(text, col)?,
+            _ => {
+                // Function labels mark offsets the function table already has.
+                let here = self.module.code.len() as u32;
+                let Some(label) = text.strip_suffix(':') else {
+                    return Ok(false);
+                };
+                let is_function =
+                    |(name, f): (&String, &FunctionEntry)| name == label && f.code_offset == here;
+                if !self
+                    .function_names
+                    .iter()
+                    .zip(&self.module.functions)
+                    .any(is_function)
+                {
+                    return Ok(false);
+                }
+            }
+        }
+        Ok(true)
+    }
+
+    // `kind value` from the constant pool, as `disasm` prints it.
+    fn pool_entry(&self, text: &str, col: usize) -> Result<Value, CompileError> {
+        let (kind, value) = split_word(text);
+        let parsed = match kind {
+            "number" => match value.strip_prefix("NaN 0x") {
+                Some(bits) => u64::from_str_radix(bits, 16).ok().map(f64::from_bits),
+                None => value.parse().ok(),
+            }
+            .map(Value::Number),
+            "string" if value.starts_with('"') => {
+                Some(Value::String(self.string(value, col)?.into()))
+            }
+            "bigint" => BigInt::parse(value).map(|n| Value::Big(n.into())),
+            _ => None,
+        };
+        parsed.ok_or_else(|| self.error(col, &format!("'{}' is not a constant", text)))
+    }
+
+    // `name(arity A, locals L, stack S) @ 0xOFFSET (name #N)` from the
+    // function table; the stack and the name index are optional.
+    fn function_entry(&mut self, text: &str, col: usize) -> Result<(), CompileError> {
+        let bad = || self.error(col, "expected name(arity A, locals L) @ 0xOFFSET");
+        let (entry, name_const) = match text
+            .strip_suffix(')')
+            .and_then(|t| t.rsplit_once(" (name #"))
+        {
+            Some((entry, index)) => (entry, Some(index)),
+            None => (text, None),
+        };
+        let (signature, offset) = entry.rsplit_once(") @ ").ok_or_else(bad)?;
+        let (name, fields) = signature.rsplit_once("(arity ").ok_or_else(bad)?;
+        let mut numbers = [0u32; 3];
+        for (i, field) in fields.split(", ").enumerate() {
+            let (label, number) = match i {
+                0 => ("", field),
+                _ => field.split_once(' ').ok_or_else(bad)?,
+            };
+            if i > 2 || label != ["", "locals", "stack"][i] {
+                return Err(bad());
+            }
+            let number = self.immediate(number, col)?;
+            numbers[i] = self.in_range(number, u16::MAX as u32, col)?;
+        }
+        let code_offset = self.immediate(offset, col)?;
+        let code_offset = self.in_range(code_offset, u32::MAX, col)?;
+        let name_const = match name_const {
+            Some(index) => {
+                let index = self.immediate(index, col)?;
+                self.in_range(index, u32::MAX, col)?
+            }
+            None => self.intern(Value::String(name.to_string().into())),
+        };
+        self.module.functions.push(FunctionEntry {
+            name_const,
+            arity: numbers[0] as u16,
+            local_count: numbers[1] as u16,
+            code_offset,
+            max_stack: numbers[2] as u16,
+        });
+        self.function_names.push(name.to_string());
+        Ok(())
+    }
+
+    // Adds a label or `.set` name; the two share one namespace.
+    fn define<T>(
+        &mut self,
+        name: &str,
+        col: usize,
+        table: impl Fn(&mut Self) -> &mut HashMap<String, T>,
+        value: T,
+    ) -> Result<(), CompileError> {
+        if !is_name(name) {
+            return Err(self.error(col, &format!("'{}' is not a valid name", name)));
+        }
+        if self.labels.contains_key(name) || self.symbols.contains_key(name) {
+            return Err(self.error(col, &format!("'{}' is defined twice", name)));
+        }
+        table(self).insert(name.to_string(), value);
+        Ok(())
+    }
+
+    fn function(&mut self, rest: &str, col: usize) -> Result<(), CompileError> {
+        let parts: Vec<&str> = rest.split_whitespace().collect();
+        let [name, arity, locals] = parts[..] else {
+            return Err(self.error(col, ".function takes a name, an arity and a local count"));
+        };
+        if !is_name(name) {
+            return Err(self.error(col, &format!("'{}' is not a valid function name", name)));
+        }
+        if self.function_names.iter().any(|n| n == name) {
+            return Err(self.error(col, &format!("function '{}' is defined twice", name)));
+        }
+        let arity = self.immediate(arity, col)?;
+        let arity = self.in_range(arity, u16::MAX as u32, col)? as u16;
+        let local_count = self.immediate(locals, col)?;
+        let local_count = self.in_range(local_count, u16::MAX as u32, col)? as u16;
+        if local_count < arity {
+            return Err(self.error(col, "a function needs a local for each parameter"));
+        }
+        let name_const = self.intern(Value::String(name.to_string().into()));
+        self.module.functions.push(FunctionEntry {
+            name_const,
+            arity,
+            local_count,
+            code_offset: self.module.code.len() as u32,
+            max_stack: 0,
+        });
+        self.function_names.push(name.to_string());
+        Ok(())
+    }
+
+    fn instruction(
+        &mut self,
+        word: &str,
+        col: usize,
+        operand: &str,
+        operand_col: usize,
+    ) -> Result<(), CompileError> {
+        let mnemonic = word.to_ascii_uppercase();
+        let Some(&(op, name, width)) = opcode::OPCODES.iter().find(|(_, n, _)| *n == mnemonic)
+        else {
+            return Err(self.error(col, &format!("unknown instruction '{}'", word)));
+        };
+        let needs = opcode::min_version(op);
+        if self.newest_op.is_none_or(|(newest, ..)| needs > newest) {
+            self.newest_op = Some((needs, name, self.line, col));
+        }
+
+        let here = self.module.code.len() as u32;
+        self.module.code.push(op);
+        if width == 0 {
+            if !operand.is_empty() {
+                return Err(self.error(operand_col, &format!("{} takes no operand", name)));
+            }
+            return Ok(());
+        }
+        if operand.is_empty() {
+            return Err(self.error(col, &format!("{} needs an operand", name)));
+        }
+
+        let known = match op {
+            0x02 => self.constant(operand, operand_col)?,
+            0x03 | 0x04 => BRAINCELL_NAMES
+                .iter()
+                .position(|&n| n == operand)
+                .map(|slot| slot as u32),
+            0x14 => builtins::lookup(operand).map(|b| b.id as u32),
+            _ => None,
+        };
+        match known {
+            Some(value) => self
+                .module
+                .code
+                .extend_from_slice(&value.to_le_bytes()[..width]),
+            None => match operand.strip_prefix('#').filter(|_| op == 0x02) {
+                Some(index) => self.defer(index, width, false, operand_col + 1, here),
+                None => self.defer(operand, width, matches!(op, 0x0D | 0x16), operand_col, here),
+            },
+        }
+        Ok(())
+    }
+
+    // LOAD_CONST's literal operand, interned; None for a `#` pool index.
+    fn constant(&mut self, operand: &str, col: usize) -> Result<Option<u32>, CompileError> {
+        if operand.starts_with('#') {
+            return Ok(None);
+        }
+        let value = if operand.starts_with('"') {
+            Value::String(self.string(operand, col)?.into())
+        } else {
+            match operand.parse::<f64>() {
+                Ok(n) if n.is_finite() => Value::Number(n),
+                _ => {
+                    return Err(self.error(
+                        col,
+                        &format!("'{}' is not a number, string or #index", operand),
+                    ))
+                }
+            }
+        };
+        Ok(Some(self.intern(value)))
+    }
+
+    fn string(&self, operand: &str, col: usize) -> Result<String, CompileError> {
+        let mut out = String::new();
+        let mut chars = operand[1..].chars();
+        loop {
+            match chars.next() {
+                None => return Err(self.error(col, "unterminated string")),
+                Some('"') => break,
+                Some('\\') => out.push(match chars.next() {
+                    Some('n') => '\n',
+                    Some('t') => '\t',
+                    Some('r') => '\r',
+                    Some('0') => '\0',
+                    Some(c @ ('"' | '\'' | '\\')) => c,
+                    Some('u') => match unicode_escape(&mut chars) {
+                        Some(c) => c,
+                        None => return Err(self.error(col, "bad \\u{...} escape in string")),
+                    },
+                    _ => return Err(self.error(col, "unknown escape in string")),
+                }),
+                Some(c) => out.push(c),
+            }
+        }
+        if !chars.as_str().trim().is_empty() {
+            return Err(self.error(col, "unexpected text after the string"));
+        }
+        Ok(out)
+    }
+
+    // Leaves `width` zero bytes for an operand resolved by `finish`.
+    fn defer(&mut self, expr: &str, width: usize, function: bool, col: usize, here: u32) {
+        self.fixups.push(Fixup {
+            at: self.module.code.len(),
+            width,
+            expr: expr.to_string(),
+            here,
+            function,
+            line: self.line,
+            col,
+        });
+        self.module.code.extend_from_slice(&[0; 4][..width]);
+    }
+
+    // Evaluates a directive's expression with the names defined so far.
+    fn immediate(&self, expr: &str, col: usize) -> Result<i64, CompileError> {
+        let here = self.module.code.len() as u32;
+        evaluate(expr, &|name| self.resolve(name, here)).map_err(|e| self.error(col, &e))
+    }
+
+    fn resolve(&self, name: &str, here: u32) -> Option<i64> {
+        match name {
+            "$" => Some(here as i64),
+            _ => self
+                .labels
+                .get(name)
+                .map(|&offset| offset as i64)
+                .or_else(|| self.symbols.get(name).copied()),
+        }
+    }
+
+    fn in_range(&self, value: i64, max: u32, col: usize) -> Result<u32, CompileError> {
+        u32::try_from(value)
+            .ok()
+            .filter(|&v| v <= max)
+            .ok_or_else(|| self.error(col, &format!("{} is out of range (0 to {})", value, max)))
+    }
+
+    fn intern(&mut self, value: Value) -> u32 {
+        let constants = &mut self.module.constants;
+        let index = constants
+            .iter()
+            .position(|c| c.type_name() == value.type_name() && c.equals(&value))
+            .unwrap_or_else(|| {
+                constants.push(value);
+                constants.len() - 1
+            });
+        index as u32
+    }
+
+    fn finish(mut self) -> Result<Vec<u8>, CompileError> {
+        for fixup in std::mem::take(&mut self.fixups) {
+            self.line = fixup.line;
+            let function = self.function_names.iter().position(|n| *n == fixup.expr);
+            let value = match function.filter(|_| fixup.function) {
+                Some(index) => index as i64 + 2,
+                None if fixup.function
+                    && is_name(&fixup.expr)
+                    && self.resolve(&fixup.expr, fixup.here).is_none() =>
+                {
+                    let message = format!("unknown function '{}'", fixup.expr);
+                    return Err(self.error(fixup.col, &message));
+                }
+                None => evaluate(&fixup.expr, &|name| self.resolve(name, fixup.here))
+                    .map_err(|e| self.error(fixup.col, &e))?,
+            };
+            let max = if fixup.width == 4 {
+                u32::MAX
+            } else {
+                (1u32 << (8 * fixup.width)) - 1
+            };
+            let value = self.in_range(value, max, fixup.col)?;
+            self.module.code[fixup.at..fixup.at + fixup.width]
+                .copy_from_slice(&value.to_le_bytes()[..fixup.width]);
+        }
+
+        // A listing keeps whatever header it was disassembled from.
+        if let Some((needs, name, line, col)) = self.newest_op.filter(|_| !self.listing) {
+            if needs > self.version {
+                return Err(CompileError::new(
+                    self.filename,
+                    line,
+                    col,
+                    &format!(
+                        "{} needs bytecode version {}, but version {} was requested",
+                        name, needs, self.version
+                    ),
+                ));
+            }
+        }
+        if self.module.flags != 0 && self.version < 5 && !self.listing {
+            return Err(CompileError::new(
+                self.filename,
+                0,
+                0,
+                "header flags need bytecode version 5",
+            ));
+        }
+        self.module.version = self.version;
+        Ok(bytecode::encode(&self.module))
+    }
+}
+
+// Integer arithmetic over numbers (decimal or 0x hex) and names; `resolve`
+// gives a name's value.
+fn evaluate(text: &str, resolve: &dyn Fn(&str) -> Option<i64>) -> Result<i64, String> {
+    let mut parser = ExprParser {
+        text: text.trim(),
+        pos: 0,
+        resolve,
+    };
+    if parser.text.is_empty() {
+        return Err("expected a number".to_string());
+    }
+    let value = parser.sum()?;
+    parser.skip_space();
+    match parser.text[parser.pos..].chars().next() {
+        None => Ok(value),
+        Some(c) => Err(format!("unexpected '{}' in expression", c)),
+    }
+}
+
+struct ExprParser<'a> {
+    text: &'a str,
+    pos: usize,
+    resolve: &'a dyn Fn(&str) -> Option<i64>,
+}
+
+impl ExprParser<'_> {
+    fn skip_space(&mut self) {
+        let rest = &self.text[self.pos..];
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        self.skip_space();
+        if self.text[self.pos..].starts_with(c) {
+            self.pos += c.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn sum(&mut self) -> Result<i64, String> {
+        let mut value = self.product()?;
+        loop {
+            if self.eat('+') {
+                value = value
+                    .checked_add(self.product()?)
+                    .ok_or("expression overflows")?;
+            } else if self.eat('-') {
+                value = value
+                    .checked_sub(self.product()?)
+                    .ok_or("expression overflows")?;
+            } else {
+                return Ok(value);
+            }
+        }
+    }
+
+    fn product(&mut self) -> Result<i64, String> {
+        let mut value = self.unary()?;
+        loop {
+            if self.eat('*') {
+                value = value
+                    .checked_mul(self.unary()?)
+                    .ok_or("expression overflows")?;
+            } else if self.eat('/') {
+                let divisor = self.unary()?;
+                value = value
+                    .checked_div(divisor)
+                    .ok_or("division by zero in expression")?;
+            } else {
+                return Ok(value);
+            }
+        }
+    }
+
+    fn unary(&mut self) -> Result<i64, String> {
+        if self.eat('-') {
+            return self
+                .unary()?
+                .checked_neg()
+                .ok_or_else(|| "expression overflows".into());
+        }
+        if self.eat('(') {
+            let value = self.sum()?;
+            if !self.eat(')') {
+                return Err("expected ')'".to_string());
+            }
+            return Ok(value);
+        }
+        self.skip_space();
+        let rest = &self.text[self.pos..];
+        let len = rest
+            .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '$'))
+            .unwrap_or(rest.len());
+        let word = &rest[..len];
+        self.pos += len;
+        if word.is_empty() {
+            return Err(match rest.chars().next() {
+                Some(c) => format!("unexpected '{}' in expression", c),
+                None => "expression ends early".to_string(),
+            });
+        }
+        if word.starts_with(|c: char| c.is_ascii_digit()) {
+            let parsed = match word.strip_prefix("0x") {
+                Some(hex) => i64::from_str_radix(hex, 16),
+                None => word.parse(),
+            };
+            return parsed.map_err(|_| format!("'{}' is not a number", word));
+        }
+        (self.resolve)(word).ok_or_else(|| format!("unknown name '{}'", word))
+    }
+}
+
+// `\u{hex}` after the `\u`.
+fn unicode_escape(chars: &mut std::str::Chars) -> Option<char> {
+    let rest = chars.as_str().strip_prefix('{')?;
+    let (hex, after) = rest.split_once('}')?;
+    let c = char::from_u32(u32::from_str_radix(hex, 16).ok()?)?;
+    *chars = after.chars();
+    Some(c)
+}
+
+// The first word of `text`, which starts at column `col`, and the rest with
+// its column.
+fn split_at(text: &str, col: usize) -> (&str, &str, usize) {
+    let (word, rest) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+    let rest_col = col + text.len() - rest.trim_start().len();
+    (word, rest.trim(), rest_col)
+}
+
+fn split_word(text: &str) -> (&str, &str) {
+    match text.split_once(char::is_whitespace) {
+        Some((word, rest)) => (word, rest.trim()),
+        None => (text, ""),
+    }
+}
+
+// Comma-separated arguments; commas inside strings do not split.
+fn split_args(text: &str) -> Vec<String> {
+    if text.trim().is_empty() {
+        return Vec::new();
+    }
+    let mut args = vec![String::new()];
+    let mut in_string = false;
+    let mut escaped = false;
+    for c in text.chars() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            ',' if !in_string => {
+                args.push(String::new());
+                continue;
+            }
+            _ => {}
+        }
+        args.last_mut().unwrap().push(c);
+    }
+    args.iter().map(|a| a.trim().to_string()).collect()
+}
+
+fn strip_comment(line: &str) -> &str {
+    let mut in_string = false;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            ';' if !in_string => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+fn is_name(text: &str) -> bool {
+    let mut chars = text.chars();
+    chars.next().is_some_and(|c| c.is_alphabetic() || c == '_')
+        && chars.all(|c| c.is_alphanumeric() || c == '_')
+}
@@ -0,0 +1,47 @@
+// Standard base64 (RFC 4648, `+` and `/`) behind BASE64 and UNBASE64.
+// Encoding always pads with `=`; decoding accepts text with or without
+// padding but nothing outside the alphabet, whitespace included.
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+pub fn encode(data: &[u8]) -> String {
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+pub fn decode(text: &str) -> Result<Vec<u8>, &'static str> {
+    let invalid = "invalid base64 text";
+    let data = text.trim_end_matches('=');
+    let padding = text.len() - data.len();
+    if padding > 2 || (padding > 0 && !text.len().is_multiple_of(4)) || data.len() % 4 == 1 {
+        return Err(invalid);
+    }
+    let mut out = Vec::with_capacity(data.len() / 4 * 3 + 2);
+    let mut bits: u32 = 0;
+    let mut count = 0;
+    for byte in data.bytes() {
+        let value = ALPHABET.iter().position(|&c| c == byte).ok_or(invalid)?;
+        bits = bits << 6 | value as u32;
+        count += 6;
+        if count >= 8 {
+            count -= 8;
+            out.push((bits >> count) as u8);
+        }
+    }
+    Ok(out)
+}
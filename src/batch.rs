@@ -0,0 +1,188 @@
+// `brvm batch`: runs many programs in one process, for auto-graders that
+// would otherwise pay process startup once per submission.
+//
+// Each line of input is a JSON object:
+//
+//   {"id": 7, "source": "LOCK IN ...", "stdin": "bob\n", "max_instructions": 100000}
+//
+// with `bytecode` (base64 BRBC) in place of `source` to skip the compiler.
+// `id` is echoed back as given, and the `max_*` fields override the limits
+// the command was started with. Each request gets one line of JSON back,
+// written as soon as the run ends:
+//
+//   {"id": 7, "ok": true, "stage": null, "stdout": "wsg bob\n", "exit_status": 0,
+//    "instructions": 42, "error": null, "diagnostics": []}
+//
+// `stage` says what failed: "request", "compile", "bytecode" or "runtime",
+// and a failed run's `exit_status` is the code `brvm` itself would exit with.
+// Diagnostics hold the compile error, if any, and the lint warnings.
+use crate::base64;
+use crate::compiler::{self, CompileOptions};
+use crate::diagnostic::{Diagnostic, Severity};
+use crate::exit;
+use crate::host::CaptureHost;
+use crate::json::{self, Json};
+use crate::lexdump::json_string;
+use crate::serve::Limits;
+use crate::vm::{VmOptions, VM};
+use std::fmt::Write as _;
+use std::io::{self, BufRead, Write};
+
+const FILENAME: &str = "submission.br";
+
+pub fn run<R: BufRead, W: Write>(input: R, mut output: W, limits: &Limits) -> io::Result<()> {
+    for line in input.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        writeln!(output, "{}", run_request(&line, limits))?;
+        output.flush()?;
+    }
+    Ok(())
+}
+
+#[derive(Default)]
+struct Outcome {
+    id: Option<String>, // the request's `id`, already as JSON
+    stage: Option<&'static str>,
+    stdout: String,
+    exit_status: Option<i32>,
+    instructions: u64,
+    error: Option<String>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+// Handles one request line and returns the result line, without a newline.
+pub fn run_request(line: &str, limits: &Limits) -> String {
+    let mut outcome = Outcome::default();
+    if let Err(e) = handle(line, limits, &mut outcome) {
+        outcome.stage = Some("request");
+        outcome.error = Some(format!("bad request: {}", e));
+    }
+    outcome.to_json()
+}
+
+fn handle(line: &str, limits: &Limits, outcome: &mut Outcome) -> Result<(), String> {
+    let fields = json::parse_object(line)?;
+    let field = |name: &str| {
+        fields
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value)
+    };
+    outcome.id = field("id").map(|id| match id {
+        Json::String(s) => json_string(s),
+        Json::Number(n) => n.to_string(),
+        Json::Bool(b) => b.to_string(),
+        Json::Null => "null".to_string(),
+    });
+
+    let string = |name: &str| match field(name) {
+        None | Some(Json::Null) => Ok(None),
+        Some(Json::String(s)) => Ok(Some(s.as_str())),
+        Some(_) => Err(format!("\"{}\" must be a string", name)),
+    };
+    let limit = |name: &str, default: u64| match field(name) {
+        None | Some(Json::Null) => Ok(default),
+        Some(Json::Number(n)) if n.fract() == 0.0 && *n >= 0.0 => Ok(*n as u64),
+        Some(_) => Err(format!("\"{}\" must be a whole number", name)),
+    };
+    let options = VmOptions {
+        check_stack: false,
+        max_instructions: Some(limit("max_instructions", limits.max_instructions)?),
+        max_string_bytes: Some(limit("max_string_bytes", limits.max_string_bytes)?),
+        ..Default::default()
+    };
+    let max_output_bytes = limit("max_output_bytes", limits.max_output_bytes as u64)? as usize;
+    let stdin = string("stdin")?.unwrap_or("");
+
+    let bytecode = match (string("source")?, string("bytecode")?) {
+        (Some(source), None) => {
+            match compiler::compile_source_with_warnings(
+                source,
+                FILENAME,
+                &CompileOptions::default(),
+            ) {
+                Ok((bytecode, warnings)) => {
+                    outcome.diagnostics = warnings;
+                    bytecode
+                }
+                Err(e) => {
+                    outcome.stage = Some("compile");
+                    outcome.exit_status = Some(exit::COMPILE);
+                    outcome.error = Some(e.to_string());
+                    outcome.diagnostics.push(Diagnostic::from_error(&e));
+                    return Ok(());
+                }
+            }
+        }
+        (None, Some(text)) => base64::decode(text).map_err(|e| format!("bytecode: {}", e))?,
+        (Some(_), Some(_)) => return Err("give \"source\" or \"bytecode\", not both".to_string()),
+        (None, None) => return Err("missing \"source\" or \"bytecode\"".to_string()),
+    };
+
+    let host = CaptureHost::new(stdin).with_output_limit(max_output_bytes);
+    let mut vm = VM::new(host, &options);
+    if let Err(e) = vm.load(&bytecode) {
+        outcome.stage = Some("bytecode");
+        outcome.exit_status = Some(exit::runtime_code(&e));
+        outcome.error = Some(e.to_string());
+        return Ok(());
+    }
+    let run = vm.run();
+    let result = vm.result();
+    outcome.instructions = result.instructions;
+    outcome.stdout = result.output.clone().unwrap_or_default();
+    match run {
+        Ok(()) => outcome.exit_status = Some(result.exit_status()),
+        Err(e) => {
+            outcome.stage = Some("runtime");
+            outcome.exit_status = Some(exit::runtime_code(&e));
+            outcome.error = Some(e.to_string());
+        }
+    }
+    Ok(())
+}
+
+impl Outcome {
+    fn to_json(&self) -> String {
+        let optional = |value: Option<String>| value.unwrap_or_else(|| "null".to_string());
+        let diagnostics: Vec<String> = self.diagnostics.iter().map(diagnostic_json).collect();
+        let mut out = String::new();
+        let _ = write!(
+            out,
+            "{{\"id\": {}, \"ok\": {}, \"stage\": {}, \"stdout\": {}, \"exit_status\": {}, \"instructions\": {}, \"error\": {}, \"diagnostics\": [{}]}}",
+            optional(self.id.clone()),
+            self.stage.is_none(),
+            optional(self.stage.map(json_string)),
+            json_string(&self.stdout),
+            optional(self.exit_status.map(|s| s.to_string())),
+            self.instructions,
+            optional(self.error.as_deref().map(json_string)),
+            diagnostics.join(", ")
+        );
+        out
+    }
+}
+
+fn diagnostic_json(diagnostic: &Diagnostic) -> String {
+    let severity = match diagnostic.severity {
+        Severity::Error => "error",
+        Severity::Warning => "warning",
+    };
+    format!(
+        "{{\"severity\": \"{}\", \"lint\": {}, \"line\": {}, \"col\": {}, \"message\": {}, \"help\": {}}}",
+        severity,
+        diagnostic
+            .lint
+            .map_or_else(|| "null".to_string(), |lint| json_string(lint.name())),
+        diagnostic.line,
+        diagnostic.col,
+        json_string(&diagnostic.message),
+        diagnostic
+            .help
+            .as_deref()
+            .map_or_else(|| "null".to_string(), json_string)
+    )
+}
@@ -0,0 +1,279 @@
+// Arbitrary-precision integers behind BIG. The magnitude is stored in base
+// 10^9 limbs, least significant first, with no trailing zero limbs; zero has
+// no limbs and is never negative.
+use std::cmp::Ordering;
+
+const BASE: u64 = 1_000_000_000;
+
+// Largest result arithmetic may produce; keeps a runaway loop from spending
+// seconds on each multiplication.
+pub const MAX_DIGITS: usize = 20_000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BigInt {
+    negative: bool,
+    limbs: Vec<u32>,
+}
+
+// For fuzzing: any limbs, reduced below the base and trimmed the way
+// arithmetic results are.
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for BigInt {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        let negative = u.arbitrary()?;
+        let limbs: Vec<u32> = u.arbitrary()?;
+        let limbs = limbs
+            .into_iter()
+            .take(MAX_DIGITS / 9)
+            .map(|limb| limb % BASE as u32)
+            .collect();
+        Ok(Self::from_parts(negative, limbs))
+    }
+}
+
+impl BigInt {
+    // Decimal digits with an optional leading '-'.
+    pub fn parse(text: &str) -> Option<Self> {
+        let (negative, digits) = match text.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, text),
+        };
+        if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        let mut limbs = Vec::with_capacity(digits.len() / 9 + 1);
+        let mut end = digits.len();
+        while end > 0 {
+            let start = end.saturating_sub(9);
+            limbs.push(digits[start..end].parse().ok()?);
+            end = start;
+        }
+        Some(Self::from_parts(negative, limbs))
+    }
+
+    // Exact conversion of a whole, finite number.
+    pub fn from_f64(n: f64) -> Option<Self> {
+        if !n.is_finite() || n.fract() != 0.0 {
+            return None;
+        }
+        let magnitude = n.abs();
+        let mut value = if magnitude < 18_446_744_073_709_551_616.0 {
+            Self::from_u64(magnitude as u64)
+        } else {
+            // mantissa * 2^exponent, with exponent > 0 at this size
+            let bits = magnitude.to_bits();
+            let exponent = (bits >> 52) as i32 - 1075;
+            let mut value = Self::from_u64((bits & ((1 << 52) - 1)) | (1 << 52));
+            for _ in 0..exponent {
+                value.limbs = mul_small(&value.limbs, 2);
+            }
+            value
+        };
+        value.negative = n < 0.0 && !value.limbs.is_empty();
+        Some(value)
+    }
+
+    fn from_u64(mut n: u64) -> Self {
+        let mut limbs = Vec::new();
+        while n > 0 {
+            limbs.push((n % BASE) as u32);
+            n /= BASE;
+        }
+        Self {
+            negative: false,
+            limbs,
+        }
+    }
+
+    fn from_parts(negative: bool, mut limbs: Vec<u32>) -> Self {
+        while limbs.last() == Some(&0) {
+            limbs.pop();
+        }
+        Self {
+            negative: negative && !limbs.is_empty(),
+            limbs,
+        }
+    }
+
+    // Nearest f64; large values round or become infinite.
+    pub fn to_f64(&self) -> f64 {
+        self.to_string().parse().unwrap_or(f64::NAN)
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.limbs.is_empty()
+    }
+
+    pub fn digits(&self) -> usize {
+        match self.limbs.last() {
+            Some(top) => (self.limbs.len() - 1) * 9 + top.to_string().len(),
+            None => 1,
+        }
+    }
+
+    pub fn add(&self, other: &Self) -> Self {
+        if self.negative == other.negative {
+            return Self::from_parts(self.negative, add_mag(&self.limbs, &other.limbs));
+        }
+        match cmp_mag(&self.limbs, &other.limbs) {
+            Ordering::Less => Self::from_parts(other.negative, sub_mag(&other.limbs, &self.limbs)),
+            _ => Self::from_parts(self.negative, sub_mag(&self.limbs, &other.limbs)),
+        }
+    }
+
+    pub fn sub(&self, other: &Self) -> Self {
+        self.add(&Self {
+            negative: !other.negative && !other.is_zero(),
+            limbs: other.limbs.clone(),
+        })
+    }
+
+    pub fn mul(&self, other: &Self) -> Self {
+        let mut out = vec![0u64; self.limbs.len() + other.limbs.len()];
+        for (i, &a) in self.limbs.iter().enumerate() {
+            let mut carry = 0;
+            for (j, &b) in other.limbs.iter().enumerate() {
+                let cur = out[i + j] + a as u64 * b as u64 + carry;
+                out[i + j] = cur % BASE;
+                carry = cur / BASE;
+            }
+            out[i + other.limbs.len()] += carry;
+        }
+        let limbs = out.into_iter().map(|limb| limb as u32).collect();
+        Self::from_parts(self.negative != other.negative, limbs)
+    }
+
+    // Division rounding toward negative infinity; None when dividing by zero.
+    pub fn div_floor(&self, other: &Self) -> Option<Self> {
+        if other.is_zero() {
+            return None;
+        }
+        let (quotient, remainder) = divmod_mag(&self.limbs, &other.limbs);
+        let negative = self.negative != other.negative;
+        let quotient = Self::from_parts(negative, quotient);
+        if negative && !remainder.is_empty() {
+            Some(quotient.sub(&Self::from_u64(1)))
+        } else {
+            Some(quotient)
+        }
+    }
+}
+
+impl Ord for BigInt {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => cmp_mag(&self.limbs, &other.limbs),
+            (true, true) => cmp_mag(&other.limbs, &self.limbs),
+        }
+    }
+}
+
+impl PartialOrd for BigInt {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl std::fmt::Display for BigInt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Some((top, rest)) = self.limbs.split_last() else {
+            return write!(f, "0");
+        };
+        if self.negative {
+            write!(f, "-")?;
+        }
+        write!(f, "{}", top)?;
+        for limb in rest.iter().rev() {
+            write!(f, "{:09}", limb)?;
+        }
+        Ok(())
+    }
+}
+
+fn cmp_mag(a: &[u32], b: &[u32]) -> Ordering {
+    a.len()
+        .cmp(&b.len())
+        .then_with(|| a.iter().rev().cmp(b.iter().rev()))
+}
+
+fn add_mag(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut out = Vec::with_capacity(a.len().max(b.len()) + 1);
+    let mut carry = 0;
+    for i in 0..a.len().max(b.len()) {
+        let sum = *a.get(i).unwrap_or(&0) as u64 + *b.get(i).unwrap_or(&0) as u64 + carry;
+        out.push((sum % BASE) as u32);
+        carry = sum / BASE;
+    }
+    if carry > 0 {
+        out.push(carry as u32);
+    }
+    out
+}
+
+// a - b where |a| >= |b|.
+fn sub_mag(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut out = Vec::with_capacity(a.len());
+    let mut borrow = 0;
+    for (i, &limb) in a.iter().enumerate() {
+        let mut cur = limb as i64 - *b.get(i).unwrap_or(&0) as i64 - borrow;
+        borrow = i64::from(cur < 0);
+        if cur < 0 {
+            cur += BASE as i64;
+        }
+        out.push(cur as u32);
+    }
+    while out.last() == Some(&0) {
+        out.pop();
+    }
+    out
+}
+
+fn mul_small(a: &[u32], m: u64) -> Vec<u32> {
+    let mut out = Vec::with_capacity(a.len() + 1);
+    let mut carry = 0;
+    for &limb in a {
+        let cur = limb as u64 * m + carry;
+        out.push((cur % BASE) as u32);
+        carry = cur / BASE;
+    }
+    while carry > 0 {
+        out.push((carry % BASE) as u32);
+        carry /= BASE;
+    }
+    while out.last() == Some(&0) {
+        out.pop();
+    }
+    out
+}
+
+// Schoolbook long division, one base-10^9 digit at a time; each quotient
+// digit is found by binary search.
+fn divmod_mag(a: &[u32], b: &[u32]) -> (Vec<u32>, Vec<u32>) {
+    let mut quotient = vec![0; a.len()];
+    let mut remainder: Vec<u32> = Vec::new();
+    for i in (0..a.len()).rev() {
+        remainder.insert(0, a[i]);
+        while remainder.last() == Some(&0) {
+            remainder.pop();
+        }
+        let (mut lo, mut hi) = (0, BASE - 1);
+        while lo < hi {
+            let mid = (lo + hi).div_ceil(2);
+            if cmp_mag(&mul_small(b, mid), &remainder) == Ordering::Greater {
+                hi = mid - 1;
+            } else {
+                lo = mid;
+            }
+        }
+        if lo > 0 {
+            remainder = sub_mag(&remainder, &mul_small(b, lo));
+        }
+        quotient[i] = lo as u32;
+    }
+    while quotient.last() == Some(&0) {
+        quotient.pop();
+    }
+    (quotient, remainder)
+}
@@ -0,0 +1,158 @@
+// Built-in functions callable as `NAME(args)`. The compiler resolves names
+// and checks arity here, CALL_BUILTIN carries `id` in its operand, and the
+// VM's dispatch in `call_builtin` must handle every id listed. TOUCHY is not
+// in this table: it compiles to INPUT/INPUT_PROMPT/INPUT_TIMEOUT rather than
+// a call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Builtin {
+    pub name: &'static str,
+    pub id: u8,
+    pub arity: usize,
+}
+
+pub const BUILTINS: &[Builtin] = &[
+    Builtin {
+        name: "TRANSFORM",
+        id: 0,
+        arity: 1,
+    },
+    Builtin {
+        name: "RIZZED",
+        id: 1,
+        arity: 1,
+    },
+    Builtin {
+        name: "SEED",
+        id: 2,
+        arity: 1,
+    },
+    Builtin {
+        name: "RANDOM",
+        id: 3,
+        arity: 1,
+    },
+    Builtin {
+        name: "TYPEOF",
+        id: 4,
+        arity: 1,
+    },
+    Builtin {
+        name: "EMPTY",
+        id: 5,
+        arity: 1,
+    },
+    Builtin {
+        name: "DRIP",
+        id: 6,
+        arity: 2,
+    },
+    Builtin {
+        name: "YOINK",
+        id: 7,
+        arity: 1,
+    },
+    Builtin {
+        name: "YEET",
+        id: 8,
+        arity: 2,
+    },
+    Builtin {
+        name: "FORMATTIME",
+        id: 9,
+        arity: 2,
+    },
+    Builtin {
+        name: "PARSETIME",
+        id: 10,
+        arity: 2,
+    },
+    Builtin {
+        name: "SHA256",
+        id: 11,
+        arity: 1,
+    },
+    Builtin {
+        name: "HASH",
+        id: 12,
+        arity: 1,
+    },
+    Builtin {
+        name: "BASE64",
+        id: 13,
+        arity: 1,
+    },
+    Builtin {
+        name: "UNBASE64",
+        id: 14,
+        arity: 1,
+    },
+    Builtin {
+        name: "FLOORDIV",
+        id: 15,
+        arity: 2,
+    },
+    Builtin {
+        name: "TRUNC",
+        id: 16,
+        arity: 1,
+    },
+    Builtin {
+        name: "BIG",
+        id: 17,
+        arity: 1,
+    },
+    Builtin {
+        name: "TRYTRANSFORM",
+        id: 18,
+        arity: 1,
+    },
+    Builtin {
+        name: "PEEK",
+        id: 19,
+        arity: 1,
+    },
+    Builtin {
+        name: "POKE",
+        id: 20,
+        arity: 2,
+    },
+];
+
+// Style names DRIP accepts and their ANSI SGR codes.
+pub const STYLES: &[(&str, u8)] = &[
+    ("bold", 1),
+    ("dim", 2),
+    ("italic", 3),
+    ("underline", 4),
+    ("black", 30),
+    ("red", 31),
+    ("green", 32),
+    ("yellow", 33),
+    ("blue", 34),
+    ("magenta", 35),
+    ("cyan", 36),
+    ("white", 37),
+];
+
+// SGR parameters for a space-separated style list such as "bold red", or
+// None if a name is unknown. An empty list gives an empty string.
+pub fn ansi_codes(style: &str) -> Option<String> {
+    let codes = style
+        .split_whitespace()
+        .map(|name| {
+            STYLES
+                .iter()
+                .find(|(known, _)| *known == name)
+                .map(|(_, code)| code.to_string())
+        })
+        .collect::<Option<Vec<_>>>()?;
+    Some(codes.join(";"))
+}
+
+pub fn lookup(name: &str) -> Option<&'static Builtin> {
+    BUILTINS.iter().find(|b| b.name == name)
+}
+
+pub fn by_id(id: u8) -> Option<&'static Builtin> {
+    BUILTINS.iter().find(|b| b.id == id)
+}
@@ -0,0 +1,397 @@
+use crate::bigint::BigInt;
+use crate::value::Value;
+use std::rc::Rc;
+
+pub const MAGIC: &[u8; 4] = b"BRBC";
+// Version 5 added header flags and the opcodes from CALL_BUILTIN (0x14) on;
+// version 4 VMs read the rest of the format unchanged. `MIN_VERSION` is the
+// oldest version the compiler can still target.
+pub const VERSION: u16 = 5;
+pub const MIN_VERSION: u16 = 4;
+
+// Header flag bits
+pub const FLAG_STRICT: u16 = 0x0001; // 💀 rejects mixed number/string operands
+pub const FLAG_STRICT_MATH: u16 = 0x0002; // arithmetic giving NaN or infinity fails
+pub const FLAG_GLOBALS: u16 = 0x0004; // a u16 global slot count follows the flags
+pub const FLAG_STACK_DEPTH: u16 = 0x0008; // function entries end with a u16 max stack depth
+pub const KNOWN_FLAGS: u16 = FLAG_STRICT | FLAG_STRICT_MATH | FLAG_GLOBALS | FLAG_STACK_DEPTH;
+
+// Global slots when the header declares none: one per braincell. A declared
+// count can go up to MAX_GLOBALS, the most a u8 LOAD_GLOBAL operand reaches.
+pub const DEFAULT_GLOBALS: usize = 7;
+pub const MAX_GLOBALS: usize = 256;
+
+// Loader limits, also enforced by the compiler so its output always loads.
+pub const MAX_CONSTANTS: u32 = 16_777_216;
+pub const MAX_FUNCTIONS: u32 = 65_536;
+
+// Smallest encodings, used to reject counts the file cannot possibly hold.
+const MIN_CONSTANT_SIZE: usize = 1 + 4; // tag + empty string length
+const FUNCTION_ENTRY_SIZE: usize = 12;
+const STACK_DEPTH_SIZE: usize = 2;
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct FunctionEntry {
+    pub name_const: u32,
+    pub arity: u16,
+    pub local_count: u16,
+    pub code_offset: u32,
+    pub max_stack: u16, // deepest operand stack the body reaches; 0 when not declared
+}
+
+// Bytes per function-table entry under `flags`.
+pub fn function_entry_size(flags: u16) -> usize {
+    if flags & FLAG_STACK_DEPTH != 0 {
+        FUNCTION_ENTRY_SIZE + STACK_DEPTH_SIZE
+    } else {
+        FUNCTION_ENTRY_SIZE
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct Module {
+    pub version: u16,
+    pub flags: u16,
+    pub globals: usize, // global slots the program needs
+    pub constants: Vec<Value>,
+    pub functions: Vec<FunctionEntry>,
+    pub code: Vec<u8>,
+}
+
+impl Module {
+    pub fn function_name(&self, entry: &FunctionEntry) -> &str {
+        match self.constants.get(entry.name_const as usize) {
+            Some(Value::String(name)) => name,
+            _ => "?",
+        }
+    }
+
+    pub fn locate(&self, offset: usize) -> String {
+        locate(
+            self.functions
+                .iter()
+                .map(|f| (self.function_name(f), f.code_offset as usize)),
+            offset,
+        )
+    }
+}
+
+// Where `offset` falls in the code, as "fib+0x12" from the start of the
+// function it is in or "<main>+0x12" in main code. `functions` gives each
+// function's name and code offset in function-table order; a stripped
+// function shows as "function #N".
+pub fn locate<'a>(functions: impl IntoIterator<Item = (&'a str, usize)>, offset: usize) -> String {
+    let within = functions
+        .into_iter()
+        .enumerate()
+        .filter(|&(_, (_, start))| start <= offset)
+        .max_by_key(|&(_, (_, start))| start);
+    match within {
+        Some((i, ("", start))) => format!("function #{}+0x{:x}", i, offset - start),
+        Some((_, (name, start))) => format!("{}+0x{:x}", name, offset - start),
+        None => format!("{}+0x{:x}", crate::profile::MAIN, offset),
+    }
+}
+
+pub fn decode(bytecode: &[u8]) -> Result<Module, String> {
+    let mut pos = 0;
+    let mut constants = Vec::new();
+    let mut functions = Vec::new();
+
+    // Verify magic
+    if bytecode.len() < 4 || &bytecode[pos..pos + 4] != MAGIC {
+        return Err("invalid bytecode file".to_string());
+    }
+    pos += 4;
+
+    // Read version and flags
+    if bytecode.len() < pos + 4 {
+        return Err("invalid bytecode header".to_string());
+    }
+    let version = u16::from_le_bytes([bytecode[pos], bytecode[pos + 1]]);
+    let flags = u16::from_le_bytes([bytecode[pos + 2], bytecode[pos + 3]]);
+    pos += 4;
+
+    let mut globals = DEFAULT_GLOBALS;
+    if flags & FLAG_GLOBALS != 0 {
+        if bytecode.len() < pos + 2 {
+            return Err("invalid bytecode header".to_string());
+        }
+        globals = u16::from_le_bytes([bytecode[pos], bytecode[pos + 1]]) as usize;
+        pos += 2;
+        if globals > MAX_GLOBALS {
+            return Err(format!(
+                "header: declared global count {} exceeds limit {}",
+                globals, MAX_GLOBALS
+            ));
+        }
+    }
+
+    // Read constant pool
+    if bytecode.len() < pos + 4 {
+        return Err("invalid constant pool header".to_string());
+    }
+    let const_count = u32::from_le_bytes([
+        bytecode[pos],
+        bytecode[pos + 1],
+        bytecode[pos + 2],
+        bytecode[pos + 3],
+    ]);
+    pos += 4;
+
+    if const_count > MAX_CONSTANTS {
+        return Err(format!(
+            "constant pool: declared count {} exceeds limit {}",
+            const_count, MAX_CONSTANTS
+        ));
+    }
+    let remaining = bytecode.len() - pos;
+    if const_count as usize > remaining / MIN_CONSTANT_SIZE {
+        return Err(format!(
+            "constant pool: declared count {} does not fit in remaining {} bytes",
+            const_count, remaining
+        ));
+    }
+
+    for idx in 0..const_count {
+        if bytecode.len() <= pos {
+            return Err("invalid constant entry".to_string());
+        }
+        let tag = bytecode[pos];
+        pos += 1;
+
+        match tag {
+            1 => {
+                // Number
+                if bytecode.len() < pos + 8 {
+                    return Err("invalid number constant".to_string());
+                }
+                let bytes = [
+                    bytecode[pos],
+                    bytecode[pos + 1],
+                    bytecode[pos + 2],
+                    bytecode[pos + 3],
+                    bytecode[pos + 4],
+                    bytecode[pos + 5],
+                    bytecode[pos + 6],
+                    bytecode[pos + 7],
+                ];
+                let num = f64::from_le_bytes(bytes);
+                constants.push(Value::Number(num));
+                pos += 8;
+            }
+            2 => {
+                // String
+                if bytecode.len() < pos + 4 {
+                    return Err("invalid string constant".to_string());
+                }
+                let len = u32::from_le_bytes([
+                    bytecode[pos],
+                    bytecode[pos + 1],
+                    bytecode[pos + 2],
+                    bytecode[pos + 3],
+                ]) as usize;
+                pos += 4;
+
+                let remaining = bytecode.len() - pos;
+                if len > remaining {
+                    return Err(format!(
+                        "constant pool: string #{} declares length {} but only {} bytes remain",
+                        idx, len, remaining
+                    ));
+                }
+                let bytes = bytecode[pos..pos + len].to_vec();
+                pos += len;
+
+                let s = String::from_utf8(bytes)
+                    .map_err(|_| "invalid UTF-8 in string constant".to_string())?;
+                constants.push(Value::String(Rc::new(s)));
+            }
+            3 => {
+                // Big integer, as decimal text
+                if bytecode.len() < pos + 4 {
+                    return Err("invalid big integer constant".to_string());
+                }
+                let len = u32::from_le_bytes([
+                    bytecode[pos],
+                    bytecode[pos + 1],
+                    bytecode[pos + 2],
+                    bytecode[pos + 3],
+                ]) as usize;
+                pos += 4;
+                let n = bytecode
+                    .get(pos..pos.saturating_add(len))
+                    .and_then(|text| std::str::from_utf8(text).ok())
+                    .and_then(BigInt::parse)
+                    .ok_or_else(|| "invalid big integer constant".to_string())?;
+                pos += len;
+                constants.push(Value::Big(Rc::new(n)));
+            }
+            _ => return Err("unknown constant type".to_string()),
+        }
+    }
+
+    // Read function table (only for v4+)
+    if version >= 4 {
+        if bytecode.len() < pos + 4 {
+            return Err("invalid function table header".to_string());
+        }
+        let func_count = u32::from_le_bytes([
+            bytecode[pos],
+            bytecode[pos + 1],
+            bytecode[pos + 2],
+            bytecode[pos + 3],
+        ]);
+        pos += 4;
+
+        if func_count > MAX_FUNCTIONS {
+            return Err(format!(
+                "function table: declared count {} exceeds limit {}",
+                func_count, MAX_FUNCTIONS
+            ));
+        }
+        let entry_size = function_entry_size(flags);
+        let remaining = bytecode.len() - pos;
+        if func_count as usize > remaining / entry_size {
+            return Err(format!(
+                "function table: declared count {} does not fit in remaining {} bytes",
+                func_count, remaining
+            ));
+        }
+
+        for _ in 0..func_count {
+            if bytecode.len() < pos + entry_size {
+                return Err("invalid function entry".to_string());
+            }
+
+            let name_const_idx = u32::from_le_bytes([
+                bytecode[pos],
+                bytecode[pos + 1],
+                bytecode[pos + 2],
+                bytecode[pos + 3],
+            ]);
+            let arity = u16::from_le_bytes([bytecode[pos + 4], bytecode[pos + 5]]);
+            let local_count = u16::from_le_bytes([bytecode[pos + 6], bytecode[pos + 7]]);
+            let code_offset = u32::from_le_bytes([
+                bytecode[pos + 8],
+                bytecode[pos + 9],
+                bytecode[pos + 10],
+                bytecode[pos + 11],
+            ]);
+            let max_stack = if flags & FLAG_STACK_DEPTH != 0 {
+                u16::from_le_bytes([bytecode[pos + 12], bytecode[pos + 13]])
+            } else {
+                0
+            };
+            pos += entry_size;
+
+            if name_const_idx >= constants.len() as u32 {
+                return Err("function name constant index out of bounds".to_string());
+            }
+
+            if !matches!(constants[name_const_idx as usize], Value::String(_)) {
+                return Err("function name constant must be a string".to_string());
+            }
+
+            if arity > local_count {
+                return Err(format!(
+                    "function entry: arity {} exceeds local count {}",
+                    arity, local_count
+                ));
+            }
+
+            functions.push(FunctionEntry {
+                name_const: name_const_idx,
+                arity,
+                local_count,
+                code_offset,
+                max_stack,
+            });
+        }
+    }
+
+    // Read code section
+    if bytecode.len() < pos + 4 {
+        return Err("invalid code section header".to_string());
+    }
+    let code_size = u32::from_le_bytes([
+        bytecode[pos],
+        bytecode[pos + 1],
+        bytecode[pos + 2],
+        bytecode[pos + 3],
+    ]);
+    pos += 4;
+
+    let remaining = bytecode.len() - pos;
+    if code_size as usize > remaining {
+        return Err(format!(
+            "code section: declared size {} exceeds remaining {} bytes",
+            code_size, remaining
+        ));
+    }
+    let code = bytecode[pos..pos + code_size as usize].to_vec();
+    pos += code_size as usize;
+
+    if pos != bytecode.len() {
+        return Err("trailing data after code section".to_string());
+    }
+
+    Ok(Module {
+        version,
+        flags,
+        globals,
+        constants,
+        functions,
+        code,
+    })
+}
+
+// Inverse of `decode`.
+pub fn encode(module: &Module) -> Vec<u8> {
+    let mut out = MAGIC.to_vec();
+    out.extend_from_slice(&module.version.to_le_bytes());
+    out.extend_from_slice(&module.flags.to_le_bytes());
+    if module.flags & FLAG_GLOBALS != 0 {
+        out.extend_from_slice(&(module.globals as u16).to_le_bytes());
+    }
+
+    out.extend_from_slice(&(module.constants.len() as u32).to_le_bytes());
+    for constant in &module.constants {
+        match constant {
+            Value::Number(n) => {
+                out.push(1);
+                out.extend_from_slice(&n.to_le_bytes());
+            }
+            Value::String(s) => {
+                out.push(2);
+                out.extend_from_slice(&(s.len() as u32).to_le_bytes());
+                out.extend_from_slice(s.as_bytes());
+            }
+            Value::Big(n) => {
+                let text = n.to_string();
+                out.push(3);
+                out.extend_from_slice(&(text.len() as u32).to_le_bytes());
+                out.extend_from_slice(text.as_bytes());
+            }
+        }
+    }
+
+    if module.version >= 4 {
+        out.extend_from_slice(&(module.functions.len() as u32).to_le_bytes());
+        for func in &module.functions {
+            out.extend_from_slice(&func.name_const.to_le_bytes());
+            out.extend_from_slice(&func.arity.to_le_bytes());
+            out.extend_from_slice(&func.local_count.to_le_bytes());
+            out.extend_from_slice(&func.code_offset.to_le_bytes());
+            if module.flags & FLAG_STACK_DEPTH != 0 {
+                out.extend_from_slice(&func.max_stack.to_le_bytes());
+            }
+        }
+    }
+
+    out.extend_from_slice(&(module.code.len() as u32).to_le_bytes());
+    out.extend_from_slice(&module.code);
+    out
+}
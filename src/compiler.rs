@@ -1,49 +1,263 @@
-use crate::parser::{BinaryOp, Expr, Function, Program, Statement};
-use std::collections::HashMap;
+use crate::bigint::{self, BigInt};
+use crate::builtins;
+use crate::bytecode;
+use crate::diagnostic::{Diagnostic, Severity};
+use crate::error::CompileError;
+use crate::lexer::{self, Span};
+use crate::lint::{self, LintLevels};
+use crate::opcode;
+use crate::parser::{self, BinaryOp, Const, Expr, Function, Program, SpannedStatement, Statement};
+use crate::profile::{self, Profile};
+use crate::typecheck;
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+
+// Calls a one-line function needs in the profile before `--profile-use`
+// inlines it.
+pub const INLINE_MIN_CALLS: u64 = 100;
+
+#[derive(Debug, Clone, Default)]
+pub struct CompileOptions {
+    pub strict: bool,      // set the strict header flag
+    pub strict_math: bool, // set the strict-math header flag
+    pub lints: LintLevels,
+    pub version: Option<u16>, // bytecode version to emit, bytecode::VERSION if unset
+    pub no_optimize: bool,    // skip folding, DUP and loop hoisting
+    pub profile: Option<Profile>, // run profile that guides layout and inlining
+}
+
+// Full in-memory pipeline: source text -> BRBC bytes.
+pub fn compile_source(
+    source: &str,
+    filename: &str,
+    options: &CompileOptions,
+) -> Result<Vec<u8>, CompileError> {
+    compile_source_with_warnings(source, filename, options).map(|(bytecode, _)| bytecode)
+}
+
+// Like `compile_source`, but also returns the lint warnings. Lints set to
+// deny fail the compile instead.
+pub fn compile_source_with_warnings(
+    source: &str,
+    filename: &str,
+    options: &CompileOptions,
+) -> Result<(Vec<u8>, Vec<Diagnostic>), CompileError> {
+    compile_source_full(source, filename, options)
+        .map(|(bytecode, warnings, _)| (bytecode, warnings))
+}
+
+// Like `compile_source`, but also returns which source line each part of the
+// code section came from, for `brvm debug`. BRBC itself has no line table.
+pub fn compile_source_with_lines(
+    source: &str,
+    filename: &str,
+    options: &CompileOptions,
+) -> Result<(Vec<u8>, LineTable), CompileError> {
+    compile_source_full(source, filename, options).map(|(bytecode, _, lines)| (bytecode, lines))
+}
 
-pub fn compile(program: Program) -> Result<Vec<u8>, String> {
+fn compile_source_full(
+    source: &str,
+    filename: &str,
+    options: &CompileOptions,
+) -> Result<(Vec<u8>, Vec<Diagnostic>, LineTable), CompileError> {
+    let tokens = lexer::tokenize_spanned(source, filename)?;
+    log::debug!(file = filename, tokens = tokens.len(); "lexed");
+    let program = parser::parse(tokens, filename)?;
+    log::debug!(
+        file = filename,
+        functions = program.functions.len(),
+        consts = program.consts.len(),
+        statements = program.main_statements.len();
+        "parsed"
+    );
+    typecheck::check(&program, filename, options.strict)?;
+    log::debug!(file = filename, strict = options.strict; "type checked");
     let mut compiler = Compiler::new();
+    let bytecode = compiler
+        .compile_program(&program, options)
+        .map_err(|e| compiler.error(filename, &e))?;
+
+    let (denied, warnings): (Vec<_>, Vec<_>) = lint::check(&program, filename, &options.lints)
+        .into_iter()
+        .partition(|d| d.severity == Severity::Error);
+    log::debug!(file = filename, warnings = warnings.len(), denied = denied.len(); "linted");
+    let mut denied = denied.into_iter().map(Diagnostic::into_error);
+    if let Some(mut first) = denied.next() {
+        first.additional = denied.collect();
+        return Err(first);
+    }
+    let lines = LineTable {
+        entries: compiler.lines,
+    };
+    log::info!(file = filename, bytes = bytecode.len(), version = compiler.version; "compiled");
+    Ok((bytecode, warnings, lines))
+}
 
-    compiler.declare_functions(&program.functions)?;
+// (code offset, line) pairs in offset order. Each entry covers the code up
+// to the next one.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LineTable {
+    entries: Vec<(u32, usize)>,
+}
 
-    // Compile all functions first. They are appended after main in final bytecode.
-    for func in &program.functions {
-        compiler.compile_function(func)?;
+impl LineTable {
+    pub fn line_at(&self, offset: usize) -> Option<usize> {
+        let after = self
+            .entries
+            .partition_point(|&(start, _)| start as usize <= offset);
+        after.checked_sub(1).map(|i| self.entries[i].1)
     }
+}
+
+pub fn compile(program: Program, filename: &str) -> Result<Vec<u8>, CompileError> {
+    compile_with_options(program, filename, &CompileOptions::default())
+}
 
-    // Get function code
-    let function_code_parts = std::mem::take(&mut compiler.function_code_parts);
+pub fn compile_with_options(
+    program: Program,
+    filename: &str,
+    options: &CompileOptions,
+) -> Result<Vec<u8>, CompileError> {
+    let mut compiler = Compiler::new();
+    compiler
+        .compile_program(&program, options)
+        .map_err(|e| compiler.error(filename, &e))
+}
 
-    // Now compile main statements
-    for stmt in &program.main_statements {
-        compiler.compile_main_statement(stmt)?;
+// Points a duplicate definition at the first one; hand-built ASTs may have
+// no spans to point at.
+fn first_defined(span: Option<Span>) -> String {
+    match span {
+        Some(span) if span.line > 0 => {
+            format!(" (first defined on line {}, col {})", span.line, span.col)
+        }
+        _ => String::new(),
     }
+}
 
-    // Add HALT at the end of main
-    compiler.emit_op(0x01); // HALT
+fn binary_opcode(op: BinaryOp) -> u8 {
+    match op {
+        BinaryOp::Add => 0x05,
+        BinaryOp::Subtract => 0x06,
+        BinaryOp::Multiply => 0x07,
+        BinaryOp::Divide => 0x08,
+        BinaryOp::Equal => 0x1B, // EQUAL
+    }
+}
 
-    // Get main code size before appending functions
-    let mut main_code_size = compiler.code.len() as u32;
+// Call-free expressions always produce the same value (or the same error,
+// which the first evaluation would already raise), so a repeat can be DUPed.
+fn is_pure(expr: &Expr) -> bool {
+    match expr {
+        Expr::Number(..) | Expr::String(..) | Expr::Variable(..) => true,
+        Expr::Binary { left, right, .. } => is_pure(left) && is_pure(right),
+        Expr::FunctionCall { .. } | Expr::UserFunctionCall { .. } => false,
+    }
+}
 
-    // Now update function code offsets and append function code
-    for (i, mut func_code) in function_code_parts.into_iter().enumerate() {
-        compiler.relocate_jumps(&mut func_code, main_code_size)?;
-        let size = func_code.len() as u32;
-        compiler.functions[i].code_offset = main_code_size;
-        main_code_size += size; // Track cumulative offset for next function
+// The value of `BIG(<literal>)`, when the literal is a valid one.
+fn big_literal(name: &str, args: &[Expr]) -> Option<BigInt> {
+    let n = match (name, args) {
+        ("BIG", [Expr::Number(n, _)]) => BigInt::from_f64(*n),
+        ("BIG", [Expr::String(s, _)]) => BigInt::parse(s),
+        _ => None,
+    }?;
+    (n.digits() <= bigint::MAX_DIGITS).then_some(n)
+}
 
-        compiler.code.extend_from_slice(&func_code);
+// The value of an expression made only of number literals, when it has a
+// finite one; anything that would fail at runtime is left for the VM.
+fn fold_number(expr: &Expr, consts: &HashMap<String, Expr>) -> Option<f64> {
+    let n = match expr {
+        Expr::Number(n, _) => *n,
+        Expr::Variable(name, _) => fold_number(consts.get(name)?, consts)?,
+        Expr::Binary {
+            op, left, right, ..
+        } => {
+            let a = fold_number(left, consts)?;
+            let b = fold_number(right, consts)?;
+            match op {
+                BinaryOp::Add => a + b,
+                BinaryOp::Subtract => a - b,
+                BinaryOp::Multiply => a * b,
+                BinaryOp::Divide if b != 0.0 => a / b,
+                BinaryOp::Divide => return None,
+                BinaryOp::Equal => f64::from(u8::from(a == b)),
+            }
+        }
+        _ => return None,
+    };
+    n.is_finite().then_some(n)
+}
+
+// Collects variables in evaluation order; false if the expression makes calls.
+fn condition_reads(expr: &Expr, reads: &mut Vec<String>) -> bool {
+    match expr {
+        Expr::Number(..) | Expr::String(..) => true,
+        Expr::Variable(name, _) => {
+            if !reads.contains(name) {
+                reads.push(name.clone());
+            }
+            true
+        }
+        Expr::Binary { left, right, .. } => {
+            condition_reads(left, reads) && condition_reads(right, reads)
+        }
+        Expr::FunctionCall { .. } | Expr::UserFunctionCall { .. } => false,
     }
+}
 
-    // Build the bytecode
-    compiler.write_bytecode()
+// Conditional jumps the plain layout emits for `stmts`: one per ONGOD and
+// SKIBIDI, nested ones included.
+fn count_branches(stmts: &[SpannedStatement]) -> usize {
+    stmts
+        .iter()
+        .map(|stmt| match &stmt.statement {
+            Statement::If {
+                then_block,
+                else_block,
+                ..
+            } => 1 + count_branches(then_block) + else_block.as_deref().map_or(0, count_branches),
+            Statement::While { body, .. } => 1 + count_branches(body),
+            _ => 0,
+        })
+        .sum()
+}
+
+fn assigned_names(stmts: &[SpannedStatement], names: &mut HashSet<String>) {
+    for stmt in stmts {
+        match &stmt.statement {
+            Statement::Assign(name, _, _) | Statement::Copy { dest: name, .. } => {
+                names.insert(name.clone());
+            }
+            Statement::If {
+                then_block,
+                else_block,
+                ..
+            } => {
+                assigned_names(then_block, names);
+                if let Some(else_block) = else_block {
+                    assigned_names(else_block, names);
+                }
+            }
+            Statement::While { body, .. } => assigned_names(body, names),
+            Statement::Print(_)
+            | Statement::PrintInline(_)
+            | Statement::PrintError(_)
+            | Statement::Return(_)
+            | Statement::Throw(_)
+            | Statement::Halt => {}
+        }
+    }
 }
 
 struct FunctionInfo {
-    name: String,
+    name_const: u32,
     arity: u16,
     local_count: u16,
     code_offset: u32,
+    max_stack: u16,
 }
 
 struct Compiler {
@@ -51,18 +265,36 @@ struct Compiler {
     const_map: HashMap<Constant, u32>,
     functions: Vec<FunctionInfo>,
     function_map: HashMap<String, u32>, // name -> function index
+    consts: HashMap<String, Expr>,      // BASED name -> literal
     code: Vec<u8>,
+    lines: Vec<(u32, usize)>, // line table for `code`
+    flags: u16,
+    version: u16,
+    optimize: bool,
 
     // For function compilation
     current_locals: HashMap<String, u16>,
-    function_code_parts: Vec<Vec<u8>>, // Store function code separately
+    hoisted_globals: HashMap<String, u16>, // braincell -> local slot inside a loop
+    hoisted_exprs: Vec<(Expr, u16)>,       // invariant expression -> local slot
+    pokes: bool,                           // the program writes braincells through POKE
+    profile: Option<Profile>,              // set by `--profile-use` unless optimizing is off
+    branch_index: usize,                   // conditional jumps so far in this function
+    inline: HashMap<String, (Vec<String>, Expr)>, // hot function -> (params, returned expr)
+    function_code_parts: Vec<Vec<u8>>,     // Store function code separately
+    function_line_parts: Vec<Vec<(u32, usize)>>,
     in_function: bool,
+    current_function: Option<String>,
+
+    // Where errors are reported: the innermost statement, expression or
+    // declaration being compiled.
+    span: Span,
 }
 
 #[derive(Debug, Clone)]
 enum Constant {
     Number(f64),
     String(Vec<u8>),
+    Big(String), // decimal digits
 }
 
 impl PartialEq for Constant {
@@ -70,6 +302,7 @@ impl PartialEq for Constant {
         match (self, other) {
             (Constant::Number(a), Constant::Number(b)) => a == b,
             (Constant::String(a), Constant::String(b)) => a == b,
+            (Constant::Big(a), Constant::Big(b)) => a == b,
             _ => false,
         }
     }
@@ -83,6 +316,7 @@ impl std::hash::Hash for Constant {
         match self {
             Constant::Number(n) => n.to_bits().hash(state),
             Constant::String(s) => s.hash(state),
+            Constant::Big(s) => s.hash(state),
         }
     }
 }
@@ -94,57 +328,264 @@ impl Compiler {
             const_map: HashMap::new(),
             functions: Vec::new(),
             function_map: HashMap::new(),
+            consts: HashMap::new(),
             code: Vec::new(),
+            lines: Vec::new(),
+            flags: 0,
+            version: bytecode::VERSION,
+            optimize: true,
             current_locals: HashMap::new(),
+            hoisted_globals: HashMap::new(),
+            hoisted_exprs: Vec::new(),
+            pokes: false,
+            profile: None,
+            branch_index: 0,
+            inline: HashMap::new(),
             function_code_parts: Vec::new(),
+            function_line_parts: Vec::new(),
             in_function: false,
+            current_function: None,
+            span: Span::default(),
+        }
+    }
+
+    // Places an error from compile_program at the span it stopped on.
+    fn error(&self, filename: &str, message: &str) -> CompileError {
+        CompileError::new(filename, self.span.line, self.span.col, message)
+    }
+
+    fn compile_program(
+        &mut self,
+        program: &Program,
+        options: &CompileOptions,
+    ) -> Result<Vec<u8>, String> {
+        self.version = options.version.unwrap_or(bytecode::VERSION);
+        if !(bytecode::MIN_VERSION..=bytecode::VERSION).contains(&self.version) {
+            return Err(format!(
+                "unsupported bytecode version {} (expected {} to {})",
+                self.version,
+                bytecode::MIN_VERSION,
+                bytecode::VERSION
+            ));
+        }
+        if options.strict {
+            self.require_version(5, "strict mode")?;
+            self.flags |= bytecode::FLAG_STRICT;
+        }
+        if options.strict_math {
+            self.require_version(5, "strict math")?;
+            self.flags |= bytecode::FLAG_STRICT_MATH;
+        }
+        if self.version >= 5 && !program.functions.is_empty() {
+            self.flags |= bytecode::FLAG_STACK_DEPTH;
+        }
+
+        self.optimize = !options.no_optimize;
+        self.pokes = program.calls_builtin("POKE");
+        self.declare_consts(&program.consts)?;
+        self.declare_functions(&program.functions)?;
+        if self.optimize {
+            self.profile = options.profile.clone();
+        }
+        self.inline = self.inline_candidates(&program.functions);
+        log::debug!(
+            optimize = self.optimize,
+            profile = self.profile.is_some(),
+            inlined = self.inline.len();
+            "optimization settings"
+        );
+        for name in self.inline.keys() {
+            log::trace!(function = name.as_str(); "inlining calls");
+        }
+
+        // Compile all functions first. They are appended after main in final bytecode.
+        for func in &program.functions {
+            self.compile_function(func)?;
+            log::trace!(
+                function = func.name.as_str(),
+                bytes = self.function_code_parts.last().map_or(0, Vec::len);
+                "generated function"
+            );
+        }
+
+        // Get function code, most called first when there is a profile
+        let calls: Vec<u64> = program
+            .functions
+            .iter()
+            .map(|f| self.profile.as_ref().map_or(0, |p| p.calls(&f.name)))
+            .collect();
+        let mut function_parts: Vec<_> = std::mem::take(&mut self.function_code_parts)
+            .into_iter()
+            .zip(std::mem::take(&mut self.function_line_parts))
+            .enumerate()
+            .collect();
+        function_parts.sort_by_key(|&(i, _)| Reverse(calls[i]));
+
+        // Now compile main statements
+        for stmt in &program.main_statements {
+            self.compile_main_statement(stmt)?;
+        }
+
+        // Add HALT at the end of main
+        self.emit_op(0x01); // HALT
+
+        // Get main code size before appending functions
+        let mut main_code_size = self.code_offset()?;
+        let main_code_before_functions = main_code_size;
+
+        // Now update function code offsets and append function code
+        for (i, (mut func_code, func_lines)) in function_parts {
+            self.relocate_jumps(&mut func_code, main_code_size)?;
+            let size = u32::try_from(func_code.len()).map_err(|_| {
+                format!("code section exceeds u32 range ({} bytes)", func_code.len())
+            })?;
+            self.functions[i].code_offset = main_code_size;
+            self.lines.extend(
+                func_lines
+                    .into_iter()
+                    .map(|(offset, line)| (offset + main_code_size, line)),
+            );
+            // Track cumulative offset for next function
+            main_code_size = main_code_size.checked_add(size).ok_or_else(|| {
+                format!(
+                    "code section exceeds u32 range ({} bytes)",
+                    main_code_size as u64 + size as u64
+                )
+            })?;
+
+            self.code.extend_from_slice(&func_code);
         }
+
+        log::debug!(
+            main_bytes = main_code_before_functions,
+            code_bytes = self.code.len(),
+            functions = self.functions.len();
+            "generated code"
+        );
+
+        // Build the bytecode
+        self.write_bytecode()
+    }
+
+    fn declare_consts(&mut self, consts: &[Const]) -> Result<(), String> {
+        for Const { name, value, span } in consts {
+            self.span = *span;
+            if name == "TOUCHY" || builtins::lookup(name).is_some() {
+                return Err(format!("constant name is reserved for built-in: {}", name));
+            }
+            if self.get_braincell_index(name).is_ok() {
+                return Err(format!("constant name cannot be a braincell: {}", name));
+            }
+            if !matches!(value, Expr::Number(..) | Expr::String(..)) {
+                return Err(format!(
+                    "constant '{}' must be a number or string literal",
+                    name
+                ));
+            }
+            if self.consts.insert(name.clone(), value.clone()).is_some() {
+                let first = consts.iter().find(|c| c.name == *name).map(|c| c.span);
+                return Err(format!(
+                    "duplicate constant: {}{}",
+                    name,
+                    first_defined(first)
+                ));
+            }
+        }
+        self.span = Span::default();
+        Ok(())
     }
 
     fn declare_functions(&mut self, functions: &[Function]) -> Result<(), String> {
         for (idx, func) in functions.iter().enumerate() {
-            if matches!(func.name.as_str(), "TRANSFORM" | "RIZZED" | "TOUCHY") {
+            self.span = func.span;
+            if func.name == "TOUCHY" || builtins::lookup(&func.name).is_some() {
                 return Err(format!(
                     "function name is reserved for built-in: {}",
                     func.name
                 ));
             }
             if self.function_map.contains_key(&func.name) {
-                return Err(format!("duplicate function: {}", func.name));
+                let first = functions
+                    .iter()
+                    .find(|f| f.name == func.name)
+                    .map(|f| f.span);
+                return Err(format!(
+                    "duplicate function: {}{}",
+                    func.name,
+                    first_defined(first)
+                ));
+            }
+            if self.consts.contains_key(&func.name) {
+                return Err(format!(
+                    "function name is already a constant: {}",
+                    func.name
+                ));
             }
 
-            let name_bytes = func.name.as_bytes().to_vec();
-            self.add_const(Constant::String(name_bytes));
-
-            let func_index = 2 + idx as u32;
+            // Shares the pool entry with any identical string literal.
+            let name_const = self.add_const(Constant::String(func.name.as_bytes().to_vec()))?;
+
+            let func_index = u32::try_from(idx)
+                .ok()
+                .filter(|&idx| idx < bytecode::MAX_FUNCTIONS)
+                .map(|idx| idx + 2)
+                .ok_or_else(|| {
+                    format!(
+                        "too many functions ({}, max {})",
+                        idx + 1,
+                        bytecode::MAX_FUNCTIONS
+                    )
+                })?;
+            let arity = u16::try_from(func.params.len()).map_err(|_| {
+                format!(
+                    "too many parameters in function '{}' ({}, max {})",
+                    func.name,
+                    func.params.len(),
+                    u16::MAX
+                )
+            })?;
             self.function_map.insert(func.name.clone(), func_index);
             self.functions.push(FunctionInfo {
-                name: func.name.clone(),
-                arity: func.params.len() as u16,
+                name_const,
+                arity,
                 local_count: 0,
                 code_offset: 0,
+                max_stack: 0,
             });
         }
 
+        self.span = Span::default();
         Ok(())
     }
 
     fn compile_function(&mut self, func: &Function) -> Result<(), String> {
         // Save current state
         let saved_code = std::mem::take(&mut self.code);
+        let saved_lines = std::mem::take(&mut self.lines);
         let saved_locals = std::mem::take(&mut self.current_locals);
+        let saved_hoisted = std::mem::take(&mut self.hoisted_globals);
+        let saved_hoisted_exprs = std::mem::take(&mut self.hoisted_exprs);
         let saved_in_function = self.in_function;
+        let saved_function = self.current_function.replace(func.name.clone());
+        let saved_branch_index = std::mem::take(&mut self.branch_index);
         self.in_function = true;
+        self.span = func.span;
 
         // Allocate parameters as locals
-        for (idx, param) in func.params.iter().enumerate() {
+        for param in func.params.iter().map(|p| &p.name) {
+            if self.consts.contains_key(param) {
+                return Err(format!(
+                    "parameter '{}' in function {} shadows a constant",
+                    param, func.name
+                ));
+            }
             if self.current_locals.contains_key(param) {
                 return Err(format!(
                     "duplicate parameter '{}' in function {}",
                     param, func.name
                 ));
             }
-            self.current_locals.insert(param.clone(), idx as u16);
+            self.allocate_local(param)?;
         }
 
         // Compile function body
@@ -153,10 +594,13 @@ impl Compiler {
         }
 
         // If function doesn't end with RETREAT, add default return "".
-        let has_return = matches!(func.body.last(), Some(Statement::Return(_)));
+        let has_return = matches!(
+            func.body.last().map(|stmt| &stmt.statement),
+            Some(Statement::Return(_))
+        );
         if !has_return {
             // Emit default return of empty string
-            let empty_str = self.add_const(Constant::String(b"".to_vec()));
+            let empty_str = self.add_const(Constant::String(b"".to_vec()))?;
             self.emit_op(0x02); // LOAD_CONST
             self.emit_u32(empty_str);
             self.emit_op(0x0E); // UNTILWEMEETAGAIN
@@ -172,51 +616,128 @@ impl Compiler {
             .get(&func.name)
             .ok_or_else(|| format!("internal compiler error: undeclared function {}", func.name))?;
         let metadata_index = (func_index - 2) as usize;
+        // allocate_local keeps the count within u16
         self.functions[metadata_index].local_count = self.current_locals.len() as u16;
+        self.functions[metadata_index].max_stack = self.max_stack_depth(&func_code)?;
 
         // Store function code for later
         self.function_code_parts.push(func_code);
+        self.function_line_parts
+            .push(std::mem::replace(&mut self.lines, saved_lines));
 
         // Restore state
         self.code = saved_code;
         self.current_locals = saved_locals;
+        self.hoisted_globals = saved_hoisted;
+        self.hoisted_exprs = saved_hoisted_exprs;
         self.in_function = saved_in_function;
+        self.current_function = saved_function;
+        self.branch_index = saved_branch_index;
+        self.span = Span::default();
 
         Ok(())
     }
 
-    fn compile_main_statement(&mut self, stmt: &Statement) -> Result<(), String> {
+    fn compile_main_statement(&mut self, stmt: &SpannedStatement) -> Result<(), String> {
         self.compile_statement(stmt)
     }
 
-    fn compile_statement(&mut self, stmt: &Statement) -> Result<(), String> {
+    fn compile_statement(&mut self, stmt: &SpannedStatement) -> Result<(), String> {
+        let outer = std::mem::replace(&mut self.span, stmt.span);
+        self.mark_line(stmt.span.line)?;
+        self.compile_statement_kind(&stmt.statement)?;
+        // Code emitted after a nested block, such as a loop's jump back,
+        // belongs to the enclosing statement again.
+        if outer.line > 0 {
+            self.mark_line(outer.line)?;
+        }
+        self.span = outer;
+        Ok(())
+    }
+
+    fn mark_line(&mut self, line: usize) -> Result<(), String> {
+        let offset = self.code_offset()?;
+        match self.lines.last_mut() {
+            Some(last) if last.0 == offset => last.1 = line,
+            Some(last) if last.1 == line => {}
+            _ => self.lines.push((offset, line)),
+        }
+        Ok(())
+    }
+
+    fn compile_statement_kind(&mut self, stmt: &Statement) -> Result<(), String> {
         match stmt {
-            Statement::Assign(var_name, expr) => {
+            Statement::Assign(var_name, _, expr) => {
                 self.compile_expr(expr)?;
-                self.emit_store(&var_name)?;
+                self.emit_store(var_name)?;
             }
             Statement::Copy { dest, source } => {
                 self.compile_expr(source)?;
+                // Older VMs have no CLONE; with no mutable values a plain
+                // store means the same thing there.
+                if self.version >= opcode::min_version(0x1D) {
+                    self.emit_op(0x1D); // CLONE
+                }
                 self.emit_store(dest)?;
             }
             Statement::Print(expr) => {
                 self.compile_expr(expr)?;
                 self.emit_op(0x09); // PRINT
             }
-            Statement::Return(expr) => {
+            Statement::PrintInline(expr) => {
+                self.require_version(opcode::min_version(0x15), "YAP")?;
+                self.compile_expr(expr)?;
+                self.emit_op(0x15); // PRINT_RAW
+            }
+            Statement::PrintError(expr) => {
+                self.require_version(opcode::min_version(0x19), "SCREAM")?;
                 self.compile_expr(expr)?;
-                self.emit_op(0x0E); // UNTILWEMEETAGAIN
+                self.emit_op(0x19); // PRINT_ERR
             }
+            Statement::Return(expr) => match expr {
+                Expr::UserFunctionCall { name, args, span }
+                    if self.current_function.as_deref() == Some(name.as_str())
+                        && self.version >= opcode::min_version(0x16) =>
+                {
+                    self.span = *span;
+                    // Direct self tail call: reuse the current frame.
+                    for arg in args {
+                        self.compile_expr(arg)?;
+                    }
+                    let func_idx = *self
+                        .function_map
+                        .get(name)
+                        .ok_or_else(|| format!("undefined function: {}", name))?;
+                    self.emit_op(0x16); // TAILCALL
+                    self.emit_u32(func_idx);
+                }
+                _ => {
+                    self.compile_expr(expr)?;
+                    self.emit_op(0x0E); // UNTILWEMEETAGAIN
+                }
+            },
             Statement::Halt => {
                 self.emit_op(0x12); // YOUSHALLNOTPASS
             }
+            Statement::Throw(expr) => {
+                self.require_version(opcode::min_version(0x18), "CRASHOUT")?;
+                self.compile_expr(expr)?;
+                self.emit_op(0x18); // THROW
+            }
             Statement::If {
                 condition,
                 then_block,
                 else_block,
             } => {
+                let bias = self.next_branch();
                 self.compile_expr(condition)?;
 
+                if let (Some(else_block), Some((truthy, falsy))) = (else_block, bias) {
+                    if falsy > truthy {
+                        return self.compile_else_first(then_block, else_block);
+                    }
+                }
+
                 // JUMP_IF_FALSE to else/end
                 self.emit_op(0x0C); // JUMP_IF_FALSE
                 let jump_pos = self.code.len();
@@ -234,7 +755,7 @@ impl Compiler {
                     self.emit_u32(0); // placeholder
 
                     // Backpatch JUMP_IF_FALSE to else block start
-                    let else_start = self.code.len() as u32;
+                    let else_start = self.code_offset()?;
                     self.code[jump_pos..jump_pos + 4].copy_from_slice(&else_start.to_le_bytes());
 
                     // Compile else block
@@ -243,17 +764,35 @@ impl Compiler {
                     }
 
                     // Backpatch JUMP to end
-                    let end_pos = self.code.len() as u32;
+                    let end_pos = self.code_offset()?;
                     self.code[jump_end_pos..jump_end_pos + 4]
                         .copy_from_slice(&end_pos.to_le_bytes());
                 } else {
                     // Backpatch JUMP_IF_FALSE to end
-                    let end_pos = self.code.len() as u32;
+                    let end_pos = self.code_offset()?;
                     self.code[jump_pos..jump_pos + 4].copy_from_slice(&end_pos.to_le_bytes());
                 }
             }
             Statement::While { condition, body } => {
-                let loop_start = self.code.len() as u32;
+                let bias = self.next_branch();
+                let outer_exprs = self.hoisted_exprs.len();
+                let hoisted = self.hoist_loop_invariants(condition, body)?;
+                if self.compile_guarded_loop(condition, body)? {
+                    for name in hoisted {
+                        self.hoisted_globals.remove(&name);
+                    }
+                    self.hoisted_exprs.truncate(outer_exprs);
+                    return Ok(());
+                }
+                if bias.is_some_and(|(truthy, falsy)| truthy > falsy) {
+                    self.compile_bottom_tested_loop(condition, body)?;
+                    for name in hoisted {
+                        self.hoisted_globals.remove(&name);
+                    }
+                    self.hoisted_exprs.truncate(outer_exprs);
+                    return Ok(());
+                }
+                let loop_start = self.code_offset()?;
 
                 // Compile condition
                 self.compile_expr(condition)?;
@@ -273,24 +812,330 @@ impl Compiler {
                 self.emit_u32(loop_start);
 
                 // Backpatch JUMP_IF_FALSE to end
-                let end_pos = self.code.len() as u32;
+                let end_pos = self.code_offset()?;
                 self.code[jump_pos..jump_pos + 4].copy_from_slice(&end_pos.to_le_bytes());
+
+                for name in hoisted {
+                    self.hoisted_globals.remove(&name);
+                }
+                self.hoisted_exprs.truncate(outer_exprs);
             }
         }
         Ok(())
     }
 
+    // The profile's (truthy, falsy) counts for the next ONGOD or SKIBIDI, if
+    // it has them and the target version has JUMP_IF_TRUE. Branches are
+    // counted in source order whether or not there is a profile.
+    fn next_branch(&mut self) -> Option<(u64, u64)> {
+        let index = self.branch_index;
+        self.branch_index += 1;
+        if self.version < opcode::min_version(0x1C) {
+            return None;
+        }
+        let function = self.current_function.as_deref().unwrap_or(profile::MAIN);
+        let branch = self.profile.as_ref()?.branch(function, index)?;
+        Some((branch.truthy, branch.falsy))
+    }
+
+    // ONGOD whose else arm ran more often: the condition (already emitted)
+    // jumps to the then arm, so the else arm follows the test.
+    fn compile_else_first(
+        &mut self,
+        then_block: &[SpannedStatement],
+        else_block: &[SpannedStatement],
+    ) -> Result<(), String> {
+        self.emit_op(0x1C); // JUMP_IF_TRUE
+        let jump_pos = self.code.len();
+        self.emit_u32(0); // placeholder
+
+        // Nested branches keep their source-order numbers.
+        let first = self.branch_index;
+        self.branch_index = first + count_branches(then_block);
+        for stmt in else_block {
+            self.compile_statement(stmt)?;
+        }
+        let after = self.branch_index;
+
+        self.emit_op(0x0B); // JUMP to end
+        let jump_end_pos = self.code.len();
+        self.emit_u32(0); // placeholder
+
+        let then_start = self.code_offset()?;
+        self.code[jump_pos..jump_pos + 4].copy_from_slice(&then_start.to_le_bytes());
+        self.branch_index = first;
+        for stmt in then_block {
+            self.compile_statement(stmt)?;
+        }
+        self.branch_index = after;
+
+        let end_pos = self.code_offset()?;
+        self.code[jump_end_pos..jump_end_pos + 4].copy_from_slice(&end_pos.to_le_bytes());
+        Ok(())
+    }
+
+    // SKIBIDI that usually loops more than once per entry: jump to the test
+    // at the bottom, which jumps back while it holds. Each further iteration
+    // then takes one jump instead of two.
+    fn compile_bottom_tested_loop(
+        &mut self,
+        condition: &Expr,
+        body: &[SpannedStatement],
+    ) -> Result<(), String> {
+        self.emit_op(0x0B); // JUMP to the test
+        let jump_pos = self.code.len();
+        self.emit_u32(0); // placeholder
+
+        let body_start = self.code_offset()?;
+        for stmt in body {
+            self.compile_statement(stmt)?;
+        }
+
+        let test_start = self.code_offset()?;
+        self.code[jump_pos..jump_pos + 4].copy_from_slice(&test_start.to_le_bytes());
+        self.compile_expr(condition)?;
+        self.emit_op(0x1C); // JUMP_IF_TRUE
+        self.emit_u32(body_start);
+        Ok(())
+    }
+
+    // A loop whose body has invariant sub-expressions of its own (see
+    // `body_invariants`) tests the condition once up front, evaluates them
+    // into hidden locals, and then runs as a bottom-tested loop, so they are
+    // worked out once and only if the body runs at all. False when there is
+    // nothing to hoist and the loop is left to the usual layouts.
+    fn compile_guarded_loop(
+        &mut self,
+        condition: &Expr,
+        body: &[SpannedStatement],
+    ) -> Result<bool, String> {
+        let exprs = self.body_invariants(body);
+        if exprs.is_empty() {
+            return Ok(false);
+        }
+
+        self.compile_expr(condition)?;
+        self.emit_op(0x0C); // JUMP_IF_FALSE to end
+        let jump_pos = self.code.len();
+        self.emit_u32(0); // placeholder
+
+        for expr in exprs {
+            self.compile_expr(expr)?;
+            let slot = self.allocate_local(&format!("#hoisted{}", self.current_locals.len()))?;
+            self.emit_op(0x10); // BIGBACK_LOCAL
+            self.emit_u16(slot);
+            self.hoisted_exprs.push((expr.clone(), slot));
+        }
+
+        let body_start = self.code_offset()?;
+        for stmt in body {
+            self.compile_statement(stmt)?;
+        }
+        self.compile_expr(condition)?;
+        self.emit_op(0x1C); // JUMP_IF_TRUE
+        self.emit_u32(body_start);
+
+        let end_pos = self.code_offset()?;
+        self.code[jump_pos..jump_pos + 4].copy_from_slice(&end_pos.to_le_bytes());
+        Ok(true)
+    }
+
+    // Invariant sub-expressions of the body that the condition did not
+    // already hoist. Only the leading call-free assignments are searched:
+    // they run on every pass and nothing observable happens before them, so
+    // evaluating them early can at most change which error a failing first
+    // pass reports. Statements after the first other one, or inside ONGOD or
+    // a nested SKIBIDI, might never run. Same restrictions as
+    // `hoist_loop_invariants`, and JUMP_IF_TRUE is needed for the layout.
+    fn body_invariants<'e>(&self, body: &'e [SpannedStatement]) -> Vec<&'e Expr> {
+        let mut found = Vec::new();
+        if !self.optimize
+            || !self.in_function
+            || self.pokes
+            || self.version < opcode::min_version(0x1C)
+        {
+            return found;
+        }
+
+        let mut assigned = HashSet::new();
+        assigned_names(body, &mut assigned);
+        for stmt in body {
+            let expr = match &stmt.statement {
+                Statement::Assign(_, _, expr) => expr,
+                Statement::Copy { source, .. } => source,
+                _ => break,
+            };
+            if !is_pure(expr) {
+                break;
+            }
+            self.invariant_exprs(expr, &assigned, &mut found);
+        }
+        found
+    }
+
+    // Functions the profile calls often whose whole body is `RETREAT` of a
+    // call-free expression.
+    fn inline_candidates(&self, functions: &[Function]) -> HashMap<String, (Vec<String>, Expr)> {
+        let Some(profile) = &self.profile else {
+            return HashMap::new();
+        };
+        functions
+            .iter()
+            .filter(|f| profile.calls(&f.name) >= INLINE_MIN_CALLS)
+            .filter_map(|f| match f.body.as_slice() {
+                [stmt] => match &stmt.statement {
+                    Statement::Return(expr) if is_pure(expr) => Some((
+                        f.name.clone(),
+                        (
+                            f.params.iter().map(|p| p.name.clone()).collect(),
+                            expr.clone(),
+                        ),
+                    )),
+                    _ => None,
+                },
+                _ => None,
+            })
+            .collect()
+    }
+
+    // Inlines a call to a hot one-line function. The arguments are evaluated
+    // in order into hidden locals, as a call would, then the returned
+    // expression is compiled with its parameters read from them. Main has no
+    // frame for locals, so calls there are left alone.
+    fn compile_inlined_call(&mut self, name: &str, args: &[Expr]) -> Result<bool, String> {
+        let Some((params, body)) = self.inline.get(name).cloned() else {
+            return Ok(false);
+        };
+        if !self.in_function || params.len() != args.len() {
+            return Ok(false);
+        }
+
+        let mut slots = HashMap::new();
+        for (param, arg) in params.iter().zip(args) {
+            self.compile_expr(arg)?;
+            let slot = self.allocate_local(&format!("#inline{}", self.current_locals.len()))?;
+            self.emit_op(0x10); // BIGBACK_LOCAL
+            self.emit_u16(slot);
+            slots.insert(param.clone(), slot);
+        }
+
+        let caller_locals = std::mem::replace(&mut self.current_locals, slots);
+        let caller_exprs = std::mem::take(&mut self.hoisted_exprs);
+        let result = self.compile_expr(&body);
+        self.current_locals = caller_locals;
+        self.hoisted_exprs = caller_exprs;
+        result.map(|_| true)
+    }
+
+    // Inside a function, braincells can only be read (stores create locals),
+    // so a braincell the loop condition reads and the body never assigns
+    // holds one value for the whole loop. Load each such braincell into a
+    // hidden local once, before the loop. The same goes for a sub-expression
+    // of the condition whose names are all unchanged by the body, such as
+    // `n 😏 2` in `SKIBIDI i 😭 n 😏 2`; it is also reused wherever it appears
+    // in the body. Only call-free conditions qualify, so the early evaluation
+    // cannot reorder side effects. Main has no frame for locals, so loops
+    // there are left alone. POKE can store a braincell from any call, so a
+    // program that uses it gets no hoisting at all.
+    fn hoist_loop_invariants(
+        &mut self,
+        condition: &Expr,
+        body: &[SpannedStatement],
+    ) -> Result<Vec<String>, String> {
+        let mut reads = Vec::new();
+        if !self.optimize
+            || !self.in_function
+            || self.pokes
+            || !condition_reads(condition, &mut reads)
+        {
+            return Ok(Vec::new());
+        }
+
+        let mut assigned = HashSet::new();
+        assigned_names(body, &mut assigned);
+
+        let mut hoisted = Vec::new();
+        for name in reads {
+            if self.current_locals.contains_key(&name)
+                || self.hoisted_globals.contains_key(&name)
+                || assigned.contains(&name)
+                || self.get_braincell_index(&name).is_err()
+            {
+                continue;
+            }
+
+            self.emit_load(&name)?;
+            let slot = self.allocate_local(&format!("{}#hoisted", name))?;
+            self.emit_op(0x10); // BIGBACK_LOCAL
+            self.emit_u16(slot);
+            self.hoisted_globals.insert(name.clone(), slot);
+            hoisted.push(name);
+        }
+
+        let mut exprs = Vec::new();
+        self.invariant_exprs(condition, &assigned, &mut exprs);
+        for expr in exprs {
+            self.compile_expr(expr)?;
+            let slot = self.allocate_local(&format!("#hoisted{}", self.current_locals.len()))?;
+            self.emit_op(0x10); // BIGBACK_LOCAL
+            self.emit_u16(slot);
+            self.hoisted_exprs.push((expr.clone(), slot));
+        }
+        Ok(hoisted)
+    }
+
+    // The largest operator sub-expressions of `expr` that the loop body
+    // cannot change and that are not already folded or hoisted.
+    fn invariant_exprs<'e>(
+        &self,
+        expr: &'e Expr,
+        assigned: &HashSet<String>,
+        found: &mut Vec<&'e Expr>,
+    ) {
+        let Expr::Binary { left, right, .. } = expr else {
+            return;
+        };
+        if self.is_invariant(expr, assigned) {
+            if fold_number(expr, &self.consts).is_none()
+                && !self.hoisted_exprs.iter().any(|(e, _)| e == expr)
+                && !found.contains(&expr)
+            {
+                found.push(expr);
+            }
+            return;
+        }
+        self.invariant_exprs(left, assigned, found);
+        self.invariant_exprs(right, assigned, found);
+    }
+
+    fn is_invariant(&self, expr: &Expr, assigned: &HashSet<String>) -> bool {
+        match expr {
+            Expr::Number(..) | Expr::String(..) => true,
+            Expr::Variable(name, _) => {
+                self.consts.contains_key(name)
+                    || (!assigned.contains(name)
+                        && (self.current_locals.contains_key(name)
+                            || self.get_braincell_index(name).is_ok()))
+            }
+            Expr::Binary { left, right, .. } => {
+                self.is_invariant(left, assigned) && self.is_invariant(right, assigned)
+            }
+            Expr::FunctionCall { .. } | Expr::UserFunctionCall { .. } => false,
+        }
+    }
+
     fn emit_store(&mut self, var_name: &str) -> Result<(), String> {
+        if self.consts.contains_key(var_name) {
+            return Err(format!("cannot assign to constant '{}'", var_name));
+        }
+
         // If we're in a function context, all assignments are locals.
         if self.in_function {
             // Get or allocate local index
             let local_idx = if let Some(&idx) = self.current_locals.get(var_name) {
                 idx
             } else {
-                // Create new local
-                let idx = self.current_locals.len() as u16;
-                self.current_locals.insert(var_name.to_string(), idx);
-                idx
+                self.allocate_local(var_name)?
             };
             self.emit_op(0x10); // BIGBACK_LOCAL
             self.emit_u16(local_idx);
@@ -312,63 +1157,179 @@ impl Compiler {
             .ok_or_else(|| format!("unknown braincell: {}", name))
     }
 
+    // For `left <op> right` where `left` is `right <inner> x`, the inner
+    // operator and `x`, when keeping a copy of `right` beats evaluating it
+    // twice. That takes a pure binary expression on the right; a single load
+    // costs no more than the DUP and SWAP that replace it.
+    fn reused_first_operand<'e>(
+        &self,
+        left: &'e Expr,
+        right: &Expr,
+    ) -> Option<(BinaryOp, &'e Expr)> {
+        let Expr::Binary {
+            op,
+            left: first,
+            right: second,
+            ..
+        } = left
+        else {
+            return None;
+        };
+        let worthwhile = self.optimize
+            && self.version >= opcode::min_version(0x1E)
+            && matches!(right, Expr::Binary { .. })
+            && **first == *right
+            && is_pure(right)
+            && fold_number(right, &self.consts).is_none()
+            && !self
+                .hoisted_exprs
+                .iter()
+                .any(|(e, _)| e == left || e == right);
+        worthwhile.then_some((*op, &**second))
+    }
+
+    // Errors and the line table point at the innermost expression, so an
+    // argument on a line of its own gets that line.
     fn compile_expr(&mut self, expr: &Expr) -> Result<(), String> {
+        let outer = std::mem::replace(&mut self.span, expr.span());
+        let moved = self.span.line > 0 && outer.line > 0 && self.span.line != outer.line;
+        if moved {
+            self.mark_line(self.span.line)?;
+        }
+        self.compile_expr_kind(expr)?;
+        if moved {
+            self.mark_line(outer.line)?;
+        }
+        self.span = outer;
+        Ok(())
+    }
+
+    fn compile_expr_kind(&mut self, expr: &Expr) -> Result<(), String> {
         match expr {
-            Expr::Number(n) => {
-                let idx = self.add_const(Constant::Number(*n));
+            Expr::Number(n, _) => {
+                let idx = self.add_const(Constant::Number(*n))?;
                 self.emit_op(0x02); // LOAD_CONST
                 self.emit_u32(idx);
             }
-            Expr::String(s) => {
+            Expr::String(s, _) => {
                 let bytes = s.as_bytes().to_vec();
-                let idx = self.add_const(Constant::String(bytes));
+                let idx = self.add_const(Constant::String(bytes))?;
                 self.emit_op(0x02); // LOAD_CONST
                 self.emit_u32(idx);
             }
-            Expr::Variable(var_name) => {
-                self.emit_load(var_name)?;
+            Expr::Variable(var_name, _) => {
+                if let Some(value) = self.consts.get(var_name).cloned() {
+                    self.compile_expr(&value)?;
+                } else {
+                    self.emit_load(var_name)?;
+                }
             }
-            Expr::Binary { op, left, right } => {
+            Expr::Binary {
+                op, left, right, ..
+            } => {
+                if *op == BinaryOp::Equal {
+                    self.require_version(opcode::min_version(0x1B), "🟰")?;
+                }
+                if self.optimize {
+                    if let Some(n) = fold_number(expr, &self.consts) {
+                        let idx = self.add_const(Constant::Number(n))?;
+                        self.emit_op(0x02); // LOAD_CONST
+                        self.emit_u32(idx);
+                        return Ok(());
+                    }
+                    if let Some(&(_, slot)) = self.hoisted_exprs.iter().find(|(e, _)| e == expr) {
+                        self.emit_op(0x0F); // TAX_LOCAL
+                        self.emit_u16(slot);
+                        return Ok(());
+                    }
+                }
+                if let Some((inner_op, inner_right)) = self.reused_first_operand(left, right) {
+                    // `a 🚡 b 😏 2 💀 a 🚡 b`: keep a copy of `a 🚡 b` under
+                    // the inner result and swap it back up for the outer op.
+                    self.compile_expr(right)?;
+                    self.emit_op(0x17); // DUP
+                    self.compile_expr(inner_right)?;
+                    self.emit_op(binary_opcode(inner_op));
+                    self.emit_op(0x1E); // SWAP
+                    self.emit_op(binary_opcode(*op));
+                    return Ok(());
+                }
                 self.compile_expr(left)?;
-                self.compile_expr(right)?;
-
-                let opcode = match op {
-                    BinaryOp::Add => 0x05,
-                    BinaryOp::Subtract => 0x06,
-                    BinaryOp::Multiply => 0x07,
-                    BinaryOp::Divide => 0x08,
-                };
-                self.emit_op(opcode);
+                if self.optimize
+                    && left == right
+                    && is_pure(left)
+                    && self.version >= opcode::min_version(0x17)
+                {
+                    // `x 😏 x`: reuse the value instead of evaluating it again
+                    self.emit_op(0x17); // DUP
+                } else {
+                    self.compile_expr(right)?;
+                }
+                self.emit_op(binary_opcode(*op));
             }
-            Expr::FunctionCall { name, arg } => {
+            Expr::FunctionCall { name, args, .. } => {
                 if name == "TOUCHY" {
-                    if let Some(prompt) = arg {
-                        self.compile_expr(prompt)?;
-                        self.emit_op(0x13); // INPUT_PROMPT
+                    match args.as_slice() {
+                        [] => self.emit_op(0x0A), // INPUT
+                        [prompt] => {
+                            self.compile_expr(prompt)?;
+                            self.emit_op(0x13); // INPUT_PROMPT
+                        }
+                        [prompt, timeout] => {
+                            self.require_version(
+                                opcode::min_version(0x1A),
+                                "TOUCHY with a timeout",
+                            )?;
+                            self.compile_expr(prompt)?;
+                            self.compile_expr(timeout)?;
+                            self.emit_op(0x1A); // INPUT_TIMEOUT
+                        }
+                        _ => {
+                            return Err(format!(
+                                "TOUCHY expects 0 to 2 arguments, got {}",
+                                args.len()
+                            ))
+                        }
+                    }
+                } else if let Some(n) = big_literal(name, args) {
+                    // BIG of a literal becomes a constant instead of a call.
+                    self.require_version(opcode::min_version(0x14), "BIG")?;
+                    let idx = self.add_const(Constant::Big(n.to_string()))?;
+                    self.emit_op(0x02); // LOAD_CONST
+                    self.emit_u32(idx);
+                } else if let Some(builtin) = builtins::lookup(name) {
+                    if args.len() != builtin.arity {
+                        return Err(format!(
+                            "{} expects {} argument{}, got {}",
+                            builtin.name,
+                            builtin.arity,
+                            if builtin.arity == 1 { "" } else { "s" },
+                            args.len()
+                        ));
+                    }
+                    for arg in args {
+                        self.compile_expr(arg)?;
+                    }
+                    if self.version >= opcode::min_version(0x14) {
+                        self.emit_op(0x14); // CALL_BUILTIN
+                        self.emit_u8(builtin.id);
+                    } else if builtin.id < 2 {
+                        // Version 4 VMs reach TRANSFORM and RIZZED through
+                        // the legacy HITMEUP slots.
+                        self.emit_op(0x0D); // HITMEUP
+                        self.emit_u32(builtin.id as u32);
                     } else {
-                        self.emit_op(0x0A); // INPUT
+                        self.require_version(opcode::min_version(0x14), builtin.name)?;
                     }
-                } else if name == "TRANSFORM" {
-                    self.compile_expr(
-                        arg.as_ref()
-                            .ok_or_else(|| "TRANSFORM requires argument".to_string())?,
-                    )?;
-                    // Emit call to built-in function index 0
-                    self.emit_op(0x0D); // HITMEUP
-                    self.emit_u32(0); // built-in TRANSFORM
-                } else if name == "RIZZED" {
-                    self.compile_expr(
-                        arg.as_ref()
-                            .ok_or_else(|| "RIZZED requires argument".to_string())?,
-                    )?;
-                    // Emit call to built-in function index 1
-                    self.emit_op(0x0D); // HITMEUP
-                    self.emit_u32(1); // built-in RIZZED
                 } else {
                     return Err(format!("Unknown function: {}", name));
                 }
             }
-            Expr::UserFunctionCall { name, args } => {
+            Expr::UserFunctionCall { name, args, .. } => {
+                if self.compile_inlined_call(name, args)? {
+                    return Ok(());
+                }
+
                 // Compile all arguments
                 for arg in args {
                     self.compile_expr(arg)?;
@@ -389,11 +1350,24 @@ impl Compiler {
         Ok(())
     }
 
+    fn require_version(&self, version: u16, feature: &str) -> Result<(), String> {
+        if self.version < version {
+            return Err(format!(
+                "{} needs bytecode version {}, but version {} was requested",
+                feature, version, self.version
+            ));
+        }
+        Ok(())
+    }
+
     fn emit_load(&mut self, var_name: &str) -> Result<(), String> {
         // Check if it's a local variable
         if let Some(&local_idx) = self.current_locals.get(var_name) {
             self.emit_op(0x0F); // TAX_LOCAL
             self.emit_u16(local_idx);
+        } else if let Some(&local_idx) = self.hoisted_globals.get(var_name) {
+            self.emit_op(0x0F); // TAX_LOCAL
+            self.emit_u16(local_idx);
         } else {
             // It's a global braincell
             let braincell_idx = self.get_braincell_index(var_name)?;
@@ -403,15 +1377,48 @@ impl Compiler {
         Ok(())
     }
 
-    fn add_const(&mut self, constant: Constant) -> u32 {
+    fn allocate_local(&mut self, var_name: &str) -> Result<u16, String> {
+        let count = self.current_locals.len();
+        if count >= u16::MAX as usize {
+            return Err(format!(
+                "too many locals in function '{}' ({}, max {})",
+                self.current_function.as_deref().unwrap_or("?"),
+                count + 1,
+                u16::MAX
+            ));
+        }
+
+        let idx = count as u16;
+        self.current_locals.insert(var_name.to_string(), idx);
+        Ok(idx)
+    }
+
+    fn add_const(&mut self, constant: Constant) -> Result<u32, String> {
         if let Some(&idx) = self.const_map.get(&constant) {
-            return idx;
+            return Ok(idx);
         }
 
-        let idx = self.constants.len() as u32;
+        if let Constant::String(bytes) = &constant {
+            if u32::try_from(bytes.len()).is_err() {
+                return Err(format!(
+                    "string constant exceeds u32 range ({} bytes)",
+                    bytes.len()
+                ));
+            }
+        }
+
+        let idx = u32::try_from(self.constants.len())
+            .ok()
+            .filter(|&idx| idx < bytecode::MAX_CONSTANTS)
+            .ok_or_else(|| format!("too many constants (max {})", bytecode::MAX_CONSTANTS))?;
         self.constants.push(constant.clone());
         self.const_map.insert(constant, idx);
-        idx
+        Ok(idx)
+    }
+
+    fn code_offset(&self) -> Result<u32, String> {
+        u32::try_from(self.code.len())
+            .map_err(|_| format!("code section exceeds u32 range ({} bytes)", self.code.len()))
     }
 
     fn emit_op(&mut self, op: u8) {
@@ -436,41 +1443,69 @@ impl Compiler {
             let op = code[pos];
             pos += 1;
 
-            match op {
-                0x01 | 0x05 | 0x06 | 0x07 | 0x08 | 0x09 | 0x0A | 0x0E | 0x11 | 0x12 | 0x13 => {}
-                0x02 | 0x0D => {
-                    Self::ensure_operand(code, pos, 4, op)?;
-                    pos += 4;
-                }
-                0x03 | 0x04 => {
-                    Self::ensure_operand(code, pos, 1, op)?;
-                    pos += 1;
-                }
-                0x0B | 0x0C => {
-                    Self::ensure_operand(code, pos, 4, op)?;
-                    let target = u32::from_le_bytes([
-                        code[pos],
-                        code[pos + 1],
-                        code[pos + 2],
-                        code[pos + 3],
-                    ]);
-                    let relocated = target
-                        .checked_add(base)
-                        .ok_or_else(|| "jump target overflow during relocation".to_string())?;
-                    code[pos..pos + 4].copy_from_slice(&relocated.to_le_bytes());
-                    pos += 4;
-                }
-                0x0F | 0x10 => {
-                    Self::ensure_operand(code, pos, 2, op)?;
-                    pos += 2;
-                }
-                _ => return Err(format!("unknown opcode during relocation: 0x{:02x}", op)),
+            let (_, width) = opcode::lookup(op)
+                .ok_or_else(|| format!("unknown opcode during relocation: 0x{:02x}", op))?;
+            Self::ensure_operand(code, pos, width, op)?;
+
+            if opcode::is_jump(op) {
+                let target =
+                    u32::from_le_bytes([code[pos], code[pos + 1], code[pos + 2], code[pos + 3]]);
+                let relocated = target
+                    .checked_add(base)
+                    .ok_or_else(|| "jump target overflow during relocation".to_string())?;
+                code[pos..pos + 4].copy_from_slice(&relocated.to_le_bytes());
             }
+            pos += width;
         }
 
         Ok(())
     }
 
+    // Deepest the operand stack gets while `code` runs, counted from the
+    // function's entry. Compiled code leaves the stack empty between
+    // statements, so a jump never lands at a different depth than the code
+    // before it, and one pass in order finds the maximum.
+    fn max_stack_depth(&self, code: &[u8]) -> Result<u16, String> {
+        let (mut depth, mut max) = (0i64, 0i64);
+        let mut pos = 0;
+        while pos < code.len() {
+            let op = code[pos];
+            let (_, width) = opcode::lookup(op)
+                .ok_or_else(|| format!("unknown opcode in stack depth pass: 0x{:02x}", op))?;
+            Self::ensure_operand(code, pos + 1, width, op)?;
+            let operand = code[pos + 1..pos + 1 + width]
+                .iter()
+                .rev()
+                .fold(0u32, |acc, &b| (acc << 8) | b as u32);
+            let (pops, pushes) = match op {
+                0x02 | 0x03 | 0x0A | 0x0F | 0x17 => (0, 1),
+                0x04 | 0x09 | 0x0C | 0x0E | 0x10 | 0x11 | 0x15 | 0x18 | 0x19 | 0x1C => (1, 0),
+                0x05..=0x08 | 0x1B => (2, 1),
+                0x13 | 0x1D => (1, 1),
+                0x1E => (2, 2),
+                0x1F => (3, 3),
+                0x1A => (2, 1),
+                0x0D if operand < 2 => (1, 1),
+                0x0D | 0x16 => {
+                    let arity = operand
+                        .checked_sub(2)
+                        .and_then(|i| self.functions.get(i as usize))
+                        .map_or(0, |f| f.arity);
+                    (arity as i64, i64::from(op == 0x0D))
+                }
+                0x14 => (
+                    builtins::by_id(operand as u8).map_or(0, |b| b.arity) as i64,
+                    1,
+                ),
+                _ => (0, 0),
+            };
+            depth = (depth - pops).max(0) + pushes;
+            max = max.max(depth);
+            pos += 1 + width;
+        }
+        Ok(max.min(u16::MAX as i64) as u16)
+    }
+
     fn ensure_operand(code: &[u8], pos: usize, len: usize, op: u8) -> Result<(), String> {
         if pos + len > code.len() {
             return Err(format!("truncated operand for opcode 0x{:02x}", op));
@@ -482,12 +1517,13 @@ impl Compiler {
     fn write_bytecode(&self) -> Result<Vec<u8>, String> {
         let mut result = Vec::new();
 
-        // Header: "BRBC" + version (4) + flags (0)
-        result.extend_from_slice(b"BRBC");
-        result.extend_from_slice(&4u16.to_le_bytes()); // version 4
-        result.extend_from_slice(&0u16.to_le_bytes()); // flags
+        // Header: "BRBC" + version + flags
+        result.extend_from_slice(bytecode::MAGIC);
+        result.extend_from_slice(&self.version.to_le_bytes());
+        result.extend_from_slice(&self.flags.to_le_bytes());
 
         // Constant pool
+        // add_const and declare_functions keep every count and length below in u32 range
         result.extend_from_slice(&(self.constants.len() as u32).to_le_bytes());
 
         for constant in &self.constants {
@@ -501,31 +1537,28 @@ impl Compiler {
                     result.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
                     result.extend_from_slice(bytes);
                 }
+                Constant::Big(digits) => {
+                    result.push(3); // tag: Big integer
+                    result.extend_from_slice(&(digits.len() as u32).to_le_bytes());
+                    result.extend_from_slice(digits.as_bytes());
+                }
             }
         }
 
         // Function table
         result.extend_from_slice(&(self.functions.len() as u32).to_le_bytes());
         for func in &self.functions {
-            // Add function name to constant pool for lookup
-            let name_bytes = func.name.as_bytes().to_vec();
-            let name_const_idx = self
-                .constants
-                .iter()
-                .position(|c| match c {
-                    Constant::String(s) => s == &name_bytes,
-                    _ => false,
-                })
-                .unwrap() as u32;
-
-            result.extend_from_slice(&name_const_idx.to_le_bytes());
+            result.extend_from_slice(&func.name_const.to_le_bytes());
             result.extend_from_slice(&func.arity.to_le_bytes());
             result.extend_from_slice(&func.local_count.to_le_bytes());
             result.extend_from_slice(&func.code_offset.to_le_bytes());
+            if self.flags & bytecode::FLAG_STACK_DEPTH != 0 {
+                result.extend_from_slice(&func.max_stack.to_le_bytes());
+            }
         }
 
         // Code section
-        result.extend_from_slice(&(self.code.len() as u32).to_le_bytes());
+        result.extend_from_slice(&self.code_offset()?.to_le_bytes());
         result.extend_from_slice(&self.code);
 
         Ok(result)
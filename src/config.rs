@@ -0,0 +1,213 @@
+// Defaults for command-line options, read from `~/.config/brvm/config.toml`
+// and then from the nearest `brvm.toml` in the current directory or above
+// it, so a project can override what the user prefers.
+//
+// Keys are option names, with `-` or `_`. Keys before any table apply to
+// every command with that option; keys under `[run]`, `[compile]` and so on
+// apply to that command only. Only the part of TOML that options need is
+// read: strings, integers, floats, booleans and one-line arrays of them.
+use crate::error::CompileError;
+use std::path::{Path, PathBuf};
+
+pub const PROJECT_FILE: &str = "brvm.toml";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Setting {
+    One(String),
+    List(Vec<String>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    pub command: Option<String>, // None for keys before any table
+    pub option: String,          // with `_` for `-`, as clap names it
+    pub value: Setting,
+    pub line: usize,
+}
+
+// The config files that exist, in the order they apply.
+pub fn files() -> Vec<PathBuf> {
+    let user = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => Some(PathBuf::from(dir)),
+        _ => std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")),
+    };
+    let project = std::env::current_dir()
+        .ok()
+        .and_then(|dir| find_project_file(&dir));
+    user.map(|dir| dir.join("brvm").join("config.toml"))
+        .into_iter()
+        .filter(|path| path.is_file())
+        .chain(project)
+        .collect()
+}
+
+// The nearest `brvm.toml` in `dir` or one of its parents.
+pub fn find_project_file(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .map(|dir| dir.join(PROJECT_FILE))
+        .find(|path| path.is_file())
+}
+
+pub fn parse(text: &str, filename: &str) -> Result<Vec<Entry>, CompileError> {
+    let mut entries: Vec<Entry> = Vec::new();
+    let mut command = None;
+    for (index, raw) in text.lines().enumerate() {
+        let line = index + 1;
+        let error = |col: usize, message: &str| CompileError::new(filename, line, col, message);
+        let content = strip_comment(raw);
+        let trimmed = content.trim();
+        let col = content.len() - content.trim_start().len() + 1;
+        if trimmed.is_empty() {
+            continue;
+        }
+
+        if let Some(rest) = trimmed.strip_prefix('[') {
+            let name = rest
+                .strip_suffix(']')
+                .ok_or_else(|| error(col, "expected ']' after the table name"))?
+                .trim();
+            if !is_bare_key(name) {
+                return Err(error(col + 1, "expected a command name"));
+            }
+            command = Some(name.to_string());
+            continue;
+        }
+
+        let (key, value) = trimmed
+            .split_once('=')
+            .ok_or_else(|| error(col, "expected 'key = value'"))?;
+        let key = key.trim();
+        if !is_bare_key(key) {
+            return Err(error(col, "expected an option name"));
+        }
+        let option = key.replace('-', "_");
+        if entries
+            .iter()
+            .any(|entry| entry.command == command && entry.option == option)
+        {
+            return Err(error(col, &format!("'{}' is set twice", key)));
+        }
+        let value_col = col
+            + key.len()
+            + trimmed[key.len()..].find('=').unwrap_or(0)
+            + 1
+            + (value.len() - value.trim_start().len());
+        let value = parse_setting(value.trim()).map_err(|message| error(value_col, &message))?;
+        entries.push(Entry {
+            command: command.clone(),
+            option,
+            value,
+            line,
+        });
+    }
+    Ok(entries)
+}
+
+fn is_bare_key(key: &str) -> bool {
+    !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+// The line up to a `#` that is not inside a string.
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match (quote, c) {
+            (Some('"'), '\\') if !escaped => {
+                escaped = true;
+                continue;
+            }
+            (Some(q), c) if c == q && !escaped => quote = None,
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '#') => return &line[..i],
+            _ => {}
+        }
+        escaped = false;
+    }
+    line
+}
+
+fn parse_setting(text: &str) -> Result<Setting, String> {
+    let Some(inner) = text.strip_prefix('[') else {
+        let (value, rest) = parse_value(text)?;
+        return match rest.trim() {
+            "" => Ok(Setting::One(value)),
+            _ => Err("unexpected text after the value".to_string()),
+        };
+    };
+
+    let mut values = Vec::new();
+    let mut rest = inner.trim_start();
+    loop {
+        if let Some(after) = rest.strip_prefix(']') {
+            if !after.trim().is_empty() {
+                return Err("unexpected text after the array".to_string());
+            }
+            return Ok(Setting::List(values));
+        }
+        let (value, after) = parse_value(rest)?;
+        values.push(value);
+        rest = after.trim_start();
+        if let Some(after) = rest.strip_prefix(',') {
+            rest = after.trim_start();
+        } else if !rest.starts_with(']') {
+            return Err("expected ',' or ']' in the array".to_string());
+        }
+    }
+}
+
+// One value at the start of `text`, as the text clap would be given, and
+// what follows it.
+fn parse_value(text: &str) -> Result<(String, &str), String> {
+    if let Some(rest) = text.strip_prefix('\'') {
+        let end = rest.find('\'').ok_or("unterminated string")?;
+        return Ok((rest[..end].to_string(), &rest[end + 1..]));
+    }
+    if let Some(rest) = text.strip_prefix('"') {
+        let mut value = String::new();
+        let mut chars = rest.char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => return Ok((value, &rest[i + 1..])),
+                '\\' => match chars.next().map(|(_, c)| c) {
+                    Some('n') => value.push('\n'),
+                    Some('t') => value.push('\t'),
+                    Some('r') => value.push('\r'),
+                    Some('"') => value.push('"'),
+                    Some('\\') => value.push('\\'),
+                    Some('u') => {
+                        let hex: String = chars.by_ref().take(4).map(|(_, c)| c).collect();
+                        let c = u32::from_str_radix(&hex, 16)
+                            .ok()
+                            .and_then(char::from_u32)
+                            .ok_or("invalid \\u escape")?;
+                        value.push(c);
+                    }
+                    _ => return Err("invalid escape in string".to_string()),
+                },
+                c => value.push(c),
+            }
+        }
+        return Err("unterminated string".to_string());
+    }
+
+    let end = text
+        .find(|c: char| c == ',' || c == ']' || c.is_whitespace())
+        .unwrap_or(text.len());
+    let word = &text[..end];
+    let number = word
+        .chars()
+        .all(|c| c.is_ascii_digit() || "+-._eE".contains(c))
+        && word.replace('_', "").parse::<f64>().is_ok();
+    let valid = number || matches!(word, "true" | "false");
+    if !valid {
+        return Err(match word {
+            "" => "expected a value".to_string(),
+            _ => format!("expected a string, number or boolean, found '{}'", word),
+        });
+    }
+    Ok((word.replace('_', ""), &text[end..]))
+}
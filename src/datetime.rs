@@ -0,0 +1,161 @@
+// UTC dates behind FORMATTIME and PARSETIME. Times are whole seconds since
+// the Unix epoch; there is no time zone database, so everything is UTC.
+//
+// Patterns use strftime-style directives: %Y year, %m month, %d day, %H hour,
+// %M minute, %S second, %b month name ("Jan"), %F for %Y-%m-%d, %T for
+// %H:%M:%S and %% for a literal %. Formatting also knows %a (weekday name),
+// %j (day of the year) and %s (the timestamp itself).
+
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+const WEEKDAYS: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+
+// 0000-01-01T00:00:00Z through 9999-12-31T23:59:59Z.
+pub const MIN_TIME: i64 = -62_167_219_200;
+pub const MAX_TIME: i64 = 253_402_300_799;
+
+pub fn format(time: i64, pattern: &str) -> Result<String, &'static str> {
+    if !(MIN_TIME..=MAX_TIME).contains(&time) {
+        return Err("time out of range");
+    }
+    let days = time.div_euclid(86_400);
+    let secs = time.rem_euclid(86_400);
+    let (year, month, day) = civil_from_days(days);
+    let mut out = String::new();
+    let mut chars = expand(pattern)?.into_iter();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('Y') => out.push_str(&format!("{:04}", year)),
+            Some('m') => out.push_str(&format!("{:02}", month)),
+            Some('d') => out.push_str(&format!("{:02}", day)),
+            Some('H') => out.push_str(&format!("{:02}", secs / 3600)),
+            Some('M') => out.push_str(&format!("{:02}", secs / 60 % 60)),
+            Some('S') => out.push_str(&format!("{:02}", secs % 60)),
+            Some('b') => out.push_str(MONTHS[month as usize - 1]),
+            Some('a') => out.push_str(WEEKDAYS[(days + 4).rem_euclid(7) as usize]),
+            Some('j') => {
+                let yday = days - days_from_civil(year, 1, 1) + 1;
+                out.push_str(&format!("{:03}", yday));
+            }
+            Some('s') => out.push_str(&time.to_string()),
+            Some('%') => out.push('%'),
+            _ => return Err("unknown directive in pattern"),
+        }
+    }
+    Ok(out)
+}
+
+// Fields the pattern leaves out default to 1970-01-01 00:00:00.
+pub fn parse(text: &str, pattern: &str) -> Result<i64, &'static str> {
+    let mismatch = "text does not match the pattern";
+    let (mut year, mut month, mut day) = (1970, 1, 1);
+    let (mut hour, mut minute, mut second) = (0, 0, 0);
+    let mut rest = text;
+    let mut chars = expand(pattern)?.into_iter();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            rest = rest.strip_prefix(c).ok_or(mismatch)?;
+            continue;
+        }
+        let (field, max_digits) = match chars.next() {
+            Some('Y') => (&mut year, 4),
+            Some('m') => (&mut month, 2),
+            Some('d') => (&mut day, 2),
+            Some('H') => (&mut hour, 2),
+            Some('M') => (&mut minute, 2),
+            Some('S') => (&mut second, 2),
+            Some('b') => {
+                let name = rest.get(..3).ok_or(mismatch)?;
+                let idx = MONTHS
+                    .iter()
+                    .position(|m| m.eq_ignore_ascii_case(name))
+                    .ok_or(mismatch)?;
+                month = idx as i64 + 1;
+                rest = &rest[3..];
+                continue;
+            }
+            Some('%') => {
+                rest = rest.strip_prefix('%').ok_or(mismatch)?;
+                continue;
+            }
+            _ => return Err("unknown directive in pattern"),
+        };
+        let digits = rest
+            .bytes()
+            .take(max_digits)
+            .take_while(u8::is_ascii_digit)
+            .count();
+        if digits == 0 {
+            return Err(mismatch);
+        }
+        *field = rest[..digits].parse().map_err(|_| mismatch)?;
+        rest = &rest[digits..];
+    }
+    if !rest.is_empty() {
+        return Err(mismatch);
+    }
+
+    let month_days = match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    };
+    if !(1..=12).contains(&month)
+        || !(1..=month_days).contains(&day)
+        || hour > 23
+        || minute > 59
+        || second > 59
+    {
+        return Err("date out of range");
+    }
+    Ok(days_from_civil(year, month, day) * 86_400 + hour * 3600 + minute * 60 + second)
+}
+
+// The pattern with %F and %T spelled out.
+fn expand(pattern: &str) -> Result<Vec<char>, &'static str> {
+    let mut out = Vec::new();
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('F') => out.extend("%Y-%m-%d".chars()),
+            Some('T') => out.extend("%H:%M:%S".chars()),
+            Some(d) => out.extend(['%', d]),
+            None => return Err("unknown directive in pattern"),
+        }
+    }
+    Ok(out)
+}
+
+// Days since 1970-01-01 in the proleptic Gregorian calendar, and back, after
+// Howard Hinnant's `days_from_civil` and `civil_from_days`.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    (yoe + era * 400 + i64::from(month <= 2), month, day)
+}
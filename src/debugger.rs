@@ -0,0 +1,149 @@
+// Stepping state behind `brvm debug`. The program is compiled from source so
+// the current instruction can be mapped back to a line, and runs against a
+// `CaptureHost` so its output can be shown next to the code. Drawing lives in
+// `tui.rs`; everything here works without a terminal.
+use crate::compiler::{self, CompileOptions, LineTable};
+use crate::disasm;
+use crate::error::CompileError;
+use crate::host::CaptureHost;
+use crate::value::Value;
+use crate::vm::{RuntimeError, VmOptions, VM};
+use std::collections::BTreeSet;
+
+// Instructions run by one continue before control comes back, so a program
+// that never ends cannot hang the debugger.
+pub const CONTINUE_LIMIT: u64 = 1_000_000;
+
+#[derive(Debug)]
+pub enum State {
+    Paused,
+    Finished,
+    Failed(RuntimeError),
+}
+
+pub struct Debugger {
+    pub source: String,
+    pub lines: LineTable,
+    pub listing: Vec<(usize, String)>, // offset and text of every instruction
+    pub breakpoints: BTreeSet<usize>,  // source lines
+    pub state: State,
+    bytecode: Vec<u8>,
+    input: String,
+    options: VmOptions,
+    vm: VM<CaptureHost>,
+}
+
+impl Debugger {
+    // `input` holds the lines TOUCHY reads.
+    pub fn new(
+        source: &str,
+        filename: &str,
+        input: &str,
+        options: VmOptions,
+    ) -> Result<Self, CompileError> {
+        let (bytecode, lines) =
+            compiler::compile_source_with_lines(source, filename, &CompileOptions::default())?;
+        let listing =
+            disasm::listing(&bytecode).map_err(|e| CompileError::new(filename, 0, 0, &e))?;
+        let vm = Self::start(&bytecode, input, &options);
+        Ok(Self {
+            source: source.to_string(),
+            lines,
+            listing,
+            breakpoints: BTreeSet::new(),
+            state: State::Paused,
+            bytecode,
+            input: input.to_string(),
+            options,
+            vm,
+        })
+    }
+
+    fn start(bytecode: &[u8], input: &str, options: &VmOptions) -> VM<CaptureHost> {
+        let mut vm = VM::new(CaptureHost::new(input), options);
+        // The compiler just produced this bytecode, so it always loads.
+        vm.load(bytecode).expect("compiled bytecode should load");
+        vm
+    }
+
+    pub fn vm(&self) -> &VM<CaptureHost> {
+        &self.vm
+    }
+
+    pub fn output(&self) -> &str {
+        &self.vm.host().output
+    }
+
+    // Source line of the next instruction, or of the one that failed.
+    pub fn line(&self) -> Option<usize> {
+        match &self.state {
+            State::Paused => self.lines.line_at(self.vm.ip()),
+            State::Failed(e) => e.ip.and_then(|ip| self.lines.line_at(ip)),
+            State::Finished => None,
+        }
+    }
+
+    pub fn function_name(&self, function: u32) -> &str {
+        self.vm.function_name(function).unwrap_or("?")
+    }
+
+    pub fn restart(&mut self) {
+        self.vm = Self::start(&self.bytecode, &self.input, &self.options);
+        self.state = State::Paused;
+    }
+
+    pub fn toggle_breakpoint(&mut self, line: usize) {
+        if !self.breakpoints.remove(&line) {
+            self.breakpoints.insert(line);
+        }
+    }
+
+    // Runs one instruction.
+    pub fn step(&mut self) {
+        if !matches!(self.state, State::Paused) {
+            return;
+        }
+        match self.vm.step() {
+            Ok(false) => {}
+            Ok(true) => self.state = State::Finished,
+            Err(e) => self.state = State::Failed(e),
+        }
+    }
+
+    // Runs until a different line is reached in this call or a caller,
+    // stepping over any calls made on the way.
+    pub fn next_line(&mut self) {
+        let line = self.line();
+        let depth = self.vm.call_depth();
+        self.run_until(|d| d.vm.call_depth() <= depth && d.line() != line);
+    }
+
+    // Runs until the program ends or reaches a line with a breakpoint.
+    pub fn resume(&mut self) {
+        let line = self.line();
+        let mut moved = false;
+        self.run_until(|d| {
+            let now = d.line();
+            moved |= now != line;
+            moved && now.is_some_and(|l| d.breakpoints.contains(&l))
+        });
+    }
+
+    fn run_until(&mut self, mut stop: impl FnMut(&Self) -> bool) {
+        for _ in 0..CONTINUE_LIMIT {
+            self.step();
+            if !matches!(self.state, State::Paused) || stop(self) {
+                return;
+            }
+        }
+    }
+}
+
+// Text for a braincell or local slot.
+pub fn describe(value: Option<&Value>) -> String {
+    match value {
+        Some(Value::String(s)) => format!("{:?}", s.as_str()),
+        Some(value) => value.to_string(),
+        None => "unset".to_string(),
+    }
+}
@@ -0,0 +1,185 @@
+// Messages reported to the user, with a severity. Errors travel through the
+// pipeline as `CompileError` and are converted here for display; warnings
+// come from the lint pass in `lint.rs`.
+use crate::error::CompileError;
+use crate::lint::Lint;
+use std::fmt::Write;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub lint: Option<Lint>,
+    pub filename: String,
+    pub line: usize,
+    pub col: usize,
+    pub message: String,
+    pub help: Option<String>, // suggested fix, shown under the snippet
+}
+
+impl Diagnostic {
+    pub fn from_error(error: &CompileError) -> Self {
+        Self {
+            severity: Severity::Error,
+            lint: None,
+            filename: error.filename.clone(),
+            line: error.line,
+            col: error.col,
+            message: error.message.clone(),
+            help: error.help.clone(),
+        }
+    }
+
+    // A lint set to deny fails the compile like any other error.
+    pub fn into_error(self) -> CompileError {
+        let mut error = CompileError::new(&self.filename, self.line, self.col, &self.message);
+        error.help = match self.lint {
+            Some(lint) => Some(format!(
+                "the {} lint is denied by -D or --deny-warnings",
+                lint.name()
+            )),
+            None => self.help,
+        };
+        error
+    }
+
+    fn lint_name(&self) -> &'static str {
+        self.lint.map_or("warning", |lint| lint.name())
+    }
+
+    // Renders the message with the offending source line and a caret under
+    // the token, in the style of rustc:
+    //
+    //   error: expected FR after variable
+    //    --> demo.br:5:13
+    //     |
+    //   5 | DIDDLE aura 2
+    //     |             ^
+    //     = help: copies are written DIDDLE name FR value
+    pub fn render(&self, source: &str, color: bool) -> String {
+        let paint = |code: &str, text: &str| {
+            if color {
+                format!("\x1b[{}m{}\x1b[0m", code, text)
+            } else {
+                text.to_string()
+            }
+        };
+        let (label, label_color) = match self.severity {
+            Severity::Error => ("error".to_string(), "1;31"),
+            Severity::Warning => (format!("warning[{}]", self.lint_name()), "1;33"),
+        };
+
+        let line_text = self
+            .line
+            .checked_sub(1)
+            .and_then(|idx| source.lines().nth(idx))
+            .filter(|_| self.col > 0);
+        let gutter = match line_text {
+            Some(_) => self.line.to_string().len(),
+            None => 0,
+        };
+        let pad = " ".repeat(gutter);
+        let bar = paint("1;34", "|");
+
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "{}: {}",
+            paint(label_color, &label),
+            paint("1", &self.message)
+        );
+        let _ = writeln!(
+            out,
+            "{}{} {}:{}:{}",
+            pad,
+            paint("1;34", "-->"),
+            self.filename,
+            self.line,
+            self.col
+        );
+
+        if let Some(text) = line_text {
+            let chars: Vec<char> = text.chars().collect();
+            let start = (self.col - 1).min(chars.len());
+            let indent: String = chars[..start]
+                .iter()
+                .map(|&c| if c == '\t' { "\t" } else { spaces(c) })
+                .collect();
+            let width: usize = token_at(&chars[start..])
+                .iter()
+                .map(|&c| spaces(c).len())
+                .sum();
+            let underline = "^".repeat(width.max(1));
+
+            let _ = writeln!(out, "{} {}", pad, bar);
+            let _ = writeln!(
+                out,
+                "{} {} {}",
+                paint("1;34", &self.line.to_string()),
+                bar,
+                text
+            );
+            let _ = writeln!(
+                out,
+                "{} {} {}{}",
+                pad,
+                bar,
+                indent,
+                paint(label_color, &underline)
+            );
+        }
+
+        if let Some(help) = &self.help {
+            let _ = writeln!(
+                out,
+                "{} {} {}: {}",
+                pad,
+                paint("1;34", "="),
+                paint("1", "help"),
+                help
+            );
+        }
+        out
+    }
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}:{}: ", self.filename, self.line, self.col)?;
+        if self.severity == Severity::Warning {
+            write!(f, "warning[{}]: ", self.lint_name())?;
+        }
+        write!(f, "{}", self.message)
+    }
+}
+
+// Terminal columns taken by a character: most emoji are drawn two wide.
+fn spaces(c: char) -> &'static str {
+    if c as u32 >= 0x1F000 {
+        "  "
+    } else {
+        " "
+    }
+}
+
+// The characters of the token starting a line slice: a whole string literal,
+// or a run up to whitespace or punctuation.
+fn token_at(chars: &[char]) -> &[char] {
+    if chars.first() == Some(&'"') {
+        let end = chars[1..]
+            .iter()
+            .position(|&c| c == '"')
+            .map_or(chars.len(), |pos| pos + 2);
+        return &chars[..end];
+    }
+    let end = chars
+        .iter()
+        .position(|&c| c.is_whitespace() || matches!(c, '(' | ')' | ','))
+        .unwrap_or(chars.len());
+    &chars[..end.max(chars.len().min(1))]
+}
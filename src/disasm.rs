@@ -0,0 +1,213 @@
+use crate::builtins;
+use crate::bytecode::{self, Module};
+use crate::opcode;
+use crate::value::Value;
+use std::fmt::Write;
+
+const BRAINCELL_NAMES: [&str; 7] = ["aura", "peak", "goon", "mog", "npc", "sigma", "gyatt"];
+
+pub fn disassemble(bytecode: &[u8]) -> Result<String, String> {
+    let module = bytecode::decode(bytecode)?;
+    let mut out = String::new();
+
+    write_header(&mut out, &module);
+    write_code(&mut out, &module);
+
+    Ok(out)
+}
+
+fn write_header(out: &mut String, module: &Module) {
+    let _ = writeln!(out, "BRBC version {}", module.version);
+
+    let mut flag_names = Vec::new();
+    if module.flags & bytecode::FLAG_STRICT != 0 {
+        flag_names.push("strict");
+    }
+    if module.flags & bytecode::FLAG_STRICT_MATH != 0 {
+        flag_names.push("strict-math");
+    }
+    if module.flags & bytecode::FLAG_GLOBALS != 0 {
+        flag_names.push("globals");
+    }
+    if module.flags & bytecode::FLAG_STACK_DEPTH != 0 {
+        flag_names.push("stack-depth");
+    }
+    if module.flags & !bytecode::KNOWN_FLAGS != 0 {
+        flag_names.push("unknown");
+    }
+    if flag_names.is_empty() {
+        let _ = writeln!(out, "flags: 0x{:04x}", module.flags);
+    } else {
+        let _ = writeln!(
+            out,
+            "flags: 0x{:04x} ({})",
+            module.flags,
+            flag_names.join(", ")
+        );
+    }
+    if module.flags & bytecode::FLAG_GLOBALS != 0 {
+        let _ = writeln!(out, "globals: {}", module.globals);
+    }
+
+    let pool_bytes: usize = module
+        .constants
+        .iter()
+        .map(|c| match c {
+            Value::Number(_) => 1 + 8,
+            Value::String(s) => 1 + 4 + s.len(),
+            Value::Big(n) => 1 + 4 + n.to_string().len(),
+        })
+        .sum();
+    let _ = writeln!(
+        out,
+        "constant pool: {} entries ({} bytes)",
+        module.constants.len(),
+        pool_bytes
+    );
+    for (idx, constant) in module.constants.iter().enumerate() {
+        let _ = writeln!(out, "  #{:<4} {}", idx, describe_constant(constant));
+    }
+
+    let _ = writeln!(out, "functions: {}", module.functions.len());
+    for func in &module.functions {
+        let stack = if module.flags & bytecode::FLAG_STACK_DEPTH != 0 {
+            format!(", stack {}", func.max_stack)
+        } else {
+            String::new()
+        };
+        let _ = writeln!(
+            out,
+            "  {}(arity {}, locals {}{}) @ 0x{:04x} (name #{})",
+            module.function_name(func),
+            func.arity,
+            func.local_count,
+            stack,
+            func.code_offset,
+            func.name_const
+        );
+    }
+}
+
+// One entry per instruction: its offset and the text `disassemble` prints
+// for it. Function labels are not included.
+pub fn listing(bytecode: &[u8]) -> Result<Vec<(usize, String)>, String> {
+    let module = bytecode::decode(bytecode)?;
+    let mut lines = Vec::new();
+    let mut pos = 0;
+    while pos < module.code.len() {
+        let (text, next) = instruction(&module, pos);
+        lines.push((pos, text));
+        pos = next;
+    }
+    Ok(lines)
+}
+
+fn write_code(out: &mut String, module: &Module) {
+    let code = &module.code;
+    let _ = writeln!(out, "code: {} bytes", code.len());
+
+    let mut pos = 0;
+    while pos < code.len() {
+        for func in &module.functions {
+            if func.code_offset as usize == pos {
+                let _ = writeln!(out, "{}:", module.function_name(func));
+            }
+        }
+
+        let (text, next) = instruction(module, pos);
+        let _ = writeln!(out, "  0x{:04x}  {}", pos, text);
+        pos = next;
+    }
+}
+
+// The instruction at `pos` and the offset after it. A truncated operand
+// runs to the end of the code and is printed as the bytes that are there.
+fn instruction(module: &Module, pos: usize) -> (String, usize) {
+    let code = &module.code;
+    let op = code[pos];
+    let Some((name, width)) = opcode::lookup(op) else {
+        return (format!(".byte 0x{:02x}", op), pos + 1);
+    };
+
+    if pos + 1 + width > code.len() {
+        let bytes: Vec<String> = code[pos..].iter().map(|b| format!("0x{:02x}", b)).collect();
+        let line = format!(".byte {}", bytes.join(", "));
+        return (format!("{:<24} ; {}, truncated", line, name), code.len());
+    }
+
+    let operand = read_operand(&code[pos + 1..pos + 1 + width]);
+    let line = match (op, width) {
+        (_, 0) => name.to_string(),
+        (0x02, _) => format!("{} #{}", name, operand),
+        _ => format!("{} {}", name, operand),
+    };
+    let text = match describe_operand(module, op, operand) {
+        Some(note) => format!("{:<24} ; {}", line, note),
+        None => line,
+    };
+    (text, pos + 1 + width)
+}
+
+fn read_operand(bytes: &[u8]) -> u32 {
+    bytes
+        .iter()
+        .rev()
+        .fold(0u32, |acc, &b| (acc << 8) | b as u32)
+}
+
+fn describe_operand(module: &Module, op: u8, operand: u32) -> Option<String> {
+    match op {
+        0x02 => Some(
+            module
+                .constants
+                .get(operand as usize)
+                .map_or_else(|| "<bad constant>".to_string(), describe_constant),
+        ),
+        0x03 | 0x04 => Some(
+            BRAINCELL_NAMES
+                .get(operand as usize)
+                .map_or("<bad braincell>", |n| n)
+                .to_string(),
+        ),
+        // Targets inside functions also say where, as runtime backtraces do.
+        0x0B | 0x0C | 0x1C if module.functions.iter().any(|f| f.code_offset <= operand) => Some(
+            format!("-> 0x{:04x} ({})", operand, module.locate(operand as usize)),
+        ),
+        0x0B | 0x0C | 0x1C => Some(format!("-> 0x{:04x}", operand)),
+        0x0D | 0x16 => Some(function_label(module, operand)),
+        0x14 => Some(builtin_label(operand)),
+        _ => None,
+    }
+}
+
+fn function_label(module: &Module, index: u32) -> String {
+    if index < 2 {
+        return builtin_label(index);
+    }
+    index
+        .checked_sub(2)
+        .and_then(|i| module.functions.get(i as usize))
+        .map_or_else(
+            || "<bad function>".to_string(),
+            |f| module.function_name(f).to_string(),
+        )
+}
+
+fn builtin_label(id: u32) -> String {
+    u8::try_from(id)
+        .ok()
+        .and_then(builtins::by_id)
+        .map_or_else(|| "<bad built-in>".to_string(), |b| b.name.to_string())
+}
+
+fn describe_constant(constant: &Value) -> String {
+    match constant {
+        // Only the usual NaN reads back from plain `NaN`.
+        Value::Number(n) if n.is_nan() && n.to_bits() != f64::NAN.to_bits() => {
+            format!("number NaN 0x{:016x}", n.to_bits())
+        }
+        Value::Number(n) => format!("number {}", n),
+        Value::String(s) => format!("string {:?}", s.as_str()),
+        Value::Big(n) => format!("bigint {}", n),
+    }
+}
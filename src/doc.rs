@@ -0,0 +1,156 @@
+// Documentation export behind `brvm doc`.
+//
+// A run of `🖕🖕` or `##` comment lines directly above a TRALALERO documents that
+// function. `extract` pairs those lines with the parsed signature, and
+// `markdown` / `html` render a listing for one or more files.
+use crate::error::CompileError;
+use crate::highlight::escape;
+use crate::lexer::{ASCII_LINE_COMMENT, LINE_COMMENT};
+use crate::parser::{self, Param};
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone)]
+pub struct FunctionDoc {
+    pub name: String,
+    pub params: Vec<Param>,
+    pub doc: String, // comment text without the markers, one line per comment
+    pub line: usize,
+}
+
+impl FunctionDoc {
+    pub fn signature(&self) -> String {
+        let params: Vec<String> = self
+            .params
+            .iter()
+            .map(|p| match p.ty {
+                Some(ty) => format!("{}: {}", p.name, ty.name()),
+                None => p.name.clone(),
+            })
+            .collect();
+        format!("{}({})", self.name, params.join(", "))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FileDoc {
+    pub path: String,
+    pub functions: Vec<FunctionDoc>,
+}
+
+pub fn extract(source: &str, filename: &str) -> Result<Vec<FunctionDoc>, CompileError> {
+    let (program, trivia) = parser::parse_with_trivia(source, filename)?;
+    Ok(program
+        .functions
+        .into_iter()
+        .map(|f| {
+            let leading = trivia.get(f.span).map_or(&[][..], |t| &t.leading);
+            // The doc comment lines that run up to the line above.
+            let mut doc = Vec::new();
+            let mut line = f.span.line;
+            for comment in leading.iter().rev() {
+                let text = comment
+                    .text
+                    .strip_prefix([LINE_COMMENT, ASCII_LINE_COMMENT]);
+                let Some(text) = text.filter(|_| !comment.block && comment.span.line + 1 == line)
+                else {
+                    break;
+                };
+                doc.push(text.strip_prefix(' ').unwrap_or(text));
+                line = comment.span.line;
+            }
+            doc.reverse();
+            FunctionDoc {
+                name: f.name,
+                params: f.params,
+                doc: doc.join("\n"),
+                line: f.span.line,
+            }
+        })
+        .collect())
+}
+
+// The `.br` / `.brainrot` files named, with directories searched
+// recursively, in a stable order.
+pub fn collect_sources(paths: &[PathBuf]) -> Result<Vec<PathBuf>, CompileError> {
+    let mut sources = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            walk(path, &mut sources)?;
+        } else {
+            sources.push(path.clone());
+        }
+    }
+    Ok(sources)
+}
+
+fn walk(dir: &Path, sources: &mut Vec<PathBuf>) -> Result<(), CompileError> {
+    let entries = std::fs::read_dir(dir).map_err(|_| {
+        CompileError::new(&dir.display().to_string(), 0, 0, "failed to read directory")
+    })?;
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .collect();
+    paths.sort();
+    for path in paths {
+        if path.is_dir() {
+            walk(&path, sources)?;
+        } else if path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| ext == "br" || ext == "brainrot")
+        {
+            sources.push(path);
+        }
+    }
+    Ok(())
+}
+
+pub fn markdown(files: &[FileDoc]) -> String {
+    let mut out = String::from("# Functions\n");
+    for file in files {
+        let _ = write!(out, "\n## {}\n", file.path);
+        if file.functions.is_empty() {
+            out.push_str("\nNo functions.\n");
+        }
+        for function in &file.functions {
+            let _ = write!(out, "\n### `{}`\n", function.signature());
+            if !function.doc.is_empty() {
+                let _ = write!(out, "\n{}\n", function.doc);
+            }
+            let _ = write!(out, "\nDefined on line {}.\n", function.line);
+        }
+    }
+    out
+}
+
+// A standalone page. Blank doc lines separate paragraphs.
+pub fn html(files: &[FileDoc]) -> String {
+    let mut out = String::from(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Functions</title>\n</head>\n<body>\n<h1>Functions</h1>\n",
+    );
+    for file in files {
+        let _ = writeln!(out, "<h2>{}</h2>", escape(&file.path));
+        if file.functions.is_empty() {
+            out.push_str("<p>No functions.</p>\n");
+        }
+        for function in &file.functions {
+            let _ = writeln!(
+                out,
+                "<h3 id=\"{}\"><code>{}</code></h3>",
+                escape(&function.name),
+                escape(&function.signature())
+            );
+            for paragraph in function.doc.split("\n\n").filter(|p| !p.trim().is_empty()) {
+                let _ = writeln!(out, "<p>{}</p>", escape(paragraph.trim()));
+            }
+            let _ = writeln!(
+                out,
+                "<p><small>Defined on line {}.</small></p>",
+                function.line
+            );
+        }
+    }
+    out.push_str("</body>\n</html>\n");
+    out
+}
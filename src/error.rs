@@ -1,9 +1,15 @@
+use crate::diagnostic::Diagnostic;
+
 #[derive(Debug, Clone)]
 pub struct CompileError {
     pub filename: String,
     pub line: usize,
     pub col: usize,
     pub message: String,
+    // Suggested fix, shown under the snippet by `render`.
+    pub help: Option<String>,
+    // Further errors found after this one when the parser recovers.
+    pub additional: Vec<CompileError>,
 }
 
 impl CompileError {
@@ -13,8 +19,24 @@ impl CompileError {
             line,
             col,
             message: message.to_string(),
+            help: None,
+            additional: Vec::new(),
         }
     }
+
+    pub fn with_help(mut self, help: &str) -> Self {
+        self.help = Some(help.to_string());
+        self
+    }
+
+    // Renders every error with its source line; see `Diagnostic::render`.
+    pub fn render(&self, source: &str, color: bool) -> String {
+        std::iter::once(self)
+            .chain(&self.additional)
+            .map(|error| Diagnostic::from_error(error).render(source, color))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
 }
 
 impl std::fmt::Display for CompileError {
@@ -23,7 +45,11 @@ impl std::fmt::Display for CompileError {
             f,
             "{}:{}:{}: {}",
             self.filename, self.line, self.col, self.message
-        )
+        )?;
+        for error in &self.additional {
+            write!(f, "\n{}", error)?;
+        }
+        Ok(())
     }
 }
 
@@ -0,0 +1,45 @@
+// Process exit codes by failure class, listed in the README. They are stable
+// across releases, and `brvm batch` reports the same numbers. A run that
+// finishes exits with its own status instead; see `RunResult::exit_status`.
+use crate::error::CompileError;
+use crate::vm::{ErrorKind, RuntimeError};
+
+pub const FAILURE: i32 = 1; // runtime errors, and failures like I/O errors
+pub const USAGE: i32 = 2; // what clap uses for bad arguments
+pub const COMPILE: i32 = 3;
+pub const BYTECODE: i32 = 4;
+pub const LIMIT: i32 = 5;
+// main RETREATed a value that is not a whole number from 0 to 255, or one of
+// the codes reserved here, which would pass for a failure class.
+pub const BAD_STATUS: i32 = 6;
+// Shell convention for a process stopped by SIGINT.
+pub const INTERRUPTED: i32 = 130;
+
+// True for the codes above; a program's own status cannot use them.
+pub fn is_reserved(status: i32) -> bool {
+    (FAILURE..=BAD_STATUS).contains(&status) || status == INTERRUPTED
+}
+
+pub fn code(e: &(dyn std::error::Error + 'static)) -> i32 {
+    if e.is::<CompileError>() {
+        return COMPILE;
+    }
+    match e.downcast_ref::<RuntimeError>() {
+        Some(e) => runtime_code(e),
+        None => FAILURE,
+    }
+}
+
+pub fn runtime_code(e: &RuntimeError) -> i32 {
+    match e.kind {
+        ErrorKind::InvalidBytecode(_)
+        | ErrorKind::UnknownOpcode { .. }
+        | ErrorKind::UnknownBuiltin { .. }
+        | ErrorKind::IndexOutOfBounds { .. }
+        | ErrorKind::JumpOutOfBounds { .. }
+        | ErrorKind::TruncatedOperand { .. } => BYTECODE,
+        ErrorKind::InstructionLimit | ErrorKind::MemoryLimit => LIMIT,
+        ErrorKind::Interrupted => INTERRUPTED,
+        _ => FAILURE,
+    }
+}
@@ -0,0 +1,437 @@
+// Golden-output runner behind `brvm test <dir>`.
+//
+// Every `*.br` / `*.brainrot` program in the directory, and every `*_test.br`
+// / `*_test.brainrot` program below it, is compiled and run in memory. Its
+// stdout is compared with a sibling `*.expected` file or, failing that, with
+// the program's `🖕 EXPECT: <line>` comments. A sibling `*.stdin` file, when
+// present, is fed to TOUCHY. `bless_dir` writes the current output to the
+// `*.expected` files instead of comparing.
+use crate::compiler::{self, CompileOptions};
+use crate::lexer::{self, Token};
+use crate::vm::{self, VmOptions};
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+
+const SOURCE_EXTENSIONS: [&str; 2] = ["br", "brainrot"];
+
+// Golden runs are seeded so programs using RANDOM stay reproducible.
+pub const GOLDEN_SEED: u64 = 0;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    Pass,
+    Fail {
+        line: usize,
+        expected: Option<String>,
+        actual: Option<String>,
+    },
+    Error(String),
+    Skipped,
+    Blessed, // `.expected` written or changed by `bless_dir`
+}
+
+#[derive(Debug, Clone)]
+pub struct CaseResult {
+    pub name: String,
+    pub outcome: Outcome,
+    pub diff: Option<String>, // unified diff of expected and actual output on failure
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Report {
+    pub cases: Vec<CaseResult>,
+}
+
+impl Report {
+    pub fn count(&self, matches: fn(&Outcome) -> bool) -> usize {
+        self.cases.iter().filter(|c| matches(&c.outcome)).count()
+    }
+
+    pub fn passed(&self) -> bool {
+        self.count(|o| matches!(o, Outcome::Fail { .. } | Outcome::Error(_))) == 0
+    }
+}
+
+impl std::fmt::Display for Report {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for case in &self.cases {
+            match &case.outcome {
+                Outcome::Pass => writeln!(f, "PASS  {}", case.name)?,
+                Outcome::Skipped => writeln!(
+                    f,
+                    "SKIP  {} (no .expected file or EXPECT comments)",
+                    case.name
+                )?,
+                Outcome::Error(e) => writeln!(f, "ERROR {}: {}", case.name, e)?,
+                Outcome::Fail {
+                    line,
+                    expected,
+                    actual,
+                } => {
+                    writeln!(f, "FAIL  {}", case.name)?;
+                    writeln!(f, "  first difference at line {}:", line)?;
+                    writeln!(f, "    expected: {}", describe_line(expected))?;
+                    writeln!(f, "    actual:   {}", describe_line(actual))?;
+                    if let Some(diff) = &case.diff {
+                        for line in diff.lines() {
+                            writeln!(f, "  {}", line)?;
+                        }
+                    }
+                }
+                Outcome::Blessed => writeln!(f, "BLESS {}", case.name)?,
+            }
+        }
+
+        write!(
+            f,
+            "{} cases: {} passed, {} failed, {} errored, {} skipped",
+            self.cases.len(),
+            self.count(|o| matches!(o, Outcome::Pass)),
+            self.count(|o| matches!(o, Outcome::Fail { .. })),
+            self.count(|o| matches!(o, Outcome::Error(_))),
+            self.count(|o| matches!(o, Outcome::Skipped)),
+        )?;
+        match self.count(|o| matches!(o, Outcome::Blessed)) {
+            0 => writeln!(f),
+            blessed => writeln!(f, ", {} blessed", blessed),
+        }
+    }
+}
+
+fn describe_line(line: &Option<String>) -> String {
+    match line {
+        Some(text) => format!("{:?}", text),
+        None => "<end of output>".to_string(),
+    }
+}
+
+pub fn run_dir(dir: &Path) -> Result<Report, String> {
+    run_cases(dir, false)
+}
+
+// Runs every case and records its output as the sibling `.expected` file.
+// Programs that fail to compile or run are reported and left alone.
+pub fn bless_dir(dir: &Path) -> Result<Report, String> {
+    run_cases(dir, true)
+}
+
+fn run_cases(dir: &Path, bless: bool) -> Result<Report, String> {
+    let mut sources = Vec::new();
+    collect_sources(dir, true, &mut sources)?;
+    sources.sort();
+
+    let cases = sources
+        .iter()
+        .map(|path| {
+            let (outcome, diff) = if bless {
+                (bless_case(path), None)
+            } else {
+                run_case_with_diff(path)
+            };
+            CaseResult {
+                name: path
+                    .strip_prefix(dir)
+                    .unwrap_or(path)
+                    .to_string_lossy()
+                    .to_string(),
+                outcome,
+                diff,
+            }
+        })
+        .collect();
+
+    Ok(Report { cases })
+}
+
+// Every program at the top level, but only `_test` programs in
+// subdirectories, so examples kept next to a suite are not picked up.
+fn collect_sources(dir: &Path, top: bool, sources: &mut Vec<PathBuf>) -> Result<(), String> {
+    let entries = std::fs::read_dir(dir)
+        .map_err(|e| format!("failed to read test directory {}: {}", dir.display(), e))?;
+
+    for path in entries.filter_map(|entry| entry.ok().map(|e| e.path())) {
+        if path.is_dir() {
+            collect_sources(&path, false, sources)?;
+            continue;
+        }
+        let is_source = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| SOURCE_EXTENSIONS.contains(&ext));
+        let is_test = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .is_some_and(|stem| stem.ends_with("_test"));
+        if is_source && (top || is_test) {
+            sources.push(path);
+        }
+    }
+    Ok(())
+}
+
+pub fn run_case(source_path: &Path) -> Outcome {
+    run_case_with_diff(source_path).0
+}
+
+fn run_case_with_diff(source_path: &Path) -> (Outcome, Option<String>) {
+    let expected = match std::fs::read_to_string(source_path.with_extension("expected")) {
+        Ok(expected) => expected,
+        Err(_) => match std::fs::read_to_string(source_path)
+            .ok()
+            .and_then(|source| expected_from_comments(&source))
+        {
+            Some(expected) => expected,
+            None => return (Outcome::Skipped, None),
+        },
+    };
+
+    let actual = match run_program(source_path, &read_stdin(source_path)) {
+        Ok(actual) => actual,
+        Err(e) => return (Outcome::Error(e), None),
+    };
+
+    match compare_output(&expected, &actual) {
+        Outcome::Pass => (Outcome::Pass, None),
+        outcome => (outcome, Some(unified_diff(&expected, &actual))),
+    }
+}
+
+pub fn bless_case(source_path: &Path) -> Outcome {
+    let actual = match run_program(source_path, &read_stdin(source_path)) {
+        Ok(actual) => actual,
+        Err(e) => return Outcome::Error(e),
+    };
+    let expected_path = source_path.with_extension("expected");
+    if std::fs::read_to_string(&expected_path).ok().as_deref() == Some(actual.as_str()) {
+        return Outcome::Pass;
+    }
+    match std::fs::write(&expected_path, actual) {
+        Ok(()) => Outcome::Blessed,
+        Err(e) => Outcome::Error(format!(
+            "failed to write {}: {}",
+            expected_path.display(),
+            e
+        )),
+    }
+}
+
+fn read_stdin(source_path: &Path) -> String {
+    std::fs::read_to_string(source_path.with_extension("stdin")).unwrap_or_default()
+}
+
+// The output described by `🖕 EXPECT: <line>` comments, one line each, or
+// None when there are none. Source that does not lex has none; compiling it
+// reports the error.
+pub fn expected_from_comments(source: &str) -> Option<String> {
+    let tokens = lexer::tokenize_with_comments(source, "<test>").ok()?;
+    let lines: Vec<&str> = tokens
+        .iter()
+        .filter_map(|t| match &t.token {
+            Token::Comment(text) => text.strip_prefix("EXPECT:"),
+            _ => None,
+        })
+        .collect();
+    if lines.is_empty() {
+        return None;
+    }
+    Some(
+        lines
+            .iter()
+            .map(|line| format!("{}\n", line.strip_prefix(' ').unwrap_or(line)))
+            .collect(),
+    )
+}
+
+fn run_program(source_path: &Path, stdin: &str) -> Result<String, String> {
+    let filename = source_path.to_string_lossy();
+    let source = std::fs::read_to_string(source_path)
+        .map_err(|e| format!("failed to read {}: {}", filename, e))?;
+
+    let bytecode = compiler::compile_source(&source, &filename, &CompileOptions::default())
+        .map_err(|e| e.to_string())?;
+
+    let mut input = Cursor::new(stdin.as_bytes());
+    let mut output = Vec::new();
+    let options = VmOptions {
+        seed: Some(GOLDEN_SEED),
+        ..Default::default()
+    };
+    vm::execute_with_options(&bytecode, &mut input, &mut output, &options)
+        .map_err(|e| e.to_string())?;
+
+    String::from_utf8(output).map_err(|_| "program output is not UTF-8".to_string())
+}
+
+pub fn compare_output(expected: &str, actual: &str) -> Outcome {
+    if expected == actual {
+        return Outcome::Pass;
+    }
+
+    let mut expected_lines = expected.split_inclusive('\n');
+    let mut actual_lines = actual.split_inclusive('\n');
+    let mut line = 1;
+    loop {
+        let e = expected_lines.next();
+        let a = actual_lines.next();
+        if e != a {
+            return Outcome::Fail {
+                line,
+                expected: e.map(str::to_string),
+                actual: a.map(str::to_string),
+            };
+        }
+        line += 1;
+    }
+}
+
+// Differences between two outputs in unified diff format, with three lines
+// of context around each change.
+pub fn unified_diff(expected: &str, actual: &str) -> String {
+    const CONTEXT: usize = 3;
+    let old: Vec<&str> = expected.split_inclusive('\n').collect();
+    let new: Vec<&str> = actual.split_inclusive('\n').collect();
+    let edits = diff_lines(&old, &new);
+
+    let mut out = String::from("--- expected\n+++ actual\n");
+    let mut start = 0;
+    while let Some(first) = edits[start..]
+        .iter()
+        .position(|e| !matches!(e, Edit::Same(..)))
+    {
+        // A hunk runs from CONTEXT lines before its first change to CONTEXT
+        // lines after its last, merging changes closer than 2 * CONTEXT.
+        let first = start + first;
+        let mut last = first;
+        let mut i = first;
+        while i < edits.len() {
+            if !matches!(edits[i], Edit::Same(..)) {
+                last = i;
+            } else if i - last > 2 * CONTEXT {
+                break;
+            }
+            i += 1;
+        }
+        let begin = first.saturating_sub(CONTEXT).max(start);
+        let end = (last + CONTEXT + 1).min(edits.len());
+        let hunk = &edits[begin..end];
+
+        let (old_start, new_start) = match hunk[0] {
+            Edit::Same(o, n) => (o, n),
+            Edit::Delete(o) => (o, new_position(&edits[..begin])),
+            Edit::Insert(n) => (old_position(&edits[..begin]), n),
+        };
+        let old_len = hunk
+            .iter()
+            .filter(|e| !matches!(e, Edit::Insert(_)))
+            .count();
+        let new_len = hunk
+            .iter()
+            .filter(|e| !matches!(e, Edit::Delete(_)))
+            .count();
+        out += &format!(
+            "@@ -{} +{} @@\n",
+            range(old_start, old_len),
+            range(new_start, new_len)
+        );
+        for edit in hunk {
+            let (mark, line) = match *edit {
+                Edit::Same(o, _) => (' ', old[o]),
+                Edit::Delete(o) => ('-', old[o]),
+                Edit::Insert(n) => ('+', new[n]),
+            };
+            out.push(mark);
+            out += line;
+            if !line.ends_with('\n') {
+                out += "\n\\ No newline at end of file\n";
+            }
+        }
+        start = end;
+    }
+    out
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Edit {
+    Same(usize, usize), // line in expected, line in actual
+    Delete(usize),
+    Insert(usize),
+}
+
+// Shortest edit script by longest common subsequence, after trimming the
+// common prefix and suffix. Very large differing middles are treated as a
+// wholesale replacement rather than filling a huge table.
+fn diff_lines(old: &[&str], new: &[&str]) -> Vec<Edit> {
+    const MAX_TABLE: usize = 4_000_000;
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let (a, b) = (
+        &old[prefix..old.len() - suffix],
+        &new[prefix..new.len() - suffix],
+    );
+
+    let mut edits: Vec<Edit> = (0..prefix).map(|i| Edit::Same(i, i)).collect();
+    if (a.len() + 1) * (b.len() + 1) > MAX_TABLE {
+        edits.extend((0..a.len()).map(|i| Edit::Delete(prefix + i)));
+        edits.extend((0..b.len()).map(|j| Edit::Insert(prefix + j)));
+    } else {
+        // lcs[i][j] is the LCS length of a[i..] and b[j..].
+        let width = b.len() + 1;
+        let mut lcs = vec![0u32; (a.len() + 1) * width];
+        for i in (0..a.len()).rev() {
+            for j in (0..b.len()).rev() {
+                lcs[i * width + j] = if a[i] == b[j] {
+                    lcs[(i + 1) * width + j + 1] + 1
+                } else {
+                    lcs[(i + 1) * width + j].max(lcs[i * width + j + 1])
+                };
+            }
+        }
+        let (mut i, mut j) = (0, 0);
+        while i < a.len() || j < b.len() {
+            if i < a.len() && j < b.len() && a[i] == b[j] {
+                edits.push(Edit::Same(prefix + i, prefix + j));
+                i += 1;
+                j += 1;
+            } else if i < a.len()
+                && (j == b.len() || lcs[(i + 1) * width + j] >= lcs[i * width + j + 1])
+            {
+                edits.push(Edit::Delete(prefix + i));
+                i += 1;
+            } else {
+                edits.push(Edit::Insert(prefix + j));
+                j += 1;
+            }
+        }
+    }
+    let (old_end, new_end) = (old.len() - suffix, new.len() - suffix);
+    edits.extend((0..suffix).map(|k| Edit::Same(old_end + k, new_end + k)));
+    edits
+}
+
+fn old_position(edits: &[Edit]) -> usize {
+    edits
+        .iter()
+        .filter(|e| !matches!(e, Edit::Insert(_)))
+        .count()
+}
+
+fn new_position(edits: &[Edit]) -> usize {
+    edits
+        .iter()
+        .filter(|e| !matches!(e, Edit::Delete(_)))
+        .count()
+}
+
+// `start,len` in 1-based lines; an empty range names the line before it.
+fn range(start: usize, len: usize) -> String {
+    match len {
+        0 => format!("{},0", start),
+        1 => format!("{}", start + 1),
+        _ => format!("{},{}", start + 1, len),
+    }
+}
@@ -0,0 +1,258 @@
+// `brvm grammar`: the parser's grammar table (`parser::GRAMMAR`) as ISO
+// EBNF or as text railroad diagrams.
+use crate::parser::{Syntax, GRAMMAR};
+use std::fmt::Write;
+
+const LEXICAL_NOTE: &str = "Whitespace separates tokens. 🖕 or # starts a comment that runs to the end of the line, and 🙈 ... 🙉 is a block comment, which may nest.";
+
+pub fn ebnf() -> String {
+    let mut out = format!("(* {} *)\n\n", LEXICAL_NOTE);
+    for (name, syntax) in GRAMMAR {
+        // A rule that is a list of alternatives gets one per line.
+        let alternatives = match syntax {
+            Syntax::Choice(_) | Syntax::Names(_) => ebnf_alternatives(syntax),
+            _ => vec![ebnf_item(syntax, false)],
+        };
+        let indent = " ".repeat(name.len() + 1);
+        let _ = writeln!(
+            out,
+            "{} = {} ;",
+            name,
+            alternatives.join(&format!("\n{}| ", indent))
+        );
+    }
+    out
+}
+
+// `in_choice`: whether the item is one alternative of a choice, where a
+// nested choice needs no parentheses.
+fn ebnf_item(syntax: &Syntax, in_choice: bool) -> String {
+    let group = |text: String| {
+        if in_choice {
+            text
+        } else {
+            format!("( {} )", text)
+        }
+    };
+    match syntax {
+        Syntax::Word(word) => quote(word),
+        Syntax::Rule(name) => name.to_string(),
+        Syntax::Lexical(text) => format!("? {} ?", text),
+        Syntax::Names(_) | Syntax::Choice(_) => group(ebnf_alternatives(syntax).join(" | ")),
+        Syntax::Seq(items) => items
+            .iter()
+            .map(|item| ebnf_item(item, false))
+            .collect::<Vec<_>>()
+            .join(" , "),
+        Syntax::Optional(item) => format!("[ {} ]", ebnf_item(item, true)),
+        Syntax::Repeat(item) => format!("{{ {} }}", ebnf_item(item, true)),
+    }
+}
+
+// A choice's alternatives, with the words of any table among them listed
+// one by one.
+fn ebnf_alternatives(syntax: &Syntax) -> Vec<String> {
+    match syntax {
+        Syntax::Names(names) => names().iter().map(|name| quote(name)).collect(),
+        Syntax::Choice(items) => items
+            .iter()
+            .flat_map(|item| match item {
+                Syntax::Names(_) => ebnf_alternatives(item),
+                _ => vec![ebnf_item(item, true)],
+            })
+            .collect(),
+        _ => vec![ebnf_item(syntax, true)],
+    }
+}
+
+fn quote(word: &str) -> String {
+    format!("\"{}\"", word)
+}
+
+// Each rule as a diagram read left to right from ├ to ┤. Words are quoted,
+// rules appear by name, and a path that turns back under an item repeats it.
+pub fn railroad() -> String {
+    let mut out = String::new();
+    for (name, syntax) in GRAMMAR {
+        let _ = writeln!(out, "{}:", name);
+        let diagram = Track::of(syntax);
+        for (row, line) in diagram.rows.iter().enumerate() {
+            let (start, end) = if row == diagram.entry {
+                ('├', '┤')
+            } else {
+                (' ', ' ')
+            };
+            let line: String = line.iter().filter(|&&c| c != WIDE_PAD).collect();
+            let line = format!("  {}{}{}", start, line, end);
+            let _ = writeln!(out, "{}", line.trim_end());
+        }
+        out.push('\n');
+    }
+    out
+}
+
+const WIDE_PAD: char = '\0';
+
+// A block of diagram text; the track enters on the left and leaves on the
+// right, both on row `entry`.
+struct Track {
+    rows: Vec<Vec<char>>,
+    entry: usize,
+}
+
+impl Track {
+    fn of(syntax: &Syntax) -> Track {
+        match syntax {
+            Syntax::Word(word) => Track::label(&quote(word)),
+            Syntax::Rule(name) => Track::label(name),
+            Syntax::Lexical(text) => Track::label(&format!("? {} ?", text)),
+            Syntax::Names(_) | Syntax::Choice(_) => Track::choice(Track::alternatives(syntax)),
+            Syntax::Seq(items) => Track::seq(items.iter().map(Track::of).collect()),
+            Syntax::Optional(item) => Track::choice(vec![Track::line(), Track::of(item)]),
+            Syntax::Repeat(item) => {
+                Track::choice(vec![Track::line(), Track::one_or_more(Track::of(item))])
+            }
+        }
+    }
+
+    // Like `ebnf_alternatives`, one track per alternative.
+    fn alternatives(syntax: &Syntax) -> Vec<Track> {
+        match syntax {
+            Syntax::Names(names) => names()
+                .iter()
+                .map(|name| Track::label(&quote(name)))
+                .collect(),
+            Syntax::Choice(items) => items
+                .iter()
+                .flat_map(|item| match item {
+                    Syntax::Names(_) => Track::alternatives(item),
+                    _ => vec![Track::of(item)],
+                })
+                .collect(),
+            _ => vec![Track::of(syntax)],
+        }
+    }
+
+    // Emoji take two terminal columns, so each is followed by a WIDE_PAD
+    // cell that keeps the columns of every row in step and prints as nothing.
+    fn label(text: &str) -> Track {
+        let mut row = Vec::new();
+        for c in format!("─ {} ─", text).chars() {
+            row.push(c);
+            if c as u32 >= 0x1F000 {
+                row.push(WIDE_PAD);
+            }
+        }
+        Track {
+            rows: vec![row],
+            entry: 0,
+        }
+    }
+
+    fn line() -> Track {
+        Track {
+            rows: vec![vec!['─']],
+            entry: 0,
+        }
+    }
+
+    fn width(&self) -> usize {
+        self.rows[0].len()
+    }
+
+    fn seq(items: Vec<Track>) -> Track {
+        let above = items.iter().map(|t| t.entry).max().unwrap_or(0);
+        let below = items
+            .iter()
+            .map(|t| t.rows.len() - t.entry - 1)
+            .max()
+            .unwrap_or(0);
+        let mut rows = vec![Vec::new(); above + 1 + below];
+        for item in items {
+            let width = item.width();
+            let top = above - item.entry;
+            for (r, row) in rows.iter_mut().enumerate() {
+                match r.checked_sub(top).and_then(|i| item.rows.get(i)) {
+                    Some(line) => row.extend(line),
+                    None => row.extend(std::iter::repeat_n(' ', width)),
+                }
+            }
+        }
+        Track { rows, entry: above }
+    }
+
+    // Alternatives stacked top to bottom, joined by rails on both sides.
+    fn choice(items: Vec<Track>) -> Track {
+        if items.len() == 1 {
+            return items.into_iter().next().unwrap();
+        }
+        let inner = items.iter().map(Track::width).max().unwrap_or(0);
+        let width = inner + 4;
+        let mut rows = Vec::new();
+        let mut entries = Vec::new();
+        for item in &items {
+            entries.push(rows.len() + item.entry);
+            for (r, line) in item.rows.iter().enumerate() {
+                let fill = if r == item.entry { '─' } else { ' ' };
+                let mut row = vec![' ', ' '];
+                row.extend(line);
+                row.resize(inner + 2, fill);
+                row.extend([' ', ' ']);
+                rows.push(row);
+            }
+        }
+        let (first, last) = (entries[0], entries[entries.len() - 1]);
+        for (r, row) in rows.iter_mut().enumerate() {
+            let (left, right) = match entries.iter().position(|&e| e == r) {
+                Some(0) => ('┬', '┬'),
+                Some(i) if i == entries.len() - 1 => ('└', '┘'),
+                Some(_) => ('├', '┤'),
+                None if r > first && r < last => ('│', '│'),
+                None => continue,
+            };
+            row[1] = left;
+            row[width - 2] = right;
+        }
+        rows[first][0] = '─';
+        rows[first][width - 1] = '─';
+        Track { rows, entry: first }
+    }
+
+    // The item with a path back from its end to its start underneath.
+    fn one_or_more(item: Track) -> Track {
+        let width = item.width() + 4;
+        let mut rows: Vec<Vec<char>> = item
+            .rows
+            .iter()
+            .map(|line| {
+                let mut row = vec![' ', ' '];
+                row.extend(line);
+                row.extend([' ', ' ']);
+                row
+            })
+            .collect();
+        let mut back = vec!['─'; width];
+        back[0] = ' ';
+        back[1] = '└';
+        back[width / 2] = '<';
+        back[width - 2] = '┘';
+        back[width - 1] = ' ';
+        rows.push(back);
+        let last = rows.len() - 1;
+        for (r, row) in rows.iter_mut().enumerate().take(last) {
+            if r == item.entry {
+                row[0] = '─';
+                row[1] = '┬';
+                row[width - 2] = '┬';
+                row[width - 1] = '─';
+            } else if r > item.entry {
+                row[1] = '│';
+                row[width - 2] = '│';
+            }
+        }
+        Track {
+            rows,
+            entry: item.entry,
+        }
+    }
+}
@@ -0,0 +1,202 @@
+// Syntax highlighting export behind `brvm highlight`.
+//
+// `html` renders a source file as a `<pre>` block with inline colors, so it
+// can be pasted into a blog post or slide without a stylesheet. Each span also
+// carries a `br-*` class for sites that prefer their own theme.
+// `textmate_grammar` builds a TextMate grammar from the lexer's keyword,
+// operator and braincell tables and the built-in table, so editors stay in
+// sync with the language.
+use crate::builtins;
+use crate::error::CompileError;
+use crate::lexdump::json_string;
+use crate::lexer::{self, Token};
+use std::fmt::Write;
+
+// (class, inline style) per token category.
+const KEYWORD: (&str, &str) = ("br-keyword", "color:#cba6f7;font-weight:bold");
+const OPERATOR: (&str, &str) = ("br-operator", "color:#f38ba8");
+const BRAINCELL: (&str, &str) = ("br-braincell", "color:#fab387");
+const NUMBER: (&str, &str) = ("br-number", "color:#89dceb");
+const STRING: (&str, &str) = ("br-string", "color:#a6e3a1");
+const COMMENT: (&str, &str) = ("br-comment", "color:#7f849c;font-style:italic");
+const IDENTIFIER: (&str, &str) = ("br-identifier", "color:#89b4fa");
+const BUILTIN: (&str, &str) = ("br-builtin", "color:#f9e2af");
+
+const PRE_STYLE: &str =
+    "background:#1e1e2e;color:#cdd6f4;padding:1em;border-radius:6px;overflow-x:auto";
+
+pub fn html(source: &str, filename: &str) -> Result<String, CompileError> {
+    let tokens = lexer::tokenize_with_comments(source, filename)?;
+
+    let mut out = format!("<pre class=\"brainrot\" style=\"{}\"><code>", PRE_STYLE);
+    let mut pos = 0;
+    for (idx, token) in tokens.iter().enumerate() {
+        if matches!(token.token, Token::Eof) {
+            break;
+        }
+
+        // Every non-whitespace character belongs to some token, so a token's
+        // text runs up to the next token minus the whitespace in between.
+        let start = token.span.offset;
+        let end = tokens
+            .get(idx + 1)
+            .map_or(source.len(), |next| next.span.offset);
+        let text = source[start..end].trim_end();
+
+        out.push_str(&escape(&source[pos..start]));
+        match category(&token.token) {
+            Some((class, style)) => {
+                let _ = write!(
+                    out,
+                    "<span class=\"{}\" style=\"{}\">{}</span>",
+                    class,
+                    style,
+                    escape(text)
+                );
+            }
+            None => out.push_str(&escape(text)),
+        }
+        pos = start + text.len();
+    }
+    out.push_str(&escape(source[pos..].trim_end()));
+    out.push_str("</code></pre>\n");
+
+    Ok(out)
+}
+
+fn category(token: &Token) -> Option<(&'static str, &'static str)> {
+    match token {
+        Token::Add | Token::Subtract | Token::Multiply | Token::Divide | Token::Equal => {
+            Some(OPERATOR)
+        }
+        Token::Braincell(_) => Some(BRAINCELL),
+        Token::Number(_) => Some(NUMBER),
+        Token::String(_) => Some(STRING),
+        Token::Comment(_) => Some(COMMENT),
+        Token::Identifier(name) if builtins::lookup(name).is_some() => Some(BUILTIN),
+        Token::Identifier(_) => Some(IDENTIFIER),
+        Token::LParen | Token::RParen | Token::Comma | Token::Colon | Token::Eof => None,
+        _ => Some(KEYWORD),
+    }
+}
+
+pub(crate) fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+pub fn textmate_grammar() -> String {
+    let keywords: Vec<&str> = lexer::KEYWORDS.iter().map(|(word, _)| *word).collect();
+    let braincells: Vec<&str> = lexer::BRAINCELLS.iter().map(|(name, _)| *name).collect();
+    let builtins: Vec<&str> = builtins::BUILTINS.iter().map(|b| b.name).collect();
+    let operators: Vec<String> = lexer::OPERATORS
+        .iter()
+        .map(|(op, _)| op.to_string())
+        .chain(
+            lexer::ASCII_OPERATORS
+                .iter()
+                .map(|(op, _)| format!("\\{}", op)),
+        )
+        .collect();
+
+    let rule = |name: &str, pattern: String| {
+        format!(
+            "{{\"name\": {}, \"match\": {}}}",
+            json_string(name),
+            json_string(&pattern)
+        )
+    };
+
+    let mut out = String::new();
+    let _ = writeln!(out, "{{");
+    let _ = writeln!(out, "  \"name\": \"Brainrot\",");
+    let _ = writeln!(out, "  \"scopeName\": \"source.brainrot\",");
+    let _ = writeln!(out, "  \"fileTypes\": [\"br\", \"brainrot\"],");
+    let includes: Vec<String> = [
+        "comments",
+        "strings",
+        "numbers",
+        "keywords",
+        "builtins",
+        "braincells",
+        "operators",
+    ]
+    .iter()
+    .map(|name| format!("    {{\"include\": \"#{}\"}}", name))
+    .collect();
+    let _ = writeln!(out, "  \"patterns\": [");
+    let _ = writeln!(out, "{}", includes.join(",\n"));
+    let _ = writeln!(out, "  ],");
+    let _ = writeln!(out, "  \"repository\": {{");
+    let _ = writeln!(
+        out,
+        "    \"comments\": {{\"patterns\": [{}, {{\"include\": \"#block-comment\"}}]}},",
+        rule(
+            "comment.line.brainrot",
+            format!("[{}{}].*$", lexer::LINE_COMMENT, lexer::ASCII_LINE_COMMENT)
+        )
+    );
+    let _ = writeln!(
+        out,
+        "    \"block-comment\": {{\"name\": \"comment.block.brainrot\", \"begin\": {}, \"end\": {}, \"patterns\": [{{\"include\": \"#block-comment\"}}]}},",
+        json_string(&lexer::BLOCK_COMMENT_OPEN.to_string()),
+        json_string(&lexer::BLOCK_COMMENT_CLOSE.to_string())
+    );
+    let _ = writeln!(
+        out,
+        "    \"strings\": {{\"name\": \"string.quoted.double.brainrot\", \"begin\": \"\\\"\", \"end\": \"\\\"\", \"patterns\": [{}]}},",
+        rule(
+            "constant.character.escape.brainrot",
+            r#"\\[nt"\\]"#.to_string()
+        )
+    );
+    let _ = writeln!(
+        out,
+        "    \"numbers\": {},",
+        rule(
+            "constant.numeric.brainrot",
+            r"\b[0-9]+(\.[0-9]*)?".to_string()
+        )
+    );
+    let _ = writeln!(
+        out,
+        "    \"keywords\": {},",
+        rule(
+            "keyword.control.brainrot",
+            format!(r"\b({})\b", keywords.join("|"))
+        )
+    );
+    let _ = writeln!(
+        out,
+        "    \"builtins\": {},",
+        rule(
+            "support.function.builtin.brainrot",
+            format!(r"\b({})\b", builtins.join("|"))
+        )
+    );
+    let _ = writeln!(
+        out,
+        "    \"braincells\": {},",
+        rule(
+            "variable.other.braincell.brainrot",
+            format!(r"\b({})\b", braincells.join("|"))
+        )
+    );
+    let _ = writeln!(
+        out,
+        "    \"operators\": {}",
+        rule("keyword.operator.brainrot", operators.join("|"))
+    );
+    let _ = writeln!(out, "  }}");
+    let _ = writeln!(out, "}}");
+    out
+}
@@ -0,0 +1,305 @@
+use std::io::{self, BufRead, BufWriter, IsTerminal, Write};
+use std::time::Duration;
+
+// Everything the VM needs from the outside world. Embedders (tests, WASM
+// playgrounds) implement this instead of handing the VM real stdio.
+pub trait VmHost {
+    fn print(&mut self, s: &str) -> Result<(), String>;
+
+    // Text for stderr, from SCREAM. Hosts without a separate error stream
+    // interleave it with the normal output.
+    fn print_error(&mut self, s: &str) -> Result<(), String> {
+        self.print(s)
+    }
+
+    // Returns one line of input, with or without its trailing newline; an
+    // empty string means end of input.
+    fn read_line(&mut self) -> Result<String, String>;
+
+    // Like `read_line`, but gives up with `None` once `timeout` passes
+    // without input. Hosts that can't wait on their input just block.
+    fn read_line_timeout(&mut self, timeout: Duration) -> Result<Option<String>, String> {
+        let _ = timeout;
+        self.read_line().map(Some)
+    }
+
+    fn flush(&mut self) -> Result<(), String> {
+        Ok(())
+    }
+
+    // An HTTP GET, or a POST of `body`, for YOINK and YEET. The VM only
+    // asks when `VmOptions::allow_net` is set.
+    fn fetch(&mut self, url: &str, body: Option<&str>) -> Result<String, String> {
+        crate::net::request(url, body)
+    }
+
+    // Everything printed so far, for hosts that keep output in memory; it
+    // ends up in `RunResult::output`.
+    fn captured_output(&self) -> Option<&str> {
+        None
+    }
+}
+
+// Lets a VM borrow a host the caller keeps using afterwards.
+impl<H: VmHost + ?Sized> VmHost for &mut H {
+    fn print(&mut self, s: &str) -> Result<(), String> {
+        (**self).print(s)
+    }
+
+    fn print_error(&mut self, s: &str) -> Result<(), String> {
+        (**self).print_error(s)
+    }
+
+    fn read_line(&mut self) -> Result<String, String> {
+        (**self).read_line()
+    }
+
+    fn read_line_timeout(&mut self, timeout: Duration) -> Result<Option<String>, String> {
+        (**self).read_line_timeout(timeout)
+    }
+
+    fn flush(&mut self) -> Result<(), String> {
+        (**self).flush()
+    }
+
+    fn fetch(&mut self, url: &str, body: Option<&str>) -> Result<String, String> {
+        (**self).fetch(url, body)
+    }
+
+    fn captured_output(&self) -> Option<&str> {
+        (**self).captured_output()
+    }
+}
+
+pub struct IoHost<R: BufRead, W: Write> {
+    input: R,
+    output: W,
+}
+
+impl<R: BufRead, W: Write> IoHost<R, W> {
+    pub fn new(input: R, output: W) -> Self {
+        Self { input, output }
+    }
+}
+
+impl<R: BufRead, W: Write> VmHost for IoHost<R, W> {
+    fn print(&mut self, s: &str) -> Result<(), String> {
+        self.output
+            .write_all(s.as_bytes())
+            .map_err(|_| "failed to write output".to_string())
+    }
+
+    // BufRead::read_line retries reads interrupted by a signal, which would
+    // leave Ctrl-C waiting for the next Enter; this loop gives up instead so
+    // the VM can stop.
+    fn read_line(&mut self) -> Result<String, String> {
+        let mut bytes = Vec::new();
+        loop {
+            let available = match self.input.fill_buf() {
+                Ok(available) => available,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {
+                    return Err("read interrupted".to_string())
+                }
+                Err(_) => return Err("failed to read from stdin".to_string()),
+            };
+            if available.is_empty() {
+                break;
+            }
+            match available.iter().position(|&b| b == b'\n') {
+                Some(end) => {
+                    bytes.extend_from_slice(&available[..=end]);
+                    self.input.consume(end + 1);
+                    break;
+                }
+                None => {
+                    let len = available.len();
+                    bytes.extend_from_slice(available);
+                    self.input.consume(len);
+                }
+            }
+        }
+        String::from_utf8(bytes).map_err(|_| "failed to read from stdin".to_string())
+    }
+
+    fn flush(&mut self) -> Result<(), String> {
+        self.output
+            .flush()
+            .map_err(|_| "failed to flush output".to_string())
+    }
+}
+
+// Serves input from a string and keeps output in memory, optionally capped.
+pub struct CaptureHost {
+    input: Vec<String>,
+    next_line: usize,
+    pub output: String,
+    max_output_bytes: Option<usize>,
+}
+
+impl CaptureHost {
+    pub fn new(input: &str) -> Self {
+        Self {
+            input: input.lines().map(str::to_string).collect(),
+            next_line: 0,
+            output: String::new(),
+            max_output_bytes: None,
+        }
+    }
+
+    pub fn with_output_limit(mut self, max_output_bytes: usize) -> Self {
+        self.max_output_bytes = Some(max_output_bytes);
+        self
+    }
+}
+
+impl VmHost for CaptureHost {
+    fn print(&mut self, s: &str) -> Result<(), String> {
+        if let Some(max) = self.max_output_bytes {
+            if self.output.len() + s.len() > max {
+                return Err("output limit exceeded".to_string());
+            }
+        }
+        self.output.push_str(s);
+        Ok(())
+    }
+
+    fn read_line(&mut self) -> Result<String, String> {
+        let line = self.input.get(self.next_line).cloned().unwrap_or_default();
+        self.next_line += 1;
+        Ok(line)
+    }
+
+    fn captured_output(&self) -> Option<&str> {
+        Some(&self.output)
+    }
+}
+
+// Output to a terminal goes out a line at a time; piped or redirected output
+// is block-buffered and written when the VM flushes.
+pub struct StdHost {
+    io: IoHost<io::StdinLock<'static>, Box<dyn Write>>,
+}
+
+impl StdHost {
+    pub fn new() -> Self {
+        let stdout = io::stdout();
+        let output: Box<dyn Write> = if stdout.is_terminal() {
+            Box::new(stdout.lock())
+        } else {
+            Box::new(BufWriter::new(stdout.lock()))
+        };
+        Self {
+            io: IoHost::new(io::stdin().lock(), output),
+        }
+    }
+}
+
+impl Default for StdHost {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl VmHost for StdHost {
+    fn print(&mut self, s: &str) -> Result<(), String> {
+        self.io.print(s)
+    }
+
+    // Flushes stdout first so the two streams stay in order on a terminal.
+    fn print_error(&mut self, s: &str) -> Result<(), String> {
+        self.io.flush()?;
+        io::stderr()
+            .write_all(s.as_bytes())
+            .map_err(|_| "failed to write to stderr".to_string())
+    }
+
+    fn read_line(&mut self) -> Result<String, String> {
+        self.io.read_line()
+    }
+
+    #[cfg(unix)]
+    fn read_line_timeout(&mut self, timeout: Duration) -> Result<Option<String>, String> {
+        if stdin_ready(&mut self.io.input, timeout)? {
+            self.io.read_line().map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn flush(&mut self) -> Result<(), String> {
+        self.io.flush()
+    }
+}
+
+// Waits up to `timeout` for stdin to have something to read: bytes already
+// buffered, new input, or end of input. stdin is non-blocking only while
+// this runs, and a partly typed line still blocks `read_line` until Enter.
+#[cfg(unix)]
+fn stdin_ready(input: &mut impl BufRead, timeout: Duration) -> Result<bool, String> {
+    use std::os::raw::{c_int, c_short};
+    use std::time::Instant;
+
+    const F_GETFL: c_int = 3;
+    const F_SETFL: c_int = 4;
+    #[cfg(target_os = "linux")]
+    const O_NONBLOCK: c_int = 0o4000;
+    #[cfg(not(target_os = "linux"))]
+    const O_NONBLOCK: c_int = 0x0004;
+    const POLLIN: c_short = 1;
+
+    #[cfg(target_os = "linux")]
+    type Nfds = std::os::raw::c_ulong;
+    #[cfg(not(target_os = "linux"))]
+    type Nfds = std::os::raw::c_uint;
+
+    #[repr(C)]
+    struct PollFd {
+        fd: c_int,
+        events: c_short,
+        revents: c_short,
+    }
+
+    extern "C" {
+        fn fcntl(fd: c_int, cmd: c_int, ...) -> c_int;
+        fn poll(fds: *mut PollFd, nfds: Nfds, timeout: c_int) -> c_int;
+    }
+
+    let failed = |e: io::Error| {
+        if e.kind() == io::ErrorKind::Interrupted {
+            "read interrupted".to_string()
+        } else {
+            "failed to read from stdin".to_string()
+        }
+    };
+
+    // SAFETY: fcntl on fd 0 only reads and sets its status flags.
+    let flags = unsafe { fcntl(0, F_GETFL) };
+    if flags < 0 || unsafe { fcntl(0, F_SETFL, flags | O_NONBLOCK) } < 0 {
+        return Err(failed(io::Error::last_os_error()));
+    }
+    let deadline = Instant::now() + timeout;
+    let ready = loop {
+        match input.fill_buf() {
+            Ok(_) => break Ok(true),
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {}
+            Err(e) => break Err(failed(e)),
+        }
+        let left = deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            break Ok(false);
+        }
+        let ms = left.as_micros().div_ceil(1000).min(c_int::MAX as u128) as c_int;
+        let mut fd = PollFd {
+            fd: 0,
+            events: POLLIN,
+            revents: 0,
+        };
+        // SAFETY: `fd` outlives the call and is the only entry poll reads.
+        if unsafe { poll(&mut fd, 1, ms) } < 0 {
+            break Err(failed(io::Error::last_os_error()));
+        }
+    };
+    // SAFETY: puts back the flags read above.
+    unsafe { fcntl(0, F_SETFL, flags) };
+    ready
+}
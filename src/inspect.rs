@@ -0,0 +1,163 @@
+// Reports behind `brvm inspect`: a one-screen summary of a BRBC file, and
+// with `--size` a breakdown of where its bytes go.
+use crate::bytecode::{self, Module};
+use crate::value::Value;
+use std::collections::HashSet;
+use std::fmt::Write;
+
+const HEADER_SIZE: usize = 8; // magic, version, flags
+const GLOBALS_SIZE: usize = 2; // global count, when the header declares one
+const COUNT_SIZE: usize = 4; // leading count or length of each section
+const PREVIEW_CHARS: usize = 40;
+
+pub fn summary(bytecode: &[u8]) -> Result<String, String> {
+    let module = bytecode::decode(bytecode)?;
+    let mut out = String::new();
+    let _ = writeln!(out, "BRBC version {}", module.version);
+    let _ = writeln!(out, "flags: 0x{:04x}", module.flags);
+    let _ = writeln!(out, "globals: {}", module.globals);
+    let _ = writeln!(out, "constants: {}", module.constants.len());
+    let _ = writeln!(out, "functions: {}", module.functions.len());
+    let _ = writeln!(out, "code: {} bytes", module.code.len());
+    let _ = writeln!(out, "total: {} bytes", bytecode.len());
+    Ok(out)
+}
+
+// Bytes by section, code bytes by function, constant pool bytes by kind,
+// and the `top` largest string constants.
+pub fn size_report(bytecode: &[u8], top: usize) -> Result<String, String> {
+    let module = bytecode::decode(bytecode)?;
+    let total = bytecode.len();
+    let mut out = String::new();
+    let _ = writeln!(out, "total: {} bytes", total);
+
+    let pool: usize = module.constants.iter().map(constant_size).sum();
+    let table = if module.version >= 4 {
+        COUNT_SIZE + module.functions.len() * bytecode::function_entry_size(module.flags)
+    } else {
+        0
+    };
+    let _ = writeln!(out, "\n{:<16}{:>10}{:>8}", "section", "bytes", "share");
+    for (name, bytes) in [
+        ("header", header_size(&module)),
+        ("constant pool", COUNT_SIZE + pool),
+        ("function table", table),
+        ("code", COUNT_SIZE + module.code.len()),
+    ] {
+        let _ = writeln!(out, "{:<16}{:>10}{:>8}", name, bytes, share(bytes, total));
+    }
+
+    let _ = writeln!(out, "\n{:<16}{:>10}{:>8}", "function", "code", "share");
+    for (name, bytes) in function_sizes(&module) {
+        let _ = writeln!(
+            out,
+            "{:<16}{:>10}{:>8}",
+            name,
+            bytes,
+            share(bytes, module.code.len())
+        );
+    }
+
+    // A string used only as a function name is counted as a name.
+    let names: HashSet<usize> = module
+        .functions
+        .iter()
+        .map(|f| f.name_const as usize)
+        .collect();
+    let mut kinds = [("number", 0, 0), ("string", 0, 0), ("function name", 0, 0)];
+    for (idx, constant) in module.constants.iter().enumerate() {
+        let kind = match constant {
+            Value::Number(_) | Value::Big(_) => 0,
+            Value::String(_) if names.contains(&idx) => 2,
+            Value::String(_) => 1,
+        };
+        kinds[kind].1 += 1;
+        kinds[kind].2 += constant_size(constant);
+    }
+    let _ = writeln!(
+        out,
+        "\n{:<16}{:>10}{:>8}{:>8}",
+        "constants", "bytes", "count", "share"
+    );
+    for (name, count, bytes) in kinds {
+        let _ = writeln!(
+            out,
+            "{:<16}{:>10}{:>8}{:>8}",
+            name,
+            bytes,
+            count,
+            share(bytes, pool)
+        );
+    }
+
+    let mut strings: Vec<(usize, &str)> = module
+        .constants
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, constant)| match constant {
+            Value::String(s) => Some((idx, s.as_str())),
+            _ => None,
+        })
+        .collect();
+    strings.sort_by_key(|&(idx, s)| (std::cmp::Reverse(s.len()), idx));
+    if top > 0 && !strings.is_empty() {
+        let _ = writeln!(out, "\nlargest strings");
+        for (idx, s) in strings.into_iter().take(top) {
+            let _ = writeln!(out, "  #{:<4} {:>8} bytes  {}", idx, s.len(), preview(s));
+        }
+    }
+    Ok(out)
+}
+
+fn constant_size(constant: &Value) -> usize {
+    match constant {
+        Value::Number(_) => 1 + 8,
+        Value::String(s) => 1 + 4 + s.len(),
+        Value::Big(n) => 1 + 4 + n.to_string().len(),
+    }
+}
+
+// Main code runs from offset 0 to the first function; each function runs
+// to the next one or the end of the section.
+fn function_sizes(module: &Module) -> Vec<(String, usize)> {
+    let mut starts: Vec<(usize, String)> = module
+        .functions
+        .iter()
+        .map(|f| (f.code_offset as usize, module.function_name(f).to_string()))
+        .collect();
+    starts.sort();
+    starts.insert(0, (0, "(main)".to_string()));
+    let ends = starts
+        .iter()
+        .skip(1)
+        .map(|&(start, _)| start)
+        .chain([module.code.len()]);
+    starts
+        .iter()
+        .zip(ends)
+        .map(|((start, name), end)| (name.clone(), end.saturating_sub(*start)))
+        .collect()
+}
+
+fn header_size(module: &Module) -> usize {
+    if module.flags & bytecode::FLAG_GLOBALS != 0 {
+        HEADER_SIZE + GLOBALS_SIZE
+    } else {
+        HEADER_SIZE
+    }
+}
+
+fn share(bytes: usize, total: usize) -> String {
+    if total == 0 {
+        return "-".to_string();
+    }
+    format!("{:.1}%", bytes as f64 * 100.0 / total as f64)
+}
+
+fn preview(s: &str) -> String {
+    if s.chars().count() <= PREVIEW_CHARS {
+        return format!("{:?}", s);
+    }
+    let head: String = s.chars().take(PREVIEW_CHARS).collect();
+    format!("{:?}...", head)
+}
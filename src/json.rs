@@ -0,0 +1,155 @@
+// The small JSON reader behind `brvm serve` and `brvm batch`. Requests are
+// flat objects whose values are strings, numbers, booleans or null, so that
+// is all this reads; it keeps the crate free of a JSON dependency. Writing
+// goes through `lexdump::json_string`.
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    String(String),
+    Number(f64),
+    Bool(bool),
+    Null,
+}
+
+pub fn parse_object(text: &str) -> Result<Vec<(String, Json)>, String> {
+    let mut chars = text.chars().peekable();
+    let mut fields = Vec::new();
+
+    skip_whitespace(&mut chars);
+    expect(&mut chars, '{')?;
+    skip_whitespace(&mut chars);
+    if chars.peek() == Some(&'}') {
+        chars.next();
+    } else {
+        loop {
+            skip_whitespace(&mut chars);
+            let key = parse_string(&mut chars)?;
+            skip_whitespace(&mut chars);
+            expect(&mut chars, ':')?;
+            skip_whitespace(&mut chars);
+            let value = parse_value(&mut chars, &key)?;
+            fields.push((key, value));
+            skip_whitespace(&mut chars);
+            match chars.next() {
+                Some(',') => continue,
+                Some('}') => break,
+                _ => return Err("expected ',' or '}'".to_string()),
+            }
+        }
+    }
+
+    skip_whitespace(&mut chars);
+    if chars.next().is_some() {
+        return Err("trailing data after JSON object".to_string());
+    }
+    Ok(fields)
+}
+
+fn parse_value(chars: &mut Chars, key: &str) -> Result<Json, String> {
+    match chars.peek() {
+        Some('"') => parse_string(chars).map(Json::String),
+        Some('t') => parse_word(chars, "true").map(|_| Json::Bool(true)),
+        Some('f') => parse_word(chars, "false").map(|_| Json::Bool(false)),
+        Some('n') => parse_word(chars, "null").map(|_| Json::Null),
+        Some(c) if *c == '-' || c.is_ascii_digit() => parse_number(chars),
+        _ => Err(format!(
+            "field \"{}\" must be a string, number, boolean or null",
+            key
+        )),
+    }
+}
+
+fn parse_word(chars: &mut Chars, word: &str) -> Result<(), String> {
+    for want in word.chars() {
+        if chars.next() != Some(want) {
+            return Err(format!("expected {}", word));
+        }
+    }
+    Ok(())
+}
+
+fn parse_number(chars: &mut Chars) -> Result<Json, String> {
+    let mut text = String::new();
+    while let Some(&c) = chars.peek() {
+        if !matches!(c, '0'..='9' | '-' | '+' | '.' | 'e' | 'E') {
+            break;
+        }
+        text.push(c);
+        chars.next();
+    }
+    text.parse()
+        .map(Json::Number)
+        .map_err(|_| format!("bad number {}", text))
+}
+
+type Chars<'a> = std::iter::Peekable<std::str::Chars<'a>>;
+
+fn skip_whitespace(chars: &mut Chars) {
+    while chars
+        .peek()
+        .is_some_and(|c| matches!(c, ' ' | '\t' | '\n' | '\r'))
+    {
+        chars.next();
+    }
+}
+
+fn expect(chars: &mut Chars, want: char) -> Result<(), String> {
+    match chars.next() {
+        Some(c) if c == want => Ok(()),
+        _ => Err(format!("expected '{}'", want)),
+    }
+}
+
+fn parse_string(chars: &mut Chars) -> Result<String, String> {
+    expect(chars, '"')?;
+    let mut out = String::new();
+    loop {
+        match chars.next().ok_or("unterminated string")? {
+            '"' => return Ok(out),
+            '\\' => match chars.next().ok_or("unterminated string")? {
+                '"' => out.push('"'),
+                '\\' => out.push('\\'),
+                '/' => out.push('/'),
+                'b' => out.push('\u{8}'),
+                'f' => out.push('\u{c}'),
+                'n' => out.push('\n'),
+                'r' => out.push('\r'),
+                't' => out.push('\t'),
+                'u' => out.push(parse_unicode_escape(chars)?),
+                c => return Err(format!("unknown escape \\{}", c)),
+            },
+            c if (c as u32) < 0x20 => return Err("control character in string".to_string()),
+            c => out.push(c),
+        }
+    }
+}
+
+fn parse_unicode_escape(chars: &mut Chars) -> Result<char, String> {
+    let high = parse_hex4(chars)?;
+    if !(0xD800..0xDC00).contains(&high) {
+        return char::from_u32(high).ok_or_else(|| "invalid \\u escape".to_string());
+    }
+
+    // Characters outside the BMP arrive as a UTF-16 surrogate pair.
+    if chars.next() != Some('\\') || chars.next() != Some('u') {
+        return Err("unpaired surrogate in \\u escape".to_string());
+    }
+    let low = parse_hex4(chars)?;
+    if !(0xDC00..0xE000).contains(&low) {
+        return Err("unpaired surrogate in \\u escape".to_string());
+    }
+    char::from_u32(0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00))
+        .ok_or_else(|| "invalid \\u escape".to_string())
+}
+
+fn parse_hex4(chars: &mut Chars) -> Result<u32, String> {
+    let mut value = 0;
+    for _ in 0..4 {
+        let digit = chars
+            .next()
+            .and_then(|c| c.to_digit(16))
+            .ok_or("invalid \\u escape")?;
+        value = value * 16 + digit;
+    }
+    Ok(value)
+}
@@ -0,0 +1,129 @@
+// Token listings behind `brvm lex`, for editor tooling. Positions are the
+// 1-based line and column of the token's first character.
+use crate::lexer::{SpannedToken, Token};
+use std::fmt::Write;
+
+const BRAINCELL_NAMES: [&str; 7] = ["aura", "peak", "goon", "mog", "npc", "sigma", "gyatt"];
+
+enum Payload {
+    Number(f64),
+    Text(String),
+}
+
+pub fn listing(tokens: &[SpannedToken]) -> String {
+    let mut out = String::new();
+    for t in tokens {
+        let position = format!("{}:{}", t.span.line, t.span.col);
+        let _ = match payload(&t.token) {
+            None => writeln!(out, "{:<8} {}", position, kind(&t.token)),
+            Some(Payload::Number(n)) => {
+                writeln!(out, "{:<8} {} {}", position, kind(&t.token), n)
+            }
+            Some(Payload::Text(s)) => {
+                writeln!(out, "{:<8} {} {:?}", position, kind(&t.token), s)
+            }
+        };
+    }
+    out
+}
+
+pub fn json(tokens: &[SpannedToken]) -> String {
+    let mut out = String::from("[");
+    for (idx, t) in tokens.iter().enumerate() {
+        out.push_str(if idx == 0 { "\n  " } else { ",\n  " });
+        let _ = write!(out, "{{\"kind\": \"{}\"", kind(&t.token));
+        match payload(&t.token) {
+            None => {}
+            Some(Payload::Number(n)) => {
+                let _ = write!(out, ", \"value\": {}", n);
+            }
+            Some(Payload::Text(s)) => {
+                let _ = write!(out, ", \"value\": {}", json_string(&s));
+            }
+        }
+        let _ = write!(
+            out,
+            ", \"line\": {}, \"col\": {}}}",
+            t.span.line, t.span.col
+        );
+    }
+    out.push_str(if tokens.is_empty() { "]\n" } else { "\n]\n" });
+    out
+}
+
+pub fn kind(token: &Token) -> &'static str {
+    match token {
+        Token::Lock => "Lock",
+        Token::In => "In",
+        Token::Its => "Its",
+        Token::Over => "Over",
+        Token::Fanumtax => "Fanumtax",
+        Token::Fr => "Fr",
+        Token::Say => "Say",
+        Token::Yap => "Yap",
+        Token::Touchy => "Touchy",
+        Token::Ongod => "Ongod",
+        Token::No => "No",
+        Token::Cap => "Cap",
+        Token::Deadass => "Deadass",
+        Token::Skibidi => "Skibidi",
+        Token::Rizzup => "Rizzup",
+        Token::Tralalero => "Tralalero",
+        Token::Tralala => "Tralala",
+        Token::Retreat => "Retreat",
+        Token::Ring => "Ring",
+        Token::Yas => "Yas",
+        Token::Diddle => "Diddle",
+        Token::Youshallnotpass => "Youshallnotpass",
+        Token::Crashout => "Crashout",
+        Token::Scream => "Scream",
+        Token::Based => "Based",
+        Token::Comma => "Comma",
+        Token::Colon => "Colon",
+        Token::Identifier(_) => "Identifier",
+        Token::Add => "Add",
+        Token::Subtract => "Subtract",
+        Token::Multiply => "Multiply",
+        Token::Divide => "Divide",
+        Token::Equal => "Equal",
+        Token::Braincell(_) => "Braincell",
+        Token::Number(_) => "Number",
+        Token::String(_) => "String",
+        Token::LParen => "LParen",
+        Token::RParen => "RParen",
+        Token::Comment(_) => "Comment",
+        Token::Eof => "Eof",
+    }
+}
+
+fn payload(token: &Token) -> Option<Payload> {
+    match token {
+        Token::Number(n) => Some(Payload::Number(*n)),
+        Token::String(s) | Token::Identifier(s) | Token::Comment(s) => {
+            Some(Payload::Text(s.clone()))
+        }
+        Token::Braincell(idx) => BRAINCELL_NAMES
+            .get(*idx as usize)
+            .map(|name| Payload::Text(name.to_string())),
+        _ => None,
+    }
+}
+
+pub fn json_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for ch in s.chars() {
+        match ch {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
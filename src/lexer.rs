@@ -1,4 +1,5 @@
 use crate::error::CompileError;
+use unicode_ident::{is_xid_continue, is_xid_start};
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Token {
@@ -10,6 +11,7 @@ pub enum Token {
     Fanumtax,
     Fr,
     Say,
+    Yap, // print without newline
     Touchy,
     Ongod,              // if
     No,                 // else (part 1)
@@ -24,7 +26,11 @@ pub enum Token {
     Yas,                // call keyword part 2
     Diddle,             // copy statement
     Youshallnotpass,    // halt
+    Crashout,           // raise a runtime error
+    Scream,             // print to stderr
+    Based,              // constant declaration
     Comma,              // parameter separator
+    Colon,              // type annotation
     Identifier(String), // for function names and local variables
 
     // Operators
@@ -32,6 +38,7 @@ pub enum Token {
     Subtract, // 😭
     Multiply, // 😏
     Divide,   // 🚡
+    Equal,    // 🟰
 
     // Braincells
     Braincell(u8), // 0=aura, 1=peak, 2=goon, 3=mog, 4=npc, 5=sigma, 6=gyatt
@@ -45,18 +52,90 @@ pub enum Token {
     RParen,
 
     // Special
+    Comment(String), // only produced by tokenize_with_comments
     Eof,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Span {
+    pub line: usize,
+    pub col: usize,
+    pub offset: usize, // byte offset into the source
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpannedToken {
+    pub token: Token,
+    pub span: Span,
+}
+
+// Tokens are produced on demand, so a caller that only needs the start of a
+// file, or handles tokens one at a time, never holds the whole list. The
+// iterator ends after Eof or after the first error.
 pub struct Lexer<'a> {
-    chars: Vec<(usize, usize, char)>, // (byte_offset, char_index, char)
-    position: usize,
+    source: &'a str,
+    position: usize, // byte offset of the current char
     line: usize,
     col: usize,
     filename: &'a str,
+    keep_comments: bool,
+    done: bool,
 }
 
-const BRAINCELLS: &[(&str, u8)] = &[
+// Block comments run from 🙈 to the matching 🙉 and nest, so a chunk of code
+// that already contains a block comment can be commented out as a whole.
+pub const BLOCK_COMMENT_OPEN: char = '🙈';
+pub const BLOCK_COMMENT_CLOSE: char = '🙉';
+pub const LINE_COMMENT: char = '🖕';
+// Also starts a line comment, for editors and chat apps that lose the emoji.
+pub const ASCII_LINE_COMMENT: char = '#';
+
+pub const KEYWORDS: &[(&str, Token)] = &[
+    ("LOCK", Token::Lock),
+    ("IN", Token::In),
+    ("ITS", Token::Its),
+    ("OVER", Token::Over),
+    ("FANUMTAX", Token::Fanumtax),
+    ("FR", Token::Fr),
+    ("SAY", Token::Say),
+    ("YAP", Token::Yap),
+    ("TOUCHY", Token::Touchy),
+    ("ONGOD", Token::Ongod),
+    ("NO", Token::No),
+    ("CAP", Token::Cap),
+    ("DEADASS", Token::Deadass),
+    ("SKIBIDI", Token::Skibidi),
+    ("RIZZUP", Token::Rizzup),
+    ("TRALALERO", Token::Tralalero),
+    ("TRALALA", Token::Tralala),
+    ("RETREAT", Token::Retreat),
+    ("ring", Token::Ring),
+    ("yas", Token::Yas),
+    ("DIDDLE", Token::Diddle),
+    ("YOUSHALLNOTPASS", Token::Youshallnotpass),
+    ("CRASHOUT", Token::Crashout),
+    ("SCREAM", Token::Scream),
+    ("BASED", Token::Based),
+];
+
+pub const OPERATORS: &[(char, Token)] = &[
+    ('💀', Token::Add),
+    ('😭', Token::Subtract),
+    ('😏', Token::Multiply),
+    ('🚡', Token::Divide),
+    ('🟰', Token::Equal),
+];
+
+// Plain spellings of the arithmetic operators, for keyboards and tools that
+// mangle emoji.
+pub const ASCII_OPERATORS: &[(char, Token)] = &[
+    ('+', Token::Add),
+    ('-', Token::Subtract),
+    ('*', Token::Multiply),
+    ('/', Token::Divide),
+];
+
+pub const BRAINCELLS: &[(&str, u8)] = &[
     ("aura", 0),
     ("peak", 1),
     ("goon", 2),
@@ -67,69 +146,111 @@ const BRAINCELLS: &[(&str, u8)] = &[
 ];
 
 pub fn tokenize(input: &str, filename: &str) -> Result<Vec<Token>, CompileError> {
-    let chars: Vec<(usize, usize, char)> = input
-        .char_indices()
-        .enumerate()
-        .map(|(idx, (byte_pos, ch))| (byte_pos, idx, ch))
-        .collect();
-
-    let mut lexer = Lexer::new(chars, filename);
-    let mut tokens = Vec::new();
-
-    loop {
-        let token = lexer.next_token()?;
-        let is_eof = matches!(token, Token::Eof);
-        tokens.push(token);
-        if is_eof {
-            break;
-        }
-    }
+    let tokens = tokenize_spanned(input, filename)?;
+    Ok(tokens.into_iter().map(|t| t.token).collect())
+}
+
+pub fn tokenize_spanned(input: &str, filename: &str) -> Result<Vec<SpannedToken>, CompileError> {
+    tokenize_inner(input, filename, false)
+}
 
-    Ok(tokens)
+// Like tokenize_spanned, but each 🖕 or # line comment or 🙈 ... 🙉 block comment
+// becomes a Comment token holding its text. The parser does not accept these.
+pub fn tokenize_with_comments(
+    input: &str,
+    filename: &str,
+) -> Result<Vec<SpannedToken>, CompileError> {
+    tokenize_inner(input, filename, true)
+}
+
+fn tokenize_inner(
+    input: &str,
+    filename: &str,
+    keep_comments: bool,
+) -> Result<Vec<SpannedToken>, CompileError> {
+    let lexer = Lexer::new(input, filename);
+    match keep_comments {
+        true => lexer.with_comments().collect(),
+        false => lexer.collect(),
+    }
 }
 
 impl<'a> Lexer<'a> {
-    fn new(chars: Vec<(usize, usize, char)>, filename: &'a str) -> Self {
-        Self {
-            chars,
+    pub fn new(source: &'a str, filename: &'a str) -> Self {
+        let mut lexer = Self {
+            source,
             position: 0,
             line: 1,
             col: 1,
             filename,
+            keep_comments: false,
+            done: false,
+        };
+        if source.starts_with("#!") {
+            // Shebang line, so scripts can be run directly on Unix
+            lexer.skip_line();
         }
+        lexer
     }
 
-    fn next_token(&mut self) -> Result<Token, CompileError> {
-        self.skip_whitespace();
+    // Yield a Comment token for each comment instead of skipping it.
+    pub fn with_comments(mut self) -> Self {
+        self.keep_comments = true;
+        self
+    }
 
-        if self.position >= self.chars.len() {
-            return Ok(Token::Eof);
+    fn next_spanned_token(&mut self) -> Result<SpannedToken, CompileError> {
+        // Comments are skipped here so the span points at the real token.
+        loop {
+            self.skip_whitespace();
+            if matches!(self.current_char(), Some(LINE_COMMENT | ASCII_LINE_COMMENT)) {
+                if self.keep_comments {
+                    let span = self.span();
+                    let token = self.read_comment();
+                    return Ok(SpannedToken { token, span });
+                }
+                self.skip_line();
+            } else if self.current_char() == Some(BLOCK_COMMENT_OPEN) {
+                let span = self.span();
+                let text = self.read_block_comment()?;
+                if self.keep_comments {
+                    return Ok(SpannedToken {
+                        token: Token::Comment(text),
+                        span,
+                    });
+                }
+            } else {
+                break;
+            }
         }
 
-        let (_, _, ch) = self.current_char();
+        let span = self.span();
+        let token = self.next_token()?;
+        Ok(SpannedToken { token, span })
+    }
 
-        // Check for comment line
-        if ch == '🖕' {
-            self.skip_line();
-            return self.next_token();
-        }
+    fn next_token(&mut self) -> Result<Token, CompileError> {
+        let Some(ch) = self.current_char() else {
+            return Ok(Token::Eof);
+        };
 
         // Operators
-        if ch == '💀' {
-            self.advance();
-            return Ok(Token::Add);
-        }
-        if ch == '😭' {
-            self.advance();
-            return Ok(Token::Subtract);
-        }
-        if ch == '😏' {
+        if let Some((_, token)) = OPERATORS
+            .iter()
+            .chain(ASCII_OPERATORS)
+            .find(|(op, _)| *op == ch)
+        {
             self.advance();
-            return Ok(Token::Multiply);
+            return Ok(token.clone());
         }
-        if ch == '🚡' {
-            self.advance();
-            return Ok(Token::Divide);
+
+        if ch == BLOCK_COMMENT_CLOSE {
+            return Err(CompileError::new(
+                self.filename,
+                self.line,
+                self.col,
+                "🙉 without an opening 🙈",
+            ));
         }
 
         // String literal
@@ -150,6 +271,10 @@ impl<'a> Lexer<'a> {
             self.advance();
             return Ok(Token::Comma);
         }
+        if ch == ':' {
+            self.advance();
+            return Ok(Token::Colon);
+        }
 
         // Number
         if ch.is_ascii_digit() {
@@ -157,38 +282,42 @@ impl<'a> Lexer<'a> {
         }
 
         // Identifier
-        if ch.is_ascii_alphabetic() || ch == '_' {
+        if is_xid_start(ch) || ch == '_' {
             return self.read_identifier();
         }
 
-        Err(CompileError::new(
+        let error = CompileError::new(
             self.filename,
             self.line,
             self.col,
             &format!("unexpected character: {}", ch),
-        ))
+        );
+        Err(match ch {
+            '=' => error.with_help(
+                "assignments are written FANUMTAX name FR value, and 🟰 compares two values",
+            ),
+            _ => error,
+        })
     }
 
     fn read_string(&mut self) -> Result<Token, CompileError> {
         self.advance(); // skip opening "
         let mut result = String::new();
 
-        while self.position < self.chars.len() {
-            let (_, _, ch) = self.current_char();
+        while let Some(ch) = self.current_char() {
             if ch == '"' {
                 break;
             }
             if ch == '\\' {
                 self.advance();
-                if self.position >= self.chars.len() {
+                let Some(escaped_ch) = self.current_char() else {
                     return Err(CompileError::new(
                         self.filename,
                         self.line,
                         self.col,
                         "unexpected end of string",
                     ));
-                }
-                let (_, _, escaped_ch) = self.current_char();
+                };
                 let escaped = match escaped_ch {
                     'n' => '\n',
                     't' => '\t',
@@ -200,7 +329,8 @@ impl<'a> Lexer<'a> {
                             self.line,
                             self.col,
                             &format!("unknown escape sequence: \\{}", c),
-                        ))
+                        )
+                        .with_help(r#"supported escapes are \n, \t, \" and \\"#))
                     }
                 };
                 result.push(escaped);
@@ -211,7 +341,7 @@ impl<'a> Lexer<'a> {
             }
         }
 
-        if self.position >= self.chars.len() {
+        if self.current_char().is_none() {
             return Err(CompileError::new(
                 self.filename,
                 self.line,
@@ -227,8 +357,7 @@ impl<'a> Lexer<'a> {
     fn read_number(&mut self) -> Result<Token, CompileError> {
         let mut num_str = String::new();
 
-        while self.position < self.chars.len() {
-            let (_, _, ch) = self.current_char();
+        while let Some(ch) = self.current_char() {
             if !ch.is_ascii_digit() {
                 break;
             }
@@ -236,32 +365,34 @@ impl<'a> Lexer<'a> {
             self.advance();
         }
 
-        if self.position < self.chars.len() {
-            let (_, _, ch) = self.current_char();
-            if ch == '.' {
-                num_str.push('.');
-                self.advance();
-                while self.position < self.chars.len() {
-                    let (_, _, ch) = self.current_char();
-                    if !ch.is_ascii_digit() {
-                        break;
-                    }
-                    num_str.push(ch);
-                    self.advance();
+        if self.current_char() == Some('.') {
+            num_str.push('.');
+            self.advance();
+            while let Some(ch) = self.current_char() {
+                if !ch.is_ascii_digit() {
+                    break;
                 }
+                num_str.push(ch);
+                self.advance();
             }
         }
 
-        let num = num_str.parse::<f64>().unwrap();
+        let num = num_str.parse::<f64>().map_err(|_| {
+            CompileError::new(
+                self.filename,
+                self.line,
+                self.col,
+                &format!("invalid number literal: {}", num_str),
+            )
+        })?;
         Ok(Token::Number(num))
     }
 
     fn read_identifier(&mut self) -> Result<Token, CompileError> {
         let mut ident = String::new();
 
-        while self.position < self.chars.len() {
-            let (_, _, ch) = self.current_char();
-            if !ch.is_ascii_alphanumeric() && ch != '_' {
+        while let Some(ch) = self.current_char() {
+            if !is_identifier_char(ch) {
                 break;
             }
             ident.push(ch);
@@ -269,29 +400,8 @@ impl<'a> Lexer<'a> {
         }
 
         // Check if it's a keyword
-        match ident.as_str() {
-            "LOCK" => return Ok(Token::Lock),
-            "IN" => return Ok(Token::In),
-            "ITS" => return Ok(Token::Its),
-            "OVER" => return Ok(Token::Over),
-            "FANUMTAX" => return Ok(Token::Fanumtax),
-            "FR" => return Ok(Token::Fr),
-            "SAY" => return Ok(Token::Say),
-            "TOUCHY" => return Ok(Token::Touchy),
-            "ONGOD" => return Ok(Token::Ongod),
-            "NO" => return Ok(Token::No),
-            "CAP" => return Ok(Token::Cap),
-            "DEADASS" => return Ok(Token::Deadass),
-            "SKIBIDI" => return Ok(Token::Skibidi),
-            "RIZZUP" => return Ok(Token::Rizzup),
-            "TRALALERO" => return Ok(Token::Tralalero),
-            "TRALALA" => return Ok(Token::Tralala),
-            "RETREAT" => return Ok(Token::Retreat),
-            "ring" => return Ok(Token::Ring),
-            "yas" => return Ok(Token::Yas),
-            "DIDDLE" => return Ok(Token::Diddle),
-            "YOUSHALLNOTPASS" => return Ok(Token::Youshallnotpass),
-            _ => {}
+        if let Some((_, token)) = KEYWORDS.iter().find(|(word, _)| *word == ident) {
+            return Ok(token.clone());
         }
 
         // Check if it's a braincell
@@ -306,8 +416,7 @@ impl<'a> Lexer<'a> {
     }
 
     fn skip_whitespace(&mut self) {
-        while self.position < self.chars.len() {
-            let (_, _, ch) = self.current_char();
+        while let Some(ch) = self.current_char() {
             if !ch.is_whitespace() {
                 break;
             }
@@ -315,31 +424,99 @@ impl<'a> Lexer<'a> {
         }
     }
 
-    fn skip_line(&mut self) {
-        while self.position < self.chars.len() {
-            let (_, _, ch) = self.current_char();
+    fn read_comment(&mut self) -> Token {
+        self.advance(); // skip 🖕 or #
+        let mut text = String::new();
+        while let Some(ch) = self.current_char() {
             if ch == '\n' {
-                self.advance();
                 break;
             }
+            text.push(ch);
             self.advance();
         }
+        Token::Comment(text.trim().to_string())
     }
 
-    fn current_char(&self) -> (usize, usize, char) {
-        self.chars[self.position]
+    fn read_block_comment(&mut self) -> Result<String, CompileError> {
+        let start = self.span();
+        self.advance(); // skip 🙈
+        let mut depth = 1;
+        let mut text = String::new();
+        loop {
+            let Some(ch) = self.current_char() else {
+                return Err(CompileError::new(
+                    self.filename,
+                    self.line,
+                    self.col,
+                    &format!("unterminated block comment opened on line {}", start.line),
+                ));
+            };
+            self.advance();
+            if ch == BLOCK_COMMENT_OPEN {
+                depth += 1;
+            } else if ch == BLOCK_COMMENT_CLOSE {
+                depth -= 1;
+                if depth == 0 {
+                    return Ok(text.trim().to_string());
+                }
+            }
+            text.push(ch);
+        }
     }
 
-    fn advance(&mut self) {
-        if self.position < self.chars.len() {
-            let (_, _, ch) = self.current_char();
+    fn skip_line(&mut self) {
+        while let Some(ch) = self.current_char() {
+            self.advance();
             if ch == '\n' {
-                self.line += 1;
-                self.col = 1;
-            } else {
-                self.col += 1;
+                break;
             }
         }
-        self.position += 1;
     }
+
+    fn current_char(&self) -> Option<char> {
+        self.source[self.position..].chars().next()
+    }
+
+    fn span(&self) -> Span {
+        Span {
+            line: self.line,
+            col: self.col,
+            offset: self.position,
+        }
+    }
+
+    fn advance(&mut self) {
+        let Some(ch) = self.current_char() else {
+            return;
+        };
+        if ch == '\n' {
+            self.line += 1;
+            self.col = 1;
+        } else {
+            self.col += 1;
+        }
+        self.position += ch.len_utf8();
+    }
+}
+
+impl Iterator for Lexer<'_> {
+    type Item = Result<SpannedToken, CompileError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let result = self.next_spanned_token();
+        self.done = match &result {
+            Ok(token) => token.token == Token::Eof,
+            Err(_) => true,
+        };
+        Some(result)
+    }
+}
+
+// Identifiers follow Unicode's XID rules, so names can be written in any
+// script. No emoji is XID_Continue, so an operator always ends a name.
+fn is_identifier_char(ch: char) -> bool {
+    is_xid_continue(ch) || ch == '_'
 }
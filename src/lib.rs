@@ -1,6 +1,45 @@
+pub mod analyze;
+pub mod asm;
+pub mod base64;
+pub mod batch;
+pub mod bigint;
+pub mod builtins;
+pub mod bytecode;
 pub mod compiler;
+pub mod config;
+pub mod datetime;
+pub mod debugger;
+pub mod diagnostic;
+pub mod disasm;
+pub mod doc;
 pub mod error;
+pub mod exit;
+pub mod golden;
+pub mod grammar;
+pub mod hash;
+pub mod highlight;
+pub mod host;
+pub mod inspect;
+pub mod json;
+pub mod lexdump;
 pub mod lexer;
+pub mod lint;
+pub mod net;
+pub mod opcode;
 pub mod parser;
+pub mod profile;
+pub mod repl;
+pub mod rng;
+pub mod samples;
+pub mod serve;
+pub mod state;
+pub mod strip;
+pub mod trace;
+pub mod transpile;
+#[cfg(feature = "tui")]
+pub mod tui;
+pub mod typecheck;
 pub mod value;
 pub mod vm;
+#[cfg(feature = "wasm")]
+pub mod wasm;
@@ -0,0 +1,492 @@
+// Lint pass over the parsed program. Each lint can be allowed, reported as a
+// warning or denied, which fails the compile; the CLI sets the levels with
+// -A/-W/-D and --deny-warnings.
+use crate::diagnostic::{Diagnostic, Severity};
+use crate::lexer::{Span, BRAINCELLS};
+use crate::parser::{Expr, Function, Program, SpannedStatement, Statement};
+use std::collections::{HashMap, HashSet};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lint {
+    UnusedAssignment,  // a function local that is assigned but never read
+    ConstantCondition, // ONGOD/SKIBIDI on a literal or constant
+    ShadowedBraincell, // a function local named after a braincell
+    UnreachableCode,   // statements that can never run
+    UnassignedRead,    // a read no path can have assigned
+}
+
+impl Lint {
+    pub const ALL: [Lint; 5] = [
+        Lint::UnusedAssignment,
+        Lint::ConstantCondition,
+        Lint::ShadowedBraincell,
+        Lint::UnreachableCode,
+        Lint::UnassignedRead,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Lint::UnusedAssignment => "unused-assignment",
+            Lint::ConstantCondition => "constant-condition",
+            Lint::ShadowedBraincell => "shadowed-braincell",
+            Lint::UnreachableCode => "unreachable-code",
+            Lint::UnassignedRead => "unassigned-read",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Lint> {
+        Self::ALL.into_iter().find(|lint| lint.name() == name)
+    }
+
+    // Braincell-named locals are a deliberate idiom in the examples, so that
+    // lint is opt-in with -W.
+    pub fn default_level(self) -> Level {
+        match self {
+            Lint::ShadowedBraincell => Level::Allow,
+            Lint::UnusedAssignment
+            | Lint::ConstantCondition
+            | Lint::UnreachableCode
+            | Lint::UnassignedRead => Level::Warn,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Allow,
+    Warn,
+    Deny,
+}
+
+#[derive(Debug, Clone)]
+pub struct LintLevels {
+    levels: [Level; Lint::ALL.len()],
+}
+
+impl Default for LintLevels {
+    fn default() -> Self {
+        Self {
+            levels: Lint::ALL.map(Lint::default_level),
+        }
+    }
+}
+
+impl LintLevels {
+    pub fn get(&self, lint: Lint) -> Level {
+        self.levels[lint as usize]
+    }
+
+    pub fn set(&mut self, lint: Lint, level: Level) {
+        self.levels[lint as usize] = level;
+    }
+
+    // Every lint that would warn fails the compile instead.
+    pub fn deny_warnings(&mut self) {
+        for level in &mut self.levels {
+            if *level == Level::Warn {
+                *level = Level::Deny;
+            }
+        }
+    }
+}
+
+// Returns warnings for lints at Warn and errors for lints at Deny, in
+// source order.
+pub fn check(program: &Program, filename: &str, levels: &LintLevels) -> Vec<Diagnostic> {
+    let mut linter = Linter {
+        filename,
+        levels,
+        consts: program
+            .consts
+            .iter()
+            .map(|c| (c.name.as_str(), &c.value))
+            .collect(),
+        diagnostics: Vec::new(),
+    };
+    for func in &program.functions {
+        linter.check_function(func);
+    }
+    linter.check_block(&program.main_statements);
+    for (span, message, help) in unassigned_reads(program) {
+        linter.report(Lint::UnassignedRead, span, message, Some(help));
+    }
+    linter.diagnostics.sort_by_key(|d| (d.line, d.col));
+    linter.diagnostics
+}
+
+struct Linter<'a> {
+    filename: &'a str,
+    levels: &'a LintLevels,
+    consts: HashMap<&'a str, &'a Expr>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Linter<'_> {
+    fn report(&mut self, lint: Lint, span: Span, message: String, help: Option<&str>) {
+        let severity = match self.levels.get(lint) {
+            Level::Allow => return,
+            Level::Warn => Severity::Warning,
+            Level::Deny => Severity::Error,
+        };
+        self.diagnostics.push(Diagnostic {
+            severity,
+            lint: Some(lint),
+            filename: self.filename.to_string(),
+            line: span.line,
+            col: span.col,
+            message,
+            help: help.map(str::to_string),
+        });
+    }
+
+    fn check_function(&mut self, func: &Function) {
+        // Locals are per call, so a local nobody reads is dead. Braincells
+        // assigned in main are left alone: functions and --state files can
+        // read them later.
+        let mut assigned = Vec::new();
+        let mut reads = HashSet::new();
+        collect_names(&func.body, &mut assigned, &mut reads);
+
+        let mut seen = HashSet::new();
+        for (name, span) in assigned {
+            if !seen.insert(name) {
+                continue;
+            }
+            if BRAINCELLS.iter().any(|(braincell, _)| *braincell == name) {
+                self.report(
+                    Lint::ShadowedBraincell,
+                    span,
+                    format!(
+                        "'{}' is a local in function {}; functions cannot store braincells",
+                        name, func.name
+                    ),
+                    Some(
+                        "rename the local, or RETREAT the value and store it from the main program",
+                    ),
+                );
+            }
+            if !reads.contains(name) {
+                self.report(
+                    Lint::UnusedAssignment,
+                    span,
+                    format!("value assigned to '{}' is never read", name),
+                    Some("remove the assignment, or use the value"),
+                );
+            }
+        }
+
+        self.check_block(&func.body);
+    }
+
+    fn check_block(&mut self, stmts: &[SpannedStatement]) {
+        // The first statement after a RETREAT or YOUSHALLNOTPASS is reported;
+        // the rest of the block is dead along with it.
+        let mut exit = None;
+        let mut reported = false;
+        for stmt in stmts {
+            if let (Some(keyword), false) = (exit, reported) {
+                reported = true;
+                self.report(
+                    Lint::UnreachableCode,
+                    stmt.span,
+                    format!("unreachable statement after {}", keyword),
+                    Some("remove it, or move it before the exit"),
+                );
+            }
+            match &stmt.statement {
+                Statement::If {
+                    condition,
+                    then_block,
+                    else_block,
+                } => {
+                    self.check_condition("ONGOD", condition);
+                    self.check_block(then_block);
+                    if let Some(else_block) = else_block {
+                        self.check_block(else_block);
+                    }
+                }
+                Statement::While { condition, body } => {
+                    self.check_condition("SKIBIDI", condition);
+                    if let (Some(false), Some(first)) = (self.truthiness(condition), body.first()) {
+                        self.report(
+                            Lint::UnreachableCode,
+                            first.span,
+                            "unreachable SKIBIDI body: the condition is always false".to_string(),
+                            None,
+                        );
+                    }
+                    self.check_block(body);
+                }
+                Statement::Return(_) => exit = exit.or(Some("RETREAT")),
+                Statement::Halt => exit = exit.or(Some("YOUSHALLNOTPASS")),
+                Statement::Throw(_) => exit = exit.or(Some("CRASHOUT")),
+                _ => {}
+            }
+        }
+    }
+
+    fn check_condition(&mut self, keyword: &str, condition: &Expr) {
+        if let Some(truthy) = self.truthiness(condition) {
+            self.report(
+                Lint::ConstantCondition,
+                condition.span(),
+                format!("{} condition is always {}", keyword, truthy),
+                None,
+            );
+        }
+    }
+
+    // The condition's truth value when it is a literal or a BASED constant.
+    fn truthiness(&self, expr: &Expr) -> Option<bool> {
+        match expr {
+            Expr::Number(n, _) => Some(*n != 0.0),
+            Expr::String(s, _) => Some(!s.is_empty()),
+            Expr::Variable(name, _) => self
+                .consts
+                .get(name.as_str())
+                .and_then(|value| self.truthiness(value)),
+            _ => None,
+        }
+    }
+}
+
+// Assignments in source order, and every name read anywhere in the block.
+fn collect_names<'a>(
+    stmts: &'a [SpannedStatement],
+    assigned: &mut Vec<(&'a str, Span)>,
+    reads: &mut HashSet<&'a str>,
+) {
+    for stmt in stmts {
+        match &stmt.statement {
+            Statement::Assign(name, _, expr)
+            | Statement::Copy {
+                dest: name,
+                source: expr,
+            } => {
+                expr_reads(expr, reads);
+                assigned.push((name, stmt.span));
+            }
+            Statement::Print(expr)
+            | Statement::PrintInline(expr)
+            | Statement::PrintError(expr)
+            | Statement::Return(expr)
+            | Statement::Throw(expr) => expr_reads(expr, reads),
+            Statement::Halt => {}
+            Statement::If {
+                condition,
+                then_block,
+                else_block,
+            } => {
+                expr_reads(condition, reads);
+                collect_names(then_block, assigned, reads);
+                if let Some(else_block) = else_block {
+                    collect_names(else_block, assigned, reads);
+                }
+            }
+            Statement::While { condition, body } => {
+                expr_reads(condition, reads);
+                collect_names(body, assigned, reads);
+            }
+        }
+    }
+}
+
+fn expr_reads<'a>(expr: &'a Expr, reads: &mut HashSet<&'a str>) {
+    match expr {
+        Expr::Number(..) | Expr::String(..) => {}
+        Expr::Variable(name, _) => {
+            reads.insert(name);
+        }
+        Expr::Binary { left, right, .. } => {
+            expr_reads(left, reads);
+            expr_reads(right, reads);
+        }
+        Expr::FunctionCall { args, .. } | Expr::UserFunctionCall { args, .. } => {
+            for arg in args {
+                expr_reads(arg, reads);
+            }
+        }
+    }
+}
+
+// Finds reads that no path can have assigned, which would otherwise fail at
+// runtime with "unset braincell" or "unset local variable". Braincells are
+// only ever stored by main, so a function may read any braincell main
+// assigns somewhere; one main never assigns can only come from --state.
+// A program that uses POKE may store any braincell at any time.
+fn unassigned_reads(program: &Program) -> Vec<(Span, String, &'static str)> {
+    let consts: HashSet<&str> = program.consts.iter().map(|c| c.name.as_str()).collect();
+    let poked: HashSet<&str> = if program.calls_builtin("POKE") {
+        BRAINCELLS.iter().map(|(name, _)| *name).collect()
+    } else {
+        HashSet::new()
+    };
+    let mut main_assigned = Vec::new();
+    collect_names(
+        &program.main_statements,
+        &mut main_assigned,
+        &mut HashSet::new(),
+    );
+    let mut main_assigned: HashSet<&str> =
+        main_assigned.into_iter().map(|(name, _)| name).collect();
+    main_assigned.extend(&poked);
+
+    let mut found = Vec::new();
+    for func in &program.functions {
+        let params: HashSet<&str> = func.params.iter().map(|p| p.name.as_str()).collect();
+        let mut check = ReadCheck {
+            consts: &consts,
+            function: Some((&func.name, &main_assigned)),
+            declared: params.clone(),
+            reported: HashSet::new(),
+            found: &mut found,
+        };
+        check.block(&func.body, &mut Some(params));
+    }
+    let mut check = ReadCheck {
+        consts: &consts,
+        function: None,
+        declared: HashSet::new(),
+        reported: HashSet::new(),
+        found: &mut found,
+    };
+    check.block(&program.main_statements, &mut Some(poked));
+    found
+}
+
+// Flow state: the names that may hold a value at this point, or None once a
+// RETREAT or YOUSHALLNOTPASS has made the rest of the block dead.
+type MayBeAssigned<'a> = Option<HashSet<&'a str>>;
+
+struct ReadCheck<'a, 'f> {
+    consts: &'f HashSet<&'a str>,
+    // In a function: its name and the braincells main assigns.
+    function: Option<(&'a str, &'f HashSet<&'a str>)>,
+    // Like the compiler, a name in a function is a local from its first
+    // assignment in the text onwards, and a braincell before that.
+    declared: HashSet<&'a str>,
+    reported: HashSet<&'a str>,
+    found: &'f mut Vec<(Span, String, &'static str)>,
+}
+
+impl<'a> ReadCheck<'a, '_> {
+    fn block(&mut self, stmts: &'a [SpannedStatement], state: &mut MayBeAssigned<'a>) {
+        for stmt in stmts {
+            match &stmt.statement {
+                Statement::Assign(name, _, expr)
+                | Statement::Copy {
+                    dest: name,
+                    source: expr,
+                } => {
+                    self.expr(expr, state);
+                    if let Some(state) = state {
+                        state.insert(name);
+                    }
+                    if self.function.is_some() {
+                        self.declared.insert(name);
+                    }
+                }
+                Statement::Print(expr)
+                | Statement::PrintInline(expr)
+                | Statement::PrintError(expr) => self.expr(expr, state),
+                Statement::Return(expr) | Statement::Throw(expr) => {
+                    self.expr(expr, state);
+                    *state = None;
+                }
+                Statement::Halt => *state = None,
+                Statement::If {
+                    condition,
+                    then_block,
+                    else_block,
+                } => {
+                    self.expr(condition, state);
+                    let mut then_state = state.clone();
+                    self.block(then_block, &mut then_state);
+                    let mut else_state = state.clone();
+                    if let Some(else_block) = else_block {
+                        self.block(else_block, &mut else_state);
+                    }
+                    *state = match (then_state, else_state) {
+                        (Some(mut a), Some(b)) => {
+                            a.extend(b);
+                            Some(a)
+                        }
+                        (a, b) => a.or(b),
+                    };
+                }
+                Statement::While { condition, body } => {
+                    // The first test sees only what came before the loop;
+                    // later iterations also see what the body assigned.
+                    self.expr(condition, state);
+                    let mut assigned = Vec::new();
+                    collect_names(body, &mut assigned, &mut HashSet::new());
+                    if let Some(state) = state {
+                        state.extend(assigned.into_iter().map(|(name, _)| name));
+                    }
+                    let mut body_state = state.clone();
+                    self.block(body, &mut body_state);
+                }
+            }
+        }
+    }
+
+    fn expr(&mut self, expr: &'a Expr, state: &MayBeAssigned<'a>) {
+        let Some(assigned) = state else {
+            return;
+        };
+        let mut reads = Vec::new();
+        ordered_reads(expr, &mut reads);
+        for (name, span) in reads {
+            if self.consts.contains(name) || self.reported.contains(name) {
+                continue;
+            }
+            let is_braincell = BRAINCELLS.iter().any(|(braincell, _)| *braincell == name);
+            let message = match self.function {
+                Some(_) if self.declared.contains(name) => {
+                    if assigned.contains(name) {
+                        continue;
+                    }
+                    format!("local '{}' is read before it is assigned", name)
+                }
+                Some((func, main_assigned)) if is_braincell => {
+                    if main_assigned.contains(name) {
+                        continue;
+                    }
+                    format!(
+                        "braincell '{}' is read in function {} but never assigned",
+                        name, func
+                    )
+                }
+                None if is_braincell => {
+                    if assigned.contains(name) {
+                        continue;
+                    }
+                    format!("braincell '{}' is read before it is assigned", name)
+                }
+                _ => continue,
+            };
+            self.reported.insert(name);
+            self.found.push((
+                span,
+                message,
+                "assign it first with FANUMTAX, or ignore this if it comes from a --state file",
+            ));
+        }
+    }
+}
+
+fn ordered_reads<'a>(expr: &'a Expr, reads: &mut Vec<(&'a str, Span)>) {
+    match expr {
+        Expr::Number(..) | Expr::String(..) => {}
+        Expr::Variable(name, span) => reads.push((name, *span)),
+        Expr::Binary { left, right, .. } => {
+            ordered_reads(left, reads);
+            ordered_reads(right, reads);
+        }
+        Expr::FunctionCall { args, .. } | Expr::UserFunctionCall { args, .. } => {
+            for arg in args {
+                ordered_reads(arg, reads);
+            }
+        }
+    }
+}
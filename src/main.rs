@@ -1,5 +1,25 @@
-use brvm::{compiler, error, lexer, parser, vm};
-use clap::{Parser, Subcommand};
+use brvm::lint::{Level, Lint, LintLevels};
+use brvm::{
+    analyze, asm, batch, compiler, config, disasm, doc, error, exit, golden, grammar, highlight,
+    host, inspect, lexdump, lexer, profile, repl, samples, serve, state, strip, trace, transpile,
+    vm,
+};
+use clap::{ArgAction, Args, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::validate::Validator;
+use rustyline::{Editor, Helper};
+use std::cell::RefCell;
+use std::io::{IsTerminal, Read, Write};
+use std::path::Path;
+use std::rc::Rc;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+
+// Compile errors from `brvm eval` are reported under this name.
+const EVAL_FILENAME: &str = "<eval>";
 
 #[derive(Parser)]
 #[command(name = "brvm")]
@@ -8,6 +28,14 @@ use clap::{Parser, Subcommand};
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+    #[arg(long, global = true, value_enum, default_value_t = Color::Auto)]
+    color: Color,
+    // Same as --color never
+    #[arg(long, global = true)]
+    no_color: bool,
+    // Log compiler stages and VM events to stderr; -vv and -vvv log more
+    #[arg(short, long, global = true, action = ArgAction::Count)]
+    verbose: u8,
 }
 
 #[derive(Subcommand)]
@@ -16,65 +44,1359 @@ enum Commands {
         input: String,
         #[arg(short, long)]
         output: Option<String>,
+        #[arg(long)]
+        strict: bool,
+        // Fail when arithmetic gives NaN or infinity
+        #[arg(long)]
+        strict_math: bool,
+        #[command(flatten)]
+        lints: LintArgs,
+        // Emit an older BRBC version so older installed VMs can run the file
+        #[arg(long)]
+        bytecode_version: Option<u16>,
+        // Skip constant folding, DUP reuse and loop hoisting
+        #[arg(long)]
+        no_optimize: bool,
+        // Print the disassembly without and with optimizations
+        #[arg(long)]
+        dump_opt: bool,
+        // Lay out code and inline hot functions using an `exec --profile-out` file
+        #[arg(long, value_name = "FILE")]
+        profile_use: Option<String>,
     },
     Exec {
         input: String,
+        #[arg(long)]
+        seed: Option<u64>,
+        #[arg(long)]
+        state: Option<String>,
+        #[arg(long)]
+        check_stack: bool,
+        // Print calls and instructions per function to stderr
+        #[arg(long)]
+        stats: bool,
+        // Flush stdout after every SAY instead of only before input
+        #[arg(long)]
+        flush_every_print: bool,
+        // Let YOINK and YEET make HTTP requests
+        #[arg(long)]
+        allow_net: bool,
+        // Write hot functions and branch counts as JSON for `compile --profile-use`
+        #[arg(long, value_name = "FILE")]
+        profile_out: Option<String>,
+        // Write function and built-in calls as a Chrome trace for Perfetto
+        #[arg(long, value_name = "FILE")]
+        trace_out: Option<String>,
+        // Nested calls allowed before "call stack overflow" (default 256)
+        #[arg(long, value_name = "N")]
+        max_call_depth: Option<usize>,
+        // Stop with "instruction limit exceeded" after this many instructions
+        #[arg(long, value_name = "N")]
+        max_instructions: Option<u64>,
+    },
+    // Compile a source file in memory and run it
+    Run {
+        input: String,
+        #[arg(long)]
+        seed: Option<u64>,
+        #[arg(long)]
+        flush_every_print: bool,
+        #[arg(long)]
+        allow_net: bool,
+        #[arg(long, value_name = "FILE")]
+        trace_out: Option<String>,
+        #[arg(long, value_name = "N")]
+        max_call_depth: Option<usize>,
+        #[arg(long, value_name = "N")]
+        max_instructions: Option<u64>,
+    },
+    // Run code given on the command line ("-" reads it from stdin); statements
+    // without LOCK IN are wrapped in one
+    Eval {
+        code: String,
+        #[arg(long)]
+        seed: Option<u64>,
+        #[arg(long)]
+        allow_net: bool,
+        #[arg(long, value_name = "N")]
+        max_instructions: Option<u64>,
+    },
+    Disasm {
+        input: String,
+    },
+    // Assemble hand-written bytecode text into a .brbc file
+    Asm {
+        input: String,
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+    // Check that disassembling and reassembling a bytecode file gives the same bytes
+    Roundtrip {
+        input: String,
+    },
+    // Summarize a bytecode file; --size breaks down where its bytes go
+    Inspect {
+        input: String,
+        #[arg(long)]
+        size: bool,
+        // Largest string constants to list with --size
+        #[arg(long, default_value_t = 5)]
+        top: usize,
+    },
+    // Drop function names from a bytecode file; rewrites it unless -o is given
+    Strip {
+        input: String,
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+    // List the sample programs, or write them to a folder with --install
+    Examples {
+        #[arg(long, value_name = "DIR")]
+        install: Option<String>,
+        // Replace samples that are already in the folder
+        #[arg(long)]
+        force: bool,
+    },
+    // Report operations in a source file that will always fail at runtime
+    Analyze {
+        input: String,
+        #[arg(long)]
+        strict: bool,
+    },
+    Lex {
+        input: String,
+        #[arg(long)]
+        json: bool,
+        #[arg(long)]
+        include_comments: bool,
+    },
+    Test {
+        #[arg(default_value = ".")]
+        dir: String,
+        // Write each program's output to its .expected file
+        #[arg(long)]
+        bless: bool,
+    },
+    Repl,
+    // Step through a source file in a terminal UI
+    #[cfg(feature = "tui")]
+    Debug {
+        input: String,
+        #[arg(long)]
+        seed: Option<u64>,
+        // File whose lines TOUCHY reads
+        #[arg(long, value_name = "FILE")]
+        stdin: Option<String>,
+    },
+    // List the functions of source files and directories with their docs
+    Doc {
+        #[arg(required = true)]
+        inputs: Vec<String>,
+        #[arg(short, long)]
+        output: Option<String>,
+        #[arg(long, value_enum, default_value_t = DocFormat::Markdown)]
+        format: DocFormat,
+    },
+    // Print the language grammar the parser implements
+    Grammar {
+        #[arg(long, value_enum, default_value_t = GrammarFormat::Ebnf)]
+        format: GrammarFormat,
+    },
+    Highlight {
+        input: String,
+        #[arg(short, long)]
+        output: Option<String>,
+        #[arg(long)]
+        textmate: Option<String>,
+    },
+    Serve {
+        #[arg(long, default_value_t = 8080)]
+        port: u16,
+        #[arg(long, default_value = "127.0.0.1")]
+        host: String,
+        #[arg(long)]
+        max_instructions: Option<u64>,
+        #[arg(long)]
+        max_string_bytes: Option<u64>,
+        #[arg(long)]
+        max_connections: Option<usize>,
+    },
+    // Run JSON requests from stdin, one per line, for auto-graders
+    Batch {
+        #[arg(long)]
+        max_instructions: Option<u64>,
+        #[arg(long)]
+        max_string_bytes: Option<u64>,
+        #[arg(long)]
+        max_output_bytes: Option<usize>,
+    },
+    Transpile {
+        input: String,
+        #[arg(long, value_enum, default_value_t = Target::C)]
+        target: Target,
+        #[arg(short, long)]
+        output: Option<String>,
+        #[arg(long)]
+        strict: bool,
+        // Fail when arithmetic gives NaN or infinity
+        #[arg(long)]
+        strict_math: bool,
+        #[command(flatten)]
+        lints: LintArgs,
     },
 }
 
+// -A is applied first, then -W, then -D, then --deny-warnings.
+#[derive(Args)]
+struct LintArgs {
+    #[arg(short = 'A', long = "allow", value_name = "LINT", value_parser = parse_lint)]
+    allow: Vec<Lint>,
+    #[arg(short = 'W', long = "warn", value_name = "LINT", value_parser = parse_lint)]
+    warn: Vec<Lint>,
+    #[arg(short = 'D', long = "deny", value_name = "LINT", value_parser = parse_lint)]
+    deny: Vec<Lint>,
+    #[arg(long)]
+    deny_warnings: bool,
+}
+
+impl LintArgs {
+    fn levels(&self) -> LintLevels {
+        let mut levels = LintLevels::default();
+        for (lints, level) in [
+            (&self.allow, Level::Allow),
+            (&self.warn, Level::Warn),
+            (&self.deny, Level::Deny),
+        ] {
+            for &lint in lints {
+                levels.set(lint, level);
+            }
+        }
+        if self.deny_warnings {
+            levels.deny_warnings();
+        }
+        levels
+    }
+}
+
+fn parse_lint(name: &str) -> Result<Lint, String> {
+    Lint::from_name(name).ok_or_else(|| {
+        let names: Vec<&str> = Lint::ALL.iter().map(|lint| lint.name()).collect();
+        format!(
+            "unknown lint '{}' (expected one of: {})",
+            name,
+            names.join(", ")
+        )
+    })
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Target {
+    C,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum DocFormat {
+    Markdown,
+    Html,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum GrammarFormat {
+    Ebnf,
+    Railroad,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Color {
+    Auto,
+    Always,
+    Never,
+}
+
+impl Color {
+    // `terminal`: whether the stream being colored is one.
+    fn enabled(self, terminal: bool) -> bool {
+        match self {
+            Color::Always => true,
+            Color::Never => false,
+            Color::Auto => std::env::var_os("NO_COLOR").is_none() && terminal,
+        }
+    }
+}
+
 fn main() {
-    let cli = Cli::parse();
+    let command = apply_config(Cli::command()).unwrap_or_else(|e| {
+        report(&e, std::io::stderr().is_terminal());
+        std::process::exit(exit::USAGE);
+    });
+    let cli = Cli::from_arg_matches(&command.get_matches()).unwrap_or_else(|e| e.exit());
+    if cli.verbose > 0 && log::set_logger(&StderrLogger).is_ok() {
+        log::set_max_level(match cli.verbose {
+            1 => log::LevelFilter::Info,
+            2 => log::LevelFilter::Debug,
+            _ => log::LevelFilter::Trace,
+        });
+    }
+    let mode = if cli.no_color {
+        Color::Never
+    } else {
+        cli.color
+    };
+    let color = mode.enabled(std::io::stderr().is_terminal());
+    // DRIP's codes end up on stdout.
+    let drip = mode.enabled(std::io::stdout().is_terminal());
 
     match cli.command {
-        Commands::Compile { input, output } => {
-            let output = output.unwrap_or_else(|| {
-                // If no output specified, use same directory with .brbc extension
-                let parent = std::path::Path::new(&input)
-                    .parent()
-                    .unwrap_or(std::path::Path::new("."));
-                let stem = std::path::Path::new(&input)
-                    .file_stem()
-                    .and_then(|s| s.to_str())
-                    .unwrap_or("output");
-                parent
-                    .join(format!("{}.brbc", stem))
-                    .to_string_lossy()
-                    .to_string()
-            });
+        Commands::Compile {
+            input,
+            output,
+            strict,
+            strict_math,
+            lints,
+            bytecode_version,
+            no_optimize,
+            dump_opt,
+            profile_use,
+        } => {
+            let output = output.unwrap_or_else(|| sibling_path(&input, "brbc"));
 
-            if let Err(e) = compile_file(&input, &output) {
+            let profile = match profile_use.as_deref().map(read_profile).transpose() {
+                Ok(profile) => profile,
+                Err(e) => {
+                    report(&e, color);
+                    std::process::exit(exit::code(&e));
+                }
+            };
+            let options = compiler::CompileOptions {
+                strict,
+                strict_math,
+                lints: lints.levels(),
+                version: bytecode_version,
+                no_optimize,
+                profile,
+            };
+            if let Err(e) = compile_file(&input, &output, &options, color, dump_opt) {
+                report(e.as_ref(), color);
+                std::process::exit(exit::code(e.as_ref()));
+            }
+        }
+        Commands::Exec {
+            input,
+            seed,
+            state,
+            check_stack,
+            stats,
+            flush_every_print,
+            allow_net,
+            profile_out,
+            trace_out,
+            max_call_depth,
+            max_instructions,
+        } => {
+            let defaults = vm::VmOptions::default();
+            let options = vm::VmOptions {
+                seed,
+                check_stack: check_stack || defaults.check_stack,
+                max_call_depth: max_call_depth.unwrap_or(defaults.max_call_depth),
+                max_instructions,
+                interrupt: install_interrupt_handler(),
+                stats: stats || profile_out.is_some(),
+                flush: flush_policy(flush_every_print),
+                color: drip,
+                allow_net,
+                ..defaults
+            };
+            match execute_file(
+                &input,
+                &options,
+                state.as_deref(),
+                profile_out.as_deref(),
+                trace_out.as_deref(),
+            ) {
+                Ok(result) => {
+                    if stats {
+                        let _ = std::io::stdout().flush();
+                        eprint!("{}", format_stats(&result));
+                    }
+                    std::process::exit(result.exit_status());
+                }
+                Err(e) => {
+                    let _ = std::io::stdout().flush();
+                    report(&e, color);
+                    std::process::exit(exit::code(&e));
+                }
+            }
+        }
+        Commands::Run {
+            input,
+            seed,
+            flush_every_print,
+            allow_net,
+            trace_out,
+            max_call_depth,
+            max_instructions,
+        } => {
+            let defaults = vm::VmOptions::default();
+            let options = vm::VmOptions {
+                seed,
+                interrupt: install_interrupt_handler(),
+                flush: flush_policy(flush_every_print),
+                color: drip,
+                allow_net,
+                max_call_depth: max_call_depth.unwrap_or(defaults.max_call_depth),
+                max_instructions,
+                ..defaults
+            };
+            match run_file(&input, &options, trace_out.as_deref()) {
+                Ok(result) => std::process::exit(result.exit_status()),
+                Err(e) => {
+                    let _ = std::io::stdout().flush();
+                    report(e.as_ref(), color);
+                    std::process::exit(exit::code(e.as_ref()));
+                }
+            }
+        }
+        Commands::Eval {
+            code,
+            seed,
+            allow_net,
+            max_instructions,
+        } => {
+            let options = vm::VmOptions {
+                seed,
+                interrupt: install_interrupt_handler(),
+                color: drip,
+                allow_net,
+                max_instructions,
+                ..Default::default()
+            };
+            let code = eval_input(code).unwrap_or_else(|e| {
                 eprintln!("{}", e);
-                std::process::exit(1);
+                std::process::exit(exit::code(&e));
+            });
+            match eval_code(&code, &options) {
+                Ok(result) => std::process::exit(result.exit_status()),
+                Err(e) => {
+                    let _ = std::io::stdout().flush();
+                    match e.downcast_ref::<error::CompileError>() {
+                        Some(e) if e.filename == EVAL_FILENAME => {
+                            eprint!("{}", e.render(&code, color))
+                        }
+                        _ => report(e.as_ref(), color),
+                    }
+                    std::process::exit(exit::code(e.as_ref()));
+                }
+            }
+        }
+        Commands::Disasm { input } => {
+            if let Err(e) = disassemble_file(&input) {
+                eprintln!("{}", e);
+                std::process::exit(exit::code(&e));
+            }
+        }
+        Commands::Asm { input, output } => {
+            // A listing read from stdin goes to stdout unless -o says otherwise.
+            let output = output.unwrap_or_else(|| match input.as_str() {
+                "-" => "-".to_string(),
+                _ => sibling_path(&input, "brbc"),
+            });
+            if let Err(e) = assemble_file(&input, &output) {
+                report(e.as_ref(), color);
+                std::process::exit(exit::code(e.as_ref()));
+            }
+        }
+        Commands::Roundtrip { input } => {
+            if let Err(e) = roundtrip_file(&input) {
+                eprintln!("{}", e);
+                std::process::exit(exit::code(&e));
             }
         }
-        Commands::Exec { input } => {
-            if let Err(e) = execute_file(&input) {
+        Commands::Inspect { input, size, top } => {
+            if let Err(e) = inspect_file(&input, size, top) {
                 eprintln!("{}", e);
-                std::process::exit(1);
+                std::process::exit(exit::code(&e));
+            }
+        }
+        Commands::Strip { input, output } => {
+            if let Err(e) = strip_file(&input, output.as_deref().unwrap_or(&input)) {
+                eprintln!("{}", e);
+                std::process::exit(exit::code(&e));
+            }
+        }
+        Commands::Examples { install, force } => match install {
+            None => {
+                for sample in samples::SAMPLES {
+                    println!("{:<14} {}", sample.file_name(), sample.description);
+                }
+                println!("\nwrite them to a folder with: brvm examples --install <dir>");
+            }
+            Some(dir) => match samples::install(std::path::Path::new(&dir), force) {
+                Ok(installed) => {
+                    for (path, written) in installed {
+                        if written {
+                            println!("wrote {}", path.display());
+                        } else {
+                            println!(
+                                "kept  {} (already there; --force replaces it)",
+                                path.display()
+                            );
+                        }
+                    }
+                    println!(
+                        "\nrun one with `brvm run`, or check them all with `brvm test {}`",
+                        dir
+                    );
+                }
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(exit::FAILURE);
+                }
+            },
+        },
+        Commands::Analyze { input, strict } => match analyze_file(&input, strict, color) {
+            Ok(0) => {}
+            Ok(_) => std::process::exit(exit::FAILURE),
+            Err(e) => {
+                report(&e, color);
+                std::process::exit(exit::code(&e));
+            }
+        },
+        Commands::Lex {
+            input,
+            json,
+            include_comments,
+        } => {
+            if let Err(e) = lex_file(&input, json, include_comments) {
+                report(&e, color);
+                std::process::exit(exit::code(&e));
+            }
+        }
+        Commands::Test { dir, bless } => {
+            let dir = std::path::Path::new(&dir);
+            let report = if bless {
+                golden::bless_dir(dir)
+            } else {
+                golden::run_dir(dir)
+            };
+            match report {
+                Ok(report) => {
+                    print!("{}", report);
+                    if !report.passed() {
+                        std::process::exit(exit::FAILURE);
+                    }
+                }
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(exit::FAILURE);
+                }
+            }
+        }
+        Commands::Repl => {
+            if let Err(e) = run_repl(color, drip) {
+                eprintln!("{}", e);
+                std::process::exit(exit::FAILURE);
+            }
+        }
+        #[cfg(feature = "tui")]
+        Commands::Debug { input, seed, stdin } => {
+            if let Err(e) = debug_file(&input, seed, stdin.as_deref()) {
+                report(e.as_ref(), color);
+                std::process::exit(exit::code(e.as_ref()));
+            }
+        }
+        Commands::Doc {
+            inputs,
+            output,
+            format,
+        } => {
+            if let Err(e) = doc_files(&inputs, output.as_deref(), format) {
+                report(e.as_ref(), color);
+                std::process::exit(exit::code(e.as_ref()));
+            }
+        }
+        Commands::Grammar { format } => match format {
+            GrammarFormat::Ebnf => print!("{}", grammar::ebnf()),
+            GrammarFormat::Railroad => print!("{}", grammar::railroad()),
+        },
+        Commands::Highlight {
+            input,
+            output,
+            textmate,
+        } => {
+            if let Err(e) = highlight_file(&input, output.as_deref(), textmate.as_deref()) {
+                report(e.as_ref(), color);
+                std::process::exit(exit::code(e.as_ref()));
+            }
+        }
+        Commands::Serve {
+            port,
+            host,
+            max_instructions,
+            max_string_bytes,
+            max_connections,
+        } => {
+            let defaults = serve::Limits::default();
+            let limits = serve::Limits {
+                max_instructions: max_instructions.unwrap_or(defaults.max_instructions),
+                max_string_bytes: max_string_bytes.unwrap_or(defaults.max_string_bytes),
+                max_connections: max_connections.unwrap_or(defaults.max_connections),
+                ..defaults
+            };
+            let listener = match std::net::TcpListener::bind((host.as_str(), port)) {
+                Ok(listener) => listener,
+                Err(e) => {
+                    eprintln!("failed to listen on {}:{}: {}", host, port, e);
+                    std::process::exit(exit::FAILURE);
+                }
+            };
+            println!("Brainrot playground on http://{}:{}/", host, port);
+            if let Err(e) = serve::serve(listener, limits) {
+                eprintln!("{}", e);
+                std::process::exit(exit::FAILURE);
+            }
+        }
+        Commands::Batch {
+            max_instructions,
+            max_string_bytes,
+            max_output_bytes,
+        } => {
+            let defaults = serve::Limits::default();
+            let limits = serve::Limits {
+                max_instructions: max_instructions.unwrap_or(defaults.max_instructions),
+                max_string_bytes: max_string_bytes.unwrap_or(defaults.max_string_bytes),
+                max_output_bytes: max_output_bytes.unwrap_or(defaults.max_output_bytes),
+                ..defaults
+            };
+            if let Err(e) = batch::run(std::io::stdin().lock(), std::io::stdout().lock(), &limits) {
+                eprintln!("{}", e);
+                std::process::exit(exit::FAILURE);
+            }
+        }
+        Commands::Transpile {
+            input,
+            target: Target::C,
+            output,
+            strict,
+            strict_math,
+            lints,
+        } => {
+            let output = output.unwrap_or_else(|| sibling_path(&input, "c"));
+
+            let options = compiler::CompileOptions {
+                strict,
+                strict_math,
+                lints: lints.levels(),
+                ..Default::default()
+            };
+            if let Err(e) = transpile_file(&input, &output, &options, color) {
+                report(e.as_ref(), color);
+                std::process::exit(exit::code(e.as_ref()));
             }
         }
     }
 }
 
-fn compile_file(input: &str, output: &str) -> Result<(), Box<dyn std::error::Error>> {
+// Writes each record on one line as `[LEVEL target] message key=value ...`.
+struct StderrLogger;
+
+impl log::Log for StderrLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &log::Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let mut line = format!("[{} {}] {}", record.level(), record.target(), record.args());
+        let _ = record.key_values().visit(&mut LogFields(&mut line));
+        eprintln!("{}", line);
+    }
+
+    fn flush(&self) {}
+}
+
+struct LogFields<'a>(&'a mut String);
+
+impl<'kvs> log::kv::VisitSource<'kvs> for LogFields<'_> {
+    fn visit_pair(
+        &mut self,
+        key: log::kv::Key<'kvs>,
+        value: log::kv::Value<'kvs>,
+    ) -> Result<(), log::kv::Error> {
+        self.0.push_str(&format!(" {}={}", key, value));
+        Ok(())
+    }
+}
+
+// Makes the settings in the config files the defaults of their options, so
+// the command line still overrides them.
+fn apply_config(mut command: clap::Command) -> Result<clap::Command, error::CompileError> {
+    for path in config::files() {
+        let filename = path.to_string_lossy();
+        let text = std::fs::read_to_string(&path)
+            .map_err(|_| error::CompileError::new(&filename, 0, 0, "failed to read file"))?;
+        for entry in config::parse(&text, &filename)? {
+            let error = |message: &str| error::CompileError::new(&filename, entry.line, 1, message);
+            let targets: Vec<Option<String>> = match &entry.command {
+                Some(name) if command.find_subcommand(name).is_none() => {
+                    return Err(error(&format!("unknown command '{}'", name)));
+                }
+                Some(name) => vec![Some(name.clone())],
+                None => std::iter::once(None)
+                    .chain(
+                        command
+                            .get_subcommands()
+                            .map(|sub| Some(sub.get_name().to_string())),
+                    )
+                    .collect(),
+            };
+
+            let mut applied = false;
+            for target in targets {
+                let sub = match &target {
+                    Some(name) => command.find_subcommand(name).expect("checked above"),
+                    None => &command,
+                };
+                let Some(arg) = sub
+                    .get_arguments()
+                    .find(|arg| arg.get_id() == entry.option.as_str() && !arg.is_positional())
+                else {
+                    continue;
+                };
+                let values = match &entry.value {
+                    config::Setting::One(value) => vec![value.clone()],
+                    config::Setting::List(values)
+                        if matches!(arg.get_action(), ArgAction::Append) =>
+                    {
+                        values.clone()
+                    }
+                    config::Setting::List(_) => {
+                        return Err(error(&format!("'{}' takes a single value", entry.option)))
+                    }
+                };
+                if arg.get_action().takes_values() {
+                    let long = arg.get_long().unwrap_or_default();
+                    let argv = values.iter().map(|value| format!("--{}={}", long, value));
+                    sub.clone()
+                        .mut_args(|arg| arg.required(false))
+                        .subcommand_required(false)
+                        .arg_required_else_help(false)
+                        .try_get_matches_from(
+                            std::iter::once(sub.get_name().to_string()).chain(argv),
+                        )
+                        .map_err(|e| {
+                            let message = e.render().to_string();
+                            let message = message.lines().next().unwrap_or_default();
+                            error(message.trim_start_matches("error: "))
+                        })?;
+                } else if !matches!(values[0].as_str(), "true" | "false") {
+                    return Err(error(&format!("'{}' must be true or false", entry.option)));
+                }
+
+                let id = arg.get_id().clone();
+                let set = move |arg: clap::Arg| arg.default_values(values);
+                command = match target {
+                    Some(name) => command.mut_subcommand(name, |sub| sub.mut_arg(id, set)),
+                    None => command.mut_arg(id, set),
+                };
+                applied = true;
+            }
+            if !applied {
+                let message = match &entry.command {
+                    Some(name) => format!("'{}' is not an option of '{}'", entry.option, name),
+                    None => format!("'{}' is not an option of any command", entry.option),
+                };
+                return Err(error(&message));
+            }
+        }
+    }
+    Ok(command)
+}
+
+// Compile errors are shown with their source line when the file can be read,
+// and runtime errors with the calls they happened in.
+fn report(e: &(dyn std::error::Error + 'static), color: bool) {
+    match e.downcast_ref::<error::CompileError>() {
+        Some(e) if e.line > 0 => match std::fs::read_to_string(&e.filename) {
+            Ok(source) => eprint!("{}", e.render(&source, color)),
+            Err(_) => eprintln!("{}", e),
+        },
+        _ => eprintln!("{}", e),
+    }
+    if let Some(e) = e.downcast_ref::<vm::RuntimeError>() {
+        for line in &e.backtrace {
+            eprintln!("  {}", line);
+        }
+    }
+}
+
+// Same directory and stem as the input, with a new extension.
+fn sibling_path(input: &str, extension: &str) -> String {
+    let parent = std::path::Path::new(input)
+        .parent()
+        .unwrap_or(std::path::Path::new("."));
+    let stem = std::path::Path::new(input)
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("output");
+    parent
+        .join(format!("{}.{}", stem, extension))
+        .to_string_lossy()
+        .to_string()
+}
+
+fn compile_file(
+    input: &str,
+    output: &str,
+    options: &compiler::CompileOptions,
+    color: bool,
+    dump_opt: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
     let source = std::fs::read_to_string(input)
         .map_err(|_| error::CompileError::new(input, 0, 0, "failed to read file"))?;
 
-    let tokens = lexer::tokenize(&source, input)?;
-    let ast = parser::parse(tokens, input)?;
-    let bytecode = compiler::compile(ast).map_err(|e| error::CompileError::new(input, 0, 0, &e))?;
+    let bytecode = compile_with_warnings(&source, input, options, color)?;
+    if dump_opt {
+        let unoptimized = compiler::compile_source(
+            &source,
+            input,
+            &compiler::CompileOptions {
+                no_optimize: true,
+                ..options.clone()
+            },
+        )?;
+        println!("; before optimization");
+        print!("{}", disasm::disassemble(&unoptimized)?);
+        println!("\n; after optimization");
+        print!("{}", disasm::disassemble(&bytecode)?);
+    }
 
-    std::fs::write(output, bytecode)
-        .map_err(|_| error::CompileError::new(output, 0, 0, "failed to write bytecode"))?;
+    write_bytecode(output, &bytecode).map_err(write_failed(output))?;
 
     Ok(())
 }
 
-fn execute_file(input: &str) -> Result<(), vm::RuntimeError> {
-    let bytecode =
-        std::fs::read(input).map_err(|_| vm::RuntimeError::new("failed to read bytecode file"))?;
+fn transpile_file(
+    input: &str,
+    output: &str,
+    options: &compiler::CompileOptions,
+    color: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let source = std::fs::read_to_string(input)
+        .map_err(|_| error::CompileError::new(input, 0, 0, "failed to read file"))?;
+
+    let bytecode = compile_with_warnings(&source, input, options, color)?;
+    let c_source = transpile::to_c(&bytecode)?;
 
-    vm::execute(&bytecode)?;
+    std::fs::write(output, c_source).map_err(write_failed(output))?;
 
     Ok(())
 }
+
+// Bytecode paths accept `-` for stdin or stdout, so compile output can be
+// piped straight into exec or disasm.
+fn read_bytecode(path: &str) -> Result<Vec<u8>, vm::RuntimeError> {
+    let mut bytecode = Vec::new();
+    let read = if path == "-" {
+        std::io::stdin()
+            .lock()
+            .read_to_end(&mut bytecode)
+            .map(|_| ())
+    } else {
+        std::fs::read(path).map(|bytes| bytecode = bytes)
+    };
+    // Reported as invalid bytecode so it exits like a file that fails to load.
+    read.map_err(|_| vm::ErrorKind::InvalidBytecode("failed to read bytecode file".to_string()))?;
+    Ok(bytecode)
+}
+
+fn write_bytecode(path: &str, bytecode: &[u8]) -> std::io::Result<()> {
+    if path == "-" {
+        let mut stdout = std::io::stdout().lock();
+        stdout.write_all(bytecode)?;
+        stdout.flush()
+    } else {
+        std::fs::write(path, bytecode)
+    }
+}
+
+// Output that cannot be written is an I/O failure, which exits 1, not a
+// compile error.
+fn write_failed(path: &str) -> impl FnOnce(std::io::Error) -> std::io::Error + '_ {
+    move |e| std::io::Error::new(e.kind(), format!("failed to write {}: {}", path, e))
+}
+
+fn compile_with_warnings(
+    source: &str,
+    input: &str,
+    options: &compiler::CompileOptions,
+    color: bool,
+) -> Result<Vec<u8>, error::CompileError> {
+    let (bytecode, warnings) = compiler::compile_source_with_warnings(source, input, options)?;
+    for warning in warnings {
+        eprintln!("{}", warning.render(source, color));
+    }
+    Ok(bytecode)
+}
+
+fn execute_file(
+    input: &str,
+    options: &vm::VmOptions,
+    state_path: Option<&str>,
+    profile_out: Option<&str>,
+    trace_out: Option<&str>,
+) -> Result<vm::RunResult, vm::RuntimeError> {
+    let bytecode = read_bytecode(input)?;
+
+    let state_path = state_path.map(std::path::Path::new);
+    let mut globals = match state_path {
+        Some(path) => state::load(path).map_err(vm::ErrorKind::Host)?,
+        None => Default::default(),
+    };
+    let result = run_traced(&bytecode, options, &mut globals, trace_out)?;
+    if let Some(path) = state_path {
+        state::save(path, &globals).map_err(vm::ErrorKind::Host)?;
+    }
+
+    // Only a run that finishes writes a profile.
+    if let Some(path) = profile_out {
+        let profile =
+            profile::Profile::from_run(&bytecode, &result).map_err(vm::ErrorKind::Host)?;
+        std::fs::write(path, profile.to_json())
+            .map_err(|e| vm::ErrorKind::Host(format!("failed to write {}: {}", path, e)))?;
+    }
+
+    Ok(result)
+}
+
+fn read_profile(path: &str) -> Result<profile::Profile, error::CompileError> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|_| error::CompileError::new(path, 0, 0, "failed to read profile"))?;
+    profile::Profile::parse(&text).map_err(|e| error::CompileError::new(path, 0, 0, &e))
+}
+
+fn flush_policy(every_print: bool) -> vm::FlushPolicy {
+    if every_print {
+        vm::FlushPolicy::EveryPrint
+    } else {
+        vm::FlushPolicy::BeforeInput
+    }
+}
+
+fn run_file(
+    input: &str,
+    options: &vm::VmOptions,
+    trace_out: Option<&str>,
+) -> Result<vm::RunResult, Box<dyn std::error::Error>> {
+    let source = std::fs::read_to_string(input)
+        .map_err(|_| error::CompileError::new(input, 0, 0, "failed to read file"))?;
+    let bytecode = compiler::compile_source(&source, input, &Default::default())?;
+    Ok(run_traced(
+        &bytecode,
+        options,
+        &mut Default::default(),
+        trace_out,
+    )?)
+}
+
+// Reads the code for `brvm eval`, from stdin if it is "-".
+fn eval_input(code: String) -> Result<String, error::CompileError> {
+    if code != "-" {
+        return Ok(code);
+    }
+    let mut code = String::new();
+    std::io::stdin()
+        .lock()
+        .read_to_string(&mut code)
+        .map_err(|_| error::CompileError::new("<stdin>", 0, 0, "failed to read stdin"))?;
+    Ok(code)
+}
+
+fn eval_code(
+    code: &str,
+    options: &vm::VmOptions,
+) -> Result<vm::RunResult, Box<dyn std::error::Error>> {
+    let bytecode = repl::compile_snippet(code, EVAL_FILENAME)?;
+    Ok(run_traced(
+        &bytecode,
+        options,
+        &mut Default::default(),
+        None,
+    )?)
+}
+
+// Runs on the terminal, writing a trace to `trace_out` if given. The trace
+// is written even when the run fails, up to the point of failure.
+fn run_traced(
+    bytecode: &[u8],
+    options: &vm::VmOptions,
+    globals: &mut vm::Globals,
+    trace_out: Option<&str>,
+) -> Result<vm::RunResult, vm::RuntimeError> {
+    let mut vm = vm::VM::new(host::StdHost::new(), options);
+    let tracer = match trace_out {
+        Some(_) => {
+            Some(trace::Tracer::attach(&mut vm, bytecode).map_err(vm::ErrorKind::InvalidBytecode)?)
+        }
+        None => None,
+    };
+    let result = vm::run_with_globals(&mut vm, bytecode, globals);
+    if let (Some(path), Some(tracer)) = (trace_out, tracer) {
+        std::fs::write(path, tracer.to_json())
+            .map_err(|e| vm::ErrorKind::Host(format!("failed to write {}: {}", path, e)))?;
+    }
+    result
+}
+
+#[cfg(feature = "tui")]
+fn debug_file(
+    input: &str,
+    seed: Option<u64>,
+    stdin: Option<&str>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let source = std::fs::read_to_string(input)
+        .map_err(|_| error::CompileError::new(input, 0, 0, "failed to read file"))?;
+    let lines = match stdin {
+        Some(path) => std::fs::read_to_string(path)
+            .map_err(|_| error::CompileError::new(path, 0, 0, "failed to read file"))?,
+        None => String::new(),
+    };
+    let options = vm::VmOptions {
+        seed,
+        ..Default::default()
+    };
+    let debugger = brvm::debugger::Debugger::new(&source, input, &lines, options)?;
+    brvm::tui::run(debugger)?;
+    Ok(())
+}
+
+// Table for `exec --stats`; main's row is whatever the functions did not use.
+fn format_stats(result: &vm::RunResult) -> String {
+    let width = result
+        .function_stats
+        .iter()
+        .map(|f| f.name.chars().count())
+        .chain([8])
+        .max()
+        .unwrap_or(8);
+    let mut out = format!(
+        "{:<width$}  {:>10}  {:>12}\n",
+        "function", "calls", "instructions"
+    );
+    let mut in_functions = 0;
+    for f in &result.function_stats {
+        in_functions += f.instructions;
+        out += &format!(
+            "{:<width$}  {:>10}  {:>12}\n",
+            f.name, f.calls, f.instructions
+        );
+    }
+    out += &format!(
+        "{:<width$}  {:>10}  {:>12}\n",
+        "(main)",
+        "-",
+        result.instructions - in_functions
+    );
+
+    if !result.branch_stats.is_empty() {
+        let width = result
+            .branch_stats
+            .iter()
+            .map(|b| b.at.chars().count())
+            .chain([6])
+            .max()
+            .unwrap_or(6);
+        out += &format!(
+            "\n{:<width$}  {:>10}  {:>10}\n",
+            "branch", "truthy", "falsy"
+        );
+        for b in &result.branch_stats {
+            out += &format!("{:<width$}  {:>10}  {:>10}\n", b.at, b.truthy, b.falsy);
+        }
+    }
+    out
+}
+
+// Reads entries until end of input. A line that leaves a block open is
+// followed by `...` prompts until the block is closed. On a terminal, lines
+// are read with rustyline for editing, history in ~/.brvm_history and tab
+// completion; piped input is read as-is and no prompts are shown.
+fn run_repl(color: bool, drip: bool) -> std::io::Result<()> {
+    let session = Rc::new(RefCell::new(repl::Session::new()));
+    session.borrow_mut().options.color = drip;
+    let history = std::env::var_os("HOME").map(|home| Path::new(&home).join(".brvm_history"));
+    let mut editor = None;
+    if std::io::stdin().is_terminal() {
+        let mut rl = Editor::new().map_err(std::io::Error::other)?;
+        rl.set_helper(Some(ReplHelper {
+            session: Rc::clone(&session),
+        }));
+        if let Some(history) = &history {
+            let _ = rl.load_history(history);
+        }
+        editor = Some(rl);
+    }
+
+    let mut entry = String::new();
+    loop {
+        let line = match &mut editor {
+            Some(rl) => {
+                match rl.readline(if entry.is_empty() { "brvm> " } else { "...   " }) {
+                    Ok(line) => {
+                        let _ = rl.add_history_entry(line.as_str());
+                        line + "\n"
+                    }
+                    // Ctrl-C drops the entry being typed.
+                    Err(ReadlineError::Interrupted) => {
+                        entry.clear();
+                        continue;
+                    }
+                    Err(ReadlineError::Eof) => break,
+                    Err(e) => return Err(std::io::Error::other(e)),
+                }
+            }
+            None => {
+                let mut line = String::new();
+                if std::io::stdin().read_line(&mut line)? == 0 {
+                    break;
+                }
+                line
+            }
+        };
+        if entry.is_empty() && line.trim().is_empty() {
+            continue;
+        }
+        let mut session = session.borrow_mut();
+        if let Some(command) = line.strip_prefix(':').filter(|_| entry.is_empty()) {
+            match session.command(command, &mut host::StdHost::new()) {
+                Ok(text) => print!("{}", text),
+                Err(e) => report_repl(e.as_ref(), "", color),
+            }
+            continue;
+        }
+        entry.push_str(&line);
+        if repl::is_incomplete(&entry) {
+            continue;
+        }
+
+        if let Err(e) = session.eval(&entry, &mut host::StdHost::new()) {
+            report_repl(e.as_ref(), &entry, color);
+        }
+        entry.clear();
+    }
+
+    if let (Some(rl), Some(history)) = (&mut editor, &history) {
+        let _ = rl.save_history(history);
+    }
+    Ok(())
+}
+
+struct ReplHelper {
+    session: Rc<RefCell<repl::Session>>,
+}
+
+impl Completer for ReplHelper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _: &rustyline::Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        Ok(self.session.borrow().complete(line, pos))
+    }
+}
+
+impl Hinter for ReplHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ReplHelper {}
+
+impl Validator for ReplHelper {}
+
+impl Helper for ReplHelper {}
+
+// Errors in a typed entry are shown against it; errors from `:load` go
+// through `report`, which reads the file.
+fn report_repl(e: &(dyn std::error::Error + 'static), entry: &str, color: bool) {
+    let _ = std::io::stdout().flush();
+    match e.downcast_ref::<error::CompileError>() {
+        Some(e) if e.filename == repl::FILENAME => eprint!("{}", e.render(entry, color)),
+        _ => report(e, color),
+    }
+}
+
+fn disassemble_file(input: &str) -> Result<(), vm::RuntimeError> {
+    let bytecode = read_bytecode(input)?;
+
+    let listing = disasm::disassemble(&bytecode).map_err(vm::ErrorKind::InvalidBytecode)?;
+    print!("{}", listing);
+
+    Ok(())
+}
+
+fn assemble_file(input: &str, output: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let mut source = String::new();
+    let read = if input == "-" {
+        std::io::stdin()
+            .lock()
+            .read_to_string(&mut source)
+            .map(|_| ())
+    } else {
+        std::fs::read_to_string(input).map(|text| source = text)
+    };
+    read.map_err(|_| error::CompileError::new(input, 0, 0, "failed to read file"))?;
+    let bytecode = asm::assemble(&source, input)?;
+    write_bytecode(output, &bytecode).map_err(write_failed(output))?;
+    Ok(())
+}
+
+fn roundtrip_file(input: &str) -> Result<(), vm::RuntimeError> {
+    let bytecode = read_bytecode(input)?;
+    asm::roundtrip(&bytecode).map_err(vm::ErrorKind::InvalidBytecode)?;
+    println!("{}: {} bytes round-trip", input, bytecode.len());
+    Ok(())
+}
+
+// Prints what `analyze` found and returns how many there were.
+fn analyze_file(input: &str, strict: bool, color: bool) -> Result<usize, error::CompileError> {
+    let source = std::fs::read_to_string(input)
+        .map_err(|_| error::CompileError::new(input, 0, 0, "failed to read file"))?;
+    let found = analyze::analyze(&source, input, strict)?;
+    for diagnostic in &found {
+        eprintln!("{}", diagnostic.render(&source, color));
+    }
+    Ok(found.len())
+}
+
+fn lex_file(input: &str, json: bool, include_comments: bool) -> Result<(), error::CompileError> {
+    let source = std::fs::read_to_string(input)
+        .map_err(|_| error::CompileError::new(input, 0, 0, "failed to read file"))?;
+
+    let tokens = if include_comments {
+        lexer::tokenize_with_comments(&source, input)?
+    } else {
+        lexer::tokenize_spanned(&source, input)?
+    };
+
+    if json {
+        print!("{}", lexdump::json(&tokens));
+    } else {
+        print!("{}", lexdump::listing(&tokens));
+    }
+
+    Ok(())
+}
+
+fn doc_files(
+    inputs: &[String],
+    output: Option<&str>,
+    format: DocFormat,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let paths: Vec<_> = inputs.iter().map(std::path::PathBuf::from).collect();
+    let mut files = Vec::new();
+    for path in doc::collect_sources(&paths)? {
+        let name = path.display().to_string();
+        let source = std::fs::read_to_string(&path)
+            .map_err(|_| error::CompileError::new(&name, 0, 0, "failed to read file"))?;
+        files.push(doc::FileDoc {
+            functions: doc::extract(&source, &name)?,
+            path: name,
+        });
+    }
+
+    let text = match format {
+        DocFormat::Markdown => doc::markdown(&files),
+        DocFormat::Html => doc::html(&files),
+    };
+    match output {
+        Some(path) => std::fs::write(path, text).map_err(write_failed(path))?,
+        None => print!("{}", text),
+    }
+    Ok(())
+}
+
+fn inspect_file(input: &str, size: bool, top: usize) -> Result<(), vm::RuntimeError> {
+    let bytecode = read_bytecode(input)?;
+
+    let report = if size {
+        inspect::size_report(&bytecode, top)
+    } else {
+        inspect::summary(&bytecode)
+    };
+    print!("{}", report.map_err(vm::ErrorKind::InvalidBytecode)?);
+
+    Ok(())
+}
+
+fn strip_file(input: &str, output: &str) -> Result<(), vm::RuntimeError> {
+    let bytecode = read_bytecode(input)?;
+
+    let stripped = strip::strip(&bytecode).map_err(vm::ErrorKind::InvalidBytecode)?;
+    write_bytecode(output, &stripped)
+        .map_err(|_| vm::ErrorKind::Host("failed to write bytecode file".to_string()))?;
+
+    Ok(())
+}
+
+fn highlight_file(
+    input: &str,
+    output: Option<&str>,
+    textmate: Option<&str>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let source = std::fs::read_to_string(input)
+        .map_err(|_| error::CompileError::new(input, 0, 0, "failed to read file"))?;
+
+    let html = highlight::html(&source, input)?;
+    match output {
+        Some(path) => std::fs::write(path, html).map_err(write_failed(path))?,
+        None => print!("{}", html),
+    }
+
+    if let Some(path) = textmate {
+        std::fs::write(path, highlight::textmate_grammar()).map_err(write_failed(path))?;
+    }
+
+    Ok(())
+}
+
+// Ctrl-C sets the VM's interrupt flag instead of killing the process, so the
+// run stops cleanly: stdout is flushed and a --state file is left untouched.
+#[cfg(unix)]
+fn install_interrupt_handler() -> Option<Arc<AtomicBool>> {
+    use std::os::raw::c_int;
+    use std::sync::atomic::Ordering;
+    use std::sync::OnceLock;
+
+    const SIGINT: c_int = 2;
+
+    static INTERRUPT: OnceLock<Arc<AtomicBool>> = OnceLock::new();
+
+    extern "C" {
+        fn signal(signum: c_int, handler: usize) -> usize;
+        fn siginterrupt(signum: c_int, flag: c_int) -> c_int;
+    }
+
+    extern "C" fn on_sigint(_: c_int) {
+        if let Some(flag) = INTERRUPT.get() {
+            flag.store(true, Ordering::Relaxed);
+        }
+    }
+
+    let flag = INTERRUPT.get_or_init(|| Arc::new(AtomicBool::new(false)));
+    // SAFETY: the handler only performs an atomic store. siginterrupt makes
+    // a blocked stdin read fail with EINTR instead of restarting.
+    unsafe {
+        signal(SIGINT, on_sigint as extern "C" fn(c_int) as usize);
+        siginterrupt(SIGINT, 1);
+    }
+    Some(flag.clone())
+}
+
+#[cfg(not(unix))]
+fn install_interrupt_handler() -> Option<Arc<AtomicBool>> {
+    None
+}
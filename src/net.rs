@@ -0,0 +1,162 @@
+// Blocking HTTP client behind YOINK and YEET. It speaks plain HTTP/1.1 over
+// std's TcpStream; there is no TLS, so `https://` URLs are refused rather
+// than quietly downgraded.
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+const MAX_RESPONSE_BYTES: u64 = 16 * 1024 * 1024;
+
+// Sends a GET, or a POST when `body` is given, and returns the response body
+// of a 2xx reply. Any other status is an error naming it.
+pub fn request(url: &str, body: Option<&str>) -> Result<String, String> {
+    let url = Url::parse(url)?;
+    let addr = (url.host.as_str(), url.port)
+        .to_socket_addrs()
+        .ok()
+        .and_then(|mut addrs| addrs.next())
+        .ok_or_else(|| format!("cannot resolve {}", url.host))?;
+    let mut stream = TcpStream::connect_timeout(&addr, TIMEOUT)
+        .map_err(|e| format!("cannot connect to {}: {}", url.authority, e))?;
+    let _ = stream.set_read_timeout(Some(TIMEOUT));
+    let _ = stream.set_write_timeout(Some(TIMEOUT));
+
+    let mut head = format!(
+        "{} {} HTTP/1.1\r\nHost: {}\r\nUser-Agent: brvm/{}\r\nAccept: */*\r\nConnection: close\r\n",
+        if body.is_some() { "POST" } else { "GET" },
+        url.path,
+        url.authority,
+        env!("CARGO_PKG_VERSION")
+    );
+    if let Some(body) = body {
+        head.push_str("Content-Type: text/plain; charset=utf-8\r\n");
+        head.push_str(&format!("Content-Length: {}\r\n", body.len()));
+    }
+    head.push_str("\r\n");
+    stream
+        .write_all(head.as_bytes())
+        .and_then(|_| stream.write_all(body.unwrap_or("").as_bytes()))
+        .map_err(|e| format!("failed to send request: {}", e))?;
+
+    let mut response = Vec::new();
+    stream
+        .take(MAX_RESPONSE_BYTES + 1)
+        .read_to_end(&mut response)
+        .map_err(|e| format!("failed to read response: {}", e))?;
+    if response.len() as u64 > MAX_RESPONSE_BYTES {
+        return Err("response is larger than 16 MiB".to_string());
+    }
+    parse_response(&response)
+}
+
+struct Url {
+    authority: String, // host[:port] as written, for the Host header
+    host: String,
+    port: u16,
+    path: String,
+}
+
+impl Url {
+    fn parse(url: &str) -> Result<Self, String> {
+        let rest = match url.strip_prefix("http://") {
+            Some(rest) => rest,
+            None if url.starts_with("https://") => {
+                return Err("https is not supported; use an http:// URL".to_string())
+            }
+            None => return Err(format!("expected an http:// URL, got {:?}", url)),
+        };
+        let rest = rest.split('#').next().unwrap_or_default();
+        let (authority, path) = match rest.find(['/', '?']) {
+            Some(end) => (&rest[..end], &rest[end..]),
+            None => (rest, "/"),
+        };
+        let path = if path.starts_with('?') {
+            format!("/{}", path)
+        } else {
+            path.to_string()
+        };
+        // The port follows the last ':' unless that ':' is inside an IPv6
+        // literal such as [::1].
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) if !port.contains(']') => (
+                host,
+                port.parse::<u16>()
+                    .map_err(|_| format!("invalid port in URL: {}", port))?,
+            ),
+            _ => (authority, 80),
+        };
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        if host.is_empty() {
+            return Err(format!("missing host in URL {:?}", url));
+        }
+        Ok(Self {
+            authority: authority.to_string(),
+            host: host.to_string(),
+            port,
+            path,
+        })
+    }
+}
+
+fn parse_response(bytes: &[u8]) -> Result<String, String> {
+    let malformed = || "malformed HTTP response".to_string();
+    let end = bytes
+        .windows(4)
+        .position(|w| w == b"\r\n\r\n")
+        .ok_or_else(malformed)?;
+    let head = std::str::from_utf8(&bytes[..end]).map_err(|_| malformed())?;
+    let mut lines = head.split("\r\n");
+    let status_line = lines.next().unwrap_or_default();
+    let status = status_line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|code| code.parse::<u16>().ok())
+        .ok_or_else(malformed)?;
+
+    let mut chunked = false;
+    let mut length = None;
+    for line in lines {
+        let Some((name, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        if name.eq_ignore_ascii_case("transfer-encoding") {
+            chunked = value.eq_ignore_ascii_case("chunked");
+        } else if name.eq_ignore_ascii_case("content-length") {
+            length = value.parse::<usize>().ok();
+        }
+    }
+
+    let mut body = &bytes[end + 4..];
+    let decoded;
+    if chunked {
+        decoded = dechunk(body).ok_or_else(malformed)?;
+        body = &decoded;
+    } else if let Some(length) = length {
+        body = &body[..length.min(body.len())];
+    }
+
+    if !(200..300).contains(&status) {
+        let reason = status_line.splitn(3, ' ').nth(2).unwrap_or_default();
+        return Err(format!("HTTP {} {}", status, reason).trim_end().to_string());
+    }
+    String::from_utf8(body.to_vec()).map_err(|_| "response body is not UTF-8".to_string())
+}
+
+// Joins the chunks of a `Transfer-Encoding: chunked` body.
+fn dechunk(mut body: &[u8]) -> Option<Vec<u8>> {
+    let mut out = Vec::new();
+    loop {
+        let line_end = body.windows(2).position(|w| w == b"\r\n")?;
+        let size = std::str::from_utf8(&body[..line_end]).ok()?;
+        let size = size.split(';').next()?.trim();
+        let size = usize::from_str_radix(size, 16).ok()?;
+        body = &body[line_end + 2..];
+        if size == 0 {
+            return Some(out);
+        }
+        out.extend_from_slice(body.get(..size)?);
+        body = body.get(size + 2..)?;
+    }
+}
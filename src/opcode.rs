@@ -0,0 +1,55 @@
+// BRBC opcode table shared by the compiler's relocation pass and the
+// disassembler: (opcode, name, operand width in bytes).
+pub const OPCODES: &[(u8, &str, usize)] = &[
+    (0x01, "HALT", 0),
+    (0x02, "LOAD_CONST", 4),
+    (0x03, "LOAD_GLOBAL", 1),
+    (0x04, "STORE_GLOBAL", 1),
+    (0x05, "ADD", 0),
+    (0x06, "SUB", 0),
+    (0x07, "MUL", 0),
+    (0x08, "DIV", 0),
+    (0x09, "PRINT", 0),
+    (0x0A, "INPUT", 0),
+    (0x0B, "JUMP", 4),
+    (0x0C, "JUMP_IF_FALSE", 4),
+    (0x0D, "HITMEUP", 4),
+    (0x0E, "UNTILWEMEETAGAIN", 0),
+    (0x0F, "TAX_LOCAL", 2),
+    (0x10, "BIGBACK_LOCAL", 2),
+    (0x11, "POOPY", 0),
+    (0x12, "YOUSHALLNOTPASS", 0),
+    (0x13, "INPUT_PROMPT", 0),
+    (0x14, "CALL_BUILTIN", 1),
+    (0x15, "PRINT_RAW", 0),
+    (0x16, "TAILCALL", 4),
+    (0x17, "DUP", 0),
+    (0x18, "THROW", 0),
+    (0x19, "PRINT_ERR", 0),
+    (0x1A, "INPUT_TIMEOUT", 0),
+    (0x1B, "EQUAL", 0),
+    (0x1C, "JUMP_IF_TRUE", 4),
+    (0x1D, "CLONE", 0),
+    (0x1E, "SWAP", 0),
+    (0x1F, "ROT", 0),
+];
+
+pub fn lookup(op: u8) -> Option<(&'static str, usize)> {
+    OPCODES
+        .iter()
+        .find(|(code, _, _)| *code == op)
+        .map(|&(_, name, width)| (name, width))
+}
+
+pub fn is_jump(op: u8) -> bool {
+    matches!(op, 0x0B | 0x0C | 0x1C)
+}
+
+// Oldest bytecode version whose VM runs `op`.
+pub fn min_version(op: u8) -> u16 {
+    if op >= 0x14 {
+        5
+    } else {
+        4
+    }
+}
@@ -1,109 +1,644 @@
+use crate::builtins;
 use crate::error::CompileError;
-use crate::lexer::Token;
+use crate::lexer::{Lexer, Span, SpannedToken, Token, BLOCK_COMMENT_OPEN};
+use std::collections::{HashMap, VecDeque};
 
+// Each node carries the span of its first token, except Binary, which points
+// at its operator.
 #[derive(Debug, Clone)]
 pub enum Expr {
-    Number(f64),
-    String(String),
-    Variable(String), // Variable name (braincell or local)
+    Number(f64, Span),
+    String(String, Span),
+    Variable(String, Span), // Variable name (braincell or local)
     Binary {
         op: BinaryOp,
         left: Box<Expr>,
         right: Box<Expr>,
+        span: Span,
     },
     FunctionCall {
         name: String,
-        arg: Option<Box<Expr>>,
+        args: Vec<Expr>,
+        span: Span,
     },
     UserFunctionCall {
         name: String,
         args: Vec<Expr>,
+        span: Span,
     },
 }
 
+impl Expr {
+    pub fn span(&self) -> Span {
+        match self {
+            Expr::Number(_, span)
+            | Expr::String(_, span)
+            | Expr::Variable(_, span)
+            | Expr::Binary { span, .. }
+            | Expr::FunctionCall { span, .. }
+            | Expr::UserFunctionCall { span, .. } => *span,
+        }
+    }
+}
+
+// Spans are left out, so the same expression written in two places is equal;
+// the compiler relies on this to reuse hoisted and repeated subexpressions.
+impl PartialEq for Expr {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Expr::Number(a, _), Expr::Number(b, _)) => a == b,
+            (Expr::String(a, _), Expr::String(b, _)) => a == b,
+            (Expr::Variable(a, _), Expr::Variable(b, _)) => a == b,
+            (
+                Expr::Binary {
+                    op, left, right, ..
+                },
+                Expr::Binary {
+                    op: other_op,
+                    left: other_left,
+                    right: other_right,
+                    ..
+                },
+            ) => op == other_op && left == other_left && right == other_right,
+            (
+                Expr::FunctionCall { name, args, .. },
+                Expr::FunctionCall {
+                    name: other_name,
+                    args: other_args,
+                    ..
+                },
+            )
+            | (
+                Expr::UserFunctionCall { name, args, .. },
+                Expr::UserFunctionCall {
+                    name: other_name,
+                    args: other_args,
+                    ..
+                },
+            ) => name == other_name && args == other_args,
+            _ => false,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum BinaryOp {
     Add,
     Subtract,
     Multiply,
     Divide,
+    Equal,
+}
+
+// Optional annotation on a parameter or FANUMTAX, written `name: number`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Type {
+    Number,
+    String,
+}
+
+impl Type {
+    pub fn name(self) -> &'static str {
+        match self {
+            Type::Number => "number",
+            Type::String => "string",
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 pub enum Statement {
-    Assign(String, Expr), // variable name, expression
+    Assign(String, Option<Type>, Expr), // variable name, annotation, expression
     Copy {
         dest: String,
         source: Expr,
     }, // DIDDLE
     Print(Expr),
+    PrintInline(Expr), // YAP
+    PrintError(Expr),  // SCREAM
     If {
         condition: Expr,
-        then_block: Vec<Statement>,
-        else_block: Option<Vec<Statement>>,
+        then_block: Vec<SpannedStatement>,
+        else_block: Option<Vec<SpannedStatement>>,
     },
     While {
         condition: Expr,
-        body: Vec<Statement>,
+        body: Vec<SpannedStatement>,
     },
     Return(Expr), // RETREAT
     Halt,         // YOUSHALLNOTPASS
+    Throw(Expr),  // CRASHOUT
+}
+
+#[derive(Debug, Clone)]
+pub struct SpannedStatement {
+    pub statement: Statement,
+    pub span: Span, // first token of the statement
+}
+
+#[derive(Debug, Clone)]
+pub struct Const {
+    pub name: String,
+    pub value: Expr,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct Param {
+    pub name: String,
+    pub ty: Option<Type>,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone)]
 pub struct Function {
     pub name: String,
-    pub params: Vec<String>,
-    pub body: Vec<Statement>,
+    pub params: Vec<Param>,
+    pub body: Vec<SpannedStatement>,
+    pub span: Span, // function name
 }
 
 #[derive(Debug, Clone)]
 pub struct Program {
+    pub consts: Vec<Const>, // BASED name FR literal
     pub functions: Vec<Function>,
-    pub main_statements: Vec<Statement>,
+    pub main_statements: Vec<SpannedStatement>,
+}
+
+// A comment kept by parse_with_trivia, with its text as the lexer gives it:
+// without the marker and the whitespace around it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comment {
+    pub text: String,
+    pub span: Span,
+    pub block: bool, // 🙈 ... 🙉 rather than a line comment
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NodeTrivia {
+    pub blank_lines: usize,     // above the node and its leading comments
+    pub leading: Vec<Comment>,  // on lines of their own, directly above
+    pub trailing: Vec<Comment>, // after code on the node's first line or its closing keyword's
+    pub closing: Vec<Comment>,  // on lines of their own before NO CAP or the closing keyword
+}
+
+// What parse_with_trivia records beside the program, so a tool that rewrites
+// a file can put its comments and blank lines back. Statements, functions
+// and constants are looked up by their span; `program` belongs to LOCK IN ...
+// ITS OVER and also holds the comments after it.
+#[derive(Debug, Clone, Default)]
+pub struct Trivia {
+    pub program: NodeTrivia,
+    nodes: HashMap<usize, NodeTrivia>, // by span offset
 }
 
+impl Trivia {
+    pub fn get(&self, span: Span) -> Option<&NodeTrivia> {
+        self.nodes.get(&span.offset)
+    }
+
+    fn node(&mut self, key: Option<usize>) -> &mut NodeTrivia {
+        match key {
+            Some(offset) => self.nodes.entry(offset).or_default(),
+            None => &mut self.program,
+        }
+    }
+}
+
+impl Program {
+    // Whether any function or main calls the built-in `name`.
+    pub fn calls_builtin(&self, name: &str) -> bool {
+        self.functions
+            .iter()
+            .map(|f| &f.body)
+            .chain([&self.main_statements])
+            .any(|body| block_calls(body, name))
+    }
+}
+
+fn block_calls(stmts: &[SpannedStatement], name: &str) -> bool {
+    stmts.iter().any(|stmt| match &stmt.statement {
+        Statement::Assign(_, _, expr)
+        | Statement::Copy { source: expr, .. }
+        | Statement::Print(expr)
+        | Statement::PrintInline(expr)
+        | Statement::PrintError(expr)
+        | Statement::Return(expr)
+        | Statement::Throw(expr) => expr_calls(expr, name),
+        Statement::Halt => false,
+        Statement::If {
+            condition,
+            then_block,
+            else_block,
+        } => {
+            expr_calls(condition, name)
+                || block_calls(then_block, name)
+                || else_block.as_ref().is_some_and(|b| block_calls(b, name))
+        }
+        Statement::While { condition, body } => {
+            expr_calls(condition, name) || block_calls(body, name)
+        }
+    })
+}
+
+fn expr_calls(expr: &Expr, name: &str) -> bool {
+    match expr {
+        Expr::Number(..) | Expr::String(..) | Expr::Variable(..) => false,
+        Expr::Binary { left, right, .. } => expr_calls(left, name) || expr_calls(right, name),
+        Expr::FunctionCall {
+            name: called, args, ..
+        } => called == name || args.iter().any(|arg| expr_calls(arg, name)),
+        Expr::UserFunctionCall { args, .. } => args.iter().any(|arg| expr_calls(arg, name)),
+    }
+}
+
+// The grammar the parser below implements, one rule per entry, for `brvm
+// grammar`. A change to the parser's syntax belongs here too;
+// tests/grammar.rs checks the table against the lexer's keywords.
+#[derive(Debug)]
+pub enum Syntax {
+    Word(&'static str),               // keyword, operator or punctuation
+    Rule(&'static str),               // another entry, by name
+    Lexical(&'static str),            // a token class, in words
+    Names(fn() -> Vec<&'static str>), // any one word from a table
+    Seq(&'static [Syntax]),
+    Choice(&'static [Syntax]),
+    Optional(&'static Syntax),
+    Repeat(&'static Syntax), // zero or more times
+}
+
+use Syntax as S;
+
+pub const GRAMMAR: &[(&str, Syntax)] = &[
+    (
+        "program",
+        S::Seq(&[
+            S::Repeat(&S::Choice(&[S::Rule("constant"), S::Rule("function")])),
+            S::Word("LOCK"),
+            S::Word("IN"),
+            S::Repeat(&S::Rule("statement")),
+            S::Word("ITS"),
+            S::Word("OVER"),
+        ]),
+    ),
+    (
+        "constant",
+        S::Seq(&[
+            S::Word("BASED"),
+            S::Rule("identifier"),
+            S::Word("FR"),
+            S::Choice(&[S::Rule("number"), S::Rule("string")]),
+        ]),
+    ),
+    (
+        "function",
+        S::Seq(&[
+            S::Word("TRALALERO"),
+            S::Rule("identifier"),
+            S::Word("("),
+            S::Optional(&S::Seq(&[
+                S::Rule("parameter"),
+                S::Repeat(&S::Seq(&[S::Word(","), S::Rule("parameter")])),
+            ])),
+            S::Word(")"),
+            S::Repeat(&S::Rule("statement")),
+            S::Word("TRALALA"),
+        ]),
+    ),
+    (
+        "parameter",
+        S::Seq(&[S::Rule("identifier"), S::Optional(&S::Rule("annotation"))]),
+    ),
+    (
+        "annotation",
+        S::Seq(&[
+            S::Word(":"),
+            S::Choice(&[S::Word("number"), S::Word("string")]),
+        ]),
+    ),
+    (
+        "statement",
+        S::Choice(&[
+            S::Rule("assignment"),
+            S::Rule("copy"),
+            S::Seq(&[
+                S::Choice(&[
+                    S::Word("SAY"),
+                    S::Word("YAP"),
+                    S::Word("SCREAM"),
+                    S::Word("RETREAT"),
+                    S::Word("CRASHOUT"),
+                ]),
+                S::Rule("expression"),
+            ]),
+            S::Word("YOUSHALLNOTPASS"),
+            S::Rule("if"),
+            S::Rule("while"),
+        ]),
+    ),
+    (
+        "assignment",
+        S::Seq(&[
+            S::Word("FANUMTAX"),
+            S::Rule("variable"),
+            S::Optional(&S::Rule("annotation")),
+            S::Word("FR"),
+            S::Rule("expression"),
+        ]),
+    ),
+    (
+        "copy",
+        S::Seq(&[
+            S::Word("DIDDLE"),
+            S::Rule("variable"),
+            S::Word("FR"),
+            S::Rule("expression"),
+        ]),
+    ),
+    (
+        "if",
+        S::Seq(&[
+            S::Word("ONGOD"),
+            S::Rule("expression"),
+            S::Repeat(&S::Rule("statement")),
+            S::Optional(&S::Seq(&[
+                S::Word("NO"),
+                S::Word("CAP"),
+                S::Repeat(&S::Rule("statement")),
+            ])),
+            S::Word("DEADASS"),
+        ]),
+    ),
+    (
+        "while",
+        S::Seq(&[
+            S::Word("SKIBIDI"),
+            S::Rule("expression"),
+            S::Repeat(&S::Rule("statement")),
+            S::Word("RIZZUP"),
+        ]),
+    ),
+    // Operators bind tighter further down: 🟰, then 💀 😭, then 😏 🚡, all
+    // left to right. + - * / are the same operators spelled in ASCII.
+    (
+        "expression",
+        S::Seq(&[
+            S::Rule("sum"),
+            S::Repeat(&S::Seq(&[S::Word("🟰"), S::Rule("sum")])),
+        ]),
+    ),
+    (
+        "sum",
+        S::Seq(&[
+            S::Rule("product"),
+            S::Repeat(&S::Seq(&[
+                S::Choice(&[S::Word("💀"), S::Word("😭"), S::Word("+"), S::Word("-")]),
+                S::Rule("product"),
+            ])),
+        ]),
+    ),
+    (
+        "product",
+        S::Seq(&[
+            S::Rule("term"),
+            S::Repeat(&S::Seq(&[
+                S::Choice(&[S::Word("😏"), S::Word("🚡"), S::Word("*"), S::Word("/")]),
+                S::Rule("term"),
+            ])),
+        ]),
+    ),
+    (
+        "term",
+        S::Choice(&[
+            S::Rule("number"),
+            S::Rule("string"),
+            S::Rule("variable"),
+            S::Seq(&[S::Rule("builtin"), S::Rule("arguments")]),
+            S::Seq(&[
+                S::Optional(&S::Seq(&[S::Word("ring"), S::Word("yas")])),
+                S::Rule("identifier"),
+                S::Rule("arguments"),
+            ]),
+        ]),
+    ),
+    (
+        "arguments",
+        S::Seq(&[
+            S::Word("("),
+            S::Optional(&S::Seq(&[
+                S::Rule("expression"),
+                S::Repeat(&S::Seq(&[S::Word(","), S::Rule("expression")])),
+            ])),
+            S::Word(")"),
+        ]),
+    ),
+    (
+        "variable",
+        S::Choice(&[S::Rule("identifier"), S::Rule("braincell")]),
+    ),
+    (
+        "braincell",
+        S::Names(|| crate::lexer::BRAINCELLS.iter().map(|(name, _)| *name).collect()),
+    ),
+    (
+        "builtin",
+        S::Choice(&[
+            S::Names(|| builtins::BUILTINS.iter().map(|b| b.name).collect()),
+            S::Word("TOUCHY"),
+        ]),
+    ),
+    (
+        "identifier",
+        S::Lexical("an XID_Start character or _, then XID_Continue characters or _, that is not a keyword, braincell or built-in"),
+    ),
+    (
+        "number",
+        S::Lexical("digits, optionally followed by . and more digits"),
+    ),
+    (
+        "string",
+        S::Lexical("text between double quotes, with \\n, \\t, \\\" and \\\\ as escapes"),
+    ),
+];
+
+// Blocks and call arguments past this depth are rejected; the recursive
+// passes after parsing would overflow the stack on them.
+const MAX_NESTING: usize = 100;
+
+// Operator chains get their own budget: a binary node costs the later passes
+// far less stack than a block, and long `💀` chains are common. A release
+// build gets through this many on a 2 MiB thread stack.
+const MAX_CHAIN: usize = 2_000;
+
 pub struct Parser<'a> {
-    tokens: Vec<Token>,
+    tokens: Vec<SpannedToken>,
     position: usize,
     filename: &'a str,
+    errors: Vec<CompileError>,
+    depth: usize,
+    chain: usize,                  // binary operators enclosing the current expression
+    too_deep: bool,                // stops recording once a limit is hit
+    trivia: Option<Collector<'a>>, // only for parse_with_trivia
+}
+
+struct Collector<'a> {
+    source: &'a str,
+    comments: VecDeque<Comment>, // not attached yet
+    trivia: Trivia,
+    line_owners: HashMap<usize, Option<usize>>, // the node a line's trailing comments go to
+}
+
+impl Collector<'_> {
+    // Takes the comments before `offset`. Those after code on the same line
+    // go to that line's owner; the ones on lines of their own are returned.
+    fn drain(&mut self, tokens: &[SpannedToken], offset: usize) -> Vec<Comment> {
+        let mut own_line = Vec::new();
+        while let Some(comment) = self.comments.pop_front() {
+            if comment.span.offset >= offset {
+                self.comments.push_front(comment);
+                break;
+            }
+            let after = tokens.partition_point(|t| t.span.offset < comment.span.offset);
+            let owner = after
+                .checked_sub(1)
+                .filter(|&i| tokens[i].span.line == comment.span.line)
+                .and_then(|_| self.line_owners.get(&comment.span.line).copied());
+            match owner {
+                Some(key) => self.trivia.node(key).trailing.push(comment),
+                None => own_line.push(comment),
+            }
+        }
+        own_line
+    }
+
+    // Blank lines between `offset` and the code or comment before it.
+    fn blank_lines_before(&self, offset: usize) -> usize {
+        let before = self.source[..offset].trim_end();
+        match before.is_empty() {
+            true => 0,
+            false => self.source[before.len()..offset]
+                .matches('\n')
+                .count()
+                .saturating_sub(1),
+        }
+    }
+}
+
+// Parsing recovers at statement and block boundaries, so one run reports
+// every error it can; the first is returned with the rest in `additional`.
+pub fn parse(tokens: Vec<SpannedToken>, filename: &str) -> Result<Program, CompileError> {
+    Parser::new(tokens, filename).run()
 }
 
-pub fn parse(tokens: Vec<Token>, filename: &str) -> Result<Program, CompileError> {
+// Lexes and parses `source`, keeping its comments and blank lines in a
+// Trivia beside the program instead of dropping them.
+pub fn parse_with_trivia(source: &str, filename: &str) -> Result<(Program, Trivia), CompileError> {
+    let mut tokens = Vec::new();
+    let mut comments = VecDeque::new();
+    for token in Lexer::new(source, filename).with_comments() {
+        let SpannedToken { token, span } = token?;
+        match token {
+            Token::Comment(text) => comments.push_back(Comment {
+                text,
+                span,
+                block: source[span.offset..].starts_with(BLOCK_COMMENT_OPEN),
+            }),
+            token => tokens.push(SpannedToken { token, span }),
+        }
+    }
+
     let mut parser = Parser::new(tokens, filename);
-    parser.parse_program()
+    parser.trivia = Some(Collector {
+        source,
+        comments,
+        trivia: Trivia::default(),
+        line_owners: HashMap::new(),
+    });
+    let program = parser.run()?;
+    let mut collector = parser.trivia.take().unwrap();
+    let rest = collector.drain(&parser.tokens, usize::MAX);
+    collector.trivia.program.closing.extend(rest);
+    Ok((program, collector.trivia))
 }
 
 impl<'a> Parser<'a> {
-    fn new(tokens: Vec<Token>, filename: &'a str) -> Self {
+    fn new(tokens: Vec<SpannedToken>, filename: &'a str) -> Self {
         Self {
             tokens,
             position: 0,
             filename,
+            errors: Vec::new(),
+            depth: 0,
+            chain: 0,
+            too_deep: false,
+            trivia: None,
+        }
+    }
+
+    fn run(&mut self) -> Result<Program, CompileError> {
+        let result = self.parse_program();
+        if let Err(e) = &result {
+            self.record(e.clone());
+        }
+
+        let mut errors = std::mem::take(&mut self.errors).into_iter();
+        match errors.next() {
+            Some(mut first) => {
+                first.additional = errors.collect();
+                Err(first)
+            }
+            None => result,
         }
     }
 
     fn parse_program(&mut self) -> Result<Program, CompileError> {
-        // Parse functions before LOCK IN
+        // Parse constants and functions before LOCK IN
+        let mut consts = Vec::new();
         let mut functions = Vec::new();
-        while self.consume(Token::Tralalero)? {
-            functions.push(self.parse_function()?);
+        loop {
+            let result = if self.check(&Token::Tralalero) {
+                let opened = self.current_span();
+                self.start_node(Some(self.next_span().offset));
+                self.advance();
+                self.parse_function(opened).map(|f| functions.push(f))
+            } else if self.check(&Token::Based) {
+                self.start_node(Some(self.next_span().offset));
+                self.advance();
+                self.parse_const().map(|c| consts.push(c))
+            } else {
+                break;
+            };
+            if let Err(e) = result {
+                self.record(e);
+                while !matches!(
+                    self.current_token(),
+                    None | Some(Token::Eof | Token::Tralalero | Token::Based | Token::Lock)
+                ) {
+                    self.advance();
+                }
+            }
         }
 
         // Must start with LOCK IN
+        let opened = self.current_span();
+        self.start_node(None);
         if !self.consume(Token::Lock)? || !self.consume(Token::In)? {
             return Err(CompileError::new(
                 self.filename,
                 self.get_line(),
                 self.get_col(),
                 "program must start with LOCK IN",
-            ));
+            )
+            .with_help("functions and BASED constants come first, then the program between LOCK IN and ITS OVER"));
         }
 
         let mut statements = Vec::new();
 
         while !self.check(&Token::Its) {
-            statements.push(self.parse_statement()?);
+            if self.at_end() {
+                return Err(self.unterminated("LOCK IN", "ITS OVER", opened));
+            }
+            self.parse_block_statement(&mut statements);
         }
 
         // Must end with ITS OVER
@@ -115,6 +650,7 @@ impl<'a> Parser<'a> {
                 "program must end with ITS OVER",
             ));
         }
+        self.close_node(None);
 
         if !self.check(&Token::Eof) {
             return Err(CompileError::new(
@@ -126,13 +662,64 @@ impl<'a> Parser<'a> {
         }
 
         Ok(Program {
+            consts,
             functions,
             main_statements: statements,
         })
     }
 
-    fn parse_function(&mut self) -> Result<Function, CompileError> {
+    fn parse_const(&mut self) -> Result<Const, CompileError> {
+        // BASED <name> FR <number or string literal>
+        let span = self.current_span();
+        let name = match self.current_token().cloned() {
+            Some(Token::Identifier(n)) => {
+                self.advance();
+                n
+            }
+            Some(Token::Braincell(_)) => {
+                return Err(CompileError::new(
+                    self.filename,
+                    self.get_line(),
+                    self.get_col(),
+                    "constant name cannot be a braincell",
+                ));
+            }
+            _ => {
+                return Err(CompileError::new(
+                    self.filename,
+                    self.get_line(),
+                    self.get_col(),
+                    "expected constant name after BASED",
+                ));
+            }
+        };
+
+        if !self.consume(Token::Fr)? {
+            return Err(CompileError::new(
+                self.filename,
+                self.get_line(),
+                self.get_col(),
+                &format!("expected FR after constant name '{}'", name),
+            ));
+        }
+
+        let start = self.current_span();
+        let value = self.parse_expression()?;
+        if !matches!(value, Expr::Number(..) | Expr::String(..)) {
+            return Err(CompileError::new(
+                self.filename,
+                start.line,
+                start.col,
+                &format!("constant '{}' must be a number or string literal", name),
+            ));
+        }
+
+        Ok(Const { name, value, span })
+    }
+
+    fn parse_function(&mut self, opened: Span) -> Result<Function, CompileError> {
         // TRALALERO <name>(<params>) ... TRALALA
+        let span = self.current_span();
         let name = match self.current_token().cloned() {
             Some(Token::Identifier(n)) => {
                 self.advance();
@@ -161,6 +748,7 @@ impl<'a> Parser<'a> {
         let mut params = Vec::new();
         if !matches!(self.current_token(), Some(Token::RParen)) {
             loop {
+                let param_span = self.current_span();
                 let param_name = match self.current_token().cloned() {
                     Some(Token::Identifier(n)) => {
                         self.advance();
@@ -175,7 +763,24 @@ impl<'a> Parser<'a> {
                         ));
                     }
                 };
-                params.push(param_name);
+                if let Some(idx) = params.iter().position(|p: &Param| p.name == param_name) {
+                    // Not a syntax problem, so keep parsing the function.
+                    self.record(CompileError::new(
+                        self.filename,
+                        param_span.line,
+                        param_span.col,
+                        &format!(
+                            "duplicate parameter '{}' in function {} (first defined on line {}, col {})",
+                            param_name, name, params[idx].span.line, params[idx].span.col
+                        ),
+                    ));
+                }
+                let ty = self.parse_annotation()?;
+                params.push(Param {
+                    name: param_name,
+                    ty,
+                    span: param_span,
+                });
 
                 if self.consume(Token::Comma)? {
                     continue;
@@ -197,13 +802,24 @@ impl<'a> Parser<'a> {
         // Parse function body
         let mut body = Vec::new();
         while !self.consume(Token::Tralala)? {
-            body.push(self.parse_statement()?);
+            if self.at_end() {
+                let construct = format!("TRALALERO '{}'", name);
+                return Err(self.unterminated(&construct, "TRALALA", opened));
+            }
+            self.parse_block_statement(&mut body);
         }
+        self.close_node(Some(span.offset));
 
-        Ok(Function { name, params, body })
+        Ok(Function {
+            name,
+            params,
+            body,
+            span,
+        })
     }
 
     fn parse_statement(&mut self) -> Result<Statement, CompileError> {
+        let start = self.current_span();
         if self.consume(Token::Fanumtax)? {
             // FANUMTAX <var> FR <expr>
             let var_name = match self.current_token().cloned() {
@@ -226,17 +842,19 @@ impl<'a> Parser<'a> {
                 }
             };
 
+            let ty = self.parse_annotation()?;
             if !self.consume(Token::Fr)? {
                 return Err(CompileError::new(
                     self.filename,
                     self.get_line(),
                     self.get_col(),
                     "expected FR after variable",
-                ));
+                )
+                .with_help("assignments are written FANUMTAX name FR value"));
             }
 
             let expr = self.parse_expression()?;
-            Ok(Statement::Assign(var_name, expr))
+            Ok(Statement::Assign(var_name, ty, expr))
         } else if self.consume(Token::Diddle)? {
             // DIDDLE <dest> FR <source>
             let dest = match self.current_token().cloned() {
@@ -265,7 +883,8 @@ impl<'a> Parser<'a> {
                     self.get_line(),
                     self.get_col(),
                     "expected FR after variable",
-                ));
+                )
+                .with_help("copies are written DIDDLE name FR value"));
             }
 
             let source = self.parse_expression()?;
@@ -274,6 +893,10 @@ impl<'a> Parser<'a> {
             // SAY <expr>
             let expr = self.parse_expression()?;
             Ok(Statement::Print(expr))
+        } else if self.consume(Token::Yap)? {
+            // YAP <expr>
+            let expr = self.parse_expression()?;
+            Ok(Statement::PrintInline(expr))
         } else if self.consume(Token::Retreat)? {
             // RETREAT <expr>
             let expr = self.parse_expression()?;
@@ -281,44 +904,62 @@ impl<'a> Parser<'a> {
         } else if self.consume(Token::Youshallnotpass)? {
             // YOUSHALLNOTPASS
             Ok(Statement::Halt)
+        } else if self.consume(Token::Scream)? {
+            // SCREAM <expr>
+            let expr = self.parse_expression()?;
+            Ok(Statement::PrintError(expr))
+        } else if self.consume(Token::Crashout)? {
+            // CRASHOUT <expr>
+            let expr = self.parse_expression()?;
+            Ok(Statement::Throw(expr))
         } else if self.consume(Token::Ongod)? {
             // ONGOD <expr> ... (NO CAP ...)? DEADASS
-            self.parse_if()
+            self.parse_if(start)
         } else if self.consume(Token::Skibidi)? {
             // SKIBIDI <expr> ... RIZZUP
-            self.parse_while()
+            self.parse_while(start)
         } else {
             Err(CompileError::new(
                 self.filename,
                 self.get_line(),
                 self.get_col(),
                 "expected statement",
+            )
+            .with_help(
+                "statements start with FANUMTAX, DIDDLE, SAY, YAP, SCREAM, RETREAT, YOUSHALLNOTPASS, CRASHOUT, ONGOD or SKIBIDI",
             ))
         }
     }
 
-    fn parse_if(&mut self) -> Result<Statement, CompileError> {
+    fn parse_if(&mut self, opened: Span) -> Result<Statement, CompileError> {
         // ONGOD <expr> ... (NO CAP ...)? DEADASS
-        let condition = self.parse_expression()?;
+        let condition = self.parse_condition();
 
         let mut then_block = Vec::new();
         while !matches!(self.current_token(), Some(Token::No | Token::Deadass)) {
-            then_block.push(self.parse_statement()?);
+            if self.at_end() {
+                return Err(self.unterminated("ONGOD", "DEADASS", opened));
+            }
+            self.parse_block_statement(&mut then_block);
         }
 
         let else_block = if self.consume(Token::No)? {
             if !self.consume(Token::Cap)? {
-                return Err(CompileError::new(
+                self.record(CompileError::new(
                     self.filename,
                     self.get_line(),
                     self.get_col(),
                     "expected CAP after NO",
                 ));
             }
+            self.close_node(Some(opened.offset));
 
             let mut else_stmt = Vec::new();
             while !matches!(self.current_token(), Some(Token::Deadass)) {
-                else_stmt.push(self.parse_statement()?);
+                if self.at_end() {
+                    return Err(self.unterminated("ONGOD", "DEADASS", opened));
+                }
+                self.parse_block_statement(&mut else_stmt);
             }
             Some(else_stmt)
         } else {
@@ -333,6 +974,7 @@ impl<'a> Parser<'a> {
                 "expected DEADASS to close ONGOD block",
             ));
         }
+        self.close_node(Some(opened.offset));
 
         Ok(Statement::If {
             condition,
@@ -341,13 +983,16 @@ impl<'a> Parser<'a> {
         })
     }
 
-    fn parse_while(&mut self) -> Result<Statement, CompileError> {
+    fn parse_while(&mut self, opened: Span) -> Result<Statement, CompileError> {
         // SKIBIDI <expr> ... RIZZUP
-        let condition = self.parse_expression()?;
+        let condition = self.parse_condition();
 
         let mut body = Vec::new();
         while !matches!(self.current_token(), Some(Token::Rizzup)) {
-            body.push(self.parse_statement()?);
+            if self.at_end() {
+                return Err(self.unterminated("SKIBIDI", "RIZZUP", opened));
+            }
+            self.parse_block_statement(&mut body);
         }
 
         if !self.consume(Token::Rizzup)? {
@@ -358,15 +1003,145 @@ impl<'a> Parser<'a> {
                 "expected RIZZUP to close SKIBIDI block",
             ));
         }
+        self.close_node(Some(opened.offset));
 
         Ok(Statement::While { condition, body })
     }
 
+    // Parses one statement into `block`. On error, records it and skips to the
+    // next statement or block boundary so the rest of the file still parses.
+    fn parse_block_statement(&mut self, block: &mut Vec<SpannedStatement>) {
+        let start = self.position;
+        let span = self.current_span();
+        self.start_node(Some(span.offset));
+        match self.nested(Self::parse_statement) {
+            Ok(statement) => block.push(SpannedStatement { statement, span }),
+            Err(e) => {
+                self.record(e);
+                if self.position == start {
+                    self.advance();
+                }
+                self.synchronize();
+            }
+        }
+    }
+
+    // A broken ONGOD/SKIBIDI condition should not orphan the block's body and
+    // closer, so record the error and keep parsing the block.
+    fn parse_condition(&mut self) -> Expr {
+        match self.parse_expression() {
+            Ok(expr) => expr,
+            Err(e) => {
+                self.record(e);
+                self.synchronize();
+                Expr::Number(0.0, self.current_span())
+            }
+        }
+    }
+
+    fn synchronize(&mut self) {
+        while !matches!(
+            self.current_token(),
+            None | Some(
+                Token::Eof
+                    | Token::Fanumtax
+                    | Token::Diddle
+                    | Token::Say
+                    | Token::Yap
+                    | Token::Scream
+                    | Token::Retreat
+                    | Token::Youshallnotpass
+                    | Token::Crashout
+                    | Token::Ongod
+                    | Token::Skibidi
+                    | Token::No
+                    | Token::Deadass
+                    | Token::Rizzup
+                    | Token::Tralala
+                    | Token::Its
+            )
+        ) {
+            self.advance();
+        }
+    }
+
+    // Keeps one error per position; an unclosed inner block and the blocks
+    // around it would otherwise all report at end of file.
+    fn record(&mut self, error: CompileError) {
+        if !self.too_deep
+            && !self
+                .errors
+                .iter()
+                .any(|e| e.line == error.line && e.col == error.col)
+        {
+            self.errors.push(error);
+        }
+    }
+
     fn parse_expression(&mut self) -> Result<Expr, CompileError> {
         self.parse_binary_expression(0)
     }
 
+    // Runs `parse` one level deeper.
+    fn nested<T>(
+        &mut self,
+        parse: impl FnOnce(&mut Self) -> Result<T, CompileError>,
+    ) -> Result<T, CompileError> {
+        self.descend()?;
+        let result = parse(self);
+        self.depth -= 1;
+        result
+    }
+
+    // Past MAX_NESTING the rest of the file is skipped, so the enclosing
+    // blocks do not each report that they were never closed.
+    fn descend(&mut self) -> Result<(), CompileError> {
+        if self.depth >= MAX_NESTING {
+            let error = CompileError::new(
+                self.filename,
+                self.get_line(),
+                self.get_col(),
+                &format!("code nests more than {} levels deep", MAX_NESTING),
+            )
+            .with_help("move the inner part into a function");
+            self.record(error.clone());
+            self.too_deep = true;
+            self.position = self.tokens.len().saturating_sub(1);
+            return Err(error);
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    // Like `descend`, but counts toward MAX_CHAIN.
+    fn extend_chain(&mut self) -> Result<(), CompileError> {
+        if self.chain >= MAX_CHAIN {
+            let error = CompileError::new(
+                self.filename,
+                self.get_line(),
+                self.get_col(),
+                &format!("expression chains more than {} operators", MAX_CHAIN),
+            )
+            .with_help("split the expression across several variables");
+            self.record(error.clone());
+            self.too_deep = true;
+            self.position = self.tokens.len().saturating_sub(1);
+            return Err(error);
+        }
+        self.chain += 1;
+        Ok(())
+    }
+
     fn parse_binary_expression(&mut self, min_precedence: u8) -> Result<Expr, CompileError> {
+        let chain = self.chain;
+        let result = self.parse_binary_chain(min_precedence);
+        self.chain = chain;
+        result
+    }
+
+    // Each operator puts the expression so far one level deeper in the tree,
+    // so a long chain counts toward MAX_CHAIN.
+    fn parse_binary_chain(&mut self, min_precedence: u8) -> Result<Expr, CompileError> {
         let mut expr = self.parse_term()?;
 
         loop {
@@ -379,12 +1154,15 @@ impl<'a> Parser<'a> {
                 break;
             }
 
+            let span = self.current_span();
             self.advance();
+            self.extend_chain()?;
             let rhs = self.parse_binary_expression(precedence + 1)?;
             expr = Expr::Binary {
                 op,
                 left: Box::new(expr),
                 right: Box::new(rhs),
+                span,
             };
         }
 
@@ -393,35 +1171,36 @@ impl<'a> Parser<'a> {
 
     fn parse_term(&mut self) -> Result<Expr, CompileError> {
         let token = self.current_token().cloned();
+        let start = self.current_span();
         match token {
             Some(Token::Number(n)) => {
                 self.advance();
-                Ok(Expr::Number(n))
+                Ok(Expr::Number(n, start))
             }
             Some(Token::String(s)) => {
                 self.advance();
-                Ok(Expr::String(s))
+                Ok(Expr::String(s, start))
             }
             Some(Token::Braincell(idx)) => {
                 self.advance();
                 let names = ["aura", "peak", "goon", "mog", "npc", "sigma", "gyatt"];
-                Ok(Expr::Variable(names[idx as usize].to_string()))
+                Ok(Expr::Variable(names[idx as usize].to_string(), start))
             }
             Some(Token::Identifier(name)) => {
                 self.advance();
                 // Check if it's a built-in function call
-                if name == "TRANSFORM" || name == "RIZZED" {
-                    self.parse_function_call(&name)
+                if builtins::lookup(&name).is_some() {
+                    self.parse_function_call(&name, start)
                 } else if matches!(self.current_token(), Some(Token::LParen)) {
-                    self.parse_user_function_call(&name)
+                    self.parse_user_function_call(&name, start)
                 } else {
                     // It's a variable
-                    Ok(Expr::Variable(name.clone()))
+                    Ok(Expr::Variable(name.clone(), start))
                 }
             }
             Some(Token::Touchy) => {
                 self.advance();
-                self.parse_function_call("TOUCHY")
+                self.parse_function_call("TOUCHY", start)
             }
             Some(Token::Ring) => {
                 self.advance();
@@ -441,7 +1220,7 @@ impl<'a> Parser<'a> {
                             ));
                         }
                     };
-                    self.parse_user_function_call(&name)
+                    self.parse_user_function_call(&name, start)
                 } else {
                     Err(CompileError::new(
                         self.filename,
@@ -460,7 +1239,7 @@ impl<'a> Parser<'a> {
         }
     }
 
-    fn parse_user_function_call(&mut self, name: &str) -> Result<Expr, CompileError> {
+    fn parse_user_function_call(&mut self, name: &str, start: Span) -> Result<Expr, CompileError> {
         // Parse (args...)
         if !matches!(self.current_token(), Some(Token::LParen)) {
             return Err(CompileError::new(
@@ -472,35 +1251,16 @@ impl<'a> Parser<'a> {
         }
         self.advance();
 
-        let mut args = Vec::new();
-        if !matches!(self.current_token(), Some(Token::RParen)) {
-            loop {
-                args.push(self.parse_expression()?);
-
-                if self.consume(Token::Comma)? {
-                    continue;
-                }
-                if self.consume(Token::RParen)? {
-                    break;
-                }
-                return Err(CompileError::new(
-                    self.filename,
-                    self.get_line(),
-                    self.get_col(),
-                    "expected ',' or ')' in argument list",
-                ));
-            }
-        } else {
-            self.advance(); // consume RParen
-        }
+        let args = self.parse_call_args()?;
 
         Ok(Expr::UserFunctionCall {
             name: name.to_string(),
             args,
+            span: start,
         })
     }
 
-    fn parse_function_call(&mut self, name: &str) -> Result<Expr, CompileError> {
+    fn parse_function_call(&mut self, name: &str, start: Span) -> Result<Expr, CompileError> {
         // Expect opening parenthesis
         if !matches!(self.current_token(), Some(Token::LParen)) {
             return Err(CompileError::new(
@@ -512,37 +1272,112 @@ impl<'a> Parser<'a> {
         }
         self.advance();
 
-        // Parse optional argument
-        let arg = if matches!(self.current_token(), Some(Token::RParen)) {
-            None
-        } else {
-            let expr = self.parse_expression()?;
-            Some(Box::new(expr))
-        };
+        let args = self.parse_call_args()?;
 
-        // Expect closing parenthesis
-        if !matches!(self.current_token(), Some(Token::RParen)) {
+        // TOUCHY takes an optional prompt and timeout; everything else has a
+        // fixed arity.
+        let arity_error = match builtins::lookup(name) {
+            Some(builtin) if args.len() != builtin.arity => Some(format!(
+                "{} expects {} argument{}, got {}",
+                name,
+                builtin.arity,
+                if builtin.arity == 1 { "" } else { "s" },
+                args.len()
+            )),
+            None if args.len() > 2 => Some(format!(
+                "{} expects 0 to 2 arguments, got {}",
+                name,
+                args.len()
+            )),
+            _ => None,
+        };
+        if let Some(message) = arity_error {
             return Err(CompileError::new(
                 self.filename,
-                self.get_line(),
-                self.get_col(),
-                &format!("expected ')' after {} argument", name),
+                start.line,
+                start.col,
+                &message,
             ));
         }
-        self.advance();
 
         Ok(Expr::FunctionCall {
             name: name.to_string(),
-            arg,
+            args,
+            span: start,
         })
     }
 
+    // Parses an optional `: number` or `: string` after a name.
+    fn parse_annotation(&mut self) -> Result<Option<Type>, CompileError> {
+        if !self.consume(Token::Colon)? {
+            return Ok(None);
+        }
+        match self.current_token().cloned() {
+            Some(Token::Identifier(n)) if n == "number" || n == "string" => {
+                self.advance();
+                Ok(Some(if n == "number" {
+                    Type::Number
+                } else {
+                    Type::String
+                }))
+            }
+            Some(Token::Identifier(n)) => Err(CompileError::new(
+                self.filename,
+                self.get_line(),
+                self.get_col(),
+                &format!("unknown type '{}'", n),
+            )
+            .with_help("the types are number and string")),
+            _ => Err(CompileError::new(
+                self.filename,
+                self.get_line(),
+                self.get_col(),
+                "expected type after ':'",
+            )
+            .with_help("the types are number and string")),
+        }
+    }
+
+    // Parses `arg, arg, ...)` after the opening parenthesis.
+    fn parse_call_args(&mut self) -> Result<Vec<Expr>, CompileError> {
+        let mut args = Vec::new();
+        if self.consume(Token::RParen)? {
+            return Ok(args);
+        }
+
+        loop {
+            args.push(self.nested(Self::parse_expression)?);
+
+            if self.consume(Token::Comma)? {
+                if self.check(&Token::RParen) {
+                    return Err(CompileError::new(
+                        self.filename,
+                        self.get_line(),
+                        self.get_col(),
+                        "trailing comma in argument list",
+                    ));
+                }
+                continue;
+            }
+            if self.consume(Token::RParen)? {
+                return Ok(args);
+            }
+            return Err(CompileError::new(
+                self.filename,
+                self.get_line(),
+                self.get_col(),
+                "expected ',' or ')' in argument list",
+            ));
+        }
+    }
+
     fn current_binary_op(&self) -> Option<(BinaryOp, u8)> {
         match self.current_token()? {
             Token::Add => Some((BinaryOp::Add, 1)),           // 💀
             Token::Subtract => Some((BinaryOp::Subtract, 1)), // 😭
             Token::Multiply => Some((BinaryOp::Multiply, 2)), // 😏
             Token::Divide => Some((BinaryOp::Divide, 2)),     // 🚡
+            Token::Equal => Some((BinaryOp::Equal, 0)),       // 🟰
             _ => None,
         }
     }
@@ -557,13 +1392,16 @@ impl<'a> Parser<'a> {
     }
 
     fn check(&self, expected: &Token) -> bool {
-        self.current_token().map_or(false, |t| {
-            std::mem::discriminant(t) == std::mem::discriminant(expected)
-        })
+        self.current_token()
+            .is_some_and(|t| std::mem::discriminant(t) == std::mem::discriminant(expected))
     }
 
     fn current_token(&self) -> Option<&Token> {
-        self.tokens.get(self.position)
+        self.tokens.get(self.position).map(|t| &t.token)
+    }
+
+    fn at_end(&self) -> bool {
+        matches!(self.current_token(), None | Some(Token::Eof))
     }
 
     fn advance(&mut self) {
@@ -572,11 +1410,67 @@ impl<'a> Parser<'a> {
         }
     }
 
+    fn unterminated(&self, construct: &str, closer: &str, opened: Span) -> CompileError {
+        CompileError::new(
+            self.filename,
+            self.get_line(),
+            self.get_col(),
+            &format!(
+                "unterminated {} block opened on line {}: expected {}",
+                construct, opened.line, closer
+            ),
+        )
+        .with_help(&format!("add {} to close the block", closer))
+    }
+
+    // Attaches the comments above the node starting at the current token,
+    // and makes it the owner of comments after code on this line.
+    fn start_node(&mut self, key: Option<usize>) {
+        let span = self.current_span();
+        let Some(collector) = self.trivia.as_mut() else {
+            return;
+        };
+        let leading = collector.drain(&self.tokens, span.offset);
+        let first = leading.first().map_or(span.offset, |c| c.span.offset);
+        let blank_lines = collector.blank_lines_before(first);
+        collector.line_owners.insert(span.line, key);
+        let node = collector.trivia.node(key);
+        node.blank_lines = blank_lines;
+        node.leading = leading;
+    }
+
+    // Called just after a node's NO CAP or closing keyword.
+    fn close_node(&mut self, key: Option<usize>) {
+        let Some(collector) = self.trivia.as_mut() else {
+            return;
+        };
+        let Some(closer) = self.position.checked_sub(1).map(|i| self.tokens[i].span) else {
+            return;
+        };
+        let closing = collector.drain(&self.tokens, closer.offset);
+        collector.trivia.node(key).closing.extend(closing);
+        collector.line_owners.insert(closer.line, key);
+    }
+
+    fn next_span(&self) -> Span {
+        self.tokens
+            .get(self.position + 1)
+            .map_or_else(|| self.current_span(), |t| t.span)
+    }
+
+    fn current_span(&self) -> Span {
+        // Past the end, report the last token (normally Eof).
+        self.tokens
+            .get(self.position)
+            .or_else(|| self.tokens.last())
+            .map_or_else(Span::default, |t| t.span)
+    }
+
     fn get_line(&self) -> usize {
-        1 // Simplified for now
+        self.current_span().line
     }
 
     fn get_col(&self) -> usize {
-        1 // Simplified for now
+        self.current_span().col
     }
 }
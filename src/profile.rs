@@ -0,0 +1,363 @@
+// Run profiles for profile-guided compiles: `brvm exec --profile-out` writes
+// one as JSON and `brvm compile --profile-use` reads it back. A branch is
+// named by the function it sits in ("<main>" for main code) and its position
+// among that function's conditional jumps. The plain layout emits those in
+// source order, which is how the compiler counts them, so profiles should be
+// recorded from bytecode compiled without a profile.
+use crate::bytecode;
+use crate::lexdump::json_string;
+use crate::opcode;
+use crate::vm::RunResult;
+use std::collections::HashMap;
+use std::fmt::Write;
+
+pub const MAIN: &str = "<main>";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Profile {
+    pub functions: Vec<FunctionProfile>,
+    pub branches: Vec<BranchProfile>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FunctionProfile {
+    pub name: String,
+    pub calls: u64,
+    pub instructions: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BranchProfile {
+    pub function: String,
+    pub index: usize,
+    pub truthy: u64,
+    pub falsy: u64,
+}
+
+impl Profile {
+    // Builds a profile from a run made with `VmOptions::stats`.
+    pub fn from_run(bytecode: &[u8], result: &RunResult) -> Result<Self, String> {
+        let module = bytecode::decode(bytecode)?;
+        let mut starts: Vec<(usize, &str)> = module
+            .functions
+            .iter()
+            .map(|f| (f.code_offset as usize, module.function_name(f)))
+            .collect();
+        starts.push((0, MAIN));
+        starts.sort_by_key(|&(offset, _)| offset);
+
+        // Offset of every conditional jump -> (function, index)
+        let mut jumps = HashMap::new();
+        for (i, &(start, name)) in starts.iter().enumerate() {
+            let end = starts.get(i + 1).map_or(module.code.len(), |s| s.0);
+            let mut index = 0;
+            let mut pos = start;
+            while pos < end {
+                let op = module.code[pos];
+                let Some((_, width)) = opcode::lookup(op) else {
+                    break;
+                };
+                if matches!(op, 0x0C | 0x1C) {
+                    jumps.insert(pos, (name, index));
+                    index += 1;
+                }
+                pos += 1 + width;
+            }
+        }
+
+        let functions = result
+            .function_stats
+            .iter()
+            .map(|f| FunctionProfile {
+                name: f.name.clone(),
+                calls: f.calls,
+                instructions: f.instructions,
+            })
+            .collect();
+        let mut branches: Vec<BranchProfile> = result
+            .branch_stats
+            .iter()
+            .filter_map(|b| {
+                let &(function, index) = jumps.get(&b.offset)?;
+                Some(BranchProfile {
+                    function: function.to_string(),
+                    index,
+                    truthy: b.truthy,
+                    falsy: b.falsy,
+                })
+            })
+            .collect();
+        branches.sort_by(|a, b| (&a.function, a.index).cmp(&(&b.function, b.index)));
+        Ok(Self {
+            functions,
+            branches,
+        })
+    }
+
+    pub fn calls(&self, function: &str) -> u64 {
+        self.functions
+            .iter()
+            .find(|f| f.name == function)
+            .map_or(0, |f| f.calls)
+    }
+
+    pub fn branch(&self, function: &str, index: usize) -> Option<&BranchProfile> {
+        self.branches
+            .iter()
+            .find(|b| b.function == function && b.index == index)
+    }
+
+    pub fn to_json(&self) -> String {
+        let mut out = String::from("{\n  \"functions\": [");
+        for (i, f) in self.functions.iter().enumerate() {
+            let _ = write!(
+                out,
+                "{}\n    {{\"name\": {}, \"calls\": {}, \"instructions\": {}}}",
+                if i == 0 { "" } else { "," },
+                json_string(&f.name),
+                f.calls,
+                f.instructions
+            );
+        }
+        out.push_str(if self.functions.is_empty() {
+            "],\n"
+        } else {
+            "\n  ],\n"
+        });
+        out.push_str("  \"branches\": [");
+        for (i, b) in self.branches.iter().enumerate() {
+            let _ = write!(
+                out,
+                "{}\n    {{\"function\": {}, \"index\": {}, \"truthy\": {}, \"falsy\": {}}}",
+                if i == 0 { "" } else { "," },
+                json_string(&b.function),
+                b.index,
+                b.truthy,
+                b.falsy
+            );
+        }
+        out.push_str(if self.branches.is_empty() {
+            "]\n}\n"
+        } else {
+            "\n  ]\n}\n"
+        });
+        out
+    }
+
+    // Reads what `to_json` writes. Unknown keys are ignored so profiles can
+    // grow fields without breaking older compilers.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut parser = Parser {
+            text: text.as_bytes(),
+            pos: 0,
+        };
+        let root = parser.value()?;
+        parser.skip_space();
+        if parser.pos < parser.text.len() {
+            return Err(parser.error("unexpected text after the profile"));
+        }
+
+        let mut profile = Self::default();
+        for f in root.field("functions")?.array("functions")? {
+            profile.functions.push(FunctionProfile {
+                name: f.field("name")?.string("name")?,
+                calls: f.field("calls")?.count("calls")?,
+                instructions: f.field("instructions")?.count("instructions")?,
+            });
+        }
+        for b in root.field("branches")?.array("branches")? {
+            profile.branches.push(BranchProfile {
+                function: b.field("function")?.string("function")?,
+                index: b.field("index")?.count("index")? as usize,
+                truthy: b.field("truthy")?.count("truthy")?,
+                falsy: b.field("falsy")?.count("falsy")?,
+            });
+        }
+        Ok(profile)
+    }
+}
+
+enum Json {
+    Null,
+    Bool,
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    fn field(&self, key: &str) -> Result<&Json, String> {
+        match self {
+            Json::Object(fields) => fields
+                .iter()
+                .find(|(k, _)| k == key)
+                .map(|(_, v)| v)
+                .ok_or_else(|| format!("profile: missing \"{}\"", key)),
+            _ => Err(format!("profile: expected an object around \"{}\"", key)),
+        }
+    }
+
+    fn array(&self, key: &str) -> Result<&[Json], String> {
+        match self {
+            Json::Array(items) => Ok(items),
+            _ => Err(format!("profile: \"{}\" must be an array", key)),
+        }
+    }
+
+    fn string(&self, key: &str) -> Result<String, String> {
+        match self {
+            Json::String(s) => Ok(s.clone()),
+            _ => Err(format!("profile: \"{}\" must be a string", key)),
+        }
+    }
+
+    fn count(&self, key: &str) -> Result<u64, String> {
+        match self {
+            Json::Number(n) if *n >= 0.0 && n.fract() == 0.0 => Ok(*n as u64),
+            _ => Err(format!("profile: \"{}\" must be a whole number", key)),
+        }
+    }
+}
+
+struct Parser<'a> {
+    text: &'a [u8],
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: &str) -> String {
+        format!("profile: {} at byte {}", message, self.pos)
+    }
+
+    fn skip_space(&mut self) {
+        while self
+            .text
+            .get(self.pos)
+            .is_some_and(|b| b.is_ascii_whitespace())
+        {
+            self.pos += 1;
+        }
+    }
+
+    fn eat(&mut self, byte: u8) -> bool {
+        self.skip_space();
+        if self.text.get(self.pos) == Some(&byte) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn value(&mut self) -> Result<Json, String> {
+        self.skip_space();
+        match self.text.get(self.pos) {
+            Some(b'{') => {
+                self.pos += 1;
+                let mut fields = Vec::new();
+                if !self.eat(b'}') {
+                    loop {
+                        self.skip_space();
+                        let key = self.string()?;
+                        if !self.eat(b':') {
+                            return Err(self.error("expected ':'"));
+                        }
+                        fields.push((key, self.value()?));
+                        if self.eat(b'}') {
+                            break;
+                        }
+                        if !self.eat(b',') {
+                            return Err(self.error("expected ',' or '}'"));
+                        }
+                    }
+                }
+                Ok(Json::Object(fields))
+            }
+            Some(b'[') => {
+                self.pos += 1;
+                let mut items = Vec::new();
+                if !self.eat(b']') {
+                    loop {
+                        items.push(self.value()?);
+                        if self.eat(b']') {
+                            break;
+                        }
+                        if !self.eat(b',') {
+                            return Err(self.error("expected ',' or ']'"));
+                        }
+                    }
+                }
+                Ok(Json::Array(items))
+            }
+            Some(b'"') => self.string().map(Json::String),
+            Some(_) => {
+                let start = self.pos;
+                while self
+                    .text
+                    .get(self.pos)
+                    .is_some_and(|b| b.is_ascii_alphanumeric() || b"+-.".contains(b))
+                {
+                    self.pos += 1;
+                }
+                match &self.text[start..self.pos] {
+                    b"null" => Ok(Json::Null),
+                    b"true" | b"false" => Ok(Json::Bool),
+                    word => std::str::from_utf8(word)
+                        .ok()
+                        .and_then(|w| w.parse().ok())
+                        .map(Json::Number)
+                        .ok_or_else(|| {
+                            self.pos = start;
+                            self.error("expected a value")
+                        }),
+                }
+            }
+            None => Err(self.error("unexpected end of input")),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        if self.text.get(self.pos) != Some(&b'"') {
+            return Err(self.error("expected a string"));
+        }
+        self.pos += 1;
+        let mut out = Vec::new();
+        loop {
+            let Some(&byte) = self.text.get(self.pos) else {
+                return Err(self.error("unterminated string"));
+            };
+            self.pos += 1;
+            match byte {
+                b'"' => break,
+                b'\\' => {
+                    let escaped = self.text.get(self.pos).copied();
+                    self.pos += 1;
+                    let c = match escaped {
+                        Some(b'"') => '"',
+                        Some(b'\\') => '\\',
+                        Some(b'/') => '/',
+                        Some(b'n') => '\n',
+                        Some(b'r') => '\r',
+                        Some(b't') => '\t',
+                        Some(b'u') => {
+                            let hex = self
+                                .text
+                                .get(self.pos..self.pos + 4)
+                                .and_then(|h| std::str::from_utf8(h).ok())
+                                .and_then(|h| u32::from_str_radix(h, 16).ok())
+                                .and_then(char::from_u32)
+                                .ok_or_else(|| self.error("bad \\u escape"))?;
+                            self.pos += 4;
+                            hex
+                        }
+                        _ => return Err(self.error("bad escape")),
+                    };
+                    out.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                }
+                _ => out.push(byte),
+            }
+        }
+        String::from_utf8(out).map_err(|_| self.error("string is not UTF-8"))
+    }
+}
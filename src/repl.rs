@@ -0,0 +1,244 @@
+// Interactive session behind `brvm repl`.
+//
+// Each entry is either a function definition, which is kept for later
+// entries, or statements, which are wrapped in `LOCK IN` / `ITS OVER` and run
+// straight away. Braincells carry over from one entry to the next, so the
+// session behaves like one long program typed a piece at a time.
+//
+// Lines starting with `:` are meta-commands for looking at what the session
+// compiled; see `Session::command`.
+use crate::builtins::BUILTINS;
+use crate::compiler::{self, CompileOptions};
+use crate::disasm;
+use crate::error::CompileError;
+use crate::host::VmHost;
+use crate::lexer::{self, Lexer, Token, BRAINCELLS, KEYWORDS};
+use crate::parser;
+use crate::value::Value;
+use crate::vm::{self, Globals, VmOptions};
+use std::error::Error;
+use std::fmt::Write as _;
+
+pub const FILENAME: &str = "<repl>";
+
+const COMMANDS: &[&str] = &[":dis", ":ast", ":vars", ":load", ":reset"];
+
+#[derive(Default)]
+pub struct Session {
+    functions: String, // source of every definition entered so far
+    globals: Globals,
+    last: Option<(String, Vec<u8>)>, // source and bytecode of the last compile
+    pub options: VmOptions,
+}
+
+impl Session {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Compile errors come back with lines counted from the start of `input`.
+    pub fn eval<H: VmHost>(&mut self, input: &str, host: &mut H) -> Result<(), Box<dyn Error>> {
+        let first = Lexer::new(input, FILENAME).next().transpose()?;
+        let defines = matches!(
+            first.map(|t| t.token),
+            Some(Token::Tralalero | Token::Based)
+        );
+        let (source, offset) = if defines {
+            (
+                format!("{}{}\nLOCK IN\nITS OVER\n", self.functions, input),
+                self.functions.lines().count(),
+            )
+        } else {
+            (
+                format!("{}LOCK IN\n{}\nITS OVER\n", self.functions, input),
+                self.functions.lines().count() + 1,
+            )
+        };
+
+        let bytecode = self.compile(source, FILENAME, offset)?;
+        if defines {
+            self.define(input);
+            return Ok(());
+        }
+        vm::execute_with_globals(&bytecode, host, &self.options, &mut self.globals)?;
+        Ok(())
+    }
+
+    // Runs a `:` line (without the colon) and returns the text to show.
+    pub fn command<H: VmHost>(
+        &mut self,
+        line: &str,
+        host: &mut H,
+    ) -> Result<String, Box<dyn Error>> {
+        let (name, arg) = line
+            .trim()
+            .split_once(char::is_whitespace)
+            .map_or((line.trim(), ""), |(name, arg)| (name, arg.trim()));
+        match (name, arg) {
+            ("dis", "") => {
+                let (_, bytecode) = self.last.as_ref().ok_or("nothing compiled yet")?;
+                Ok(disasm::disassemble(bytecode)?)
+            }
+            ("ast", "") => {
+                let (source, _) = self.last.as_ref().ok_or("nothing compiled yet")?;
+                let tokens = lexer::tokenize_spanned(source, FILENAME)?;
+                Ok(format!("{:#?}\n", parser::parse(tokens, FILENAME)?))
+            }
+            ("vars", "") => {
+                let mut out = String::new();
+                for &(name, index) in BRAINCELLS {
+                    match self.globals.get(index as usize).and_then(Option::as_ref) {
+                        Some(Value::String(s)) => writeln!(out, "{} = {:?}", name, s),
+                        Some(value) => writeln!(out, "{} = {}", name, value),
+                        None => writeln!(out, "{} is unset", name),
+                    }?;
+                }
+                Ok(out)
+            }
+            ("load", path) if !path.is_empty() => {
+                self.load(path, host)?;
+                Ok(String::new())
+            }
+            ("reset", "") => {
+                *self = Self {
+                    options: self.options.clone(),
+                    ..Self::default()
+                };
+                Ok(String::new())
+            }
+            _ => Err(format!(
+                "unknown command ':{}' (expected :dis, :ast, :vars, :load <file> or :reset)",
+                line.trim()
+            )
+            .into()),
+        }
+    }
+
+    // Runs a whole program file. Its functions and constants stay defined
+    // for later entries; errors point into the file.
+    fn load<H: VmHost>(&mut self, path: &str, host: &mut H) -> Result<(), Box<dyn Error>> {
+        let source = std::fs::read_to_string(path)
+            .map_err(|_| CompileError::new(path, 0, 0, "failed to read file"))?;
+        let main = find_lock(&source, path)?.unwrap_or(source.len());
+
+        let offset = self.functions.lines().count();
+        let bytecode = self.compile(format!("{}{}", self.functions, source), path, offset)?;
+        self.define(&source[..main]);
+        vm::execute_with_globals(&bytecode, host, &self.options, &mut self.globals)?;
+        Ok(())
+    }
+
+    // Tab completion for the word ending at byte `pos`: keywords, braincells,
+    // built-ins and the functions and constants defined so far, or a
+    // meta-command at the start of the line. Returns where the word starts
+    // and the candidates, matched case-insensitively.
+    pub fn complete(&self, line: &str, pos: usize) -> (usize, Vec<String>) {
+        let start = line[..pos]
+            .char_indices()
+            .rev()
+            .take_while(|&(_, c)| c.is_alphanumeric() || c == '_')
+            .last()
+            .map_or(pos, |(i, _)| i);
+        let start = match line[..start].strip_suffix(':') {
+            Some(before) if before.trim().is_empty() => start - 1,
+            _ => start,
+        };
+        let word = line[start..pos].to_lowercase();
+
+        let mut names: Vec<String> = if word.starts_with(':') {
+            COMMANDS.iter().map(|c| c.to_string()).collect()
+        } else {
+            let defined = lexer::tokenize(&self.functions, FILENAME).unwrap_or_default();
+            let defined = defined.windows(2).filter_map(|pair| match pair {
+                [Token::Tralalero | Token::Based, Token::Identifier(name)] => Some(name.clone()),
+                _ => None,
+            });
+            KEYWORDS
+                .iter()
+                .map(|(k, _)| k.to_string())
+                .chain(BRAINCELLS.iter().map(|(b, _)| b.to_string()))
+                .chain(BUILTINS.iter().map(|b| b.name.to_string()))
+                .chain(defined)
+                .collect()
+        };
+        names.retain(|name| name.to_lowercase().starts_with(&word));
+        names.sort();
+        names.dedup();
+        (start, names)
+    }
+
+    fn compile(
+        &mut self,
+        source: String,
+        filename: &str,
+        offset: usize,
+    ) -> Result<Vec<u8>, CompileError> {
+        let bytecode = compiler::compile_source(&source, filename, &CompileOptions::default())
+            .map_err(|mut e| {
+                shift_lines(&mut e, offset);
+                e
+            })?;
+        self.last = Some((source, bytecode.clone()));
+        Ok(bytecode)
+    }
+
+    fn define(&mut self, definitions: &str) {
+        let definitions = definitions.trim_end();
+        if !definitions.is_empty() {
+            self.functions.push_str(definitions);
+            self.functions.push('\n');
+        }
+    }
+}
+
+// True while `source` has a TRALALERO, ONGOD or SKIBIDI block that has not
+// been closed yet, so the REPL should keep reading lines. Source that does
+// not lex is complete: the error is reported when it is evaluated.
+pub fn is_incomplete(source: &str) -> bool {
+    let mut depth = 0i32;
+    for token in Lexer::new(source, FILENAME) {
+        let Ok(token) = token else {
+            return false;
+        };
+        match token.token {
+            Token::Tralalero | Token::Ongod | Token::Skibidi => depth += 1,
+            Token::Tralala | Token::Deadass | Token::Rizzup => depth -= 1,
+            _ => {}
+        }
+    }
+    depth > 0
+}
+
+// Compiles the code given to `brvm eval`. Code without `LOCK IN` is taken to
+// be statements and wrapped like a REPL entry; errors still count lines from
+// the start of `code`.
+pub fn compile_snippet(code: &str, filename: &str) -> Result<Vec<u8>, CompileError> {
+    if find_lock(code, filename)?.is_some() {
+        return compiler::compile_source(code, filename, &CompileOptions::default());
+    }
+    let source = format!("LOCK IN\n{}\nITS OVER\n", code);
+    compiler::compile_source(&source, filename, &CompileOptions::default()).map_err(|mut e| {
+        shift_lines(&mut e, 1);
+        e
+    })
+}
+
+// The byte offset of `LOCK IN`, lexing no further than that.
+fn find_lock(source: &str, filename: &str) -> Result<Option<usize>, CompileError> {
+    for token in Lexer::new(source, filename) {
+        let token = token?;
+        if token.token == Token::Lock {
+            return Ok(Some(token.span.offset));
+        }
+    }
+    Ok(None)
+}
+
+fn shift_lines(error: &mut CompileError, offset: usize) {
+    if error.line > offset {
+        error.line -= offset;
+    }
+    for error in &mut error.additional {
+        shift_lines(error, offset);
+    }
+}
@@ -0,0 +1,56 @@
+// Deterministic xorshift64* generator used by the RANDOM built-in.
+//
+// The output sequence for a given seed is part of the observable behavior of
+// programs run with `--seed`, so changes here must keep the known-sequence
+// tests in tests/language_smoke.rs passing.
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        let mut rng = Self { state: 0 };
+        rng.reseed(seed);
+        rng
+    }
+
+    // wasm32-unknown-unknown has no clock; embedders there should pass a seed.
+    #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+    pub fn from_time() -> Self {
+        Self::new(0)
+    }
+
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    pub fn from_time() -> Self {
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0);
+        Self::new(nanos)
+    }
+
+    pub fn reseed(&mut self, seed: u64) {
+        // SplitMix64 scramble so small or similar seeds still start far apart.
+        let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+
+        // xorshift has a fixed point at zero
+        self.state = if z == 0 { 0x9E37_79B9_7F4A_7C15 } else { z };
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let mut x = self.state;
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        self.state = x;
+        x.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    pub fn below(&mut self, bound: u64) -> u64 {
+        ((self.next_u64() as u128 * bound as u128) >> 64) as u64
+    }
+}
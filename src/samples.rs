@@ -0,0 +1,98 @@
+// Sample programs behind `brvm examples`. Each is a template embedded in the
+// binary; `{{file}}` becomes the path it is installed at and `{{stdin}}` the
+// path of its input file. Installing also writes the `.stdin` answers for
+// programs that read input and a blessed `.expected` file, so `brvm test`
+// passes on the new folder straight away.
+use crate::golden::{self, Outcome};
+use std::path::{Path, PathBuf};
+
+pub struct Sample {
+    pub name: &'static str,
+    pub description: &'static str,
+    template: &'static str,
+    stdin: Option<&'static str>, // answers for TOUCHY, for `brvm test`
+}
+
+pub const SAMPLES: &[Sample] = &[
+    Sample {
+        name: "hello",
+        description: "printing, braincells and joining text",
+        template: include_str!("samples/hello.br"),
+        stdin: None,
+    },
+    Sample {
+        name: "loops",
+        description: "a countdown and FizzBuzz with SKIBIDI",
+        template: include_str!("samples/loops.br"),
+        stdin: None,
+    },
+    Sample {
+        name: "functions",
+        description: "recursion, tail calls, type annotations and BIG",
+        template: include_str!("samples/functions.br"),
+        stdin: None,
+    },
+    Sample {
+        name: "input",
+        description: "reading lines with TOUCHY and numbers with TRYTRANSFORM",
+        template: include_str!("samples/input.br"),
+        stdin: Some(include_str!("samples/input.stdin")),
+    },
+    Sample {
+        name: "guess",
+        description: "a number guessing game",
+        template: include_str!("samples/guess.br"),
+        stdin: Some(include_str!("samples/guess.stdin")),
+    },
+    Sample {
+        name: "rps",
+        description: "rock, paper, scissors against the computer",
+        template: include_str!("samples/rps.br"),
+        stdin: Some(include_str!("samples/rps.stdin")),
+    },
+];
+
+impl Sample {
+    pub fn file_name(&self) -> String {
+        format!("{}.br", self.name)
+    }
+
+    // The program as installed at `path`.
+    pub fn render(&self, path: &Path) -> String {
+        self.template
+            .replace("{{file}}", &path.display().to_string())
+            .replace(
+                "{{stdin}}",
+                &path.with_extension("stdin").display().to_string(),
+            )
+    }
+}
+
+// Writes every sample into `dir`, creating it if needed, and returns each
+// program's path with whether it was written. A program that is already
+// there is left alone, along with its other files, unless `force` is set.
+pub fn install(dir: &Path, force: bool) -> Result<Vec<(PathBuf, bool)>, String> {
+    std::fs::create_dir_all(dir)
+        .map_err(|e| format!("failed to create {}: {}", dir.display(), e))?;
+    let write = |path: &Path, text: &str| {
+        std::fs::write(path, text).map_err(|e| format!("failed to write {}: {}", path.display(), e))
+    };
+
+    let mut installed = Vec::new();
+    for sample in SAMPLES {
+        let path = dir.join(sample.file_name());
+        if path.exists() && !force {
+            installed.push((path, false));
+            continue;
+        }
+        write(&path, &sample.render(&path))?;
+        if let Some(stdin) = sample.stdin {
+            write(&path.with_extension("stdin"), stdin)?;
+        }
+        if let Outcome::Error(e) = golden::bless_case(&path) {
+            return Err(e);
+        }
+        installed.push((path, true));
+    }
+    Ok(installed)
+}
@@ -0,0 +1,256 @@
+// Playground HTTP server behind `brvm serve`.
+//
+// `POST /run` takes `{"source": "...", "stdin": "..."}`, compiles and runs the
+// program in memory, and answers with JSON holding the captured output and
+// any diagnostic. Every run gets an instruction budget, a string-memory
+// budget and an output cap, and only so many connections are served at once,
+// so runaway programs cannot take the machine down. `GET /` serves a one-page
+// editor that talks to `/run`.
+use crate::compiler::{self, CompileOptions};
+use crate::host::CaptureHost;
+use crate::json::{self, Json};
+use crate::lexdump::json_string;
+use crate::vm::{self, VmOptions};
+use std::fmt::Write as _;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+const PAGE: &str = include_str!("serve_page.html");
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+const REFUSE_LINGER: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone, Copy)]
+pub struct Limits {
+    pub max_instructions: u64,
+    pub max_string_bytes: u64,
+    pub max_output_bytes: usize,
+    pub max_request_bytes: usize,
+    pub max_connections: usize, // handled at once; the rest get 503
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_instructions: 10_000_000,
+            max_string_bytes: 16 * 1024 * 1024,
+            max_output_bytes: 1024 * 1024,
+            max_request_bytes: 256 * 1024,
+            max_connections: 8,
+        }
+    }
+}
+
+pub fn serve(listener: TcpListener, limits: Limits) -> io::Result<()> {
+    let active = Arc::new(AtomicUsize::new(0));
+    for stream in listener.incoming() {
+        let mut stream = match stream {
+            Ok(stream) => stream,
+            Err(_) => continue,
+        };
+        if active.fetch_add(1, Ordering::SeqCst) >= limits.max_connections {
+            active.fetch_sub(1, Ordering::SeqCst);
+            let _ = refuse(&mut stream);
+            continue;
+        }
+        let slot = Slot(active.clone());
+        std::thread::spawn(move || {
+            let _ = handle_connection(stream, &limits);
+            drop(slot);
+        });
+    }
+    Ok(())
+}
+
+// Answers 503 from the accepting thread. Closing with the request still
+// unread would reset the connection and could lose the answer, so whatever
+// the client sends next is drained for a moment first.
+fn refuse(stream: &mut TcpStream) -> io::Result<()> {
+    respond(
+        stream,
+        503,
+        "application/json",
+        &error_json("server is busy, try again later"),
+    )?;
+    stream.shutdown(std::net::Shutdown::Write)?;
+    stream.set_read_timeout(Some(REFUSE_LINGER))?;
+    io::copy(&mut Read::by_ref(stream).take(64 * 1024), &mut io::sink())?;
+    Ok(())
+}
+
+// One connection's share of `max_connections`, given back when the handler
+// ends, even by panicking.
+struct Slot(Arc<AtomicUsize>);
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+fn handle_connection(stream: TcpStream, limits: &Limits) -> io::Result<()> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = stream;
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or("");
+    let path = parts.next().unwrap_or("");
+
+    let mut content_length = 0;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header.trim_end().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().unwrap_or(0);
+            }
+        }
+    }
+
+    let (status, content_type, body) = match (method, path) {
+        ("GET", "/") => (200, "text/html; charset=utf-8", PAGE.to_string()),
+        ("POST", "/run") if content_length > limits.max_request_bytes => (
+            413,
+            "application/json",
+            error_json("request body is too large"),
+        ),
+        ("POST", "/run") => {
+            let mut body = vec![0; content_length];
+            reader.read_exact(&mut body)?;
+            match String::from_utf8(body) {
+                Ok(body) => run_request(&body, limits),
+                Err(_) => (
+                    400,
+                    "application/json",
+                    error_json("request body is not UTF-8"),
+                ),
+            }
+        }
+        (_, "/" | "/run") => (405, "application/json", error_json("method not allowed")),
+        _ => (404, "application/json", error_json("not found")),
+    };
+
+    respond(&mut writer, status, content_type, &body)
+}
+
+fn respond(writer: &mut TcpStream, status: u16, content_type: &str, body: &str) -> io::Result<()> {
+    write!(
+        writer,
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        reason(status),
+        content_type,
+        body.len(),
+        body
+    )?;
+    writer.flush()
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        413 => "Payload Too Large",
+        503 => "Service Unavailable",
+        _ => "Error",
+    }
+}
+
+fn error_json(message: &str) -> String {
+    format!("{{\"ok\": false, \"error\": {}}}", json_string(message))
+}
+
+// Handles one `/run` body; returns the HTTP status, content type and JSON.
+pub fn run_request(body: &str, limits: &Limits) -> (u16, &'static str, String) {
+    // `/run` only takes strings.
+    let fields = match json::parse_object(body).and_then(|fields| {
+        fields
+            .into_iter()
+            .map(|(key, value)| match value {
+                Json::String(value) => Ok((key, value)),
+                _ => Err(format!("field \"{}\" must be a string", key)),
+            })
+            .collect::<Result<Vec<_>, _>>()
+    }) {
+        Ok(fields) => fields,
+        Err(e) => {
+            return (
+                400,
+                "application/json",
+                error_json(&format!("bad request: {}", e)),
+            )
+        }
+    };
+    let field = |name: &str| {
+        fields
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    };
+    let Some(source) = field("source") else {
+        return (
+            400,
+            "application/json",
+            error_json("bad request: missing \"source\""),
+        );
+    };
+
+    (
+        200,
+        "application/json",
+        run_source(source, field("stdin").unwrap_or(""), limits),
+    )
+}
+
+fn run_source(source: &str, stdin: &str, limits: &Limits) -> String {
+    let bytecode = match compiler::compile_source(source, "playground.br", &CompileOptions::default())
+    {
+        Ok(bytecode) => bytecode,
+        Err(e) => {
+            return format!(
+                "{{\"ok\": false, \"stage\": \"compile\", \"stdout\": \"\", \"error\": {}, \"line\": {}, \"col\": {}}}",
+                json_string(&e.to_string()),
+                e.line,
+                e.col
+            )
+        }
+    };
+
+    let mut host = CaptureHost::new(stdin).with_output_limit(limits.max_output_bytes);
+    let options = VmOptions {
+        check_stack: false,
+        max_instructions: Some(limits.max_instructions),
+        max_string_bytes: Some(limits.max_string_bytes),
+        ..Default::default()
+    };
+    let result = vm::execute_with_host_options(&bytecode, &mut host, &options);
+
+    let mut out = String::new();
+    match result {
+        Ok(_) => {
+            let _ = write!(
+                out,
+                "{{\"ok\": true, \"stdout\": {}, \"error\": null}}",
+                json_string(&host.output)
+            );
+        }
+        Err(e) => {
+            let _ = write!(
+                out,
+                "{{\"ok\": false, \"stage\": \"runtime\", \"stdout\": {}, \"error\": {}}}",
+                json_string(&host.output),
+                json_string(&e.to_string())
+            );
+        }
+    }
+    out
+}
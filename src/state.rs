@@ -0,0 +1,146 @@
+// Braincell persistence behind `brvm exec --state <file>`.
+//
+// Layout (little-endian):
+//   magic "BRST", u16 version, u16 global count,
+//   then one entry per global: tag 0 = unset, 1 = number (f64),
+//   2 = string (u32 length + UTF-8 bytes), 3 = big integer (u32 length +
+//   decimal digits).
+//
+// A newer version or an unknown tag is an error rather than a partial load,
+// so value types added later cannot be silently dropped by an older VM.
+use crate::bigint::BigInt;
+use crate::bytecode::MAX_GLOBALS;
+use crate::value::Value;
+use crate::vm::{Globals, GLOBAL_COUNT};
+use std::path::Path;
+use std::rc::Rc;
+
+pub const MAGIC: &[u8; 4] = b"BRST";
+pub const VERSION: u16 = 1;
+
+pub fn encode(globals: &Globals) -> Result<Vec<u8>, String> {
+    let mut bytes = MAGIC.to_vec();
+    bytes.extend_from_slice(&VERSION.to_le_bytes());
+    bytes.extend_from_slice(&(globals.len() as u16).to_le_bytes());
+    for global in globals {
+        match global {
+            None => bytes.push(0),
+            Some(Value::Number(n)) => {
+                bytes.push(1);
+                bytes.extend_from_slice(&n.to_le_bytes());
+            }
+            Some(Value::String(s)) => {
+                bytes.push(2);
+                let len = u32::try_from(s.len()).map_err(|_| {
+                    format!("braincell string too long to save ({} bytes)", s.len())
+                })?;
+                bytes.extend_from_slice(&len.to_le_bytes());
+                bytes.extend_from_slice(s.as_bytes());
+            }
+            Some(Value::Big(n)) => {
+                let text = n.to_string();
+                bytes.push(3);
+                bytes.extend_from_slice(&(text.len() as u32).to_le_bytes());
+                bytes.extend_from_slice(text.as_bytes());
+            }
+        }
+    }
+    Ok(bytes)
+}
+
+pub fn decode(bytes: &[u8]) -> Result<Globals, String> {
+    let mut reader = Reader { bytes, pos: 0 };
+
+    if reader.take(4)? != MAGIC {
+        return Err("not a braincell state file".to_string());
+    }
+    let version = reader.u16()?;
+    if version != VERSION {
+        return Err(format!("unsupported state version {}", version));
+    }
+    let count = reader.u16()? as usize;
+    if count > MAX_GLOBALS {
+        return Err(format!(
+            "state holds {} braincells, max {}",
+            count, MAX_GLOBALS
+        ));
+    }
+
+    let mut globals: Globals = vec![None; count.max(GLOBAL_COUNT)];
+    for (idx, global) in globals.iter_mut().enumerate().take(count) {
+        *global = match reader.take(1)?[0] {
+            0 => None,
+            1 => {
+                let bytes = reader.take(8)?;
+                let mut n = [0u8; 8];
+                n.copy_from_slice(bytes);
+                Some(Value::Number(f64::from_le_bytes(n)))
+            }
+            2 => {
+                let len = reader.u32()? as usize;
+                let s = std::str::from_utf8(reader.take(len)?)
+                    .map_err(|_| format!("invalid UTF-8 in braincell {}", idx))?;
+                Some(Value::String(Rc::new(s.to_string())))
+            }
+            3 => {
+                let len = reader.u32()? as usize;
+                let n = std::str::from_utf8(reader.take(len)?)
+                    .ok()
+                    .and_then(BigInt::parse)
+                    .ok_or_else(|| format!("invalid big integer in braincell {}", idx))?;
+                Some(Value::Big(Rc::new(n)))
+            }
+            tag => return Err(format!("unknown value tag {} in braincell {}", tag, idx)),
+        };
+    }
+
+    if reader.pos != bytes.len() {
+        return Err("trailing data after braincells".to_string());
+    }
+    Ok(globals)
+}
+
+// A missing file is an empty state; anything unreadable is an error.
+pub fn load(path: &Path) -> Result<Globals, String> {
+    match std::fs::read(path) {
+        Ok(bytes) => decode(&bytes).map_err(|e| format!("{}: {}", path.display(), e)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Default::default()),
+        Err(e) => Err(format!("failed to read {}: {}", path.display(), e)),
+    }
+}
+
+// Writes through a sibling temp file so a failed write never leaves a
+// truncated state behind.
+pub fn save(path: &Path, globals: &Globals) -> Result<(), String> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    std::fs::write(&tmp, encode(globals)?)
+        .and_then(|_| std::fs::rename(&tmp, path))
+        .map_err(|e| format!("failed to write {}: {}", path.display(), e))
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        if len > self.bytes.len() - self.pos {
+            return Err("truncated state file".to_string());
+        }
+        let slice = &self.bytes[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(slice)
+    }
+
+    fn u16(&mut self) -> Result<u16, String> {
+        let b = self.take(2)?;
+        Ok(u16::from_le_bytes([b[0], b[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        let b = self.take(4)?;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+}
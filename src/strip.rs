@@ -0,0 +1,97 @@
+// `brvm strip`: drops what a BRBC file carries only for people reading it.
+//
+// BRBC has no line table or embedded source (`brvm debug` keeps lines in
+// memory and recompiles), so the only debug data in a file is function
+// names. Each name that code never loads is replaced by one shared empty
+// string, and constants nothing refers to are removed. Code keeps its size
+// and offsets: only LOAD_CONST operands are renumbered.
+use crate::bytecode::{self, Module};
+use crate::opcode;
+use crate::value::Value;
+use std::collections::HashSet;
+use std::rc::Rc;
+
+pub fn strip(bytecode: &[u8]) -> Result<Vec<u8>, String> {
+    let mut module = bytecode::decode(bytecode)?;
+    let loads = const_loads(&module)?;
+
+    let loaded: HashSet<u32> = loads
+        .iter()
+        .map(|&pos| read_u32(&module.code, pos))
+        .collect();
+    if !module.functions.is_empty() {
+        let empty = match module
+            .constants
+            .iter()
+            .position(|c| matches!(c, Value::String(s) if s.is_empty()))
+        {
+            Some(idx) => idx as u32,
+            None => {
+                module.constants.push(Value::String(Rc::new(String::new())));
+                (module.constants.len() - 1) as u32
+            }
+        };
+        for func in &mut module.functions {
+            if !loaded.contains(&func.name_const) {
+                func.name_const = empty;
+            }
+        }
+    }
+
+    // Old index -> new index for every constant still referred to.
+    let used: HashSet<u32> = loaded
+        .iter()
+        .copied()
+        .chain(module.functions.iter().map(|f| f.name_const))
+        .collect();
+    let mut renumber = vec![None; module.constants.len()];
+    let mut constants = Vec::new();
+    for (idx, constant) in module.constants.iter().enumerate() {
+        if used.contains(&(idx as u32)) {
+            renumber[idx] = Some(constants.len() as u32);
+            constants.push(constant.clone());
+        }
+    }
+    let new_index = |old: u32| {
+        renumber
+            .get(old as usize)
+            .copied()
+            .flatten()
+            .ok_or_else(|| format!("constant index {} out of bounds", old))
+    };
+
+    for pos in loads {
+        let idx = new_index(read_u32(&module.code, pos))?;
+        module.code[pos..pos + 4].copy_from_slice(&idx.to_le_bytes());
+    }
+    for func in &mut module.functions {
+        func.name_const = new_index(func.name_const)?;
+    }
+    module.constants = constants;
+
+    Ok(bytecode::encode(&module))
+}
+
+// Operand offsets of every LOAD_CONST in the code section.
+fn const_loads(module: &Module) -> Result<Vec<usize>, String> {
+    let mut loads = Vec::new();
+    let mut pos = 0;
+    while pos < module.code.len() {
+        let op = module.code[pos];
+        let (_, width) = opcode::lookup(op)
+            .ok_or_else(|| format!("unknown opcode 0x{:02x} at offset {}", op, pos))?;
+        if pos + 1 + width > module.code.len() {
+            return Err(format!("truncated operand at offset {}", pos));
+        }
+        if op == 0x02 {
+            // LOAD_CONST
+            loads.push(pos + 1);
+        }
+        pos += 1 + width;
+    }
+    Ok(loads)
+}
+
+fn read_u32(code: &[u8], pos: usize) -> u32 {
+    u32::from_le_bytes([code[pos], code[pos + 1], code[pos + 2], code[pos + 3]])
+}
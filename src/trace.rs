@@ -0,0 +1,170 @@
+// Execution timelines for `--trace-out`, in the Chrome trace event format
+// that about://tracing and Perfetto open. Every user function call and
+// built-in call becomes a span, named from the function table, inside one
+// span for the whole run. A function's span records where it was called
+// from, as "fib+0x12". The tracer works through the VM's hooks, so it
+// replaces any hooks already set.
+use crate::builtins;
+use crate::bytecode;
+use crate::host::VmHost;
+use crate::lexdump::json_string;
+use crate::profile::MAIN;
+use crate::vm::VM;
+use std::cell::RefCell;
+use std::fmt::Write;
+use std::rc::Rc;
+use std::time::Instant;
+
+pub struct Tracer {
+    state: Rc<RefCell<State>>,
+}
+
+struct State {
+    start: Instant,
+    names: Vec<String>, // by function-table index
+    module: bytecode::Module,
+    pos: usize, // offset of the current instruction
+    events: Vec<Event>,
+    open: Vec<String>, // names of the spans not yet ended, innermost last
+    in_builtin: bool,  // the innermost span is a built-in still running
+    tail_call: bool,   // the current instruction is a TAILCALL
+}
+
+struct Event {
+    name: String,
+    category: &'static str,
+    phase: char, // 'B' begins a span and 'E' ends the innermost one
+    micros: f64,
+    from: Option<String>, // where a function was called, as "fib+0x12"
+}
+
+impl Tracer {
+    pub fn attach<H: VmHost>(vm: &mut VM<H>, bytecode: &[u8]) -> Result<Self, String> {
+        let module = bytecode::decode(bytecode)?;
+        let names = module
+            .functions
+            .iter()
+            .enumerate()
+            .map(|(i, f)| match module.function_name(f) {
+                // A stripped file has no names left.
+                "" => format!("function #{}", i),
+                name => name.to_string(),
+            })
+            .collect();
+        let mut state = State {
+            start: Instant::now(),
+            names,
+            module,
+            pos: 0,
+            events: Vec::new(),
+            open: Vec::new(),
+            in_builtin: false,
+            tail_call: false,
+        };
+        state.begin(MAIN.to_string(), "function", None);
+        let state = Rc::new(RefCell::new(state));
+
+        let hook = state.clone();
+        vm.on_instruction(move |pos, op| hook.borrow_mut().instruction(pos, op));
+        let hook = state.clone();
+        vm.on_call(move |function, _| hook.borrow_mut().call(function));
+        let hook = state.clone();
+        vm.on_return(move |_, _| hook.borrow_mut().end());
+        Ok(Self { state })
+    }
+
+    // The trace so far as JSON. Spans still open, such as those a runtime
+    // error cut short, end now.
+    pub fn to_json(&self) -> String {
+        let mut state = self.state.borrow_mut();
+        while !state.open.is_empty() {
+            state.end();
+        }
+        let mut out = String::from("{\"traceEvents\": [");
+        for (i, event) in state.events.iter().enumerate() {
+            let _ = write!(
+                out,
+                "{}\n  {{\"name\": {}, \"cat\": \"{}\", \"ph\": \"{}\", \"ts\": {:.3}, \"pid\": 1, \"tid\": 1",
+                if i == 0 { "" } else { "," },
+                json_string(&event.name),
+                event.category,
+                event.phase,
+                event.micros
+            );
+            if let Some(from) = &event.from {
+                let _ = write!(out, ", \"args\": {{\"from\": {}}}", json_string(from));
+            }
+            out.push('}');
+        }
+        out.push_str("\n], \"displayTimeUnit\": \"ms\"}\n");
+        out
+    }
+}
+
+impl State {
+    fn push(&mut self, name: String, category: &'static str, phase: char, from: Option<String>) {
+        let micros = self.start.elapsed().as_secs_f64() * 1e6;
+        self.events.push(Event {
+            name,
+            category,
+            phase,
+            micros,
+            from,
+        });
+    }
+
+    fn begin(&mut self, name: String, category: &'static str, from: Option<String>) {
+        self.open.push(name.clone());
+        self.push(name, category, 'B', from);
+    }
+
+    fn end(&mut self) {
+        let category = if self.in_builtin {
+            "builtin"
+        } else {
+            "function"
+        };
+        self.in_builtin = false;
+        if let Some(name) = self.open.pop() {
+            self.push(name, category, 'E', None);
+        }
+    }
+
+    // A built-in runs within one instruction, so it ends when the next one
+    // starts. Calls to user functions are left to the call hook.
+    fn instruction(&mut self, pos: usize, op: u8) {
+        if self.in_builtin {
+            self.end();
+        }
+        self.pos = pos;
+        self.tail_call = op == 0x16;
+        let code = &self.module.code;
+        let builtin = match op {
+            0x14 => code.get(pos + 1).copied(),
+            0x0D => code
+                .get(pos + 1..pos + 5)
+                .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                .filter(|&index| index < 2)
+                .map(|index| index as u8),
+            _ => None,
+        };
+        if let Some(builtin) = builtin.and_then(builtins::by_id) {
+            self.begin(builtin.name.to_string(), "builtin", None);
+            self.in_builtin = true;
+        }
+    }
+
+    // A tail call replaces the caller's frame, so the caller's span ends.
+    fn call(&mut self, function: u32) {
+        if self.tail_call && self.open.len() > 1 {
+            self.end();
+        }
+        let name = self
+            .names
+            .get(function as usize)
+            .cloned()
+            .unwrap_or_else(|| format!("function #{}", function));
+        let from = self.module.locate(self.pos);
+        self.begin(name, "function", Some(from));
+    }
+}
@@ -0,0 +1,335 @@
+// Bytecode-to-C translation behind `brvm transpile --target c`.
+//
+// Every instruction becomes a `case` of a switch on its bytecode offset, so
+// jumps, calls and returns are plain assignments to `pc` and straight-line
+// code falls through from one case to the next. The runtime in
+// transpile_rt.c is pasted in front, making the output a single portable C
+// file: `cc prog.c -lm -o prog`.
+use crate::bytecode::{self, Module};
+use crate::opcode;
+use crate::value::Value;
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+const RUNTIME: &str = include_str!("transpile_rt.c");
+
+enum Instr {
+    Op { op: u8, operand: u32, next: usize },
+    Unknown(u8),
+    Truncated(usize),
+}
+
+pub fn to_c(bytecode: &[u8]) -> Result<String, String> {
+    let module = bytecode::decode(bytecode)?;
+    if module.flags & !bytecode::KNOWN_FLAGS != 0 {
+        return Err(format!(
+            "unsupported bytecode flags: 0x{:04x}",
+            module.flags
+        ));
+    }
+
+    let instrs = decode_code(&module.code);
+    check_targets(&module, &instrs)?;
+
+    let mut out = String::from(RUNTIME);
+    let _ = writeln!(out);
+    let _ = writeln!(
+        out,
+        "/* Generated by brvm {} from BRBC version {}. */",
+        env!("CARGO_PKG_VERSION"),
+        module.version
+    );
+    write_constants(&mut out, &module)?;
+    write_functions(&mut out, &module);
+    write_main(&mut out, &module, &instrs);
+
+    Ok(out)
+}
+
+fn decode_code(code: &[u8]) -> Vec<(usize, Instr)> {
+    let mut instrs = Vec::new();
+    let mut pos = 0;
+    while pos < code.len() {
+        let op = code[pos];
+        let Some((_, width)) = opcode::lookup(op) else {
+            instrs.push((pos, Instr::Unknown(op)));
+            pos += 1;
+            continue;
+        };
+        if pos + 1 + width > code.len() {
+            instrs.push((pos, Instr::Truncated(width)));
+            break;
+        }
+
+        let operand = code[pos + 1..pos + 1 + width]
+            .iter()
+            .rev()
+            .fold(0u32, |acc, &b| (acc << 8) | b as u32);
+        let next = pos + 1 + width;
+        instrs.push((pos, Instr::Op { op, operand, next }));
+        pos = next;
+    }
+    instrs
+}
+
+// The VM can jump into the middle of an instruction; a switch cannot, so such
+// bytecode is refused up front. Nothing the compiler emits does this.
+fn check_targets(module: &Module, instrs: &[(usize, Instr)]) -> Result<(), String> {
+    let len = module.code.len();
+    let boundaries: BTreeSet<usize> = instrs.iter().map(|(pos, _)| *pos).collect();
+    let lands = |target: usize| target >= len || boundaries.contains(&target);
+
+    for (pos, instr) in instrs {
+        if let Instr::Op {
+            op: 0x0B | 0x0C | 0x1C,
+            operand,
+            ..
+        } = instr
+        {
+            if !lands(*operand as usize) {
+                return Err(format!(
+                    "jump at 0x{:04x} targets 0x{:04x}, which is not an instruction boundary",
+                    pos, operand
+                ));
+            }
+        }
+    }
+    for func in &module.functions {
+        if !lands(func.code_offset as usize) {
+            return Err(format!(
+                "function {} starts at 0x{:04x}, which is not an instruction boundary",
+                module.function_name(func),
+                func.code_offset
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn write_constants(out: &mut String, module: &Module) -> Result<(), String> {
+    if module.constants.is_empty() {
+        return Ok(());
+    }
+
+    let _ = writeln!(
+        out,
+        "\nstatic brv_value brv_constants[{}];\n",
+        module.constants.len()
+    );
+    let _ = writeln!(out, "static void brv_load_constants(void) {{");
+    for (idx, constant) in module.constants.iter().enumerate() {
+        let value = match constant {
+            Value::Number(n) => format!("brv_number({})", c_double(*n)),
+            Value::String(s) => format!("brv_string(brv_str_new({}, {}))", c_string(s), s.len()),
+            Value::Big(_) => return Err("big integers are not supported in C output".to_string()),
+        };
+        let _ = writeln!(out, "    brv_constants[{}] = {};", idx, value);
+    }
+    let _ = writeln!(out, "}}");
+    Ok(())
+}
+
+fn write_functions(out: &mut String, module: &Module) {
+    if module.functions.is_empty() {
+        return;
+    }
+
+    let code_len = module.code.len() as u32;
+    let _ = writeln!(
+        out,
+        "\nstatic const brv_function brv_functions[{}] = {{",
+        module.functions.len()
+    );
+    for func in &module.functions {
+        let _ = writeln!(
+            out,
+            "    {{{}, {}, {}, {}}},",
+            func.arity,
+            func.local_count,
+            func.code_offset.min(code_len),
+            c_string(module.function_name(func))
+        );
+    }
+    let _ = writeln!(out, "}};");
+}
+
+fn write_main(out: &mut String, module: &Module, instrs: &[(usize, Instr)]) {
+    let strict = module.flags & bytecode::FLAG_STRICT != 0;
+    let strict_math = module.flags & bytecode::FLAG_STRICT_MATH != 0;
+    let _ = writeln!(out, "\nint main(void) {{");
+    let _ = writeln!(out, "    uint32_t pc = 0;");
+    let _ = writeln!(
+        out,
+        "    brv_init({}, {}, {});",
+        u8::from(strict),
+        u8::from(strict_math),
+        module.globals
+    );
+    if !module.constants.is_empty() {
+        let _ = writeln!(out, "    brv_load_constants();");
+    }
+    let _ = writeln!(out, "    for (;;) {{");
+    let _ = writeln!(out, "        switch (pc) {{");
+
+    for (pos, instr) in instrs {
+        let (comment, body) = translate(module, instr);
+        let _ = writeln!(out, "        case {}: /* {} */", pos, comment);
+        for line in &body {
+            let _ = writeln!(out, "            {}", line);
+        }
+        if !body.last().is_some_and(|line| ends_case(line)) {
+            let _ = writeln!(out, "            /* fall through */");
+        }
+    }
+
+    let _ = writeln!(out, "        case {}:", module.code.len());
+    let _ = writeln!(out, "            return brv_halt();");
+    let _ = writeln!(out, "        default:");
+    let _ = writeln!(out, "            brv_fail(\"jump target out of bounds\");");
+    let _ = writeln!(out, "        }}");
+    let _ = writeln!(out, "    }}");
+    let _ = writeln!(out, "}}");
+}
+
+fn translate(module: &Module, instr: &Instr) -> (String, Vec<String>) {
+    let (op, operand, next) = match instr {
+        Instr::Op { op, operand, next } => (*op, *operand, *next),
+        Instr::Unknown(op) => {
+            return (
+                format!(".byte 0x{:02x}", op),
+                vec![fail(&format!("unknown opcode: 0x{:02x}", op))],
+            )
+        }
+        Instr::Truncated(width) => {
+            let kind = match width {
+                1 => "u8",
+                2 => "u16",
+                _ => "u32",
+            };
+            return (
+                "truncated operand".to_string(),
+                vec![fail(&format!(
+                    "unexpected end of bytecode while reading {}",
+                    kind
+                ))],
+            );
+        }
+    };
+
+    let (name, width) = opcode::lookup(op).unwrap_or(("?", 0));
+    let comment = match width {
+        0 => name.to_string(),
+        _ => format!("{} {}", name, operand),
+    };
+    let code_len = module.code.len() as u32;
+    let function = |index: u32| {
+        index
+            .checked_sub(2)
+            .filter(|&i| (i as usize) < module.functions.len())
+    };
+
+    let body = match op {
+        0x01 | 0x12 => vec!["return brv_halt();".to_string()],
+        0x02 if (operand as usize) < module.constants.len() => {
+            vec![format!("brv_push(brv_retain(brv_constants[{}]));", operand)]
+        }
+        0x02 => vec![fail("constant index out of bounds")],
+        0x03 => vec![format!("brv_load_global({});", operand)],
+        0x04 => vec![format!("brv_store_global({});", operand)],
+        0x05 => vec!["brv_add();".to_string()],
+        0x06 => vec!["brv_sub();".to_string()],
+        0x07 => vec!["brv_mul();".to_string()],
+        0x08 => vec!["brv_div();".to_string()],
+        0x09 => vec!["brv_print(1);".to_string()],
+        0x15 => vec!["brv_print(0);".to_string()],
+        0x0A => vec!["brv_input(0);".to_string()],
+        0x13 => vec!["brv_input(1);".to_string()],
+        0x0B if operand < code_len => vec![format!("pc = {};", operand), "continue;".to_string()],
+        0x0B => vec![fail("jump target out of bounds")],
+        0x0C | 0x1C => {
+            let taken = if operand < code_len {
+                format!("pc = {}; continue;", operand)
+            } else {
+                fail("jump target out of bounds")
+            };
+            let not = if op == 0x0C { "!" } else { "" };
+            vec![format!("if ({}brv_pop_truthy()) {{ {} }}", not, taken)]
+        }
+        0x0D if operand < 2 => vec![
+            "brv_check_depth();".to_string(),
+            format!("brv_builtin({});", operand),
+        ],
+        0x0D => match function(operand) {
+            Some(i) => vec![
+                format!("pc = brv_call(&brv_functions[{}], {});", i, next),
+                "continue;".to_string(),
+            ],
+            None => vec![
+                "brv_check_depth();".to_string(),
+                fail("function index out of bounds"),
+            ],
+        },
+        0x16 => match function(operand) {
+            Some(i) => vec![
+                format!("pc = brv_tailcall(&brv_functions[{}]);", i),
+                "continue;".to_string(),
+            ],
+            None => vec![fail("function index out of bounds")],
+        },
+        0x0E => vec!["pc = brv_return();".to_string(), "continue;".to_string()],
+        0x0F => vec![format!("brv_load_local({});", operand)],
+        0x10 => vec![format!("brv_store_local({});", operand)],
+        0x11 => vec!["brv_discard();".to_string()],
+        0x14 => vec![format!("brv_builtin({});", operand)],
+        0x17 => vec!["brv_dup();".to_string()],
+        0x18 => vec!["brv_throw();".to_string()],
+        0x19 => vec!["brv_print_err();".to_string()],
+        0x1A => vec!["brv_input_timeout();".to_string()],
+        0x1B => vec!["brv_equal();".to_string()],
+        0x1D => vec!["brv_clone();".to_string()],
+        0x1E => vec!["brv_swap();".to_string()],
+        0x1F => vec!["brv_rot();".to_string()],
+        _ => vec![fail(&format!("unknown opcode: 0x{:02x}", op))],
+    };
+
+    (comment, body)
+}
+
+fn ends_case(line: &str) -> bool {
+    line == "continue;" || line.starts_with("return ") || line.starts_with("brv_fail(")
+}
+
+fn fail(msg: &str) -> String {
+    format!("brv_fail({});", c_string(msg))
+}
+
+fn c_double(n: f64) -> String {
+    if n.is_nan() {
+        "NAN".to_string()
+    } else if n.is_infinite() {
+        if n < 0.0 { "-INFINITY" } else { "INFINITY" }.to_string()
+    } else {
+        // `{:e}` is the shortest round-tripping form and valid C syntax.
+        format!("{:e}", n)
+    }
+}
+
+// Octal escapes are fixed-width, unlike `\x`, so a following digit can never
+// be swallowed into the escape.
+fn c_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for &b in s.as_bytes() {
+        match b {
+            b'"' | b'\\' | b'?' => {
+                out.push('\\');
+                out.push(b as char);
+            }
+            0x20..=0x7E => out.push(b as char),
+            _ => {
+                let _ = write!(out, "\\{:03o}", b);
+            }
+        }
+    }
+    out.push('"');
+    out
+}
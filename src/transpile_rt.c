@@ -0,0 +1,1240 @@
+/* BRVM C runtime, embedded by `brvm transpile --target c`.
+ *
+ * Mirrors the bytecode VM: values are numbers or refcounted byte strings,
+ * there is one operand stack and a call-frame stack, and every runtime error
+ * message matches the VM's so a native build fails the same way. */
+#if defined(__unix__) || defined(__APPLE__)
+#define _POSIX_C_SOURCE 200112L
+#define BRV_POSIX 1
+#endif
+#include <ctype.h>
+#include <math.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <time.h>
+#ifdef BRV_POSIX
+#include <sys/select.h>
+#include <unistd.h>
+#endif
+
+/* A program only calls the helpers its opcodes need. */
+#ifdef __GNUC__
+#define BRV_RT static __attribute__((unused))
+#else
+#define BRV_RT static
+#endif
+
+#define BRV_BRAINCELLS 7
+#ifndef BRV_MAX_CALL_DEPTH
+#define BRV_MAX_CALL_DEPTH 256
+#endif
+#define BRV_MAX_REPEAT_BYTES (10u * 1024u * 1024u)
+#define BRV_MAX_RANDOM_BOUND 9007199254740992.0
+#define BRV_MIN_TIME (-62167219200.0) /* 0000-01-01T00:00:00Z */
+#define BRV_MAX_TIME 253402300799.0   /* 9999-12-31T23:59:59Z */
+
+enum { BRV_UNSET, BRV_NUM, BRV_STR };
+
+typedef struct brv_str {
+    size_t rc;
+    size_t len;
+    char data[];
+} brv_str;
+
+typedef struct {
+    int tag;
+    double num;
+    brv_str *str;
+} brv_value;
+
+typedef struct {
+    uint16_t arity;
+    uint16_t local_count;
+    uint32_t code_offset;
+    const char *name;
+} brv_function;
+
+typedef struct {
+    const brv_function *fn;
+    uint32_t ret;
+    uint16_t count;
+    brv_value *locals;
+    size_t base; /* brv_sp once the arguments are popped */
+} brv_frame;
+
+static int brv_strict, brv_strict_math;
+static brv_value *brv_globals;
+static size_t brv_global_count;
+static brv_value *brv_stack;
+static size_t brv_sp, brv_stack_cap;
+static brv_frame brv_frames[BRV_MAX_CALL_DEPTH];
+static size_t brv_depth;
+static uint64_t brv_rng_state;
+
+static const char *const brv_braincells[BRV_BRAINCELLS] = {
+    "aura", "peak", "goon", "mog", "npc", "sigma", "gyatt"};
+
+BRV_RT void brv_fail(const char *msg) {
+    fflush(stdout);
+    fprintf(stderr, "runtime: %s\n", msg);
+    exit(1);
+}
+
+BRV_RT void *brv_alloc(size_t size) {
+    void *p = malloc(size ? size : 1);
+    if (!p) brv_fail("out of memory");
+    return p;
+}
+
+BRV_RT brv_str *brv_str_new(const char *data, size_t len) {
+    brv_str *s = brv_alloc(sizeof(brv_str) + len + 1);
+    s->rc = 1;
+    s->len = len;
+    if (len) memcpy(s->data, data, len);
+    s->data[len] = '\0';
+    return s;
+}
+
+BRV_RT brv_value brv_number(double n) {
+    brv_value v = {BRV_NUM, n, NULL};
+    return v;
+}
+
+BRV_RT brv_value brv_string(brv_str *s) {
+    brv_value v = {BRV_STR, 0.0, s};
+    return v;
+}
+
+BRV_RT brv_value brv_retain(brv_value v) {
+    if (v.tag == BRV_STR) v.str->rc++;
+    return v;
+}
+
+BRV_RT void brv_release(brv_value v) {
+    if (v.tag == BRV_STR && --v.str->rc == 0) free(v.str);
+}
+
+BRV_RT void brv_push(brv_value v) {
+    if (brv_sp == brv_stack_cap) {
+        brv_stack_cap = brv_stack_cap ? brv_stack_cap * 2 : 64;
+        brv_value *grown = realloc(brv_stack, brv_stack_cap * sizeof(brv_value));
+        if (!grown) brv_fail("out of memory");
+        brv_stack = grown;
+    }
+    brv_stack[brv_sp++] = v;
+}
+
+BRV_RT brv_value brv_pop(void) {
+    if (brv_sp == 0) brv_fail("stack underflow");
+    return brv_stack[--brv_sp];
+}
+
+/* Same text as Rust's `{}` for f64: the shortest digits that round-trip,
+ * written out in plain decimal notation. */
+BRV_RT size_t brv_format_number(double n, char *out) {
+    char sci[40], digits[20];
+    int precision, ndigits = 0, exponent, point, i;
+    char *o = out;
+    const char *c;
+
+    if (isnan(n)) return (size_t)sprintf(out, "NaN");
+    if (isinf(n)) return (size_t)sprintf(out, n < 0 ? "-inf" : "inf");
+
+    for (precision = 0; precision < 17; precision++) {
+        snprintf(sci, sizeof sci, "%.*e", precision, n);
+        if (strtod(sci, NULL) == n) break;
+    }
+
+    c = sci;
+    if (*c == '-') {
+        *o++ = '-';
+        c++;
+    }
+    for (; *c && *c != 'e'; c++) {
+        if (*c >= '0' && *c <= '9') digits[ndigits++] = *c;
+    }
+    exponent = atoi(c + 1);
+    while (ndigits > 1 && digits[ndigits - 1] == '0') ndigits--;
+
+    point = exponent + 1;
+    if (point <= 0) {
+        *o++ = '0';
+        *o++ = '.';
+        for (i = 0; i < -point; i++) *o++ = '0';
+        for (i = 0; i < ndigits; i++) *o++ = digits[i];
+    } else if (point >= ndigits) {
+        for (i = 0; i < ndigits; i++) *o++ = digits[i];
+        for (i = ndigits; i < point; i++) *o++ = '0';
+    } else {
+        for (i = 0; i < point; i++) *o++ = digits[i];
+        *o++ = '.';
+        for (i = point; i < ndigits; i++) *o++ = digits[i];
+    }
+    *o = '\0';
+    return (size_t)(o - out);
+}
+
+/* Printable text of a value; *scratch receives number text. */
+BRV_RT const char *brv_text(brv_value v, char *scratch, size_t *len) {
+    if (v.tag == BRV_STR) {
+        *len = v.str->len;
+        return v.str->data;
+    }
+    *len = brv_format_number(v.num, scratch);
+    return scratch;
+}
+
+BRV_RT const char *brv_type_name(brv_value v) {
+    return v.tag == BRV_STR ? "string" : "number";
+}
+
+BRV_RT int brv_truthy(brv_value v) {
+    return v.tag == BRV_STR ? v.str->len != 0 : v.num != 0.0;
+}
+
+BRV_RT void brv_write(brv_value v, int newline) {
+    char scratch[512];
+    size_t len;
+    const char *text = brv_text(v, scratch, &len);
+    if (fwrite(text, 1, len, stdout) != len || (newline && putchar('\n') == EOF)) {
+        brv_fail("failed to write output");
+    }
+}
+
+BRV_RT void brv_print(int newline) {
+    brv_value v = brv_pop();
+    brv_write(v, newline);
+    if (!newline && fflush(stdout) != 0) brv_fail("failed to flush output");
+    brv_release(v);
+}
+
+BRV_RT void brv_print_err(void) {
+    char scratch[512];
+    size_t len;
+    brv_value v = brv_pop();
+    const char *text = brv_text(v, scratch, &len);
+    if (fflush(stdout) != 0) brv_fail("failed to flush output");
+    if (fwrite(text, 1, len, stderr) != len || fputc('\n', stderr) == EOF) {
+        brv_fail("failed to write to stderr");
+    }
+    brv_release(v);
+}
+
+BRV_RT brv_value brv_read_input(void) {
+    size_t len = 0, cap = 64;
+    char *buf = brv_alloc(cap);
+    brv_str *s;
+    int ch;
+
+    if (fflush(stdout) != 0) brv_fail("failed to flush output");
+    while ((ch = getchar()) != EOF) {
+        if (len + 1 >= cap) {
+            char *grown = realloc(buf, cap *= 2);
+            if (!grown) brv_fail("out of memory");
+            buf = grown;
+        }
+        buf[len++] = (char)ch;
+        if (ch == '\n') break;
+    }
+    if (ferror(stdin)) brv_fail("failed to read from stdin");
+    while (len > 0 && strchr(" \t\n\v\f\r", buf[len - 1])) len--;
+
+    s = brv_str_new(buf, len);
+    free(buf);
+    return brv_string(s);
+}
+
+BRV_RT void brv_input(int prompted) {
+    if (prompted) {
+        brv_value prompt = brv_pop();
+        brv_write(prompt, 0);
+        brv_release(prompt);
+    }
+    brv_push(brv_read_input());
+}
+
+BRV_RT void brv_load_global(uint8_t idx) {
+    char msg[64];
+    if (idx >= brv_global_count) brv_fail("global index out of bounds");
+    if (brv_globals[idx].tag == BRV_UNSET) {
+        if (idx < BRV_BRAINCELLS) {
+            snprintf(msg, sizeof msg, "unset braincell: %s", brv_braincells[idx]);
+        } else {
+            snprintf(msg, sizeof msg, "unset braincell: %u", (unsigned)idx);
+        }
+        brv_fail(msg);
+    }
+    brv_push(brv_retain(brv_globals[idx]));
+}
+
+BRV_RT void brv_store_global(uint8_t idx) {
+    brv_value v;
+    if (idx >= brv_global_count) brv_fail("global index out of bounds");
+    v = brv_pop();
+    brv_release(brv_globals[idx]);
+    brv_globals[idx] = v;
+}
+
+BRV_RT brv_value brv_concat(brv_value a, brv_value b) {
+    char sa[512], sb[512];
+    size_t la, lb;
+    const char *ta = brv_text(a, sa, &la);
+    const char *tb = brv_text(b, sb, &lb);
+    brv_str *s = brv_alloc(sizeof(brv_str) + la + lb + 1);
+    s->rc = 1;
+    s->len = la + lb;
+    memcpy(s->data, ta, la);
+    memcpy(s->data + la, tb, lb);
+    s->data[la + lb] = '\0';
+    return brv_string(s);
+}
+
+/* Strict math: arithmetic that gives NaN or infinity fails. */
+BRV_RT brv_value brv_finite(const char *what, double n) {
+    char shown[512], msg[576];
+    if (brv_strict_math && !isfinite(n)) {
+        brv_format_number(n, shown);
+        snprintf(msg, sizeof msg, "strict math: %s gave %s", what, shown);
+        brv_fail(msg);
+    }
+    return brv_number(n);
+}
+
+BRV_RT void brv_add(void) {
+    brv_value b = brv_pop(), a = brv_pop();
+    if (brv_strict && a.tag != b.tag) {
+        char msg[64];
+        snprintf(msg, sizeof msg, "strict mode: cannot add %s and %s", brv_type_name(a),
+                 brv_type_name(b));
+        brv_fail(msg);
+    }
+    if (a.tag == BRV_NUM && b.tag == BRV_NUM) {
+        brv_push(brv_finite("addition", a.num + b.num));
+    } else {
+        brv_push(brv_concat(a, b));
+    }
+    brv_release(a);
+    brv_release(b);
+}
+
+BRV_RT void brv_sub(void) {
+    brv_value b = brv_pop(), a = brv_pop();
+    if (a.tag != BRV_NUM || b.tag != BRV_NUM) {
+        brv_fail("subtraction requires both operands to be numbers");
+    }
+    brv_push(brv_finite("subtraction", a.num - b.num));
+}
+
+BRV_RT brv_value brv_repeat(brv_str *s, double n) {
+    char shown[512], msg[640];
+    double count;
+    size_t times, i;
+    brv_str *out;
+
+    if (!isfinite(n)) {
+        brv_format_number(n, shown);
+        snprintf(msg, sizeof msg, "string repeat count must be finite, got %s", shown);
+        brv_fail(msg);
+    }
+    count = trunc(n);
+    if (count < 0.0) count = 0.0;
+    if (count * (double)s->len > (double)BRV_MAX_REPEAT_BYTES) {
+        brv_format_number(count, shown);
+        snprintf(msg, sizeof msg, "string repeat of %lu bytes x %s exceeds the %u MB limit",
+                 (unsigned long)s->len, shown, BRV_MAX_REPEAT_BYTES / (1024u * 1024u));
+        brv_fail(msg);
+    }
+
+    times = s->len ? (size_t)count : 0;
+    out = brv_alloc(sizeof(brv_str) + s->len * times + 1);
+    out->rc = 1;
+    out->len = s->len * times;
+    for (i = 0; i < times; i++) memcpy(out->data + i * s->len, s->data, s->len);
+    out->data[out->len] = '\0';
+    return brv_string(out);
+}
+
+BRV_RT void brv_mul(void) {
+    brv_value b = brv_pop(), a = brv_pop();
+    if (a.tag == BRV_NUM && b.tag == BRV_NUM) {
+        brv_push(brv_finite("multiplication", a.num * b.num));
+    } else if (a.tag == BRV_STR && b.tag == BRV_NUM) {
+        brv_push(brv_repeat(a.str, b.num));
+    } else if (a.tag == BRV_NUM && b.tag == BRV_STR) {
+        brv_push(brv_repeat(b.str, a.num));
+    } else {
+        brv_fail("multiplication requires both operands to be numbers");
+    }
+    brv_release(a);
+    brv_release(b);
+}
+
+BRV_RT void brv_div(void) {
+    brv_value b = brv_pop(), a = brv_pop();
+    if (a.tag != BRV_NUM || b.tag != BRV_NUM) {
+        brv_fail("division requires both operands to be numbers");
+    }
+    if (b.num == 0.0) brv_fail("division by zero");
+    brv_push(brv_finite("division", a.num / b.num));
+}
+
+BRV_RT void brv_equal(void) {
+    brv_value b = brv_pop(), a = brv_pop();
+    int equal = a.tag != b.tag ? 0
+                : a.tag == BRV_NUM
+                    ? a.num == b.num
+                    : a.str->len == b.str->len && memcmp(a.str->data, b.str->data, a.str->len) == 0;
+    brv_push(brv_number(equal));
+    brv_release(a);
+    brv_release(b);
+}
+
+BRV_RT int brv_pop_truthy(void) {
+    brv_value v = brv_pop();
+    int truthy = brv_truthy(v);
+    brv_release(v);
+    return truthy;
+}
+
+BRV_RT void brv_dup(void) {
+    if (brv_sp == 0) brv_fail("stack underflow");
+    brv_push(brv_retain(brv_stack[brv_sp - 1]));
+}
+
+/* a b -> b a */
+BRV_RT void brv_swap(void) {
+    brv_value a;
+    if (brv_sp < 2) brv_fail("stack underflow");
+    a = brv_stack[brv_sp - 2];
+    brv_stack[brv_sp - 2] = brv_stack[brv_sp - 1];
+    brv_stack[brv_sp - 1] = a;
+}
+
+/* a b c -> b c a */
+BRV_RT void brv_rot(void) {
+    brv_value a;
+    if (brv_sp < 3) brv_fail("stack underflow");
+    a = brv_stack[brv_sp - 3];
+    brv_stack[brv_sp - 3] = brv_stack[brv_sp - 2];
+    brv_stack[brv_sp - 2] = brv_stack[brv_sp - 1];
+    brv_stack[brv_sp - 1] = a;
+}
+
+/* DIDDLE: replaces a shared string on top of the stack with its own copy. */
+BRV_RT void brv_clone(void) {
+    brv_value *top;
+    if (brv_sp == 0) brv_fail("stack underflow");
+    top = &brv_stack[brv_sp - 1];
+    if (top->tag == BRV_STR && top->str->rc > 1) {
+        brv_str *copy = brv_str_new(top->str->data, top->str->len);
+        brv_release(*top);
+        top->str = copy;
+    }
+}
+
+BRV_RT void brv_discard(void) {
+    brv_release(brv_pop());
+}
+
+BRV_RT void brv_bind_args(brv_frame *frame, const brv_function *f) {
+    uint16_t i;
+    if (brv_sp < f->arity) brv_fail("not enough arguments on stack");
+    frame->count = f->local_count;
+    frame->locals = calloc(f->local_count ? f->local_count : 1, sizeof(brv_value));
+    if (!frame->locals) brv_fail("out of memory");
+    for (i = f->arity; i > 0; i--) frame->locals[i - 1] = brv_pop();
+}
+
+BRV_RT void brv_free_locals(brv_frame *frame) {
+    uint16_t i;
+    for (i = 0; i < frame->count; i++) brv_release(frame->locals[i]);
+    free(frame->locals);
+}
+
+/* Prints the overflow with its call chain the way the VM's
+   format_call_chain does: repeats folded, long chains cut to 3 + 4 entries. */
+BRV_RT void brv_check_depth(void) {
+    size_t i, j, runs = 0, run = 0;
+    if (brv_depth < BRV_MAX_CALL_DEPTH) return;
+    for (i = 0; i < brv_depth; i++)
+        if (i == 0 || brv_frames[i].fn != brv_frames[i - 1].fn) runs++;
+    fflush(stdout);
+    fprintf(stderr, "runtime: call stack overflow (depth %lu)", (unsigned long)brv_depth);
+    for (i = 0; i < brv_depth; i = j, run++) {
+        j = i + 1;
+        while (j < brv_depth && brv_frames[j].fn == brv_frames[i].fn) j++;
+        if (runs > 7 && run >= 3 && run < runs - 4) {
+            if (run == 3) fputs(" → …", stderr);
+            continue;
+        }
+        fputs(run == 0 ? " in " : " → ", stderr);
+        fputs(brv_frames[i].fn->name, stderr);
+        if (j - i > 1) fprintf(stderr, " ×%lu", (unsigned long)(j - i));
+    }
+    fputc('\n', stderr);
+    exit(1);
+}
+
+BRV_RT uint32_t brv_call(const brv_function *f, uint32_t ret) {
+    brv_check_depth();
+    brv_frames[brv_depth].fn = f;
+    brv_frames[brv_depth].ret = ret;
+    brv_bind_args(&brv_frames[brv_depth], f);
+    brv_frames[brv_depth].base = brv_sp;
+    brv_depth++;
+    return f->code_offset;
+}
+
+/* Ends a frame whose result or tail-call arguments are off the stack, like
+   the VM's settle_frame with its stack check off. */
+BRV_RT void brv_settle_frame(const brv_frame *frame) {
+    char msg[96];
+    if (brv_sp < frame->base) {
+        snprintf(msg, sizeof msg, "stack check: function took %lu value(s) from its caller's stack",
+                 (unsigned long)(frame->base - brv_sp));
+        brv_fail(msg);
+    }
+    while (brv_sp > frame->base) brv_discard();
+}
+
+BRV_RT uint32_t brv_tailcall(const brv_function *f) {
+    brv_frame *frame;
+    if (brv_sp < f->arity) brv_fail("not enough arguments on stack");
+    if (brv_depth == 0) brv_fail("tail call outside of function");
+    frame = &brv_frames[brv_depth - 1];
+    brv_free_locals(frame);
+    frame->fn = f;
+    brv_bind_args(frame, f);
+    brv_settle_frame(frame);
+    return f->code_offset;
+}
+
+/* RETREAT in main: exits with the value, as RunResult::exit_status maps it. */
+BRV_RT void brv_exit(brv_value v) {
+    double n = v.tag == BRV_NUM ? v.num : -1;
+    int status = n == floor(n) && n >= 0 && n <= 255 ? (int)n : 6;
+    /* 1-6 and 130 are brvm's own failure codes (see exit.rs). */
+    if ((status >= 1 && status <= 6) || status == 130) status = 6;
+    brv_release(v);
+    if (fflush(stdout) != 0) brv_fail("failed to flush output");
+    exit(status);
+}
+
+BRV_RT uint32_t brv_return(void) {
+    brv_frame *frame;
+    brv_value result;
+    result = brv_pop();
+    if (brv_depth == 0) brv_exit(result);
+    frame = &brv_frames[--brv_depth];
+    brv_settle_frame(frame);
+    brv_push(result);
+    brv_free_locals(frame);
+    return frame->ret;
+}
+
+BRV_RT brv_frame *brv_current_frame(const char *outside) {
+    if (brv_depth == 0) brv_fail(outside);
+    return &brv_frames[brv_depth - 1];
+}
+
+BRV_RT void brv_load_local(uint16_t idx) {
+    brv_frame *frame = brv_current_frame("local access outside of function");
+    if (idx >= frame->count) brv_fail("local index out of bounds");
+    if (frame->locals[idx].tag == BRV_UNSET) brv_fail("unset local variable");
+    brv_push(brv_retain(frame->locals[idx]));
+}
+
+BRV_RT void brv_store_local(uint16_t idx) {
+    brv_frame *frame = brv_current_frame("local assignment outside of function");
+    brv_value v;
+    if (idx >= frame->count) brv_fail("local index out of bounds");
+    v = brv_pop();
+    brv_release(frame->locals[idx]);
+    frame->locals[idx] = v;
+}
+
+BRV_RT void brv_rng_reseed(uint64_t seed) {
+    uint64_t z = seed + 0x9E3779B97F4A7C15ull;
+    z = (z ^ (z >> 30)) * 0xBF58476D1CE4E5B9ull;
+    z = (z ^ (z >> 27)) * 0x94D049BB133111EBull;
+    z ^= z >> 31;
+    brv_rng_state = z ? z : 0x9E3779B97F4A7C15ull;
+}
+
+BRV_RT uint64_t brv_rng_next(void) {
+    uint64_t x = brv_rng_state;
+    x ^= x >> 12;
+    x ^= x << 25;
+    x ^= x >> 27;
+    brv_rng_state = x;
+    return x * 0x2545F4914F6CDD1Dull;
+}
+
+/* High 64 bits of a 64x64 product, without relying on 128-bit integers. */
+BRV_RT uint64_t brv_mul_high(uint64_t a, uint64_t b) {
+    uint64_t a_lo = a & 0xFFFFFFFFu, a_hi = a >> 32;
+    uint64_t b_lo = b & 0xFFFFFFFFu, b_hi = b >> 32;
+    uint64_t lo_lo = a_lo * b_lo, hi_lo = a_hi * b_lo;
+    uint64_t lo_hi = a_lo * b_hi, hi_hi = a_hi * b_hi;
+    uint64_t cross = (lo_lo >> 32) + (hi_lo & 0xFFFFFFFFu) + lo_hi;
+    return hi_hi + (hi_lo >> 32) + (cross >> 32);
+}
+
+BRV_RT int brv_whole_number(brv_value v, double max, uint64_t *out) {
+    if (v.tag != BRV_NUM || !isfinite(v.num) || v.num < 0.0 || v.num != floor(v.num) ||
+        v.num > max) {
+        return 0;
+    }
+    *out = v.num >= 18446744073709551615.0 ? UINT64_MAX : (uint64_t)v.num;
+    return 1;
+}
+
+/* TOUCHY(prompt, ms): pushes the line read, or 0 if none arrives in time.
+ * Waits with select(), so a line stdio has already buffered but not yet
+ * returned can still time out; without POSIX it blocks like TOUCHY. */
+BRV_RT void brv_input_timeout(void) {
+    brv_value ms = brv_pop();
+    brv_value prompt = brv_pop();
+    uint64_t n;
+
+    if (!brv_whole_number(ms, 4294967295.0, &n)) {
+        brv_fail("TOUCHY: expected a non-negative whole number of milliseconds");
+    }
+    brv_write(prompt, 0);
+    brv_release(prompt);
+#ifdef BRV_POSIX
+    {
+        fd_set ready;
+        struct timeval tv;
+        int rc;
+
+        FD_ZERO(&ready);
+        FD_SET(0, &ready);
+        tv.tv_sec = (long)(n / 1000);
+        tv.tv_usec = (long)(n % 1000) * 1000;
+        rc = select(1, &ready, NULL, NULL, &tv);
+        if (rc < 0) brv_fail("failed to read from stdin");
+        if (rc == 0) {
+            brv_push(brv_number(0.0));
+            return;
+        }
+    }
+#endif
+    brv_push(brv_read_input());
+}
+
+/* Accepts exactly what Rust's f64::from_str does. */
+BRV_RT int brv_parse_number(const brv_str *s, double *out) {
+    static const char *const words[] = {"inf", "infinity", "nan"};
+    const char *p = s->data, *end = s->data + s->len;
+    int digits = 0;
+    size_t i, w;
+
+    if (s->len == 0 || memchr(s->data, '\0', s->len)) return 0;
+    if (*p == '+' || *p == '-') p++;
+    for (w = 0; w < 3; w++) {
+        size_t wl = strlen(words[w]);
+        if ((size_t)(end - p) != wl) continue;
+        for (i = 0; i < wl && (p[i] | 0x20) == words[w][i]; i++) {
+        }
+        if (i == wl) {
+            *out = strtod(s->data, NULL);
+            return 1;
+        }
+    }
+
+    for (; p < end && *p >= '0' && *p <= '9'; p++) digits++;
+    if (p < end && *p == '.') {
+        for (p++; p < end && *p >= '0' && *p <= '9'; p++) digits++;
+    }
+    if (!digits) return 0;
+    if (p < end && (*p == 'e' || *p == 'E')) {
+        p++;
+        if (p < end && (*p == '+' || *p == '-')) p++;
+        if (p == end || *p < '0' || *p > '9') return 0;
+        while (p < end && *p >= '0' && *p <= '9') p++;
+    }
+    if (p != end) return 0;
+    *out = strtod(s->data, NULL);
+    return 1;
+}
+
+/* TRANSFORM's reading of a number: surrounding whitespace is ignored, and a
+   lone comma with no '.' is the decimal separator, so "3,5" is 3.5. A comma
+   followed by exactly three digits could group thousands and is rejected. */
+BRV_RT int brv_transform_number(const brv_str *s, double *out) {
+    const char *start = s->data, *end = s->data + s->len;
+    char *comma;
+    brv_str *t;
+    int ok;
+
+    while (start < end && *start && strchr(" \t\n\f\r", *start)) start++;
+    while (end > start && end[-1] && strchr(" \t\n\f\r", end[-1])) end--;
+    t = brv_str_new(start, (size_t)(end - start));
+    comma = memchr(t->data, ',', t->len);
+    if (comma && !memchr(comma + 1, ',', t->len - (size_t)(comma + 1 - t->data)) &&
+        !memchr(t->data, '.', t->len)) {
+        size_t after = t->len - (size_t)(comma + 1 - t->data);
+        if (after == 3 && isdigit((unsigned char)comma[1]) &&
+            isdigit((unsigned char)comma[2]) && isdigit((unsigned char)comma[3])) {
+            free(t);
+            return 0;
+        }
+        *comma = '.';
+    }
+    ok = brv_parse_number(t, out);
+    free(t);
+    return ok;
+}
+
+BRV_RT size_t brv_char_count(const brv_str *s) {
+    size_t i, count = 0;
+    for (i = 0; i < s->len; i++) {
+        if (((unsigned char)s->data[i] & 0xC0) != 0x80) count++;
+    }
+    return count;
+}
+
+static const char *const brv_months[12] = {"Jan", "Feb", "Mar", "Apr", "May", "Jun",
+                                           "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"};
+static const char *const brv_weekdays[7] = {"Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"};
+
+BRV_RT int64_t brv_floor_div(int64_t a, int64_t b) {
+    return a / b - (a % b != 0 && (a < 0) != (b < 0));
+}
+
+/* Days since 1970-01-01 and back, as in the VM's src/datetime.rs. */
+BRV_RT int64_t brv_days_from_civil(int64_t y, int64_t m, int64_t d) {
+    int64_t era, yoe, doy, doe;
+    if (m <= 2) y--;
+    era = brv_floor_div(y, 400);
+    yoe = y - era * 400;
+    doy = (153 * ((m + 9) % 12) + 2) / 5 + d - 1;
+    doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    return era * 146097 + doe - 719468;
+}
+
+BRV_RT void brv_civil_from_days(int64_t days, int64_t *y, int64_t *m, int64_t *d) {
+    int64_t z = days + 719468, era = brv_floor_div(z, 146097), doe = z - era * 146097;
+    int64_t yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    int64_t doy = doe - (365 * yoe + yoe / 4 - yoe / 100), mp = (5 * doy + 2) / 153;
+    *d = doy - (153 * mp + 2) / 5 + 1;
+    *m = mp < 10 ? mp + 3 : mp - 9;
+    *y = yoe + era * 400 + (*m <= 2);
+}
+
+/* The pattern with %F and %T spelled out, or NULL if it ends in a lone %. */
+BRV_RT char *brv_expand_pattern(const brv_str *pattern, size_t *len) {
+    char *out = brv_alloc(pattern->len * 4 + 1);
+    size_t i, n = 0;
+    for (i = 0; i < pattern->len; i++) {
+        char c = pattern->data[i];
+        if (c != '%') {
+            out[n++] = c;
+            continue;
+        }
+        if (++i == pattern->len) {
+            free(out);
+            return NULL;
+        }
+        c = pattern->data[i];
+        if (c == 'F' || c == 'T') {
+            memcpy(out + n, c == 'F' ? "%Y-%m-%d" : "%H:%M:%S", 8);
+            n += 8;
+        } else {
+            out[n++] = '%';
+            out[n++] = c;
+        }
+    }
+    *len = n;
+    return out;
+}
+
+BRV_RT brv_value brv_formattime(brv_value t, brv_value pattern) {
+    char *pat, *out;
+    size_t len, i, n = 0;
+    int64_t time, days, secs, y, m, d;
+    brv_value result;
+
+    if (t.tag != BRV_NUM || t.num != floor(t.num) || t.num < BRV_MIN_TIME ||
+        t.num > BRV_MAX_TIME) {
+        brv_fail("FORMATTIME: expected whole seconds between the years 0 and 9999");
+    }
+    if (pattern.tag != BRV_STR) brv_fail("FORMATTIME: expected a pattern string");
+    pat = brv_expand_pattern(pattern.str, &len);
+    if (!pat) brv_fail("FORMATTIME: unknown directive in pattern");
+    time = (int64_t)t.num;
+    days = brv_floor_div(time, 86400);
+    secs = time - days * 86400;
+    brv_civil_from_days(days, &y, &m, &d);
+
+    /* No directive grows more than sixfold (%s is 2 bytes, up to 12 out). */
+    out = brv_alloc(len * 6 + 1);
+    for (i = 0; i < len; i++) {
+        if (pat[i] != '%') {
+            out[n++] = pat[i];
+            continue;
+        }
+        switch (pat[++i]) {
+        case 'Y': n += (size_t)sprintf(out + n, "%04d", (int)y); break;
+        case 'm': n += (size_t)sprintf(out + n, "%02d", (int)m); break;
+        case 'd': n += (size_t)sprintf(out + n, "%02d", (int)d); break;
+        case 'H': n += (size_t)sprintf(out + n, "%02d", (int)(secs / 3600)); break;
+        case 'M': n += (size_t)sprintf(out + n, "%02d", (int)(secs / 60 % 60)); break;
+        case 'S': n += (size_t)sprintf(out + n, "%02d", (int)(secs % 60)); break;
+        case 'b': memcpy(out + n, brv_months[m - 1], 3); n += 3; break;
+        case 'a': memcpy(out + n, brv_weekdays[((days + 4) % 7 + 7) % 7], 3); n += 3; break;
+        case 'j':
+            n += (size_t)sprintf(out + n, "%03d", (int)(days - brv_days_from_civil(y, 1, 1) + 1));
+            break;
+        case 's': n += (size_t)sprintf(out + n, "%lld", (long long)time); break;
+        case '%': out[n++] = '%'; break;
+        default: brv_fail("FORMATTIME: unknown directive in pattern");
+        }
+    }
+    result = brv_string(brv_str_new(out, n));
+    free(pat);
+    free(out);
+    return result;
+}
+
+/* Fields the pattern leaves out default to 1970-01-01 00:00:00. */
+BRV_RT brv_value brv_parsetime(brv_value text, brv_value pattern) {
+    static const char fields[] = "YmdHMS";
+    const char *mismatch = "PARSETIME: text does not match the pattern";
+    int64_t f[6] = {1970, 1, 1, 0, 0, 0}, mdays, v;
+    const char *p, *end, *slot;
+    char *pat, c;
+    size_t len, i, k, digits;
+
+    if (text.tag != BRV_STR || pattern.tag != BRV_STR) {
+        brv_fail("PARSETIME: expected a string and a pattern string");
+    }
+    pat = brv_expand_pattern(pattern.str, &len);
+    if (!pat) brv_fail("PARSETIME: unknown directive in pattern");
+    p = text.str->data;
+    end = p + text.str->len;
+    for (i = 0; i < len; i++) {
+        c = pat[i];
+        if (c == '%' && pat[i + 1] == '%') c = pat[++i];
+        else if (c == '%') {
+            c = pat[++i];
+            if (c == 'b') {
+                for (k = 0; k < 12 && end - p >= 3; k++) {
+                    if (tolower((unsigned char)p[0]) == tolower((unsigned char)brv_months[k][0]) &&
+                        tolower((unsigned char)p[1]) == brv_months[k][1] &&
+                        tolower((unsigned char)p[2]) == brv_months[k][2]) {
+                        break;
+                    }
+                }
+                if (end - p < 3 || k == 12) brv_fail(mismatch);
+                f[1] = (int64_t)k + 1;
+                p += 3;
+                continue;
+            }
+            slot = c ? strchr(fields, c) : NULL;
+            if (!slot) brv_fail("PARSETIME: unknown directive in pattern");
+            k = (size_t)(slot - fields);
+            for (digits = 0, v = 0; digits < (k == 0 ? 4u : 2u) && p + digits < end &&
+                                    p[digits] >= '0' && p[digits] <= '9';
+                 digits++) {
+                v = v * 10 + (p[digits] - '0');
+            }
+            if (digits == 0) brv_fail(mismatch);
+            f[k] = v;
+            p += digits;
+            continue;
+        }
+        if (p == end || *p != c) brv_fail(mismatch);
+        p++;
+    }
+    if (p != end) brv_fail(mismatch);
+    free(pat);
+
+    if (f[1] == 2) {
+        mdays = f[0] % 4 == 0 && (f[0] % 100 != 0 || f[0] % 400 == 0) ? 29 : 28;
+    } else {
+        mdays = f[1] == 4 || f[1] == 6 || f[1] == 9 || f[1] == 11 ? 30 : 31;
+    }
+    if (f[1] < 1 || f[1] > 12 || f[2] < 1 || f[2] > mdays || f[3] > 23 || f[4] > 59 ||
+        f[5] > 59) {
+        brv_fail("PARSETIME: date out of range");
+    }
+    return brv_number((double)(brv_days_from_civil(f[0], f[1], f[2]) * 86400 + f[3] * 3600 +
+                               f[4] * 60 + f[5]));
+}
+
+static const uint32_t brv_sha256_k[64] = {
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4,
+    0xab1c5ed5, 0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe,
+    0x9bdc06a7, 0xc19bf174, 0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f,
+    0x4a7484aa, 0x5cb0a9dc, 0x76f988da, 0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7,
+    0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967, 0x27b70a85, 0x2e1b2138, 0x4d2c6dfc,
+    0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85, 0xa2bfe8a1, 0xa81a664b,
+    0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070, 0x19a4c116,
+    0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7,
+    0xc67178f2};
+
+#define BRV_ROTR(x, n) (((x) >> (n)) | ((x) << (32 - (n))))
+
+/* SHA256(x): lowercase hex digest of x's printed text. */
+BRV_RT brv_value brv_sha256(brv_value v) {
+    uint32_t h[8] = {0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a,
+                     0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19};
+    uint32_t w[64], a, b, c, d, e, f, g, hh, t1, t2;
+    unsigned char *msg;
+    char scratch[512], hex[65];
+    const char *data;
+    size_t len, total, i, j;
+    uint64_t bits;
+
+    data = brv_text(v, scratch, &len);
+    total = (len + 9 + 63) / 64 * 64;
+    msg = brv_alloc(total);
+    memcpy(msg, data, len);
+    msg[len] = 0x80;
+    memset(msg + len + 1, 0, total - len - 1);
+    bits = (uint64_t)len * 8;
+    for (i = 0; i < 8; i++) msg[total - 1 - i] = (unsigned char)(bits >> (8 * i));
+
+    for (j = 0; j < total; j += 64) {
+        for (i = 0; i < 16; i++) {
+            w[i] = (uint32_t)msg[j + 4 * i] << 24 | (uint32_t)msg[j + 4 * i + 1] << 16 |
+                   (uint32_t)msg[j + 4 * i + 2] << 8 | msg[j + 4 * i + 3];
+        }
+        for (i = 16; i < 64; i++) {
+            uint32_t s0 = BRV_ROTR(w[i - 15], 7) ^ BRV_ROTR(w[i - 15], 18) ^ (w[i - 15] >> 3);
+            uint32_t s1 = BRV_ROTR(w[i - 2], 17) ^ BRV_ROTR(w[i - 2], 19) ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16] + s0 + w[i - 7] + s1;
+        }
+        a = h[0], b = h[1], c = h[2], d = h[3], e = h[4], f = h[5], g = h[6], hh = h[7];
+        for (i = 0; i < 64; i++) {
+            t1 = hh + (BRV_ROTR(e, 6) ^ BRV_ROTR(e, 11) ^ BRV_ROTR(e, 25)) + ((e & f) ^ (~e & g)) +
+                 brv_sha256_k[i] + w[i];
+            t2 = (BRV_ROTR(a, 2) ^ BRV_ROTR(a, 13) ^ BRV_ROTR(a, 22)) +
+                 ((a & b) ^ (a & c) ^ (b & c));
+            hh = g, g = f, f = e, e = d + t1, d = c, c = b, b = a, a = t1 + t2;
+        }
+        h[0] += a, h[1] += b, h[2] += c, h[3] += d, h[4] += e, h[5] += f, h[6] += g, h[7] += hh;
+    }
+    free(msg);
+    for (i = 0; i < 8; i++) sprintf(hex + 8 * i, "%08lx", (unsigned long)h[i]);
+    return brv_string(brv_str_new(hex, 64));
+}
+
+/* HASH(x): 64-bit FNV-1a of x's printed text, cut to its low 53 bits. */
+BRV_RT brv_value brv_fnv1a(brv_value v) {
+    uint64_t hash = UINT64_C(0xcbf29ce484222325);
+    char scratch[512];
+    size_t len, i;
+    const char *data = brv_text(v, scratch, &len);
+    for (i = 0; i < len; i++) {
+        hash ^= (unsigned char)data[i];
+        hash *= UINT64_C(0x100000001b3);
+    }
+    return brv_number((double)(hash & ((UINT64_C(1) << 53) - 1)));
+}
+
+static const char brv_base64_chars[] =
+    "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/* BASE64(x): x's printed text, base64-encoded with padding. */
+BRV_RT brv_value brv_base64(brv_value v) {
+    char scratch[512], *out;
+    const unsigned char *data;
+    size_t len, i, j, n = 0;
+    uint32_t bits;
+    brv_value result;
+
+    data = (const unsigned char *)brv_text(v, scratch, &len);
+    out = brv_alloc((len + 2) / 3 * 4);
+    for (i = 0; i < len; i += 3) {
+        bits = (uint32_t)data[i] << 16 | (i + 1 < len ? (uint32_t)data[i + 1] << 8 : 0) |
+               (i + 2 < len ? data[i + 2] : 0);
+        for (j = 0; j < 4; j++) {
+            out[n++] = j <= len - i ? brv_base64_chars[bits >> (18 - 6 * j) & 63] : '=';
+        }
+    }
+    result = brv_string(brv_str_new(out, n));
+    free(out);
+    return result;
+}
+
+BRV_RT int brv_valid_utf8(const unsigned char *s, size_t len) {
+    size_t i = 0, k, extra;
+    uint32_t cp;
+    while (i < len) {
+        if (s[i] < 0x80) {
+            i++;
+            continue;
+        }
+        if (s[i] >= 0xC2 && s[i] <= 0xDF) extra = 1, cp = s[i] & 0x1F;
+        else if (s[i] >= 0xE0 && s[i] <= 0xEF) extra = 2, cp = s[i] & 0x0F;
+        else if (s[i] >= 0xF0 && s[i] <= 0xF4) extra = 3, cp = s[i] & 0x07;
+        else return 0;
+        if (len - i <= extra) return 0;
+        for (k = 1; k <= extra; k++) {
+            if ((s[i + k] & 0xC0) != 0x80) return 0;
+            cp = cp << 6 | (s[i + k] & 0x3F);
+        }
+        if ((extra == 2 && (cp < 0x800 || (cp >= 0xD800 && cp <= 0xDFFF))) ||
+            (extra == 3 && (cp < 0x10000 || cp > 0x10FFFF))) {
+            return 0;
+        }
+        i += extra + 1;
+    }
+    return 1;
+}
+
+/* UNBASE64(s): decodes padded or unpadded base64 into UTF-8 text. */
+BRV_RT brv_value brv_unbase64(brv_value v) {
+    const char *invalid = "UNBASE64: invalid base64 text", *at;
+    size_t len, data_len, i, n = 0;
+    unsigned char *out;
+    uint32_t bits = 0;
+    int count = 0;
+    brv_value result;
+
+    if (v.tag != BRV_STR) brv_fail("UNBASE64: expected string argument");
+    len = v.str->len;
+    for (data_len = len; data_len > 0 && v.str->data[data_len - 1] == '='; data_len--) {
+    }
+    if (len - data_len > 2 || (len != data_len && len % 4 != 0) || data_len % 4 == 1) {
+        brv_fail(invalid);
+    }
+    out = brv_alloc(data_len / 4 * 3 + 2);
+    for (i = 0; i < data_len; i++) {
+        at = v.str->data[i] ? strchr(brv_base64_chars, v.str->data[i]) : NULL;
+        if (!at) brv_fail(invalid);
+        bits = bits << 6 | (uint32_t)(at - brv_base64_chars);
+        count += 6;
+        if (count >= 8) {
+            count -= 8;
+            out[n++] = (unsigned char)(bits >> count);
+        }
+    }
+    if (!brv_valid_utf8(out, n)) brv_fail("UNBASE64: decoded bytes are not UTF-8 text");
+    result = brv_string(brv_str_new((const char *)out, n));
+    free(out);
+    return result;
+}
+
+static const struct {
+    const char *name;
+    int code;
+} brv_styles[] = {{"bold", 1},   {"dim", 2},      {"italic", 3}, {"underline", 4},
+                  {"black", 30}, {"red", 31},     {"green", 32}, {"yellow", 33},
+                  {"blue", 34},  {"magenta", 35}, {"cyan", 36},  {"white", 37}};
+
+/* DRIP(style, text): the text, in ANSI codes when stdout is a terminal and
+ * NO_COLOR is unset, as with `brvm exec --color auto`. */
+BRV_RT void brv_drip(void) {
+    brv_value text = brv_pop(), style = brv_pop();
+    const char *p, *end, *t;
+    char scratch[512], *codes, *buf;
+    size_t n = 0, i, len, tlen, count = sizeof brv_styles / sizeof brv_styles[0];
+    int color = 0;
+
+    if (style.tag != BRV_STR) brv_fail("DRIP: expected style names like \"bold red\"");
+    /* Every name is at least as long as its code plus a separator. */
+    codes = brv_alloc(style.str->len + 2);
+    p = style.str->data;
+    end = p + style.str->len;
+    while (p < end) {
+        if (*p && strchr(" \t\n\v\f\r", *p)) {
+            p++;
+            continue;
+        }
+        for (len = 0; p + len < end && !(p[len] && strchr(" \t\n\v\f\r", p[len])); len++) {
+        }
+        for (i = 0; i < count; i++) {
+            if (strlen(brv_styles[i].name) == len && memcmp(brv_styles[i].name, p, len) == 0) {
+                break;
+            }
+        }
+        if (i == count) brv_fail("DRIP: expected style names like \"bold red\"");
+        n += (size_t)sprintf(codes + n, "%s%d", n ? ";" : "", brv_styles[i].code);
+        p += len;
+    }
+#ifdef BRV_POSIX
+    color = getenv("NO_COLOR") == NULL && isatty(1);
+#endif
+    t = brv_text(text, scratch, &tlen);
+    if (n == 0 || !color) {
+        brv_push(brv_string(brv_str_new(t, tlen)));
+    } else {
+        buf = brv_alloc(n + tlen + 7);
+        memcpy(buf, "\x1b[", 2);
+        memcpy(buf + 2, codes, n);
+        buf[2 + n] = 'm';
+        memcpy(buf + 3 + n, t, tlen);
+        memcpy(buf + 3 + n + tlen, "\x1b[0m", 4);
+        brv_push(brv_string(brv_str_new(buf, n + tlen + 7)));
+        free(buf);
+    }
+    free(codes);
+    brv_release(style);
+    brv_release(text);
+}
+
+/* Pops a braincell name for PEEK or POKE and returns its global slot. */
+BRV_RT uint8_t brv_braincell_slot(const char *who) {
+    char msg[64];
+    brv_value name = brv_pop();
+    uint8_t idx;
+    for (idx = 0; idx < BRV_BRAINCELLS; idx++) {
+        if (name.tag == BRV_STR && name.str->len == strlen(brv_braincells[idx]) &&
+            memcmp(name.str->data, brv_braincells[idx], name.str->len) == 0) {
+            brv_release(name);
+            return idx;
+        }
+    }
+    snprintf(msg, sizeof msg, "%s: expected a braincell name like \"aura\"", who);
+    brv_fail(msg);
+    return 0;
+}
+
+BRV_RT void brv_builtin(uint8_t id) {
+    brv_value arg, result;
+    uint64_t n;
+    char msg[64];
+
+    if (id > 20) {
+        snprintf(msg, sizeof msg, "unknown built-in: %u", (unsigned)id);
+        brv_fail(msg);
+    }
+    if (id == 6) {
+        brv_drip();
+        return;
+    }
+    /* Native builds have no --allow-net, so they fail like a VM without it. */
+    if (id == 7) brv_fail("YOINK: network access is disabled; run with --allow-net");
+    if (id == 8) brv_fail("YEET: network access is disabled; run with --allow-net");
+    /* Nor big integers: BIG of a literal is already refused by `brvm transpile`. */
+    if (id == 17) brv_fail("BIG: big integers are not supported in native builds");
+    if (id == 9 || id == 10) {
+        brv_value b = brv_pop(), a = brv_pop();
+        result = id == 9 ? brv_formattime(a, b) : brv_parsetime(a, b);
+        brv_release(a);
+        brv_release(b);
+        brv_push(result);
+        return;
+    }
+    if (id == 15) {
+        brv_value b = brv_pop(), a = brv_pop();
+        if (a.tag != BRV_NUM || b.tag != BRV_NUM) brv_fail("FLOORDIV: expected two numbers");
+        if (b.num == 0.0) brv_fail("FLOORDIV: division by zero");
+        brv_push(brv_number(floor(a.num / b.num)));
+        return;
+    }
+    if (id == 19) {
+        brv_load_global(brv_braincell_slot("PEEK"));
+        return;
+    }
+    if (id == 20) {
+        brv_value value = brv_pop();
+        uint8_t idx = brv_braincell_slot("POKE");
+        brv_push(brv_retain(value));
+        brv_push(value);
+        brv_store_global(idx);
+        return;
+    }
+    arg = brv_pop();
+    switch (id) {
+    case 0: /* TRANSFORM */
+        if (arg.tag != BRV_STR) brv_fail("TRANSFORM: expected string argument");
+        result.tag = BRV_NUM;
+        result.str = NULL;
+        if (!brv_transform_number(arg.str, &result.num)) {
+            brv_fail("TRANSFORM: invalid number string");
+        }
+        break;
+    case 1: /* RIZZED */
+        if (arg.tag != BRV_STR) brv_fail("RIZZED: expected string argument");
+        result = brv_number((double)brv_char_count(arg.str));
+        break;
+    case 2: /* SEED */
+        if (!brv_whole_number(arg, 18446744073709551615.0, &n)) {
+            brv_fail("SEED: expected a non-negative whole number");
+        }
+        brv_rng_reseed(n);
+        result = brv_retain(arg);
+        break;
+    case 3: /* RANDOM */
+        if (!brv_whole_number(arg, BRV_MAX_RANDOM_BOUND, &n) || n == 0) {
+            brv_fail("RANDOM: expected a positive whole number bound");
+        }
+        result = brv_number((double)brv_mul_high(brv_rng_next(), n));
+        break;
+    case 4: /* TYPEOF */
+        result = brv_string(brv_str_new(brv_type_name(arg), strlen(brv_type_name(arg))));
+        break;
+    case 5: /* EMPTY */
+        result = brv_number(arg.tag == BRV_STR ? arg.str->len == 0 : arg.num == 0.0);
+        break;
+    case 11: /* SHA256 */
+        result = brv_sha256(arg);
+        break;
+    case 12: /* HASH */
+        result = brv_fnv1a(arg);
+        break;
+    case 13: /* BASE64 */
+        result = brv_base64(arg);
+        break;
+    case 14: /* UNBASE64 */
+        result = brv_unbase64(arg);
+        break;
+    case 16: /* TRUNC */
+        if (arg.tag != BRV_NUM) brv_fail("TRUNC: expected a number");
+        result = brv_number(trunc(arg.num));
+        break;
+    default: /* TRYTRANSFORM */
+        if (arg.tag != BRV_STR) {
+            result = brv_retain(arg);
+        } else if (brv_transform_number(arg.str, &result.num)) {
+            result = brv_number(result.num);
+        } else {
+            result = brv_string(brv_str_new("", 0));
+        }
+        break;
+    }
+    brv_release(arg);
+    brv_push(result);
+}
+
+/* BRVM_SEED plays the part of `brvm exec --seed`. */
+BRV_RT void brv_init(int strict, int strict_math, size_t globals) {
+    const char *seed = getenv("BRVM_SEED");
+    size_t i;
+    brv_strict = strict;
+    brv_strict_math = strict_math;
+    brv_global_count = globals;
+    brv_globals = brv_alloc(globals * sizeof *brv_globals);
+    for (i = 0; i < globals; i++) {
+        brv_globals[i].tag = BRV_UNSET;
+        brv_globals[i].str = NULL;
+    }
+    if (seed && *seed) {
+        brv_rng_reseed(strtoull(seed, NULL, 10));
+    } else {
+        brv_rng_reseed((uint64_t)time(NULL) ^ ((uint64_t)clock() << 32));
+    }
+}
+
+BRV_RT void brv_throw(void) {
+    char scratch[512];
+    size_t len;
+    brv_value v = brv_pop();
+    const char *text = brv_text(v, scratch, &len);
+    fflush(stdout);
+    fprintf(stderr, "runtime: CRASHOUT: %.*s\n", (int)len, text);
+    exit(1);
+}
+
+BRV_RT int brv_halt(void) {
+    if (fflush(stdout) != 0) brv_fail("failed to flush output");
+    return 0;
+}
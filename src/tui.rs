@@ -0,0 +1,175 @@
+// Terminal UI for `brvm debug`, built with the `tui` feature. Panes show the
+// source, disassembly, operand stack, braincells, call frames and output of
+// a `Debugger`; keys step and continue it.
+use crate::debugger::{describe, Debugger, State};
+use crate::lexer::BRAINCELLS;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Style, Stylize};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Paragraph};
+use ratatui::Frame;
+use std::io;
+
+const KEYS: &str = " s step  n next line  c continue  b breakpoint  r restart  q quit";
+
+pub fn run(mut debugger: Debugger) -> io::Result<()> {
+    let mut terminal = ratatui::init();
+    let result = (|| loop {
+        terminal.draw(|frame| draw(frame, &debugger))?;
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        match key.code {
+            KeyCode::Char('s') => debugger.step(),
+            KeyCode::Char('n') | KeyCode::Enter => debugger.next_line(),
+            KeyCode::Char('c') => debugger.resume(),
+            KeyCode::Char('b') => {
+                if let Some(line) = debugger.line() {
+                    debugger.toggle_breakpoint(line);
+                }
+            }
+            KeyCode::Char('r') => debugger.restart(),
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+            _ => {}
+        }
+    })();
+    ratatui::restore();
+    result
+}
+
+fn draw(frame: &mut Frame, debugger: &Debugger) {
+    let [main, status] =
+        Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(frame.area());
+    let [left, right] =
+        Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)]).areas(main);
+    let [source, output] =
+        Layout::vertical([Constraint::Percentage(65), Constraint::Percentage(35)]).areas(left);
+    let [code, state] =
+        Layout::vertical([Constraint::Percentage(55), Constraint::Percentage(45)]).areas(right);
+    let [stack, braincells, frames] = Layout::horizontal([
+        Constraint::Percentage(30),
+        Constraint::Percentage(30),
+        Constraint::Percentage(40),
+    ])
+    .areas(state);
+
+    let current = debugger.line();
+    let source_lines = debugger.source.lines().enumerate().map(|(i, text)| {
+        let line = i + 1;
+        let mark = if debugger.breakpoints.contains(&line) {
+            '●'
+        } else {
+            ' '
+        };
+        (
+            format!("{}{:>4}  {}", mark, line, text),
+            Some(line) == current,
+        )
+    });
+    scrolled(frame, source, "Source", source_lines);
+
+    let ip = debugger.vm().ip();
+    let listing = debugger
+        .listing
+        .iter()
+        .map(|(offset, text)| (format!("0x{:04x}  {}", offset, text), *offset == ip));
+    scrolled(frame, code, "Disassembly", listing);
+
+    // Top of the stack first.
+    let values = debugger
+        .vm()
+        .stack()
+        .iter()
+        .rev()
+        .map(|value| Line::from(describe(Some(value))));
+    pane(frame, stack, "Stack", values.collect());
+
+    let globals = debugger.vm().globals();
+    let cells = BRAINCELLS.iter().map(|&(name, index)| {
+        Line::from(format!(
+            "{:<6}{}",
+            name,
+            describe(globals.get(index as usize).and_then(Option::as_ref))
+        ))
+    });
+    pane(frame, braincells, "Braincells", cells.collect());
+
+    let mut calls: Vec<Line> = debugger
+        .vm()
+        .frames()
+        .iter()
+        .rev()
+        .map(|f| Line::from(debugger.function_name(f.function).to_string()))
+        .chain(["(main)".into()])
+        .collect();
+    for (slot, value) in debugger.vm().locals().iter().enumerate() {
+        calls.insert(
+            slot + 1,
+            Line::from(format!("  local {}: {}", slot, describe(value.as_ref()))),
+        );
+    }
+    pane(frame, frames, "Call frames", calls);
+
+    let printed: Vec<Line> = debugger.output().lines().map(Line::from).collect();
+    let skip = printed
+        .len()
+        .saturating_sub(output.height.saturating_sub(2) as usize);
+    pane(
+        frame,
+        output,
+        "Output",
+        printed.into_iter().skip(skip).collect(),
+    );
+
+    let state = match &debugger.state {
+        State::Paused => format!(
+            "paused at {}",
+            current.map_or("?".to_string(), |l| format!("line {}", l))
+        ),
+        State::Finished => "finished".to_string(),
+        State::Failed(e) => e.to_string(),
+    };
+    frame.render_widget(
+        Paragraph::new(format!(" {} |{}", state, KEYS)).reversed(),
+        status,
+    );
+}
+
+fn pane(frame: &mut Frame, area: Rect, title: &str, lines: Vec<Line>) {
+    frame.render_widget(
+        Paragraph::new(lines).block(Block::bordered().title(title.to_string())),
+        area,
+    );
+}
+
+// A pane that keeps its highlighted row in view.
+fn scrolled(
+    frame: &mut Frame,
+    area: Rect,
+    title: &str,
+    rows: impl Iterator<Item = (String, bool)>,
+) {
+    let rows: Vec<(String, bool)> = rows.collect();
+    let height = area.height.saturating_sub(2) as usize;
+    let selected = rows.iter().position(|(_, current)| *current).unwrap_or(0);
+    let skip = selected
+        .saturating_sub(height / 2)
+        .min(rows.len().saturating_sub(height));
+    let lines = rows
+        .into_iter()
+        .skip(skip)
+        .map(|(text, current)| {
+            let style = if current {
+                Style::new().reversed()
+            } else {
+                Style::new()
+            };
+            Line::styled(text, style)
+        })
+        .collect();
+    pane(frame, area, title, lines);
+}
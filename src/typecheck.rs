@@ -0,0 +1,378 @@
+// Best-effort type checking between parsing and codegen. Types come from
+// literals, BASED constants, built-in results and `: number` / `: string`
+// annotations; anything else is unknown and left to the VM. Only operations
+// that are certain to fail are reported, using the VM's own messages.
+use crate::bigint::BigInt;
+use crate::error::CompileError;
+use crate::lexer::{Span, BRAINCELLS};
+use crate::parser::{BinaryOp, Expr, Function, Program, SpannedStatement, Statement, Type};
+use std::collections::HashMap;
+
+pub fn check(program: &Program, filename: &str, strict: bool) -> Result<(), CompileError> {
+    let mut checker = Checker {
+        filename,
+        strict,
+        consts: program
+            .consts
+            .iter()
+            .filter_map(|c| literal_type(&c.value).map(|ty| (c.name.as_str(), ty)))
+            .collect(),
+        functions: program
+            .functions
+            .iter()
+            .map(|f| (f.name.as_str(), f))
+            .collect(),
+        annotations: HashMap::new(),
+        pokes: program.calls_builtin("POKE"),
+        span: Span::default(),
+        errors: Vec::new(),
+    };
+
+    for func in &program.functions {
+        checker.annotations.clear();
+        let mut env = HashMap::new();
+        for param in &func.params {
+            if let Some(ty) = param.ty {
+                checker.annotations.insert(param.name.as_str(), ty);
+                env.insert(param.name.as_str(), ty);
+            }
+        }
+        checker.block(&func.body, &mut env);
+    }
+    checker.annotations.clear();
+    checker.block(&program.main_statements, &mut HashMap::new());
+
+    let mut errors = checker.errors.into_iter();
+    match errors.next() {
+        Some(mut first) => {
+            first.additional = errors.collect();
+            Err(first)
+        }
+        None => Ok(()),
+    }
+}
+
+// Names whose type is known at this point; a missing name is unknown.
+type Env<'a> = HashMap<&'a str, Type>;
+
+struct Checker<'a> {
+    filename: &'a str,
+    strict: bool,
+    consts: HashMap<&'a str, Type>,
+    functions: HashMap<&'a str, &'a Function>,
+    annotations: HashMap<&'a str, Type>, // per function, and for main
+    pokes: bool,                         // POKE can give a braincell any type
+    span: Span,                          // statement being checked
+    errors: Vec<CompileError>,
+}
+
+impl<'a> Checker<'a> {
+    fn error(&mut self, span: Span, message: String) {
+        self.errors.push(CompileError::new(
+            self.filename,
+            span.line,
+            span.col,
+            &message,
+        ));
+    }
+
+    fn block(&mut self, stmts: &'a [SpannedStatement], env: &mut Env<'a>) {
+        for stmt in stmts {
+            self.span = stmt.span;
+            match &stmt.statement {
+                Statement::Assign(name, annotation, expr) => {
+                    let found = self.expr(expr, env);
+                    match (annotation, self.annotations.get(name.as_str())) {
+                        (Some(ty), Some(previous)) if previous != ty => {
+                            let message = format!(
+                                "'{}' is annotated {} but was annotated {} before",
+                                name,
+                                ty.name(),
+                                previous.name()
+                            );
+                            self.error(stmt.span, message);
+                            continue;
+                        }
+                        (Some(ty), _) => {
+                            self.annotations.insert(name, *ty);
+                        }
+                        (None, _) => {}
+                    }
+                    self.store(name, found, env);
+                }
+                Statement::Copy { dest, source } => {
+                    let found = self.expr(source, env);
+                    self.store(dest, found, env);
+                }
+                Statement::Print(expr)
+                | Statement::PrintInline(expr)
+                | Statement::PrintError(expr)
+                | Statement::Return(expr)
+                | Statement::Throw(expr) => {
+                    self.expr(expr, env);
+                }
+                Statement::Halt => {}
+                Statement::If {
+                    condition,
+                    then_block,
+                    else_block,
+                } => {
+                    self.expr(condition, env);
+                    let mut then_env = env.clone();
+                    self.block(then_block, &mut then_env);
+                    let mut else_env = env.clone();
+                    if let Some(else_block) = else_block {
+                        self.block(else_block, &mut else_env);
+                    }
+                    then_env.retain(|name, ty| else_env.get(name) == Some(ty));
+                    *env = then_env;
+                }
+                Statement::While { condition, body } => {
+                    // Later iterations see whatever the body stored, so
+                    // names it assigns are only known through annotations.
+                    let mut assigned = Vec::new();
+                    assigned_names(body, &mut assigned);
+                    for name in assigned {
+                        match self.annotations.get(name) {
+                            Some(&ty) => env.insert(name, ty),
+                            None => env.remove(name),
+                        };
+                    }
+                    self.span = stmt.span;
+                    self.expr(condition, env);
+                    let mut body_env = env.clone();
+                    self.block(body, &mut body_env);
+                }
+            }
+        }
+    }
+
+    fn store(&mut self, name: &'a str, found: Option<Type>, env: &mut Env<'a>) {
+        let annotated = self.annotations.get(name).copied();
+        if let (Some(ty), Some(found)) = (annotated, found) {
+            if ty != found {
+                self.error(
+                    self.span,
+                    format!(
+                        "cannot assign a {} to '{}', which is annotated {}",
+                        found.name(),
+                        name,
+                        ty.name()
+                    ),
+                );
+            }
+        }
+        let poked = self.pokes && BRAINCELLS.iter().any(|(braincell, _)| *braincell == name);
+        match annotated.or(found) {
+            Some(ty) if !poked => env.insert(name, ty),
+            _ => env.remove(name),
+        };
+    }
+
+    fn expr(&mut self, expr: &'a Expr, env: &Env<'a>) -> Option<Type> {
+        match expr {
+            Expr::Number(..) => Some(Type::Number),
+            Expr::String(..) => Some(Type::String),
+            Expr::Variable(name, _) => self
+                .consts
+                .get(name.as_str())
+                .or_else(|| env.get(name.as_str()))
+                .copied(),
+            Expr::Binary {
+                op,
+                left,
+                right,
+                span,
+            } => {
+                let left = self.expr(left, env);
+                let right = self.expr(right, env);
+                self.binary(*op, left, right, *span)
+            }
+            Expr::FunctionCall {
+                name,
+                args: call_args,
+                span,
+            } => {
+                let args: Vec<_> = call_args.iter().map(|arg| self.expr(arg, env)).collect();
+                let (expects, message) = match name.as_str() {
+                    "TRANSFORM" => (Type::String, "TRANSFORM: expected string argument"),
+                    "RIZZED" => (Type::String, "RIZZED: expected string argument"),
+                    "SEED" => (Type::Number, "SEED: expected a non-negative whole number"),
+                    "RANDOM" => (
+                        Type::Number,
+                        "RANDOM: expected a positive whole number bound",
+                    ),
+                    "PARSETIME" => (Type::String, "PARSETIME: expected a date string"),
+                    "TRUNC" => (Type::Number, "TRUNC: expected a number"),
+                    "FLOORDIV" => {
+                        if args.iter().any(|arg| matches!(arg, Some(Type::String))) {
+                            self.error(*span, "FLOORDIV: expected two numbers".to_string());
+                        }
+                        return Some(Type::Number);
+                    }
+                    "UNBASE64" => {
+                        if matches!(args.first(), Some(Some(Type::Number))) {
+                            self.error(*span, "UNBASE64: expected string argument".to_string());
+                        }
+                        return Some(Type::String);
+                    }
+                    // A timed TOUCHY gives 0 when nothing arrives.
+                    "TOUCHY" if args.len() == 2 => {
+                        if matches!(args[1], Some(Type::String)) {
+                            self.error(
+                                *span,
+                                "TOUCHY: expected a timeout in milliseconds".to_string(),
+                            );
+                        }
+                        return None;
+                    }
+                    "TOUCHY" | "TYPEOF" | "SHA256" | "BASE64" => return Some(Type::String),
+                    "DRIP" => {
+                        if matches!(args.first(), Some(Some(Type::Number))) {
+                            self.error(*span, "DRIP: expected a style string".to_string());
+                        }
+                        return Some(Type::String);
+                    }
+                    "FORMATTIME" => {
+                        if matches!(args.first(), Some(Some(Type::String))) {
+                            self.error(
+                                *span,
+                                "FORMATTIME: expected a number of seconds".to_string(),
+                            );
+                        }
+                        return Some(Type::String);
+                    }
+                    // A number, or "" when the text is not one.
+                    "TRYTRANSFORM" => return None,
+                    // Big integers have no static type of their own.
+                    "BIG" => {
+                        if let [Expr::String(s, _)] = call_args.as_slice() {
+                            if BigInt::parse(s).is_none() {
+                                self.error(
+                                    *span,
+                                    "BIG: expected a whole number or a string of digits"
+                                        .to_string(),
+                                );
+                            }
+                        }
+                        return None;
+                    }
+                    "PEEK" | "POKE" => {
+                        if let Some(Expr::String(s, _)) = call_args.first() {
+                            if !BRAINCELLS.iter().any(|(braincell, _)| braincell == s) {
+                                self.error(
+                                    *span,
+                                    format!("{}: expected a braincell name like \"aura\"", name),
+                                );
+                            }
+                        }
+                        return args.get(1).copied().flatten();
+                    }
+                    "YOINK" | "YEET" => {
+                        if matches!(args.first(), Some(Some(Type::Number))) {
+                            self.error(*span, format!("{}: expected a URL string", name));
+                        }
+                        return Some(Type::String);
+                    }
+                    _ => return Some(Type::Number), // EMPTY, HASH
+                };
+                if matches!(args.first(), Some(Some(ty)) if *ty != expects) {
+                    self.error(*span, message.to_string());
+                }
+                Some(Type::Number)
+            }
+            Expr::UserFunctionCall { name, args, .. } => {
+                let params = self.functions.get(name.as_str()).map(|f| &f.params);
+                for (i, arg) in args.iter().enumerate() {
+                    let found = self.expr(arg, env);
+                    let expected = params.and_then(|p| p.get(i)).and_then(|p| p.ty);
+                    if let (Some(expected), Some(found)) = (expected, found) {
+                        if expected != found {
+                            let param = &params.unwrap()[i].name;
+                            self.error(
+                                arg.span(),
+                                format!(
+                                    "argument '{}' of function {} is annotated {}, got a {}",
+                                    param,
+                                    name,
+                                    expected.name(),
+                                    found.name()
+                                ),
+                            );
+                        }
+                    }
+                }
+                None
+            }
+        }
+    }
+
+    fn binary(
+        &mut self,
+        op: BinaryOp,
+        left: Option<Type>,
+        right: Option<Type>,
+        span: Span,
+    ) -> Option<Type> {
+        use Type::{Number, String};
+        let message = match (op, left, right) {
+            (BinaryOp::Add, Some(a), Some(b)) if self.strict && a != b => {
+                format!("strict mode: cannot add {} and {}", a.name(), b.name())
+            }
+            (BinaryOp::Add, Some(String), _) | (BinaryOp::Add, _, Some(String)) => {
+                return Some(String)
+            }
+            (BinaryOp::Add, Some(Number), Some(Number)) => return Some(Number),
+            (BinaryOp::Subtract, Some(String), _) | (BinaryOp::Subtract, _, Some(String)) => {
+                "subtraction requires both operands to be numbers".to_string()
+            }
+            (BinaryOp::Divide, Some(String), _) | (BinaryOp::Divide, _, Some(String)) => {
+                "division requires both operands to be numbers".to_string()
+            }
+            (BinaryOp::Subtract | BinaryOp::Divide, _, _) => return Some(Number),
+            (BinaryOp::Multiply, Some(String), Some(String)) => {
+                "multiplication requires both operands to be numbers".to_string()
+            }
+            (BinaryOp::Multiply, Some(Number), Some(Number)) => return Some(Number),
+            (BinaryOp::Multiply, Some(String), Some(Number))
+            | (BinaryOp::Multiply, Some(Number), Some(String)) => return Some(String),
+            (BinaryOp::Equal, _, _) => return Some(Number),
+            _ => return None,
+        };
+        self.error(span, message);
+        None
+    }
+}
+
+fn literal_type(expr: &Expr) -> Option<Type> {
+    match expr {
+        Expr::Number(..) => Some(Type::Number),
+        Expr::String(..) => Some(Type::String),
+        _ => None,
+    }
+}
+
+pub(crate) fn assigned_names<'a>(stmts: &'a [SpannedStatement], names: &mut Vec<&'a str>) {
+    for stmt in stmts {
+        match &stmt.statement {
+            Statement::Assign(name, _, _) | Statement::Copy { dest: name, .. } => names.push(name),
+            Statement::If {
+                then_block,
+                else_block,
+                ..
+            } => {
+                assigned_names(then_block, names);
+                if let Some(else_block) = else_block {
+                    assigned_names(else_block, names);
+                }
+            }
+            Statement::While { body, .. } => assigned_names(body, names),
+            Statement::Print(_)
+            | Statement::PrintInline(_)
+            | Statement::PrintError(_)
+            | Statement::Return(_)
+            | Statement::Throw(_)
+            | Statement::Halt => {}
+        }
+    }
+}
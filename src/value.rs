@@ -1,56 +1,116 @@
+use crate::bigint::{self, BigInt};
+use std::borrow::Cow;
 use std::rc::Rc;
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub enum Value {
     Number(f64),
     String(Rc<String>),
+    Big(Rc<BigInt>), // from BIG(); arithmetic on it stays exact
 }
 
 impl Value {
     pub fn add(&self, other: &Value) -> Result<Value, String> {
-        match (self, other) {
-            (Value::String(s1), _) => Ok(Value::String(Rc::new(format!(
-                "{}{}",
-                s1,
-                other.to_string()
-            )))),
-            (_, Value::String(s2)) => Ok(Value::String(Rc::new(format!(
-                "{}{}",
-                self.to_string(),
-                s2
-            )))),
-            (Value::Number(n1), Value::Number(n2)) => Ok(Value::Number(n1 + n2)),
+        match numeric(self, other) {
+            Some(Operands::Bigs(a, b)) => big_result(a.add(&b)),
+            Some(Operands::Numbers(n1, n2)) => Ok(Value::Number(n1 + n2)),
+            None => Ok(Value::String(Rc::new(format!("{}{}", self, other)))),
+        }
+    }
+
+    // Strict-mode 💀: no implicit stringification of numbers.
+    pub fn add_strict(&self, other: &Value) -> Result<Value, String> {
+        if matches!(self, Value::String(_)) != matches!(other, Value::String(_)) {
+            return Err(format!(
+                "strict mode: cannot add {} and {}",
+                self.type_name(),
+                other.type_name()
+            ));
         }
+        self.add(other)
     }
 
     pub fn sub(&self, other: &Value) -> Result<Value, String> {
-        match (self, other) {
-            (Value::Number(n1), Value::Number(n2)) => Ok(Value::Number(n1 - n2)),
-            _ => Err("subtraction requires both operands to be numbers".to_string()),
+        match numeric(self, other) {
+            Some(Operands::Bigs(a, b)) => big_result(a.sub(&b)),
+            Some(Operands::Numbers(n1, n2)) => Ok(Value::Number(n1 - n2)),
+            None => Err("subtraction requires both operands to be numbers".to_string()),
         }
     }
 
     pub fn mul(&self, other: &Value) -> Result<Value, String> {
         match (self, other) {
-            (Value::Number(n1), Value::Number(n2)) => Ok(Value::Number(n1 * n2)),
             (Value::String(s), Value::Number(n)) | (Value::Number(n), Value::String(s)) => {
-                let count = repeat_count(*n)?;
+                let count = repeat_count(*n, s.len())?;
+                Ok(Value::String(Rc::new(s.repeat(count))))
+            }
+            (Value::String(s), Value::Big(n)) | (Value::Big(n), Value::String(s)) => {
+                let count = repeat_count(n.to_f64(), s.len())?;
                 Ok(Value::String(Rc::new(s.repeat(count))))
             }
-            _ => Err("multiplication requires both operands to be numbers".to_string()),
+            _ => match numeric(self, other) {
+                Some(Operands::Bigs(a, b)) => {
+                    if a.digits() + b.digits() - 1 > bigint::MAX_DIGITS {
+                        return Err(too_many_digits());
+                    }
+                    big_result(a.mul(&b))
+                }
+                Some(Operands::Numbers(n1, n2)) => Ok(Value::Number(n1 * n2)),
+                None => Err("multiplication requires both operands to be numbers".to_string()),
+            },
         }
     }
 
+    // On big integers 🚡 rounds down, as the result has to stay whole.
     pub fn div(&self, other: &Value) -> Result<Value, String> {
-        match (self, other) {
-            (Value::Number(n1), Value::Number(n2)) => {
-                if *n2 == 0.0 {
+        match numeric(self, other) {
+            Some(Operands::Bigs(a, b)) => match a.div_floor(&b) {
+                Some(quotient) => big_result(quotient),
+                None => Err("division by zero".to_string()),
+            },
+            Some(Operands::Numbers(n1, n2)) => {
+                if n2 == 0.0 {
                     Err("division by zero".to_string())
                 } else {
                     Ok(Value::Number(n1 / n2))
                 }
             }
-            _ => Err("division requires both operands to be numbers".to_string()),
+            None => Err("division requires both operands to be numbers".to_string()),
+        }
+    }
+
+    // 🟰: numbers compare by value (so NaN equals nothing), strings by their
+    // text, and a number never equals a string. A big integer equals a number
+    // with exactly the same value.
+    pub fn equals(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::Number(n1), Value::Number(n2)) => n1 == n2,
+            (Value::String(s1), Value::String(s2)) => s1 == s2,
+            (Value::Big(b1), Value::Big(b2)) => b1 == b2,
+            (Value::Big(b), Value::Number(n)) | (Value::Number(n), Value::Big(b)) => {
+                BigInt::from_f64(*n).is_some_and(|n| n == **b)
+            }
+            _ => false,
+        }
+    }
+
+    // DIDDLE's copy: a value that shares no storage with any other. A value
+    // nothing else holds is moved rather than copied. Containers, once they
+    // exist, copy their elements the same way.
+    pub fn deep_copy(self) -> Value {
+        match self {
+            Value::Number(n) => Value::Number(n),
+            Value::String(s) => Value::String(Rc::new(Rc::unwrap_or_clone(s))),
+            Value::Big(n) => Value::Big(Rc::new(Rc::unwrap_or_clone(n))),
+        }
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Number(_) => "number",
+            Value::String(_) => "string",
+            Value::Big(_) => "bigint",
         }
     }
 
@@ -58,20 +118,74 @@ impl Value {
         match self {
             Value::Number(n) => format!("{}", n),
             Value::String(s) => s.to_string(),
+            Value::Big(n) => n.to_string(),
         }
     }
 }
 
-fn repeat_count(value: f64) -> Result<usize, String> {
-    if !value.is_finite() || value < 0.0 || value.fract() != 0.0 {
-        return Err("string repeat count must be a non-negative whole number".to_string());
+enum Operands<'a> {
+    Numbers(f64, f64),
+    Bigs(Cow<'a, BigInt>, Cow<'a, BigInt>),
+}
+
+// Arithmetic operands when neither is a string. A big integer with another
+// big integer or a whole number is exact; with a fraction, infinity or NaN
+// the big integer becomes the nearest number instead.
+fn numeric<'a>(left: &'a Value, right: &'a Value) -> Option<Operands<'a>> {
+    let big_and_number = |big: &'a BigInt, n: f64| match BigInt::from_f64(n) {
+        Some(n) => Some((Cow::Borrowed(big), Cow::Owned(n))),
+        None => None,
+    };
+    Some(match (left, right) {
+        (Value::Number(n1), Value::Number(n2)) => Operands::Numbers(*n1, *n2),
+        (Value::Big(b1), Value::Big(b2)) => Operands::Bigs(Cow::Borrowed(b1), Cow::Borrowed(b2)),
+        (Value::Big(b), Value::Number(n)) => match big_and_number(b, *n) {
+            Some((b, n)) => Operands::Bigs(b, n),
+            None => Operands::Numbers(b.to_f64(), *n),
+        },
+        (Value::Number(n), Value::Big(b)) => match big_and_number(b, *n) {
+            Some((b, n)) => Operands::Bigs(n, b),
+            None => Operands::Numbers(*n, b.to_f64()),
+        },
+        _ => return None,
+    })
+}
+
+fn big_result(n: BigInt) -> Result<Value, String> {
+    if n.digits() > bigint::MAX_DIGITS {
+        return Err(too_many_digits());
+    }
+    Ok(Value::Big(Rc::new(n)))
+}
+
+fn too_many_digits() -> String {
+    format!(
+        "big integer result has more than {} digits",
+        bigint::MAX_DIGITS
+    )
+}
+
+// Largest string 😏 may build; keeps a typo'd count from exhausting memory.
+pub const MAX_REPEAT_BYTES: usize = 10 * 1024 * 1024;
+
+// Python-style: the count is truncated toward zero and anything <= 0 repeats
+// zero times.
+fn repeat_count(value: f64, unit_len: usize) -> Result<usize, String> {
+    if !value.is_finite() {
+        return Err(format!("string repeat count must be finite, got {}", value));
     }
 
-    if value > usize::MAX as f64 {
-        return Err("string repeat count is too large".to_string());
+    let count = value.trunc().max(0.0);
+    if count * unit_len as f64 > MAX_REPEAT_BYTES as f64 {
+        return Err(format!(
+            "string repeat of {} bytes x {} exceeds the {} MB limit",
+            unit_len,
+            count,
+            MAX_REPEAT_BYTES / (1024 * 1024)
+        ));
     }
 
-    Ok(value as usize)
+    Ok(count as usize)
 }
 
 impl std::fmt::Display for Value {
@@ -0,0 +1,69 @@
+// wasm-bindgen entry points for running Brainrot in a browser without a
+// server, built with `--no-default-features --features wasm`.
+//
+// `compileSource` returns BRBC bytes; `runBytecode` runs them with JavaScript
+// callbacks for I/O. `readLine` is called synchronously, so it must return
+// the line (or null/undefined at end of input) rather than a Promise.
+use crate::compiler::{self, CompileOptions};
+use crate::host::VmHost;
+use crate::vm::{self, VmOptions};
+use js_sys::Function;
+use wasm_bindgen::prelude::*;
+
+struct JsHost<'a> {
+    print: &'a Function,
+    read_line: &'a Function,
+}
+
+impl VmHost for JsHost<'_> {
+    fn print(&mut self, s: &str) -> Result<(), String> {
+        self.print
+            .call1(&JsValue::NULL, &JsValue::from_str(s))
+            .map(|_| ())
+            .map_err(|_| "failed to write output".to_string())
+    }
+
+    fn read_line(&mut self) -> Result<String, String> {
+        let line = self
+            .read_line
+            .call0(&JsValue::NULL)
+            .map_err(|_| "failed to read from stdin".to_string())?;
+        if line.is_null() || line.is_undefined() {
+            return Ok(String::new());
+        }
+        line.as_string()
+            .ok_or_else(|| "failed to read from stdin".to_string())
+    }
+}
+
+#[wasm_bindgen(js_name = compileSource)]
+pub fn compile_source(source: &str, filename: &str, strict: bool) -> Result<Vec<u8>, JsValue> {
+    compiler::compile_source(
+        source,
+        filename,
+        &CompileOptions {
+            strict,
+            ..Default::default()
+        },
+    )
+    .map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+// There is no clock to seed RANDOM from on wasm32-unknown-unknown, so a
+// missing seed means seed 0.
+#[wasm_bindgen(js_name = runBytecode)]
+pub fn run_bytecode(
+    bytecode: &[u8],
+    print: &Function,
+    read_line: &Function,
+    seed: Option<u32>,
+) -> Result<(), JsValue> {
+    let options = VmOptions {
+        seed: Some(u64::from(seed.unwrap_or(0))),
+        check_stack: false,
+        ..Default::default()
+    };
+    vm::execute_with_host_options(bytecode, &mut JsHost { print, read_line }, &options)
+        .map(|_| ())
+        .map_err(|e| JsValue::from_str(&e.to_string()))
+}
@@ -0,0 +1,84 @@
+use brvm::analyze::analyze;
+
+fn found(source: &str) -> Vec<(usize, String)> {
+    analyze(source, "<test>", false)
+        .unwrap()
+        .into_iter()
+        .map(|d| (d.line, d.message))
+        .collect()
+}
+
+#[test]
+fn certain_runtime_errors_are_reported() {
+    let source = "BASED zero FR 0
+
+TRALALERO half(n)
+  RETREAT n 🚡 2
+TRALALA
+
+LOCK IN
+FANUMTAX aura FR 10 🚡 zero
+FANUMTAX peak FR TRANSFORM(\"12abc\")
+FANUMTAX goon FR TRANSFORM(\" 3,5 \") 😏 2
+FANUMTAX mog FR goon 😭 7
+SAY 5 🚡 mog
+SAY half(1, 2)
+SAY 5 🚡 mog
+SAY \"x\" 😭 1
+ITS OVER
+";
+    assert_eq!(
+        found(source),
+        [
+            (8, "division by zero".to_string()),
+            (9, "TRANSFORM: invalid number string: \"12abc\"".to_string()),
+            (12, "division by zero".to_string()),
+            (13, "half takes 1 argument, got 2".to_string()),
+            (
+                15,
+                "subtraction requires both operands to be numbers".to_string()
+            ),
+        ]
+    );
+}
+
+#[test]
+fn values_that_may_change_are_not_assumed() {
+    let source = "TRALALERO reset()
+  FANUMTAX aura FR 1
+  RETREAT 0
+TRALALA
+
+LOCK IN
+FANUMTAX aura FR 0
+SAY reset()
+SAY 1 🚡 aura
+FANUMTAX peak FR 0
+SKIBIDI peak 🟰 0
+  SAY 1 🚡 peak
+  FANUMTAX peak FR 1
+RIZZUP
+FANUMTAX goon FR 0
+ONGOD TOUCHY()
+  FANUMTAX goon FR 2
+DEADASS
+SAY 1 🚡 goon
+ONGOD 0
+  SAY 1 🚡 0
+DEADASS
+ITS OVER
+";
+    assert_eq!(found(source), []);
+}
+
+#[test]
+fn examples_are_clean() {
+    for entry in std::fs::read_dir("examples").unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_some_and(|ext| ext == "brainrot") {
+            let source = std::fs::read_to_string(&path).unwrap();
+            let found = analyze(&source, &path.to_string_lossy(), false).unwrap();
+            assert!(found.is_empty(), "{}: {:?}", path.display(), found);
+        }
+    }
+}
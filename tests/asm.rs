@@ -0,0 +1,229 @@
+use brvm::bigint::BigInt;
+use brvm::value::Value;
+use brvm::{asm, bytecode, disasm, vm};
+use std::io::Cursor;
+use std::rc::Rc;
+
+fn run(bytecode: &[u8]) -> String {
+    let mut output = Vec::new();
+    vm::execute_with_io(bytecode, &mut Cursor::new(&b""[..]), &mut output).expect("should run");
+    String::from_utf8(output).unwrap()
+}
+
+const COUNTDOWN: &str = r#"
+; counts down from 3, then shuffles three values with SWAP and ROT
+    LOAD_CONST 3
+    STORE_GLOBAL aura
+top:
+    LOAD_GLOBAL aura
+    JUMP_IF_FALSE done
+    LOAD_GLOBAL aura
+    HITMEUP show
+    POOPY
+    LOAD_GLOBAL aura
+    LOAD_CONST 1
+    SUB
+    STORE_GLOBAL aura
+    JUMP top
+done:
+    LOAD_CONST "a;b"        ; the ; in the string is not a comment
+    LOAD_CONST "c"
+    SWAP
+    ADD
+    PRINT
+    LOAD_CONST 1
+    LOAD_CONST 2
+    LOAD_CONST 3
+    ROT
+    CALL_BUILTIN TYPEOF
+    PRINT_RAW
+    PRINT_RAW
+    PRINT
+    HALT
+
+.function show 1 1
+    TAX_LOCAL 0
+    DUP
+    PRINT
+    UNTILWEMEETAGAIN
+"#;
+
+#[test]
+fn assembled_programs_run() {
+    let bytecode = asm::assemble(COUNTDOWN, "<test>").expect("should assemble");
+    assert_eq!(run(&bytecode), "3\n2\n1\nca;b\nnumber32\n");
+
+    let listing = disasm::disassemble(&bytecode).unwrap();
+    assert!(listing.contains("HITMEUP 2                ; show"));
+    assert!(listing.contains("show(arity 1, locals 1) @ 0x"));
+}
+
+#[test]
+fn header_directives_set_version_flags_and_globals() {
+    let bytecode = asm::assemble(
+        ".version 5\n.flags strict strict-math\n.globals 9\n  LOAD_CONST 1.5\n  STORE_GLOBAL 8\n  HALT\n",
+        "<test>",
+    )
+    .unwrap();
+    let listing = disasm::disassemble(&bytecode).unwrap();
+    assert!(listing
+        .starts_with("BRBC version 5\nflags: 0x0007 (strict, strict-math, globals)\nglobals: 9\n"));
+    assert_eq!(run(&bytecode), "");
+}
+
+#[test]
+fn mistakes_point_at_their_line() {
+    let error = |source: &str| {
+        let e = asm::assemble(source, "<test>").expect_err("should not assemble");
+        (e.line, e.col, e.message)
+    };
+    assert_eq!(
+        error("  HALT\n  JUMP nowhere\n"),
+        (2, 8, "unknown name 'nowhere'".to_string())
+    );
+    assert_eq!(
+        error(".version 4\n  LOAD_CONST 1\n  LOAD_CONST 1\n  SWAP\n"),
+        (
+            4,
+            3,
+            "SWAP needs bytecode version 5, but version 4 was requested".to_string()
+        )
+    );
+    assert_eq!(
+        error("  SWAP 1\n"),
+        (1, 8, "SWAP takes no operand".to_string())
+    );
+    assert_eq!(
+        error("  LOAD_GLOBAL 256\n"),
+        (1, 15, "256 is out of range (0 to 255)".to_string())
+    );
+    assert_eq!(
+        error("  PUSH 1\n"),
+        (1, 3, "unknown instruction 'PUSH'".to_string())
+    );
+}
+
+#[test]
+fn swap_and_rot_need_enough_values() {
+    for source in ["  LOAD_CONST 1\n  SWAP\n", "  LOAD_CONST 1\n  DUP\n  ROT\n"] {
+        let bytecode = asm::assemble(source, "<test>").unwrap();
+        let err = vm::execute_with_io(&bytecode, &mut Cursor::new(&b""[..]), &mut Vec::new())
+            .expect_err("should underflow");
+        assert_eq!(err.to_string(), "runtime: stack underflow");
+    }
+}
+
+#[test]
+fn expressions_and_raw_bytes_reach_any_offset() {
+    let bytecode = asm::assemble(
+        ".set skip 2 * (1 + 1)\n  JUMP body+1\nbody:\n  LOAD_CONST 1\n  JUMP $ + skip + 3\n  .byte 0xFF, skip\n  HALT\n",
+        "<test>",
+    )
+    .unwrap();
+    let code = bytecode::decode(&bytecode).unwrap().code;
+    assert_eq!(&code[..5], &[0x0B, 6, 0, 0, 0]);
+    assert_eq!(&code[10..], &[0x0B, 17, 0, 0, 0, 0xFF, 4, 0x01]);
+}
+
+#[test]
+fn macros_and_repeats_expand_inline() {
+    let source = r#"
+.macro count_down cell, from
+    LOAD_CONST \from
+    STORE_GLOBAL \cell
+loop\@:
+    LOAD_GLOBAL \cell
+    JUMP_IF_FALSE done\@
+    LOAD_GLOBAL \cell
+    PRINT
+    LOAD_GLOBAL \cell
+    LOAD_CONST 1
+    SUB
+    STORE_GLOBAL \cell
+    JUMP loop\@
+done\@:
+.endm
+    count_down aura, 2
+    count_down peak, 1
+.rept 300
+    LOAD_CONST 1
+.endr
+.rept 299
+    ADD
+.endr
+    PRINT
+    HALT
+"#;
+    let bytecode = asm::assemble(source, "<test>").unwrap();
+    assert_eq!(run(&bytecode), "2\n1\n1\n300\n");
+
+    let error = |source: &str| {
+        let e = asm::assemble(source, "<test>").expect_err("should not assemble");
+        (e.line, e.message)
+    };
+    assert_eq!(
+        error(".macro two a, b\n  LOAD_CONST \\a\n.endm\n  two 1\n"),
+        (4, "macro 'two' takes 2 argument(s), got 1".to_string())
+    );
+    assert_eq!(
+        error(".macro again\n  again\n.endm\n  again\n"),
+        (2, "macros nest more than 64 deep".to_string())
+    );
+    assert_eq!(
+        error(".rept 2\n  HALT\n"),
+        (1, ".rept without .endr".to_string())
+    );
+}
+
+#[test]
+fn disassembly_reassembles_byte_for_byte() {
+    for example in ["v1", "v2", "v3", "v4"] {
+        let path = format!("examples/{}.brainrot", example);
+        let source = std::fs::read_to_string(&path).unwrap();
+        let bytecode = brvm::compiler::compile_source(&source, &path, &Default::default()).unwrap();
+        assert_eq!(asm::roundtrip(&bytecode), Ok(()), "{}", example);
+    }
+
+    // Duplicate and unusual constants keep their places in the pool, function
+    // names come from their recorded constant, and bytes that are not whole
+    // instructions survive as `.byte`.
+    let odd = bytecode::Module {
+        version: 5,
+        flags: bytecode::FLAG_GLOBALS | bytecode::FLAG_STACK_DEPTH | 0x8000,
+        globals: 3,
+        constants: vec![
+            Value::String(Rc::new("f".to_string())),
+            Value::Number(1.0),
+            Value::Number(1.0),
+            Value::Number(-0.0),
+            Value::Number(f64::from_bits(0x7ff8_0000_0000_0001)),
+            Value::Number(f64::INFINITY),
+            Value::String(Rc::new("tab\t\"q\" ; \u{7} é".to_string())),
+            Value::Big(Rc::new(BigInt::parse("-123456789012345678901").unwrap())),
+            Value::String(Rc::new("f".to_string())),
+        ],
+        functions: vec![bytecode::FunctionEntry {
+            name_const: 8,
+            arity: 0,
+            local_count: 2,
+            code_offset: 10,
+            max_stack: 1,
+        }],
+        code: vec![0x02, 2, 0, 0, 0, 0x0D, 2, 0, 0, 0, 0xEE, 0x0B, 1, 0],
+    };
+    let bytecode = bytecode::encode(&odd);
+    assert_eq!(asm::roundtrip(&bytecode), Ok(()));
+
+    let mut v4 = odd;
+    v4.version = 4;
+    v4.flags = 0;
+    v4.code.truncate(10);
+    assert_eq!(asm::roundtrip(&bytecode::encode(&v4)), Ok(()));
+
+    // Offsets in a listing are checked against where the line lands.
+    let listing = disasm::disassemble(&bytecode)
+        .unwrap()
+        .replace("0x0005", "0x0006");
+    let e = asm::assemble(&listing, "<test>").expect_err("should not assemble");
+    assert!(e.message.contains("this is offset 0x0005"), "{}", e.message);
+}
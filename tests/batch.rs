@@ -0,0 +1,111 @@
+use brvm::compiler;
+use brvm::serve::Limits;
+use brvm::{base64, batch};
+
+fn run(input: &str) -> Vec<String> {
+    let mut out = Vec::new();
+    batch::run(input.as_bytes(), &mut out, &Limits::default()).unwrap();
+    String::from_utf8(out)
+        .unwrap()
+        .lines()
+        .map(str::to_string)
+        .collect()
+}
+
+#[test]
+fn each_request_gets_one_result_line() {
+    let bytecode = compiler::compile_source(
+        "LOCK IN\nSAY \"hi\"\nRETREAT 9\nITS OVER\n",
+        "<test>",
+        &Default::default(),
+    )
+    .unwrap();
+    let input = format!(
+        "{}\n\n{}\nnot json\n",
+        r#"{"id": 1, "source": "LOCK IN\nSAY \"wsg \" 💀 TOUCHY()\nITS OVER\n", "stdin": "bob\n"}"#,
+        format_args!(
+            r#"{{"id": "two", "bytecode": "{}"}}"#,
+            base64::encode(&bytecode)
+        )
+    );
+    let lines = run(&input);
+    assert_eq!(lines.len(), 3, "{:?}", lines);
+    assert_eq!(
+        lines[0],
+        r#"{"id": 1, "ok": true, "stage": null, "stdout": "wsg bob\n", "exit_status": 0, "instructions": 5, "error": null, "diagnostics": []}"#
+    );
+    assert!(
+        lines[1].starts_with(
+            r#"{"id": "two", "ok": true, "stage": null, "stdout": "hi\n", "exit_status": 9,"#
+        ),
+        "{}",
+        lines[1]
+    );
+    assert!(
+        lines[2].contains(r#""stage": "request", "stdout": "", "exit_status": null"#),
+        "{}",
+        lines[2]
+    );
+}
+
+#[test]
+fn compile_errors_and_warnings_are_diagnostics() {
+    let lines = run(concat!(
+        r#"{"source": "LOCK IN\nSAY 1 = 2\nITS OVER\n"}"#,
+        "\n",
+        r#"{"source": "LOCK IN\nSKIBIDI \"\"\nSAY 1\nRIZZUP\nITS OVER\n"}"#,
+        "\n",
+    ));
+    assert!(
+        lines[0].contains(r#""stage": "compile", "stdout": "", "exit_status": 3,"#),
+        "{}",
+        lines[0]
+    );
+    assert!(
+        lines[0].contains(r#"[{"severity": "error", "lint": null, "line": 2, "col": 7,"#),
+        "{}",
+        lines[0]
+    );
+    assert!(lines[1].contains(r#""ok": true"#), "{}", lines[1]);
+    assert!(
+        lines[1].contains(r#"[{"severity": "warning", "lint": "constant-condition", "line": 2,"#),
+        "{}",
+        lines[1]
+    );
+}
+
+#[test]
+fn requests_can_lower_the_limits() {
+    let lines = run(concat!(
+        r#"{"id": 1, "source": "LOCK IN\nFANUMTAX aura FR 1\nSKIBIDI aura\nRIZZUP\nITS OVER\n", "max_instructions": 500}"#,
+        "\n",
+        r#"{"id": 2, "source": "LOCK IN\nSAY \"spam spam spam\"\nITS OVER\n", "max_output_bytes": 4}"#,
+        "\n",
+        r#"{"id": 3, "source": "LOCK IN\nITS OVER\n", "max_instructions": -1}"#,
+        "\n",
+    ));
+    assert!(
+        lines[0].contains(r#""stage": "runtime", "stdout": "", "exit_status": 5, "instructions": 500, "error": "runtime: instruction limit exceeded""#),
+        "{}",
+        lines[0]
+    );
+    assert!(lines[1].contains("output limit exceeded"), "{}", lines[1]);
+    assert!(
+        lines[2].contains(r#""error": "bad request: \"max_instructions\" must be a whole number""#),
+        "{}",
+        lines[2]
+    );
+}
+
+#[test]
+fn bytecode_that_will_not_load_is_its_own_stage() {
+    let lines = run(&format!(
+        r#"{{"bytecode": "{}"}}"#,
+        base64::encode(b"not bytecode")
+    ));
+    assert!(
+        lines[0].contains(r#""stage": "bytecode", "stdout": "", "exit_status": 4,"#),
+        "{}",
+        lines[0]
+    );
+}
@@ -0,0 +1,171 @@
+use brvm::{bytecode, vm};
+use std::io::Cursor;
+use std::time::{Duration, Instant};
+
+fn header() -> Vec<u8> {
+    let mut bytes = bytecode::MAGIC.to_vec();
+    bytes.extend_from_slice(&bytecode::VERSION.to_le_bytes());
+    bytes.extend_from_slice(&0u16.to_le_bytes());
+    bytes
+}
+
+fn push_u32(bytes: &mut Vec<u8>, value: u32) {
+    bytes.extend_from_slice(&value.to_le_bytes());
+}
+
+fn assert_rejected(bytes: &[u8], expected: &str) {
+    let started = Instant::now();
+    let err = bytecode::decode(bytes).expect_err("malformed bytecode should be rejected");
+    assert!(
+        started.elapsed() < Duration::from_millis(100),
+        "slow rejection"
+    );
+    assert!(err.contains(expected), "unexpected error: {}", err);
+
+    let mut output = Vec::new();
+    let err = vm::execute_with_io(bytes, &mut Cursor::new(&b""[..]), &mut output)
+        .expect_err("vm should refuse to load malformed bytecode");
+    assert!(
+        err.to_string().contains(expected),
+        "unexpected error: {}",
+        err
+    );
+}
+
+#[test]
+fn rejects_constant_count_larger_than_file() {
+    let mut bytes = header();
+    push_u32(&mut bytes, 4_000_000);
+    assert_rejected(&bytes, "constant pool: declared count 4000000");
+
+    let mut bytes = header();
+    push_u32(&mut bytes, u32::MAX);
+    assert_rejected(
+        &bytes,
+        "constant pool: declared count 4294967295 exceeds limit",
+    );
+}
+
+#[test]
+fn rejects_string_length_larger_than_file() {
+    let mut bytes = header();
+    push_u32(&mut bytes, 1);
+    bytes.push(2);
+    push_u32(&mut bytes, u32::MAX);
+    bytes.extend_from_slice(b"hi");
+    assert_rejected(
+        &bytes,
+        "string #0 declares length 4294967295 but only 2 bytes remain",
+    );
+}
+
+#[test]
+fn rejects_function_count_larger_than_file() {
+    let mut bytes = header();
+    push_u32(&mut bytes, 0);
+    push_u32(&mut bytes, 1_000);
+    assert_rejected(&bytes, "function table: declared count 1000 does not fit");
+
+    let mut bytes = header();
+    push_u32(&mut bytes, 0);
+    push_u32(&mut bytes, u32::MAX);
+    assert_rejected(
+        &bytes,
+        "function table: declared count 4294967295 exceeds limit",
+    );
+}
+
+#[test]
+fn rejects_code_size_larger_than_file() {
+    let mut bytes = header();
+    push_u32(&mut bytes, 0);
+    push_u32(&mut bytes, 0);
+    push_u32(&mut bytes, u32::MAX);
+    bytes.push(0x01);
+    assert_rejected(
+        &bytes,
+        "code section: declared size 4294967295 exceeds remaining 1 bytes",
+    );
+}
+
+#[test]
+fn rejects_arity_larger_than_local_count() {
+    let mut bytes = header();
+    push_u32(&mut bytes, 1);
+    bytes.push(2);
+    push_u32(&mut bytes, 1);
+    bytes.push(b'f');
+    push_u32(&mut bytes, 1);
+    push_u32(&mut bytes, 0);
+    bytes.extend_from_slice(&2u16.to_le_bytes());
+    bytes.extend_from_slice(&0u16.to_le_bytes());
+    push_u32(&mut bytes, 0);
+    push_u32(&mut bytes, 1);
+    bytes.push(0x01);
+    assert_rejected(&bytes, "function entry: arity 2 exceeds local count 0");
+}
+
+#[test]
+fn vm_rejects_newer_versions() {
+    let mut bytes = bytecode::MAGIC.to_vec();
+    bytes.extend_from_slice(&(bytecode::VERSION + 1).to_le_bytes());
+    bytes.extend_from_slice(&0u16.to_le_bytes());
+    push_u32(&mut bytes, 0);
+    push_u32(&mut bytes, 0);
+    push_u32(&mut bytes, 1);
+    bytes.push(0x01);
+    let err = vm::execute_with_io(&bytes, &mut Cursor::new(&b""[..]), &mut Vec::new())
+        .expect_err("newer bytecode should be rejected");
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "runtime: unsupported bytecode version {} (this VM reads up to {})",
+            bytecode::VERSION + 1,
+            bytecode::VERSION
+        )
+    );
+}
+
+// LOAD_CONST 0, STORE_GLOBAL `slot`, LOAD_GLOBAL `slot`, PRINT, HALT with a
+// header declaring `globals` slots.
+fn uses_global(globals: u16, slot: u8) -> Vec<u8> {
+    let mut bytes = bytecode::MAGIC.to_vec();
+    bytes.extend_from_slice(&bytecode::VERSION.to_le_bytes());
+    bytes.extend_from_slice(&bytecode::FLAG_GLOBALS.to_le_bytes());
+    bytes.extend_from_slice(&globals.to_le_bytes());
+    push_u32(&mut bytes, 1);
+    bytes.push(1);
+    bytes.extend_from_slice(&42f64.to_le_bytes());
+    push_u32(&mut bytes, 0);
+    push_u32(&mut bytes, 11);
+    bytes.push(0x02);
+    push_u32(&mut bytes, 0);
+    bytes.extend_from_slice(&[0x04, slot, 0x03, slot, 0x09, 0x01]);
+    bytes
+}
+
+#[test]
+fn header_declares_global_slots() {
+    let bytes = uses_global(200, 199);
+    let module = bytecode::decode(&bytes).unwrap();
+    assert_eq!(module.globals, 200);
+    assert_eq!(bytecode::encode(&module), bytes);
+
+    let mut output = Vec::new();
+    let result = vm::execute_with_io(&bytes, &mut Cursor::new(&b""[..]), &mut output).unwrap();
+    assert_eq!(output, b"42\n");
+    assert_eq!(result.globals.len(), 200);
+
+    let err = vm::execute_with_io(
+        &uses_global(3, 5),
+        &mut Cursor::new(&b""[..]),
+        &mut Vec::new(),
+    )
+    .expect_err("slot 5 of 3");
+    assert_eq!(err.to_string(), "runtime: global index out of bounds");
+
+    assert_rejected(
+        &uses_global(300, 0),
+        "header: declared global count 300 exceeds limit 256",
+    );
+}
@@ -0,0 +1,360 @@
+use brvm::value::Value;
+use brvm::{bytecode, compiler, disasm, vm};
+use std::cell::RefCell;
+use std::io::Cursor;
+use std::rc::Rc;
+
+fn compile(source: &str) -> Vec<u8> {
+    compiler::compile_source(source, "<test>", &Default::default()).expect("should compile")
+}
+
+fn run(bytecode: &[u8]) -> String {
+    let mut output = Vec::new();
+    vm::execute_with_io(bytecode, &mut Cursor::new(&b""[..]), &mut output).expect("should run");
+    String::from_utf8(output).unwrap()
+}
+
+fn count_ops(bytecode: &[u8], name: &str) -> usize {
+    disasm::disassemble(bytecode)
+        .unwrap()
+        .lines()
+        .filter(|line| line.split_whitespace().nth(1) == Some(name))
+        .count()
+}
+
+// `peak` is read by the loop condition and body; `count` never assigns it.
+fn counting_loop(body_extra: &str) -> String {
+    format!(
+        r#"
+TRALALERO count(n)
+  FANUMTAX i FR n
+  FANUMTAX total FR 0
+  SKIBIDI i 😏 peak
+    FANUMTAX total FR total 💀 peak
+    FANUMTAX i FR i 😭 1
+{}  RIZZUP
+  RETREAT total
+TRALALA
+
+LOCK IN
+FANUMTAX peak FR 2
+SAY count(5)
+ITS OVER
+"#,
+        body_extra
+    )
+}
+
+#[test]
+fn repeated_operand_uses_dup() {
+    let bytecode = compile(
+        "LOCK IN\nFANUMTAX aura FR 7\nSAY aura 😏 aura\nSAY aura 😏 2 💀 aura 😏 2\nITS OVER\n",
+    );
+    assert_eq!(run(&bytecode), "49\n28\n");
+    assert_eq!(count_ops(&bytecode, "DUP"), 2);
+    assert_eq!(count_ops(&bytecode, "LOAD_GLOBAL"), 2);
+}
+
+#[test]
+fn reused_first_operand_is_swapped_back_up() {
+    let source = "LOCK IN\nFANUMTAX aura FR 6\nFANUMTAX peak FR 3\nSAY aura 🚡 peak 😏 2 💀 aura 🚡 peak\nSAY aura 😏 2 💀 aura\nITS OVER\n";
+    let bytecode = compile(source);
+    // Only the first line repeats more than a single load.
+    assert_eq!(count_ops(&bytecode, "SWAP"), 1);
+    assert_eq!(count_ops(&bytecode, "DIV"), 1);
+    assert_eq!(run(&bytecode), "6\n18\n");
+
+    let options = compiler::CompileOptions {
+        version: Some(4),
+        ..Default::default()
+    };
+    let bytecode = compiler::compile_source(source, "<test>", &options).unwrap();
+    assert_eq!(count_ops(&bytecode, "SWAP"), 0);
+    assert_eq!(run(&bytecode), "6\n18\n");
+}
+
+#[test]
+fn calls_are_never_duplicated() {
+    let bytecode = compile("LOCK IN\nSAY RANDOM(10) 💀 RANDOM(10)\nITS OVER\n");
+    assert_eq!(count_ops(&bytecode, "DUP"), 0);
+    assert_eq!(count_ops(&bytecode, "CALL_BUILTIN"), 2);
+}
+
+#[test]
+fn loop_invariant_braincell_is_hoisted() {
+    let hoisted = compile(&counting_loop(""));
+    assert_eq!(run(&hoisted), "10\n");
+
+    // Before hoisting the loop read `peak` from the globals twice per pass;
+    // now one load runs ahead of the loop.
+    assert_eq!(count_ops(&hoisted, "LOAD_GLOBAL"), 1);
+    assert!(disasm::disassemble(&hoisted)
+        .unwrap()
+        .contains("count(arity 1, locals 4, stack 2)"));
+}
+
+#[test]
+fn loop_that_assigns_braincell_is_not_hoisted() {
+    // The body assigns `peak`, so the reads stay as they were.
+    let bytecode = compile(&counting_loop("    FANUMTAX peak FR 0\n"));
+    assert_eq!(run(&bytecode), "10\n");
+    assert_eq!(count_ops(&bytecode, "LOAD_GLOBAL"), 2);
+    assert!(disasm::disassemble(&bytecode)
+        .unwrap()
+        .contains("count(arity 1, locals 4, stack 2)"));
+}
+
+#[test]
+fn programs_that_poke_braincells_are_not_hoisted() {
+    // POKE could store `peak` from inside the loop, so nothing is cached.
+    let bytecode = compile(&counting_loop(
+        "    FANUMTAX poked FR POKE(\"peak\", peak)\n",
+    ));
+    assert_eq!(run(&bytecode), "10\n");
+    assert_eq!(count_ops(&bytecode, "LOAD_GLOBAL"), 3);
+    assert!(disasm::disassemble(&bytecode)
+        .unwrap()
+        .contains("count(arity 1, locals 4, stack 2)"));
+}
+
+#[test]
+fn constant_subexpressions_are_folded() {
+    let source = "BASED MINUTE FR 60\nLOCK IN\nSAY MINUTE 😏 60 💀 1 🚡 4\nFANUMTAX aura FR 0\nONGOD aura\n  SAY 1 🚡 0\nDEADASS\nITS OVER\n";
+    let folded = compile(source);
+    assert_eq!(run(&folded), "3600.25\n");
+    assert_eq!(count_ops(&folded, "MUL"), 0);
+    assert_eq!(count_ops(&folded, "ADD"), 0);
+    // Division by zero is left for the VM to report.
+    assert_eq!(count_ops(&folded, "DIV"), 1);
+
+    let options = compiler::CompileOptions {
+        no_optimize: true,
+        ..Default::default()
+    };
+    let plain = compiler::compile_source(source, "<test>", &options).unwrap();
+    assert_eq!(run(&plain), "3600.25\n");
+    assert_eq!(count_ops(&plain, "MUL"), 1);
+}
+
+#[test]
+fn loop_invariant_subexpression_is_hoisted() {
+    let source = r#"
+TRALALERO count(n)
+  FANUMTAX i FR 0
+  SKIBIDI i 😭 n 😏 2
+    SAY n 😏 2 😭 i
+    FANUMTAX i FR i 💀 1
+  RIZZUP
+TRALALA
+
+LOCK IN
+FANUMTAX aura FR count(2)
+ITS OVER
+"#;
+    let hoisted = compile(source);
+    assert_eq!(run(&hoisted), "4\n3\n2\n1\n");
+    // `n 😏 2` runs once before the loop and is read back in the condition
+    // and the body.
+    assert_eq!(count_ops(&hoisted, "MUL"), 1);
+    assert!(disasm::disassemble(&hoisted)
+        .unwrap()
+        .contains("count(arity 1, locals 3, stack 2)"));
+
+    let options = compiler::CompileOptions {
+        no_optimize: true,
+        ..Default::default()
+    };
+    let plain = compiler::compile_source(source, "<test>", &options).unwrap();
+    assert_eq!(run(&plain), "4\n3\n2\n1\n");
+    assert_eq!(count_ops(&plain, "MUL"), 2);
+}
+
+// Runs `bytecode` and counts the MUL instructions it dispatches.
+fn run_counting_mul(bytecode: &[u8]) -> (String, usize) {
+    let count = Rc::new(RefCell::new(0));
+    let mut output = Vec::new();
+    let mut machine = vm::VM::builder().stdout(&mut output).build();
+    let seen = count.clone();
+    machine.on_instruction(move |_, op| {
+        if op == 0x07 {
+            *seen.borrow_mut() += 1;
+        }
+    });
+    machine.execute(bytecode).expect("should run");
+    drop(machine);
+    let count = *count.borrow();
+    (String::from_utf8(output).unwrap(), count)
+}
+
+#[test]
+fn body_only_invariant_is_evaluated_once() {
+    let source = |n: u32| {
+        format!(
+            r#"
+TRALALERO scale(n, k)
+  FANUMTAX i FR 0
+  FANUMTAX total FR 0
+  SKIBIDI i 😭 n
+    FANUMTAX total FR total 💀 k 😏 3
+    FANUMTAX i FR i 💀 1
+  RIZZUP
+  RETREAT total
+TRALALA
+
+LOCK IN
+SAY scale({}, 2)
+ITS OVER
+"#,
+            n
+        )
+    };
+
+    // `k 😏 3` runs once, after the first test passes, not once per pass.
+    let (output, muls) = run_counting_mul(&compile(&source(5)));
+    assert_eq!(output, "30\n");
+    assert_eq!(muls, 1);
+
+    // A loop that never runs never evaluates it.
+    let (output, muls) = run_counting_mul(&compile(&source(0)));
+    assert_eq!(output, "0\n");
+    assert_eq!(muls, 0);
+
+    let options = compiler::CompileOptions {
+        no_optimize: true,
+        ..Default::default()
+    };
+    let plain = compiler::compile_source(&source(5), "<test>", &options).unwrap();
+    assert_eq!(run_counting_mul(&plain), ("30\n".to_string(), 5));
+}
+
+#[test]
+fn body_invariant_after_output_is_not_hoisted() {
+    // `k 🚡 0` fails, but only after SAY has printed; hoisting it would
+    // fail before the output.
+    let bytecode = compile(
+        "TRALALERO f(k)\n  FANUMTAX i FR 0\n  SKIBIDI i 😭 1\n    SAY \"pass\"\n    FANUMTAX x FR k 🚡 0\n    FANUMTAX i FR i 💀 1\n  RIZZUP\n  RETREAT 0\nTRALALA\n\nLOCK IN\nSAY f(1)\nITS OVER\n",
+    );
+    let mut output = Vec::new();
+    vm::execute_with_io(&bytecode, &mut Cursor::new(&b""[..]), &mut output)
+        .expect_err("the division fails");
+    assert_eq!(output, b"pass\n");
+}
+
+#[test]
+fn function_table_records_max_stack_depth() {
+    let bytecode = compile(
+        "TRALALERO f(a, b, c)\n  RETREAT a 💀 b 😏 c 😭 1\nTRALALA\nLOCK IN\nSAY f(1, 2, 3)\nITS OVER\n",
+    );
+    let mut module = bytecode::decode(&bytecode).unwrap();
+    assert_eq!(module.flags, bytecode::FLAG_STACK_DEPTH);
+    assert_eq!(module.functions[0].max_stack, 3);
+    assert_eq!(bytecode::encode(&module), bytecode);
+    assert!(disasm::disassemble(&bytecode)
+        .unwrap()
+        .contains("f(arity 3, locals 3, stack 3)"));
+
+    // The depth is only a hint: without it the program runs the same.
+    module.flags = 0;
+    assert_eq!(run(&bytecode::encode(&module)), run(&bytecode));
+    assert_eq!(run(&bytecode), "6\n");
+}
+
+// FANUMTAX stores the value it was given, so two braincells can share one
+// string; DIDDLE stores a copy that shares nothing.
+#[test]
+fn diddle_copies_where_fanumtax_aliases() {
+    let bytecode = compile(
+        "LOCK IN\nFANUMTAX aura FR TOUCHY()\nFANUMTAX peak FR aura\nDIDDLE goon FR aura\nITS OVER\n",
+    );
+    assert_eq!(count_ops(&bytecode, "CLONE"), 1);
+
+    let result = vm::execute_with_io(&bytecode, &mut Cursor::new(&b"hi\n"[..]), &mut Vec::new())
+        .expect("should run");
+    let string = |slot: usize| match &result.globals[slot] {
+        Some(Value::String(s)) => s.clone(),
+        other => panic!("expected a string, got {:?}", other),
+    };
+    let (aura, peak, goon) = (string(0), string(1), string(2));
+    assert!(Rc::ptr_eq(&aura, &peak));
+    assert!(!Rc::ptr_eq(&aura, &goon));
+    assert_eq!(goon, aura);
+}
+
+#[test]
+fn version_4_target_avoids_newer_opcodes() {
+    let source = "TRALALERO count(n)
+  ONGOD n
+    RETREAT count(n 😭 1)
+  DEADASS
+  RETREAT RIZZED(\"ab\") 😏 RIZZED(\"ab\")
+TRALALA
+
+LOCK IN
+DIDDLE aura FR count(3)
+SAY aura
+ITS OVER
+";
+    let options = compiler::CompileOptions {
+        version: Some(4),
+        ..Default::default()
+    };
+    let bytecode = compiler::compile_source(source, "<test>", &options).unwrap();
+    assert_eq!(bytecode::decode(&bytecode).unwrap().version, 4);
+    for op in ["TAILCALL", "DUP", "CALL_BUILTIN", "CLONE"] {
+        assert_eq!(count_ops(&bytecode, op), 0, "{} emitted", op);
+    }
+    assert_eq!(run(&bytecode), "4\n");
+
+    let err = compiler::compile_source("LOCK IN\nYAP 1\nITS OVER\n", "<test>", &options)
+        .expect_err("YAP needs version 5");
+    assert_eq!(err.line, 2);
+    assert_eq!(
+        err.message,
+        "YAP needs bytecode version 5, but version 4 was requested"
+    );
+}
+
+#[test]
+#[ignore = "benchmark; run with --ignored --nocapture"]
+fn bench_hoisted_counting_loop() {
+    let source = counting_loop("").replace("count(5)", "count(1000000)");
+    let bytecode = compile(&source);
+    let started = std::time::Instant::now();
+    assert_eq!(run(&bytecode), "2000000\n");
+    println!(
+        "1,000,000 iterations: {:?} ({} LOAD_GLOBAL in listing)",
+        started.elapsed(),
+        count_ops(&bytecode, "LOAD_GLOBAL")
+    );
+}
+
+#[test]
+#[ignore = "benchmark; run with --ignored --nocapture"]
+fn bench_string_constant_loads() {
+    let text = "brainrot ".repeat(1000);
+    let source = format!(
+        r#"
+TRALALERO spin(n)
+  FANUMTAX s FR ""
+  SKIBIDI n
+    FANUMTAX s FR "{text}"
+    FANUMTAX s FR "{text}"
+    FANUMTAX n FR n 😭 1
+  RIZZUP
+  RETREAT EMPTY(s)
+TRALALA
+
+LOCK IN
+SAY spin(1000000)
+ITS OVER
+"#
+    );
+    let bytecode = compile(&source);
+    let started = std::time::Instant::now();
+    assert_eq!(run(&bytecode), "0\n");
+    println!(
+        "2,000,000 loads of a {}-byte string: {:?}",
+        text.len(),
+        started.elapsed()
+    );
+}
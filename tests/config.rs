@@ -0,0 +1,88 @@
+use brvm::config::{self, Entry, Setting};
+
+#[test]
+fn settings_are_read_per_command() {
+    let text = "# defaults for every command
+seed = 0
+color = \"never\"  # not a # comment inside the string
+
+[compile]
+no-optimize = true
+deny = ['unused-assignment', \"unreachable-code\"]
+max_call_depth = 1_000
+";
+    let entry = |command: Option<&str>, option: &str, value: Setting, line| Entry {
+        command: command.map(str::to_string),
+        option: option.to_string(),
+        value,
+        line,
+    };
+    let one = |value: &str| Setting::One(value.to_string());
+    assert_eq!(
+        config::parse(text, "brvm.toml").unwrap(),
+        [
+            entry(None, "seed", one("0"), 2),
+            entry(None, "color", one("never"), 3),
+            entry(Some("compile"), "no_optimize", one("true"), 6),
+            entry(
+                Some("compile"),
+                "deny",
+                Setting::List(vec![
+                    "unused-assignment".to_string(),
+                    "unreachable-code".to_string()
+                ]),
+                7
+            ),
+            entry(Some("compile"), "max_call_depth", one("1000"), 8),
+        ]
+    );
+}
+
+#[test]
+fn malformed_lines_are_located() {
+    let error = |text: &str| {
+        let e = config::parse(text, "brvm.toml").unwrap_err();
+        (e.line, e.col, e.message)
+    };
+    assert_eq!(
+        error("seed = 1\n[run]\nseed = 2\nseed-x = on\n"),
+        (
+            4,
+            10,
+            "expected a string, number or boolean, found 'on'".to_string()
+        )
+    );
+    assert_eq!(
+        error("[run]\nseed = 1\nseed = 2\n"),
+        (3, 1, "'seed' is set twice".to_string())
+    );
+    assert_eq!(
+        error("  color = \"never\n"),
+        (1, 11, "unterminated string".to_string())
+    );
+    assert_eq!(
+        error("[run\n"),
+        (1, 1, "expected ']' after the table name".to_string())
+    );
+}
+
+#[test]
+fn the_nearest_project_file_wins() {
+    let dir = std::env::temp_dir().join(format!("brvm-config-{}", std::process::id()));
+    let nested = dir.join("a").join("b");
+    std::fs::create_dir_all(&nested).unwrap();
+    assert_eq!(config::find_project_file(&nested), None);
+
+    std::fs::write(dir.join(config::PROJECT_FILE), "").unwrap();
+    assert_eq!(
+        config::find_project_file(&nested),
+        Some(dir.join(config::PROJECT_FILE))
+    );
+    std::fs::write(dir.join("a").join(config::PROJECT_FILE), "").unwrap();
+    assert_eq!(
+        config::find_project_file(&nested),
+        Some(dir.join("a").join(config::PROJECT_FILE))
+    );
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,86 @@
+use brvm::{compiler, vm};
+use std::io::Cursor;
+
+fn compile(source: &str) -> Result<Vec<u8>, String> {
+    compiler::compile_source(source, "<test>", &Default::default()).map_err(|e| e.to_string())
+}
+
+fn run(source: &str) -> String {
+    let bytecode = compile(source).expect("should compile");
+    let mut output = Vec::new();
+    vm::execute_with_io(&bytecode, &mut Cursor::new(&b""[..]), &mut output).expect("should run");
+    String::from_utf8(output).unwrap()
+}
+
+#[test]
+fn constants_work_in_main_and_functions() {
+    let output = run(r#"
+BASED LAPS FR 3
+BASED PI FR 3.5
+
+TRALALERO circle(r)
+  RETREAT PI 😏 r 😏 2
+TRALALA
+
+BASED CHEER FR "go"
+
+LOCK IN
+FANUMTAX aura FR LAPS
+SKIBIDI aura
+  SAY CHEER 💀 " " 💀 aura
+  FANUMTAX aura FR aura 😭 1
+RIZZUP
+SAY circle(LAPS)
+ITS OVER
+"#);
+    assert_eq!(output, "go 3\ngo 2\ngo 1\n21\n");
+}
+
+#[test]
+fn constants_cannot_be_reassigned_or_shadowed() {
+    let cases = [
+        (
+            "BASED N FR 1\nTRALALERO f()\n  FANUMTAX N FR 2\n  RETREAT N\nTRALALA\nLOCK IN\nSAY f()\nITS OVER\n",
+            "cannot assign to constant 'N'",
+        ),
+        (
+            "BASED N FR 1\nTRALALERO f()\n  DIDDLE N FR 2\nTRALALA\nLOCK IN\nSAY 1\nITS OVER\n",
+            "cannot assign to constant 'N'",
+        ),
+        (
+            "BASED N FR 1\nTRALALERO f(N)\n  RETREAT N\nTRALALA\nLOCK IN\nSAY 1\nITS OVER\n",
+            "parameter 'N' in function f shadows a constant",
+        ),
+        (
+            "BASED f FR 1\nTRALALERO f()\n  RETREAT 1\nTRALALA\nLOCK IN\nSAY 1\nITS OVER\n",
+            "function name is already a constant: f",
+        ),
+        (
+            "BASED N FR 1\nBASED N FR 2\nLOCK IN\nSAY N\nITS OVER\n",
+            "duplicate constant: N",
+        ),
+        (
+            "BASED aura FR 1\nLOCK IN\nSAY 1\nITS OVER\n",
+            "constant name cannot be a braincell",
+        ),
+        (
+            "BASED RANDOM FR 1\nLOCK IN\nSAY 1\nITS OVER\n",
+            "constant name is reserved for built-in: RANDOM",
+        ),
+    ];
+    for (source, expected) in cases {
+        let err = compile(source).expect_err("should be rejected");
+        assert!(err.contains(expected), "{}: {}", expected, err);
+    }
+}
+
+#[test]
+fn non_literal_initializer_is_rejected_with_position() {
+    let err = compile("BASED N FR 1\nBASED M FR N 💀 1\nLOCK IN\nSAY M\nITS OVER\n")
+        .expect_err("should be rejected");
+    assert!(
+        err.contains("2:12") && err.contains("constant 'M' must be a number or string literal"),
+        "{}",
+        err
+    );
+}
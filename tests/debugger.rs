@@ -0,0 +1,85 @@
+use brvm::debugger::{Debugger, State};
+
+const SOURCE: &str = "TRALALERO sq(n)
+  RETREAT n 😏 n
+TRALALA
+
+LOCK IN
+FANUMTAX aura FR 2
+SKIBIDI aura
+  SAY sq(aura)
+  FANUMTAX aura FR aura 😭 1
+RIZZUP
+SAY \"done\"
+ITS OVER
+";
+
+fn debugger() -> Debugger {
+    Debugger::new(SOURCE, "<test>", "", Default::default()).expect("program should compile")
+}
+
+#[test]
+fn next_line_steps_over_calls() {
+    let mut debugger = debugger();
+    assert_eq!(debugger.line(), Some(6));
+    let mut seen = Vec::new();
+    while matches!(debugger.state, State::Paused) {
+        seen.push(debugger.line().unwrap());
+        debugger.next_line();
+    }
+    assert_eq!(seen, [6, 7, 8, 9, 7, 8, 9, 7, 11]);
+    assert_eq!(debugger.output(), "4\n1\ndone\n");
+}
+
+#[test]
+fn stepping_instructions_enters_functions() {
+    let mut debugger = debugger();
+    while debugger.vm().call_depth() == 0 {
+        debugger.step();
+    }
+    assert_eq!(debugger.line(), Some(2));
+    assert_eq!(
+        debugger.function_name(debugger.vm().frames()[0].function),
+        "sq"
+    );
+    assert_eq!(debugger.vm().locals().len(), 1);
+}
+
+#[test]
+fn failures_point_at_the_failing_line() {
+    let mut debugger = Debugger::new(
+        "LOCK IN\nSAY 1\nSAY mog\nSAY 2\nITS OVER\n",
+        "<test>",
+        "",
+        Default::default(),
+    )
+    .unwrap();
+    debugger.resume();
+    assert!(
+        matches!(&debugger.state, State::Failed(e) if e.to_string() == "runtime: unset braincell: mog")
+    );
+    assert_eq!(debugger.line(), Some(3));
+    assert_eq!(debugger.output(), "1\n");
+}
+
+#[test]
+fn resume_stops_at_breakpoints_and_restart_starts_over() {
+    let mut debugger = debugger();
+    debugger.toggle_breakpoint(9);
+    debugger.resume();
+    assert_eq!(debugger.line(), Some(9));
+    assert_eq!(debugger.output(), "4\n");
+    debugger.resume();
+    assert_eq!(debugger.line(), Some(9));
+    assert_eq!(debugger.output(), "4\n1\n");
+
+    debugger.toggle_breakpoint(9);
+    debugger.resume();
+    assert!(matches!(debugger.state, State::Finished));
+    assert_eq!(debugger.line(), None);
+
+    debugger.restart();
+    assert!(matches!(debugger.state, State::Paused));
+    assert_eq!(debugger.output(), "");
+    assert_eq!(debugger.line(), Some(6));
+}
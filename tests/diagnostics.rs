@@ -0,0 +1,52 @@
+use brvm::compiler;
+use brvm::error::CompileError;
+
+fn compile_err(source: &str) -> CompileError {
+    compiler::compile_source(source, "demo.br", &Default::default())
+        .expect_err("compilation should fail")
+}
+
+#[test]
+fn render_shows_the_line_caret_and_help() {
+    let source = "LOCK IN\nFANUMTAX aura FR 1\nDIDDLE aura 2\nITS OVER\n";
+    assert_eq!(
+        compile_err(source).render(source, false),
+        "error: expected FR after variable\n --> demo.br:3:13\n  |\n3 | DIDDLE aura 2\n  |             ^\n  = help: copies are written DIDDLE name FR value\n"
+    );
+}
+
+#[test]
+fn carets_line_up_under_emoji_and_span_the_token() {
+    let source = "LOCK IN\nSAY 1 💀 \"two\" nope\nITS OVER\n";
+    let rendered = compile_err(source).render(source, false);
+    let lines: Vec<&str> = rendered.lines().collect();
+    assert_eq!(lines[3], "2 | SAY 1 💀 \"two\" nope");
+    assert_eq!(lines[4], "  |                ^^^^");
+}
+
+#[test]
+fn every_recovered_error_is_rendered() {
+    let source = "LOCK IN\nSAY\nSAY 1 = 2\nITS OVER\n";
+    let err = compiler::compile_source(source, "demo.br", &Default::default());
+    // The lexer stops at `=` before the parser runs.
+    assert!(err.unwrap_err().help.unwrap().contains("🟰 compares"));
+
+    let source = "LOCK IN\nFANUMTAX FR 1\nSAY\nITS OVER\n";
+    let rendered = compile_err(source).render(source, true);
+    assert_eq!(
+        rendered.matches("error\x1b[0m: ").count(),
+        2,
+        "{}",
+        rendered
+    );
+    assert!(rendered.contains("\x1b[1;31m^^\x1b[0m"), "{}", rendered);
+}
+
+#[test]
+fn errors_without_a_position_skip_the_snippet() {
+    let err = CompileError::new("demo.br", 0, 0, "code section exceeds u32 range");
+    assert_eq!(
+        err.render("LOCK IN\n", false),
+        "error: code section exceeds u32 range\n--> demo.br:0:0\n"
+    );
+}
@@ -0,0 +1,53 @@
+use brvm::doc::{self, FileDoc};
+
+const SOURCE: &str = "🖕🖕 Adds one to n.
+🖕🖕
+## Numbers <only>.
+TRALALERO inc(n: number)
+  RETREAT n 💀 1
+TRALALA
+
+🖕🖕 Detached from the function below by the blank line.
+
+🖕 An ordinary comment.
+TRALALERO pair(a, b: string)
+  RETREAT a
+TRALALA
+
+LOCK IN
+SAY inc(1) 🖕🖕 not a doc comment
+ITS OVER
+";
+
+#[test]
+fn doc_comments_attach_to_the_function_below() {
+    let functions = doc::extract(SOURCE, "lib.br").unwrap();
+    assert_eq!(functions.len(), 2);
+    assert_eq!(functions[0].signature(), "inc(n: number)");
+    assert_eq!(functions[0].doc, "Adds one to n.\n\nNumbers <only>.");
+    assert_eq!(functions[0].line, 4);
+    assert_eq!(functions[1].signature(), "pair(a, b: string)");
+    assert_eq!(functions[1].doc, "");
+}
+
+#[test]
+fn markdown_and_html_list_every_function() {
+    let files = [FileDoc {
+        path: "lib.br".to_string(),
+        functions: doc::extract(SOURCE, "lib.br").unwrap(),
+    }];
+    let markdown = doc::markdown(&files);
+    assert!(markdown.contains("## lib.br\n"));
+    assert!(markdown.contains("### `inc(n: number)`\n\nAdds one to n.\n\nNumbers <only>.\n"));
+    assert!(markdown.contains("### `pair(a, b: string)`\n"));
+
+    let html = doc::html(&files);
+    assert!(html.contains("<h3 id=\"inc\"><code>inc(n: number)</code></h3>"));
+    assert!(html.contains("<p>Adds one to n.</p>\n<p>Numbers &lt;only&gt;.</p>"));
+}
+
+#[test]
+fn source_errors_are_reported() {
+    let err = doc::extract("TRALALERO f(\nLOCK IN\nITS OVER\n", "bad.br").unwrap_err();
+    assert_eq!(err.filename, "bad.br");
+}
@@ -0,0 +1,50 @@
+use std::process::Command;
+
+// Runs the CLI away from any config file that could change its defaults.
+fn brvm(args: &[&str]) -> i32 {
+    let dir = std::env::temp_dir();
+    Command::new(env!("CARGO_BIN_EXE_brvm"))
+        .args(args)
+        .current_dir(&dir)
+        .env("XDG_CONFIG_HOME", dir.join("brvm-no-config"))
+        .output()
+        .unwrap()
+        .status
+        .code()
+        .unwrap()
+}
+
+#[test]
+fn each_failure_class_has_its_own_code() {
+    let junk = std::env::temp_dir().join(format!("brvm-exit-{}.brbc", std::process::id()));
+    std::fs::write(&junk, "not bytecode").unwrap();
+    let source = std::env::temp_dir().join(format!("brvm-exit-{}.br", std::process::id()));
+    std::fs::write(&source, "LOCK IN\nSAY 1\nITS OVER\n").unwrap();
+
+    assert_eq!(brvm(&["eval", "SAY 1"]), 0);
+    assert_eq!(brvm(&["eval", "LOCK IN RETREAT 7 ITS OVER"]), 7);
+    // A program's own status never passes for one of the failure classes.
+    assert_eq!(brvm(&["eval", "LOCK IN RETREAT 3 ITS OVER"]), 6);
+    assert_eq!(brvm(&["eval", "LOCK IN RETREAT \"done\" ITS OVER"]), 6);
+    assert_eq!(brvm(&["eval", "CRASHOUT \"boom\""]), 1);
+    assert_eq!(brvm(&["eval", "--no-such-flag", "SAY 1"]), 2);
+    assert_eq!(brvm(&["eval", "SAY 1 😭 \"x\""]), 3);
+    assert_eq!(brvm(&["exec", &junk.to_string_lossy()]), 4);
+    // Output that cannot be written is not a compile error.
+    assert_eq!(
+        brvm(&[
+            "compile",
+            &source.to_string_lossy(),
+            "-o",
+            "/nonexistent/dir/out.brbc"
+        ]),
+        1
+    );
+    assert_eq!(
+        brvm(&["eval", "--max-instructions", "100", "SKIBIDI 1 RIZZUP"]),
+        5
+    );
+
+    let _ = std::fs::remove_file(&junk);
+    let _ = std::fs::remove_file(&source);
+}
@@ -0,0 +1,234 @@
+use brvm::golden::{self, Outcome};
+use std::path::PathBuf;
+
+fn case_dir(name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("brvm-golden-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).expect("temp dir should be creatable");
+    for (file, contents) in files {
+        std::fs::write(dir.join(file), contents).expect("case file should be writable");
+    }
+    dir
+}
+
+#[test]
+fn runner_reports_pass_fail_error_and_skip() {
+    let dir = case_dir(
+        "mixed",
+        &[
+            ("a_pass.br", "LOCK IN\nSAY \"hi\"\nITS OVER\n"),
+            ("a_pass.expected", "hi\n"),
+            ("b_fail.br", "LOCK IN\nSAY 1\nSAY 2\nSAY 3\nITS OVER\n"),
+            ("b_fail.expected", "1\n2\n4\n"),
+            ("c_error.br", "LOCK IN\nSAY 1 🚡 0\nITS OVER\n"),
+            ("c_error.expected", ""),
+            ("d_skip.br", "LOCK IN\nSAY 1\nITS OVER\n"),
+            (
+                "e_stdin.brainrot",
+                "LOCK IN\nSAY \"hi \" 💀 TOUCHY()\nITS OVER\n",
+            ),
+            ("e_stdin.stdin", "Ada\n"),
+            ("e_stdin.expected", "hi Ada\n"),
+            ("notes.txt", "not a program"),
+        ],
+    );
+
+    let report = golden::run_dir(&dir).expect("directory should be readable");
+    let outcomes: Vec<(&str, &Outcome)> = report
+        .cases
+        .iter()
+        .map(|c| (c.name.as_str(), &c.outcome))
+        .collect();
+
+    assert_eq!(outcomes.len(), 5);
+    assert_eq!(outcomes[0], ("a_pass.br", &Outcome::Pass));
+    assert_eq!(
+        outcomes[1],
+        (
+            "b_fail.br",
+            &Outcome::Fail {
+                line: 3,
+                expected: Some("4\n".to_string()),
+                actual: Some("3\n".to_string()),
+            }
+        )
+    );
+    assert!(
+        matches!(outcomes[2], ("c_error.br", Outcome::Error(e)) if e.contains("division by zero"))
+    );
+    assert_eq!(outcomes[3], ("d_skip.br", &Outcome::Skipped));
+    assert_eq!(outcomes[4], ("e_stdin.brainrot", &Outcome::Pass));
+    assert!(!report.passed());
+
+    let text = report.to_string();
+    assert!(text.contains("  first difference at line 3:\n"));
+    assert!(text.ends_with("5 cases: 2 passed, 1 failed, 1 errored, 1 skipped\n"));
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn runner_passes_when_everything_matches() {
+    let dir = case_dir(
+        "clean",
+        &[
+            ("only.br", "LOCK IN\nYAP \"no newline\"\nITS OVER\n"),
+            ("only.expected", "no newline"),
+        ],
+    );
+
+    let report = golden::run_dir(&dir).expect("directory should be readable");
+
+    assert!(report.passed());
+    assert_eq!(report.cases[0].outcome, Outcome::Pass);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn missing_trailing_newline_is_a_difference() {
+    assert_eq!(
+        golden::compare_output("done\n", "done"),
+        Outcome::Fail {
+            line: 1,
+            expected: Some("done\n".to_string()),
+            actual: Some("done".to_string()),
+        }
+    );
+}
+
+#[test]
+fn expect_comments_and_test_files_in_subdirectories() {
+    let dir = case_dir(
+        "expect",
+        &[
+            (
+                "inline.br",
+                "LOCK IN\nSAY 6 😏 7 🖕 EXPECT: 42\n🖕 EXPECT: done\nSAY \"done\"\nITS OVER\n",
+            ),
+            (
+                "wrong.br",
+                "🖕 EXPECT: 1\n🖕 EXPECT: 3\nLOCK IN\nSAY 1\nSAY 2\nITS OVER\n",
+            ),
+            // An .expected file wins over comments.
+            ("file.br", "LOCK IN\nSAY 1 🖕 EXPECT: 2\nITS OVER\n"),
+            ("file.expected", "1\n"),
+        ],
+    );
+    std::fs::create_dir_all(dir.join("suite/deeper")).unwrap();
+    std::fs::write(
+        dir.join("suite/deeper/math_test.brainrot"),
+        "LOCK IN\nSAY 2 💀 2 🖕 EXPECT: 4\nITS OVER\n",
+    )
+    .unwrap();
+    std::fs::write(
+        dir.join("suite/example.br"),
+        "LOCK IN\nSAY 1 🖕 EXPECT: 1\nITS OVER\n",
+    )
+    .unwrap();
+
+    let report = golden::run_dir(&dir).expect("directory should be readable");
+    let outcomes: Vec<(String, &Outcome)> = report
+        .cases
+        .iter()
+        .map(|c| (c.name.replace('\\', "/"), &c.outcome))
+        .collect();
+    assert_eq!(
+        outcomes,
+        [
+            ("file.br".to_string(), &Outcome::Pass),
+            ("inline.br".to_string(), &Outcome::Pass),
+            (
+                "suite/deeper/math_test.brainrot".to_string(),
+                &Outcome::Pass
+            ),
+            (
+                "wrong.br".to_string(),
+                &Outcome::Fail {
+                    line: 2,
+                    expected: Some("3\n".to_string()),
+                    actual: Some("2\n".to_string()),
+                }
+            ),
+        ]
+    );
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn failures_show_a_unified_diff() {
+    assert_eq!(
+        golden::unified_diff(
+            "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\nk\n",
+            "a\nB\nc\nd\ne\nf\ng\nh\ni\nj\n"
+        ),
+        "--- expected
++++ actual
+@@ -1,5 +1,5 @@
+ a
+-b
++B
+ c
+ d
+ e
+@@ -8,4 +8,3 @@
+ h
+ i
+ j
+-k
+"
+    );
+    assert_eq!(
+        golden::unified_diff("done\n", "done"),
+        "--- expected\n+++ actual\n@@ -1 +1 @@\n-done\n+done\n\\ No newline at end of file\n"
+    );
+
+    let dir = case_dir(
+        "diff",
+        &[
+            ("only.br", "LOCK IN\nSAY 1\nSAY 2\nITS OVER\n"),
+            ("only.expected", "1\n3\n"),
+        ],
+    );
+    let report = golden::run_dir(&dir).expect("directory should be readable");
+    assert!(report
+        .to_string()
+        .contains("  @@ -1,2 +1,2 @@\n   1\n  -3\n  +2\n"));
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn bless_records_output_for_later_runs() {
+    let dir = case_dir(
+        "bless",
+        &[
+            ("new.br", "LOCK IN\nSAY \"fresh\"\nITS OVER\n"),
+            ("same.br", "LOCK IN\nSAY 1\nITS OVER\n"),
+            ("same.expected", "1\n"),
+            ("stale.br", "LOCK IN\nSAY 2\nITS OVER\n"),
+            ("stale.expected", "1\n"),
+            ("broken.br", "LOCK IN\nSAY mog\nITS OVER\n"),
+        ],
+    );
+
+    let report = golden::bless_dir(&dir).expect("directory should be readable");
+    let outcomes: Vec<&Outcome> = report.cases.iter().map(|c| &c.outcome).collect();
+    assert!(matches!(outcomes[0], Outcome::Error(_)));
+    assert_eq!(
+        outcomes[1..],
+        [&Outcome::Blessed, &Outcome::Pass, &Outcome::Blessed]
+    );
+    assert!(report
+        .to_string()
+        .ends_with("4 cases: 1 passed, 0 failed, 1 errored, 0 skipped, 2 blessed\n"));
+    assert!(!dir.join("broken.expected").exists());
+    assert_eq!(
+        std::fs::read_to_string(dir.join("stale.expected")).unwrap(),
+        "2\n"
+    );
+
+    let report = golden::run_dir(&dir).expect("directory should be readable");
+    assert_eq!(report.count(|o| matches!(o, Outcome::Pass)), 3);
+    let _ = std::fs::remove_dir_all(&dir);
+}
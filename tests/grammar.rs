@@ -0,0 +1,60 @@
+use brvm::grammar;
+use brvm::lexer::{ASCII_OPERATORS, KEYWORDS, OPERATORS};
+use brvm::parser::{Syntax, GRAMMAR};
+
+fn walk<'a>(syntax: &'a Syntax, words: &mut Vec<&'a str>, rules: &mut Vec<&'a str>) {
+    match syntax {
+        Syntax::Word(word) => words.push(word),
+        Syntax::Rule(name) => rules.push(name),
+        Syntax::Lexical(_) | Syntax::Names(_) => {}
+        Syntax::Seq(items) | Syntax::Choice(items) => {
+            items.iter().for_each(|item| walk(item, words, rules))
+        }
+        Syntax::Optional(item) | Syntax::Repeat(item) => walk(item, words, rules),
+    }
+}
+
+#[test]
+fn grammar_table_matches_the_lexer() {
+    let (mut words, mut rules) = (Vec::new(), Vec::new());
+    for (_, syntax) in GRAMMAR {
+        walk(syntax, &mut words, &mut rules);
+    }
+    let defined: Vec<&str> = GRAMMAR.iter().map(|(name, _)| *name).collect();
+
+    for (keyword, _) in KEYWORDS {
+        assert!(words.contains(keyword), "{} is not in the grammar", keyword);
+    }
+    for word in &words {
+        let known = KEYWORDS.iter().any(|(k, _)| k == word)
+            || OPERATORS
+                .iter()
+                .chain(ASCII_OPERATORS)
+                .any(|(op, _)| op.to_string() == *word)
+            || ["(", ")", ",", ":", "number", "string"].contains(word);
+        assert!(known, "the lexer has no token {:?}", word);
+    }
+    for rule in &rules {
+        assert!(defined.contains(rule), "rule {} is not defined", rule);
+    }
+    for name in &defined[1..] {
+        assert!(rules.contains(name), "rule {} is never used", name);
+    }
+}
+
+#[test]
+fn grammar_prints_as_ebnf_and_railroad() {
+    let ebnf = grammar::ebnf();
+    assert!(ebnf.contains("\nwhile = \"SKIBIDI\" , expression , { statement } , \"RIZZUP\" ;\n"));
+    assert!(ebnf.contains("\nbuiltin = \"TRANSFORM\"\n        | \"RIZZED\"\n"));
+    assert!(ebnf.contains("        | \"TOUCHY\" ;\n"));
+
+    let railroad = grammar::railroad();
+    assert!(railroad.contains(
+        "while:
+  ├─ \"SKIBIDI\" ── expression ──┬─────────────────┬── \"RIZZUP\" ─┤
+                               └─┬─ statement ─┬─┘
+                                 └──────<──────┘
+"
+    ));
+}
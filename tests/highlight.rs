@@ -0,0 +1,69 @@
+use brvm::{highlight, lexer};
+
+fn strip_tags(html: &str) -> String {
+    let mut out = String::new();
+    let mut in_tag = false;
+    for ch in html.chars() {
+        match ch {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            c if !in_tag => out.push(c),
+            _ => {}
+        }
+    }
+    out.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&amp;", "&")
+}
+
+#[test]
+fn html_keeps_the_source_text() {
+    let source =
+        std::fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/examples/v4.brainrot"))
+            .unwrap();
+    let html = highlight::html(&source, "v4.brainrot").unwrap();
+    assert_eq!(strip_tags(&html).trim_end(), source.trim_end());
+}
+
+#[test]
+fn html_marks_each_token_kind() {
+    let html = highlight::html(
+        "🖕 hi <b>\nLOCK IN\nSAY \"a&b\" 💀 RIZZED(aura) 😏 2 💀 f(1)\n🙈 x 🙉\nITS OVER\n",
+        "<test>",
+    )
+    .unwrap();
+    for expected in [
+        r#"class="br-comment" style="color:#7f849c;font-style:italic">🖕 hi &lt;b&gt;</span>"#,
+        r#"class="br-keyword" style="color:#cba6f7;font-weight:bold">SAY</span>"#,
+        r#"class="br-string" style="color:#a6e3a1">&quot;a&amp;b&quot;</span>"#,
+        r#"class="br-operator" style="color:#f38ba8">💀</span>"#,
+        r#"class="br-builtin" style="color:#f9e2af">RIZZED</span>(<span"#,
+        r#"class="br-braincell" style="color:#fab387">aura</span>"#,
+        r#"class="br-number" style="color:#89dceb">2</span>"#,
+        r#"class="br-identifier" style="color:#89b4fa">f</span>"#,
+        r#"class="br-comment" style="color:#7f849c;font-style:italic">🙈 x 🙉</span>"#,
+    ] {
+        assert!(html.contains(expected), "missing {}\n{}", expected, html);
+    }
+}
+
+#[test]
+fn html_reports_lex_errors() {
+    let err = highlight::html("LOCK IN\nSAY \"open\nITS OVER\n", "bad.br").unwrap_err();
+    assert!(err.to_string().starts_with("bad.br:"), "{}", err);
+}
+
+#[test]
+fn textmate_grammar_follows_the_lexer_tables() {
+    let grammar = highlight::textmate_grammar();
+    assert!(grammar.contains(r#""scopeName": "source.brainrot""#));
+    let alternatives: Vec<&str> = grammar.split(['(', '|', ')']).collect();
+    for (word, _) in lexer::KEYWORDS {
+        assert!(alternatives.contains(word), "{}", word);
+    }
+    for (op, _) in lexer::OPERATORS {
+        assert!(grammar.contains(*op), "{}", op);
+    }
+    assert!(grammar.contains("TRANSFORM|RIZZED"));
+}
@@ -0,0 +1,49 @@
+use brvm::{compiler, inspect};
+
+const SOURCE: &str = "TRALALERO greet(name)
+  RETREAT \"hello there, \" 💀 name
+TRALALA
+
+LOCK IN
+SAY greet(\"sigma\")
+SAY 42
+ITS OVER
+";
+
+fn row<'a>(report: &'a str, name: &str) -> Vec<&'a str> {
+    report
+        .lines()
+        .find(|line| line.starts_with(name))
+        .unwrap_or_else(|| panic!("no {} row in:\n{}", name, report))[name.len()..]
+        .split_whitespace()
+        .collect()
+}
+
+#[test]
+fn size_report_accounts_for_every_byte() {
+    let bytecode = compiler::compile_source(SOURCE, "<test>", &Default::default()).unwrap();
+    let report = inspect::size_report(&bytecode, 5).unwrap();
+
+    let sections: usize = ["header", "constant pool", "function table", "code "]
+        .iter()
+        .map(|name| row(&report, name)[0].parse::<usize>().unwrap())
+        .sum();
+    assert_eq!(sections, bytecode.len());
+
+    let module = brvm::bytecode::decode(&bytecode).unwrap();
+    let main: usize = row(&report, "(main)")[0].parse().unwrap();
+    let greet: usize = row(&report, "greet")[0].parse().unwrap();
+    assert_eq!(main, module.functions[0].code_offset as usize);
+    assert_eq!(main + greet, module.code.len());
+
+    assert_eq!(row(&report, "function name")[..2], ["10", "1"]);
+    assert_eq!(row(&report, "number")[..2], ["9", "1"]);
+    assert!(report.contains("#1          13 bytes  \"hello there, \""));
+}
+
+#[test]
+fn top_zero_hides_the_string_list() {
+    let bytecode = compiler::compile_source(SOURCE, "<test>", &Default::default()).unwrap();
+    let report = inspect::size_report(&bytecode, 0).unwrap();
+    assert!(!report.contains("largest strings"));
+}
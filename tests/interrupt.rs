@@ -0,0 +1,87 @@
+use brvm::host::VmHost;
+use brvm::{compiler, vm};
+use std::io::Cursor;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+const SPIN: &str = "LOCK IN\nFANUMTAX aura FR 1\nYAP \"started\"\nSKIBIDI aura\nRIZZUP\nITS OVER\n";
+
+fn compile(source: &str) -> Vec<u8> {
+    compiler::compile_source(source, "<test>", &Default::default()).expect("should compile")
+}
+
+fn options_with(flag: &Arc<AtomicBool>) -> vm::VmOptions {
+    vm::VmOptions {
+        interrupt: Some(flag.clone()),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn interrupt_flag_stops_a_running_loop() {
+    let flag = Arc::new(AtomicBool::new(false));
+    let setter = {
+        let flag = flag.clone();
+        std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(50));
+            flag.store(true, Ordering::Relaxed);
+        })
+    };
+
+    let mut output = Vec::new();
+    let err = vm::execute_with_options(
+        &compile(SPIN),
+        &mut Cursor::new(&b""[..]),
+        &mut output,
+        &options_with(&flag),
+    )
+    .expect_err("an endless loop should only stop when interrupted");
+    setter.join().unwrap();
+
+    assert!(err.is_interrupted());
+    assert_eq!(err.to_string(), "runtime: interrupted");
+    assert_eq!(output, b"started");
+}
+
+// Stands in for a terminal read that fails with EINTR after Ctrl-C.
+struct InterruptedRead {
+    flag: Arc<AtomicBool>,
+}
+
+impl VmHost for InterruptedRead {
+    fn print(&mut self, _: &str) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn read_line(&mut self) -> Result<String, String> {
+        self.flag.store(true, Ordering::Relaxed);
+        Err("read interrupted".to_string())
+    }
+}
+
+#[test]
+fn interrupted_input_reports_interrupt() {
+    let flag = Arc::new(AtomicBool::new(false));
+    let mut host = InterruptedRead { flag: flag.clone() };
+    let err = vm::execute_with_host_options(
+        &compile("LOCK IN\nSAY TOUCHY(\"> \")\nITS OVER\n"),
+        &mut host,
+        &options_with(&flag),
+    )
+    .expect_err("read should fail");
+    assert!(err.is_interrupted(), "{}", err);
+
+    // Without the flag the same failure is an ordinary I/O error.
+    let mut host = InterruptedRead {
+        flag: Arc::new(AtomicBool::new(false)),
+    };
+    let err = vm::execute_with_host_options(
+        &compile("LOCK IN\nSAY TOUCHY()\nITS OVER\n"),
+        &mut host,
+        &vm::VmOptions::default(),
+    )
+    .expect_err("read should fail");
+    assert!(!err.is_interrupted());
+    assert!(err.to_string().contains("read interrupted"));
+}
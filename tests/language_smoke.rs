@@ -1,10 +1,12 @@
-use brvm::{compiler, lexer, parser, vm};
+use brvm::host::VmHost;
+use brvm::value::Value;
+use brvm::{builtins, bytecode, compiler, disasm, lexer, parser, vm};
 use std::io::Cursor;
 
 fn compile_source(source: &str) -> Vec<u8> {
-    let tokens = lexer::tokenize(source, "<test>").expect("lexing should succeed");
+    let tokens = lexer::tokenize_spanned(source, "<test>").expect("lexing should succeed");
     let program = parser::parse(tokens, "<test>").expect("parsing should succeed");
-    compiler::compile(program).expect("compilation should succeed")
+    compiler::compile(program, "<test>").expect("compilation should succeed")
 }
 
 fn run_source(source: &str, stdin: &str) -> String {
@@ -15,6 +17,15 @@ fn run_source(source: &str, stdin: &str) -> String {
     String::from_utf8(output).expect("vm output should be UTF-8")
 }
 
+fn run_source_with_options(source: &str, stdin: &str, options: &vm::VmOptions) -> String {
+    let bytecode = compile_source(source);
+    let mut input = Cursor::new(stdin.as_bytes());
+    let mut output = Vec::new();
+    vm::execute_with_options(&bytecode, &mut input, &mut output, options)
+        .expect("execution should succeed");
+    String::from_utf8(output).expect("vm output should be UTF-8")
+}
+
 #[test]
 fn bundled_examples_compile() {
     for source in [
@@ -101,16 +112,1248 @@ ITS OVER
     assert_eq!(output, "120\n");
 }
 
+#[test]
+fn ascii_operators_match_the_emoji_ones() {
+    let emoji = "LOCK IN\nSAY 7 😭 2 😏 3 💀 8 🚡 4\nSAY \"ab\" 💀 \"c\"\nITS OVER\n";
+    let ascii = "LOCK IN\nSAY 7 - 2 * 3 + 8 / 4\nSAY \"ab\" + \"c\"\nITS OVER\n";
+    assert_eq!(compile_source(ascii), compile_source(emoji));
+    assert_eq!(run_source(ascii, ""), "3\nabc\n");
+}
+
+#[test]
+fn identifiers_can_use_any_script() {
+    let source = "TRALALERO 倍(größe)\n  FANUMTAX счёт FR größe😏2\n  RETREAT счёт💀2\nTRALALA\n\nLOCK IN\nSAY 倍(20)\nITS OVER\n";
+    assert_eq!(run_source(source, ""), "42\n");
+
+    let tokens = lexer::tokenize("FANUMTAX ά1😭ά2", "<test>").unwrap();
+    assert_eq!(
+        tokens,
+        [
+            lexer::Token::Fanumtax,
+            lexer::Token::Identifier("ά1".to_string()),
+            lexer::Token::Subtract,
+            lexer::Token::Identifier("ά2".to_string()),
+            lexer::Token::Eof,
+        ]
+    );
+}
+
 #[test]
 fn strings_can_be_repeated_with_multiply() {
     let output = run_source(
         r#"
 LOCK IN
 SAY "ha" 😏 3
+SAY 2.7 😏 "na" 💀 "|" 💀 "na" 😏 0
+ITS OVER
+"#,
+        "",
+    );
+
+    assert_eq!(output, "hahaha\nnana|\n");
+}
+
+const DICE_ROLLS: &str = r#"
+LOCK IN
+FANUMTAX peak FR 5
+SKIBIDI peak
+  SAY RANDOM(1000)
+  FANUMTAX peak FR peak 😭 1
+RIZZUP
+ITS OVER
+"#;
+
+#[test]
+fn fixed_seed_produces_known_random_sequence() {
+    let options = vm::VmOptions {
+        seed: Some(42),
+        ..Default::default()
+    };
+    let output = run_source_with_options(DICE_ROLLS, "", &options);
+
+    // Changing this sequence changes the behavior of every seeded program.
+    assert_eq!(output, "194\n562\n486\n271\n803\n");
+}
+
+#[test]
+fn seed_builtin_matches_seed_option() {
+    let reseeded = run_source_with_options(
+        &DICE_ROLLS.replace("LOCK IN", "LOCK IN\nFANUMTAX aura FR SEED(42)"),
+        "",
+        &vm::VmOptions {
+            seed: Some(7),
+            ..Default::default()
+        },
+    );
+    let seeded = run_source_with_options(
+        DICE_ROLLS,
+        "",
+        &vm::VmOptions {
+            seed: Some(42),
+            ..Default::default()
+        },
+    );
+
+    assert_eq!(reseeded, seeded);
+}
+
+#[test]
+fn same_seed_and_input_give_identical_output() {
+    let source = r#"
+LOCK IN
+FANUMTAX aura FR TOUCHY("sides: ")
+SAY RANDOM(TRANSFORM(aura))
+SAY RANDOM(TRANSFORM(aura))
+ITS OVER
+"#;
+    let options = vm::VmOptions {
+        seed: Some(1234),
+        ..Default::default()
+    };
+
+    let first = run_source_with_options(source, "20\n", &options);
+    let second = run_source_with_options(source, "20\n", &options);
+
+    assert_eq!(first, second);
+}
+
+#[test]
+fn truncated_programs_report_compile_errors() {
+    let source = include_str!("../examples/v4.brainrot");
+    let tokens = lexer::tokenize_spanned(source, "<test>").expect("lexing should succeed");
+
+    for token in &tokens {
+        if token.token == lexer::Token::Eof {
+            continue;
+        }
+        let truncated = &source[..token.span.offset];
+        let result = lexer::tokenize_spanned(truncated, "<test>")
+            .and_then(|tokens| parser::parse(tokens, "<test>"));
+        assert!(
+            result.is_err(),
+            "truncation at byte {} should not parse",
+            token.span.offset
+        );
+    }
+}
+
+#[test]
+fn compile_errors_point_at_the_offending_source() {
+    let cases = [
+        ("LOCK IN\nSAY 1\n  SAY 1 💀 nope(2)\nITS OVER\n", 3, 11),
+        ("LOCK IN\nSAY 1 💀\n  zork\nITS OVER\n", 3, 3),
+        ("BASED x FR 1\nBASED x FR 2\nLOCK IN\nITS OVER\n", 2, 7),
+        (
+            "TRALALERO f(a)\n  RETREAT a\nTRALALA\nTRALALERO f(b)\n  RETREAT b\nTRALALA\nLOCK IN\nITS OVER\n",
+            4,
+            11,
+        ),
+        (
+            "BASED k FR 1\nLOCK IN\nSKIBIDI aura\n   FANUMTAX k FR 2\nRIZZUP\nITS OVER\n",
+            4,
+            4,
+        ),
+    ];
+    for (source, line, col) in cases {
+        let err = compiler::compile_source(source, "<test>", &Default::default())
+            .expect_err("compilation should fail");
+        assert_eq!((err.line, err.col), (line, col), "{}", err);
+
+        // Compiling a parsed program reports the same place.
+        let tokens = lexer::tokenize_spanned(source, "<test>").unwrap();
+        let program = parser::parse(tokens, "<test>").unwrap();
+        let err = compiler::compile(program, "lib.br").expect_err("compilation should fail");
+        assert_eq!(
+            (err.filename.as_str(), err.line, err.col),
+            ("lib.br", line, col)
+        );
+    }
+}
+
+#[test]
+fn duplicate_definitions_name_the_first_one() {
+    let source =
+        "BASED N FR 1\nBASED N FR 2\nTRALALERO f(a)\n  RETREAT a\nTRALALA\nLOCK IN\nITS OVER\n";
+    let err = compiler::compile_source(source, "<test>", &Default::default()).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "<test>:2:7: duplicate constant: N (first defined on line 1, col 7)"
+    );
+
+    let source = "TRALALERO f(a)\n  RETREAT a\nTRALALA\n\nTRALALERO f(b)\n  RETREAT b\nTRALALA\nLOCK IN\nITS OVER\n";
+    let err = compiler::compile_source(source, "<test>", &Default::default()).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "<test>:5:11: duplicate function: f (first defined on line 1, col 11)"
+    );
+}
+
+#[test]
+fn unclosed_block_names_construct_and_opening_line() {
+    let source = "LOCK IN\nFANUMTAX aura FR 1\nSKIBIDI aura\n  SAY aura\n";
+    let tokens = lexer::tokenize_spanned(source, "<test>").expect("lexing should succeed");
+    let err = parser::parse(tokens, "<test>").expect_err("parsing should fail");
+
+    assert_eq!(
+        err.message,
+        "unterminated SKIBIDI block opened on line 3: expected RIZZUP"
+    );
+}
+
+struct ScriptedHost {
+    responses: Vec<String>,
+    printed: Vec<String>,
+    unflushed: bool,
+}
+
+impl ScriptedHost {
+    fn new(responses: &[&str]) -> Self {
+        Self {
+            responses: responses.iter().map(|r| r.to_string()).collect(),
+            printed: Vec::new(),
+            unflushed: false,
+        }
+    }
+}
+
+impl VmHost for ScriptedHost {
+    fn print(&mut self, s: &str) -> Result<(), String> {
+        self.printed.push(s.to_string());
+        self.unflushed = true;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), String> {
+        self.unflushed = false;
+        Ok(())
+    }
+
+    fn read_line(&mut self) -> Result<String, String> {
+        if self.unflushed {
+            return Err("read input before flushing output".to_string());
+        }
+        if self.responses.is_empty() {
+            return Ok(String::new());
+        }
+        Ok(self.responses.remove(0))
+    }
+}
+
+#[test]
+fn custom_host_scripts_input_and_collects_output() {
+    let bytecode = compile_source(
+        r#"
+LOCK IN
+FANUMTAX aura FR TOUCHY("name: ")
+FANUMTAX peak FR TOUCHY()
+SAY "hi " 💀 aura
+SAY peak
+ITS OVER
+"#,
+    );
+    let mut host = ScriptedHost::new(&["Ada", "second"]);
+
+    vm::execute_with_host(&bytecode, &mut host).expect("execution should succeed");
+
+    assert!(host.responses.is_empty());
+    assert_eq!(host.printed, ["name: ", "hi Ada\n", "second\n"]);
+}
+
+#[test]
+fn yap_prints_without_newline() {
+    let output = run_source(
+        r#"
+LOCK IN
+YAP "loading"
+YAP "."
+YAP 3
+SAY "done"
+SAY ""
+ITS OVER
+"#,
+        "",
+    );
+
+    assert_eq!(output.as_bytes(), b"loading.3done\n\n");
+}
+
+#[test]
+fn yap_is_flushed_before_touchy_blocks() {
+    let bytecode = compile_source(
+        r#"
+LOCK IN
+YAP "> "
+FANUMTAX aura FR TOUCHY()
+SAY aura
+ITS OVER
+"#,
+    );
+    let mut host = ScriptedHost::new(&["typed"]);
+
+    vm::execute_with_host(&bytecode, &mut host).expect("execution should succeed");
+
+    assert_eq!(host.printed, ["> ", "typed\n"]);
+}
+
+#[test]
+fn self_tail_calls_run_in_constant_call_depth() {
+    let output = run_source(
+        r#"
+TRALALERO count(n)
+  ONGOD n
+    RETREAT ring yas count(n 😭 1)
+  DEADASS
+  RETREAT "done"
+TRALALA
+
+LOCK IN
+SAY count(1000000)
+ITS OVER
+"#,
+        "",
+    );
+
+    assert_eq!(output, "done\n");
+}
+
+#[test]
+fn non_tail_recursion_still_hits_call_depth_limit() {
+    let bytecode = compile_source(
+        r#"
+TRALALERO count(n)
+  ONGOD n
+    RETREAT 0 💀 count(n 😭 1)
+  DEADASS
+  RETREAT 0
+TRALALA
+
+LOCK IN
+SAY count(1000000)
+ITS OVER
+"#,
+    );
+    let mut input = Cursor::new(Vec::new());
+    let mut output = Vec::new();
+    let err = vm::execute_with_io(&bytecode, &mut input, &mut output)
+        .expect_err("deep recursion should overflow");
+
+    assert_eq!(
+        err.to_string(),
+        "runtime: call stack overflow (depth 256) in count ×256"
+    );
+    // The 255 recursive calls share one backtrace line.
+    assert_eq!(err.backtrace.len(), 3);
+    assert!(err.backtrace[1].starts_with("called from count+0x"));
+    assert!(err.backtrace[1].ends_with(" (×255)"));
+    assert!(err.backtrace[2].starts_with("called from <main>+0x"));
+}
+
+#[test]
+fn retreat_in_main_ends_the_program_with_its_value() {
+    let run = |source: &str| {
+        let bytecode = compile_source(source);
+        let mut output = Vec::new();
+        let result = vm::execute_with_io(&bytecode, &mut Cursor::new(Vec::new()), &mut output)
+            .expect("execution should succeed");
+        (String::from_utf8(output).unwrap(), result)
+    };
+
+    let (output, result) = run(
+        "LOCK IN\nFANUMTAX aura FR 2\nONGOD aura\n  SAY \"bye\"\n  RETREAT aura 💀 1\nDEADASS\nSAY \"unreachable\"\nITS OVER\n",
+    );
+    assert_eq!(output, "bye\n");
+    assert_eq!(result.exit_value, Some(Value::Number(3.0)));
+    assert_eq!(result.exit_status(), 6);
+    assert!(result.stack.is_empty());
+
+    let (_, result) = run("LOCK IN\nSAY 1\nITS OVER\n");
+    assert_eq!(result.exit_value, None);
+    assert_eq!(result.exit_status(), 0);
+    for (value, status) in [
+        ("7", 7),
+        ("255", 255),
+        ("1", 6),
+        ("130", 6),
+        ("256", 6),
+        ("1.5", 6),
+        ("\"done\"", 6),
+    ] {
+        let (_, result) = run(&format!("LOCK IN\nRETREAT {}\nITS OVER\n", value));
+        assert_eq!(result.exit_status(), status, "RETREAT {}", value);
+    }
+}
+
+#[test]
+fn call_stack_overflow_names_the_call_chain() {
+    let bytecode = compile_source(
+        r#"
+TRALALERO ping(n)
+  RETREAT pong(n) 💀 0
+TRALALA
+TRALALERO pong(n)
+  RETREAT ping(n) 💀 0
+TRALALA
+TRALALERO start(n)
+  RETREAT ping(n)
+TRALALA
+
+LOCK IN
+SAY start(1)
+ITS OVER
+"#,
+    );
+    let run = |depth: usize| {
+        vm::VM::builder()
+            .max_call_depth(depth)
+            .stdout(Vec::new())
+            .build()
+            .execute(&bytecode)
+            .map(|_| ())
+            .unwrap_err()
+            .to_string()
+    };
+
+    assert_eq!(
+        run(4),
+        "runtime: call stack overflow (depth 4) in start → ping → pong → ping"
+    );
+    assert_eq!(
+        run(1000),
+        "runtime: call stack overflow (depth 1000) in start → ping → pong → … → pong → ping → pong → ping"
+    );
+}
+
+const MIXED_ADD: &str = r#"
+LOCK IN
+FANUMTAX aura FR "5"
+SAY aura 💀 "!"
+SAY 2 💀 3
+SAY 1 💀 aura
+ITS OVER
+"#;
+
+fn compile_strict(source: &str) -> Vec<u8> {
+    let tokens = lexer::tokenize_spanned(source, "<test>").expect("lexing should succeed");
+    let program = parser::parse(tokens, "<test>").expect("parsing should succeed");
+    let options = compiler::CompileOptions {
+        strict: true,
+        ..Default::default()
+    };
+    compiler::compile_with_options(program, "<test>", &options).expect("compilation should succeed")
+}
+
+#[test]
+fn default_mode_stringifies_mixed_add() {
+    assert_eq!(run_source(MIXED_ADD, ""), "5!\n5\n15\n");
+}
+
+#[test]
+fn strict_mode_rejects_mixed_add() {
+    let bytecode = compile_strict(MIXED_ADD);
+    let mut input = Cursor::new(Vec::new());
+    let mut output = Vec::new();
+    let err = vm::execute_with_io(&bytecode, &mut input, &mut output)
+        .expect_err("mixed add should fail in strict mode");
+
+    assert_eq!(String::from_utf8(output).unwrap(), "5!\n5\n");
+    assert_eq!(
+        err.to_string(),
+        "runtime: strict mode: cannot add number and string"
+    );
+}
+
+#[test]
+fn run_result_exposes_final_state() {
+    let bytecode = compile_source(
+        "LOCK IN\nFANUMTAX aura FR TOUCHY()\nFANUMTAX mog FR 2 😏 3\nSAY aura 💀 mog\nITS OVER\n",
+    );
+    let mut host = brvm::host::CaptureHost::new("hi\n");
+    let result = vm::execute_with_host(&bytecode, &mut host).expect("execution should succeed");
+
+    assert_eq!(result.output.as_deref(), Some("hi6\n"));
+    assert_eq!(
+        result.globals[0],
+        Some(Value::String("hi".to_string().into()))
+    );
+    assert_eq!(result.globals[3], Some(Value::Number(6.0)));
+    assert_eq!(result.globals[1], None);
+    assert!(result.stack.is_empty());
+    // INPUT, STORE, 6 (folded from 2 😏 3), STORE, LOAD, LOAD, ADD, PRINT, HALT
+    assert_eq!(result.instructions, 9);
+
+    // Hosts that write straight through have nothing to capture.
+    let result = vm::execute_with_io(&bytecode, &mut Cursor::new(&b"x\n"[..]), &mut Vec::new())
+        .expect("execution should succeed");
+    assert_eq!(result.output, None);
+}
+
+#[test]
+fn builder_configures_io_and_limits() {
+    let bytecode = compile_source(
+        "TRALALERO down(n)\n  ONGOD n\n    RETREAT down(n 😭 1) 💀 0\n  DEADASS\n  RETREAT TOUCHY()\nTRALALA\n\nLOCK IN\nSAY down(5)\nSAY RANDOM(1000)\nITS OVER\n",
+    );
+    let run = |depth: usize| {
+        let mut output = Vec::new();
+        let result = vm::VM::builder()
+            .max_call_depth(depth)
+            .stdin(Cursor::new("deep\n"))
+            .stdout(&mut output)
+            .seed(42)
+            .build()
+            .execute(&bytecode)
+            .map(|_| ());
+        (result, String::from_utf8(output).unwrap())
+    };
+
+    let (result, first) = run(6);
+    result.expect("six frames should be enough");
+    let (_, second) = run(6);
+    assert!(first.starts_with("deep00000\n"));
+    assert_eq!(first, second, "a fixed seed should repeat RANDOM");
+
+    let (result, _) = run(5);
+    assert_eq!(
+        result.unwrap_err().kind,
+        vm::ErrorKind::CallStackOverflow {
+            depth: 5,
+            chain: vec![("down".to_string(), 5)],
+        }
+    );
+}
+
+#[test]
+fn hooks_see_calls_returns_prints_and_instructions() {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    let bytecode = compile_source(
+        "TRALALERO count(n)\n  ONGOD n\n    RETREAT count(n 😭 1)\n  DEADASS\n  RETREAT \"done\"\nTRALALA\n\nLOCK IN\nYAP count(2)\nSAY \"!\"\nITS OVER\n",
+    );
+    let events = Rc::new(RefCell::new(Vec::new()));
+    let instructions = Rc::new(RefCell::new(0));
+
+    let mut output = Vec::new();
+    let mut machine = vm::VM::builder().stdout(&mut output).build();
+    let log = events.clone();
+    machine.on_call(move |f, args| log.borrow_mut().push(format!("call {} {:?}", f, args)));
+    let log = events.clone();
+    machine.on_return(move |f, value| log.borrow_mut().push(format!("return {} {}", f, value)));
+    let log = events.clone();
+    machine.on_print(move |text| log.borrow_mut().push(format!("print {:?}", text)));
+    let count = instructions.clone();
+    machine.on_instruction(move |_, _| *count.borrow_mut() += 1);
+    let result = machine
+        .execute(&bytecode)
+        .expect("execution should succeed");
+    drop(machine);
+
+    // The recursive call is a tail call, so only one frame returns.
+    assert_eq!(
+        *events.borrow(),
+        [
+            "call 0 [Number(2.0)]",
+            "call 0 [Number(1.0)]",
+            "call 0 [Number(0.0)]",
+            "return 0 done",
+            "print \"done\"",
+            "print \"!\\n\"",
+        ]
+    );
+    assert_eq!(*instructions.borrow(), result.instructions);
+    assert_eq!(String::from_utf8(output).unwrap(), "done!\n");
+}
+
+#[test]
+fn stats_count_calls_and_instructions_per_function() {
+    let bytecode = compile_source(
+        "TRALALERO fact(n)\n  ONGOD n\n    RETREAT n 😏 fact(n 😭 1)\n  DEADASS\n  RETREAT 1\nTRALALA\n\nTRALALERO unused()\n  RETREAT 0\nTRALALA\n\nLOCK IN\nSAY fact(3)\nITS OVER\n",
+    );
+    let mut output = Vec::new();
+    let result = vm::VM::builder()
+        .stdout(&mut output)
+        .stats(true)
+        .build()
+        .execute(&bytecode)
+        .expect("execution should succeed");
+
+    let stats: Vec<_> = result
+        .function_stats
+        .iter()
+        .map(|f| (f.name.as_str(), f.calls))
+        .collect();
+    assert_eq!(stats, [("fact", 4), ("unused", 0)]);
+    assert_eq!(result.function_stats[1].instructions, 0);
+    assert!(result.function_stats[0].instructions < result.instructions);
+
+    let result = vm::execute_with_io(&bytecode, &mut Cursor::new(Vec::new()), &mut Vec::new())
+        .expect("execution should succeed");
+    assert!(result.function_stats.is_empty());
+}
+
+#[test]
+fn crashout_raises_its_value_as_a_runtime_error() {
+    let bytecode = compile_source(
+        "TRALALERO half(n)
+  ONGOD n
+    RETREAT n 🚡 2
+  DEADASS
+  CRASHOUT \"nothing to halve: \" 💀 n
+TRALALA
+
+LOCK IN
+SAY half(4)
+SAY half(0)
+SAY \"unreachable\"
+ITS OVER
+",
+    );
+    let mut output = Vec::new();
+    let err = vm::execute_with_io(&bytecode, &mut Cursor::new(Vec::new()), &mut output)
+        .expect_err("CRASHOUT should stop the program");
+
+    assert_eq!(String::from_utf8(output).unwrap(), "2\n");
+    assert_eq!(err.to_string(), "runtime: CRASHOUT: nothing to halve: 0");
+    assert_eq!(
+        err.thrown_value(),
+        Some(&Value::String(std::rc::Rc::new(
+            "nothing to halve: 0".to_string()
+        )))
+    );
+}
+
+#[test]
+fn strict_mode_still_repeats_strings() {
+    let bytecode = compile_strict("LOCK IN\nSAY \"ab\" 😏 2\nITS OVER\n");
+    let mut output = Vec::new();
+    vm::execute_with_io(&bytecode, &mut Cursor::new(Vec::new()), &mut output)
+        .expect("string repeat is explicit, so strict mode allows it");
+    assert_eq!(String::from_utf8(output).unwrap(), "abab\n");
+}
+
+#[test]
+fn disassembly_shows_strict_flag() {
+    let strict = disasm::disassemble(&compile_strict(MIXED_ADD)).unwrap();
+    let default = disasm::disassemble(&compile_source(MIXED_ADD)).unwrap();
+
+    assert!(strict.contains("flags: 0x0001 (strict)\n"));
+    assert!(default.contains("flags: 0x0000\n"));
+}
+
+#[test]
+fn strict_math_rejects_nan_and_infinity() {
+    let source = "LOCK IN\nSAY TRANSFORM(\"1e308\") 😏 10\nITS OVER\n";
+    assert_eq!(run_source(source, ""), "inf\n");
+
+    let options = compiler::CompileOptions {
+        strict_math: true,
+        ..Default::default()
+    };
+    let bytecode = compiler::compile_source(source, "<test>", &options).unwrap();
+    assert!(disasm::disassemble(&bytecode)
+        .unwrap()
+        .contains("flags: 0x0002 (strict-math)\n"));
+    let err = vm::execute_with_io(&bytecode, &mut Cursor::new(Vec::new()), &mut Vec::new())
+        .expect_err("overflow should fail in strict math");
+    assert_eq!(
+        err.to_string(),
+        "runtime: strict math: multiplication gave inf"
+    );
+    assert!(err.ip.is_some());
+
+    let nan = "LOCK IN\nFANUMTAX aura FR TRANSFORM(\"inf\")\nSAY aura 😭 aura\nITS OVER\n";
+    let bytecode = compiler::compile_source(nan, "<test>", &options).unwrap();
+    let err = vm::execute_with_io(&bytecode, &mut Cursor::new(Vec::new()), &mut Vec::new())
+        .expect_err("NaN should fail in strict math");
+    assert_eq!(
+        err.to_string(),
+        "runtime: strict math: subtraction gave NaN"
+    );
+}
+
+#[test]
+fn constant_pool_shares_names_and_default_returns() {
+    let bytecode = compile_source(
+        r#"
+TRALALERO one()
+  SAY "one"
+TRALALA
+
+TRALALERO two()
+  SAY "two"
+TRALALA
+
+TRALALERO three()
+  SAY ""
+TRALALA
+
+TRALALERO four(x)
+  SAY x
+TRALALA
+
+TRALALERO five()
+  RETREAT "five"
+TRALALA
+
+LOCK IN
+SAY one() 💀 two() 💀 three() 💀 four("four") 💀 five()
+ITS OVER
+"#,
+    );
+    let module = bytecode::decode(&bytecode).expect("bytecode should decode");
+
+    let strings: Vec<&str> = module
+        .constants
+        .iter()
+        .filter_map(|c| match c {
+            Value::String(s) => Some(s.as_str()),
+            _ => None,
+        })
+        .collect();
+    let mut unique = strings.clone();
+    unique.sort();
+    unique.dedup();
+
+    assert_eq!(strings.len(), unique.len(), "duplicated pool entries");
+    assert_eq!(strings.iter().filter(|s| s.is_empty()).count(), 1);
+    assert_eq!(module.constants.len(), 6);
+    assert!(disasm::disassemble(&bytecode)
+        .unwrap()
+        .contains("constant pool: 6 entries"));
+}
+
+#[test]
+fn typeof_and_empty_inspect_values() {
+    let output = run_source(
+        r#"
+LOCK IN
+SAY TYPEOF(1) 💀 " " 💀 TYPEOF("1") 💀 " " 💀 TYPEOF(TOUCHY())
+SAY "" 💀 EMPTY("") 💀 EMPTY(0) 💀 EMPTY(" ") 💀 EMPTY(2) 💀 EMPTY(TOUCHY())
+ITS OVER
+"#,
+        "42\nx\n",
+    );
+
+    assert_eq!(output, "number string string\n11000\n");
+}
+
+#[test]
+fn drip_adds_ansi_codes_only_when_color_is_on() {
+    let source = "LOCK IN\nSAY DRIP(\"bold  red\", \"hot\") 💀 DRIP(\"\", 1)\nITS OVER\n";
+    assert_eq!(run_source(source, ""), "hot1\n");
+
+    let options = vm::VmOptions {
+        color: true,
+        ..Default::default()
+    };
+    assert_eq!(
+        run_source_with_options(source, "", &options),
+        "\x1b[1;31mhot\x1b[0m1\n"
+    );
+
+    let bytecode = compile_source("LOCK IN\nSAY DRIP(\"red sparkly\", 1)\nITS OVER\n");
+    let err = vm::execute_with_io(&bytecode, &mut Cursor::new(Vec::new()), &mut Vec::new())
+        .expect_err("unknown style should fail");
+    assert_eq!(
+        err.to_string(),
+        "runtime: DRIP: expected style names like \"bold red\""
+    );
+}
+
+#[test]
+fn formattime_and_parsetime_use_utc_patterns() {
+    let output = run_source(
+        r#"
+LOCK IN
+SAY FORMATTIME(1709210096, "%F %T %a %b %j")
+SAY FORMATTIME(0 😭 1, "%Y-%m-%dT%H:%M:%SZ %s %%")
+FANUMTAX aura FR PARSETIME("29/Feb/2024:12:34:56", "%d/%b/%Y:%T")
+SAY aura 💀 " " 💀 PARSETIME("1969-12-31", "%F")
 ITS OVER
 "#,
         "",
     );
+    assert_eq!(
+        output,
+        "2024-02-29 12:34:56 Thu Feb 060\n1969-12-31T23:59:59Z -1 %\n1709210096 -86400\n"
+    );
 
-    assert_eq!(output, "hahaha\n");
+    for (call, message) in [
+        (
+            "PARSETIME(\"2023-02-29\", \"%F\")",
+            "PARSETIME: date out of range",
+        ),
+        (
+            "PARSETIME(\"2024-02\", \"%F\")",
+            "PARSETIME: text does not match the pattern",
+        ),
+        (
+            "FORMATTIME(0, \"%Q\")",
+            "FORMATTIME: unknown directive in pattern",
+        ),
+        (
+            "FORMATTIME(1.5, \"%F\")",
+            "FORMATTIME: expected whole seconds between the years 0 and 9999",
+        ),
+    ] {
+        let bytecode = compile_source(&format!("LOCK IN\nSAY {}\nITS OVER\n", call));
+        let err = vm::execute_with_io(&bytecode, &mut Cursor::new(Vec::new()), &mut Vec::new())
+            .expect_err(call);
+        assert_eq!(err.to_string(), format!("runtime: {}", message));
+    }
+}
+
+#[test]
+fn sha256_and_hash_digest_printed_text() {
+    let output = run_source(
+        r#"
+LOCK IN
+SAY SHA256("")
+SAY SHA256("abc")
+SAY HASH("a") 💀 " " 💀 HASH(1.5) 💀 " " 💀 HASH("1.5")
+ITS OVER
+"#,
+        "",
+    );
+    assert_eq!(
+        output,
+        "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855\n\
+         ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad\n\
+         1086646154030220 8224450460653497 8224450460653497\n"
+    );
+}
+
+#[test]
+fn base64_round_trips_text() {
+    let output = run_source(
+        r#"
+LOCK IN
+SAY BASE64("") 💀 "|" 💀 BASE64("f") 💀 "|" 💀 BASE64("fo") 💀 "|" 💀 BASE64("foo")
+SAY BASE64("héllo 💀") 💀 " " 💀 UNBASE64(BASE64("héllo 💀"))
+SAY UNBASE64("Zm9vYg") 💀 UNBASE64("Zm9vYg==") 💀 BASE64(1.5)
+ITS OVER
+"#,
+        "",
+    );
+    assert_eq!(
+        output,
+        "|Zg==|Zm8=|Zm9v\naMOpbGxvIPCfkoA= héllo 💀\nfoobfoobMS41\n"
+    );
+
+    for (text, message) in [
+        ("Zm9v YQ", "UNBASE64: invalid base64 text"),
+        ("Zg===", "UNBASE64: invalid base64 text"),
+        ("/w==", "UNBASE64: decoded bytes are not UTF-8 text"),
+    ] {
+        let bytecode = compile_source(&format!("LOCK IN\nSAY UNBASE64(\"{}\")\nITS OVER\n", text));
+        let err = vm::execute_with_io(&bytecode, &mut Cursor::new(Vec::new()), &mut Vec::new())
+            .expect_err(text);
+        assert_eq!(err.to_string(), format!("runtime: {}", message));
+    }
+}
+
+#[test]
+fn equal_compares_values_not_identity() {
+    let output = run_source(
+        r#"
+LOCK IN
+FANUMTAX aura FR "ab"
+SAY aura 🟰 "a" 💀 "b"
+SAY 1 🟰 1
+SAY 1 🟰 "1"
+SAY 2 🟰 1 💀 1
+ONGOD TYPEOF(TOUCHY()) 🟰 "string"
+  SAY "typed"
+DEADASS
+ITS OVER
+"#,
+        "hi\n",
+    );
+    assert_eq!(output, "1\n1\n0\n1\ntyped\n");
+
+    let options = compiler::CompileOptions {
+        version: Some(4),
+        ..Default::default()
+    };
+    let err = compiler::compile_source("LOCK IN\nSAY 1 🟰 1\nITS OVER\n", "<test>", &options)
+        .expect_err("🟰 needs version 5");
+    assert_eq!(
+        err.message,
+        "🟰 needs bytecode version 5, but version 4 was requested"
+    );
+}
+
+#[test]
+fn floordiv_rounds_down_and_trunc_rounds_toward_zero() {
+    let output = run_source(
+        r#"
+LOCK IN
+SAY FLOORDIV(7, 2) 💀 " " 💀 FLOORDIV(0 😭 7, 2) 💀 " " 💀 FLOORDIV(7.5, 0.5)
+SAY TRUNC(2.9) 💀 " " 💀 TRUNC(0 😭 2.9) 💀 " " 💀 TRUNC(17 🚡 5)
+ITS OVER
+"#,
+        "",
+    );
+    assert_eq!(output, "3 -4 15\n2 -2 3\n");
+
+    for (source, message) in [
+        ("FLOORDIV(1, 0)", "FLOORDIV: division by zero"),
+        ("FLOORDIV(TOUCHY(), 2)", "FLOORDIV: expected two numbers"),
+        ("TRUNC(TOUCHY())", "TRUNC: expected a number"),
+    ] {
+        let bytecode = compile_source(&format!("LOCK IN\nSAY {}\nITS OVER\n", source));
+        let err = vm::execute_with_io(
+            &bytecode,
+            &mut Cursor::new(b"5\n".to_vec()),
+            &mut Vec::new(),
+        )
+        .expect_err(source);
+        assert_eq!(err.to_string(), format!("runtime: {}", message));
+    }
+}
+
+#[test]
+fn big_integers_keep_every_digit() {
+    let source = r#"
+LOCK IN
+FANUMTAX aura FR BIG(1)
+FANUMTAX peak FR 1
+SKIBIDI 26 😭 peak
+  FANUMTAX aura FR aura 😏 peak
+  FANUMTAX peak FR peak 💀 1
+RIZZUP
+SAY aura
+SAY TYPEOF(aura)
+SAY aura 🚡 BIG("15511210043330985984000000")
+SAY BIG("-12345678901234567890") 💀 1
+SAY BIG(TOUCHY()) 😭 1
+ITS OVER
+"#;
+    assert_eq!(
+        run_source(source, "100000000000000000000\n"),
+        "15511210043330985984000000\nbigint\n1\n-12345678901234567889\n99999999999999999999\n"
+    );
+    let listing = disasm::disassemble(&compile_source(source)).unwrap();
+    assert!(
+        listing.contains("bigint -12345678901234567890"),
+        "{}",
+        listing
+    );
+
+    for (source, message) in [
+        (
+            "BIG(TOUCHY())",
+            "BIG: expected a whole number or a string of digits",
+        ),
+        ("BIG(2) 🚡 0", "division by zero"),
+        (
+            "BIG(\"1\" 😏 15000) 😏 BIG(\"1\" 😏 15000)",
+            "big integer result has more than 20000 digits",
+        ),
+    ] {
+        let bytecode = compile_source(&format!("LOCK IN\nSAY {}\nITS OVER\n", source));
+        let err = vm::execute_with_io(
+            &bytecode,
+            &mut Cursor::new(b"1.5\n".to_vec()),
+            &mut Vec::new(),
+        )
+        .expect_err(source);
+        assert_eq!(err.to_string(), format!("runtime: {}", message));
+    }
+
+    let err = compiler::compile_source(
+        "LOCK IN\nSAY BIG(\"12a\")\nITS OVER\n",
+        "<test>",
+        &Default::default(),
+    )
+    .expect_err("bad BIG literal");
+    assert_eq!(
+        err.message,
+        "BIG: expected a whole number or a string of digits"
+    );
+}
+
+#[test]
+fn transform_tolerates_spaces_and_decimal_commas() {
+    let output = run_source(
+        r#"
+LOCK IN
+FANUMTAX aura FR TOUCHY()
+SAY TRANSFORM(aura) 💀 1
+SAY TRANSFORM("3,5") 💀 " " 💀 TRANSFORM("1,0000") 💀 " " 💀 TRANSFORM("\t-2.5e1 ")
+SAY TRYTRANSFORM(" 7 ") 💀 TYPEOF(TRYTRANSFORM("7 apples")) 💀 EMPTY(TRYTRANSFORM("1,2,3"))
+SAY EMPTY(TRYTRANSFORM("1,000")) 💀 " " 💀 TRYTRANSFORM("0,25")
+SAY TRYTRANSFORM(4) 💀 TRYTRANSFORM(BIG(5))
+ITS OVER
+"#,
+        " 41 \n",
+    );
+    assert_eq!(output, "42\n3.5 1 -25\n7string1\n1 0.25\n9\n");
+
+    for text in ["4 2", "1,000"] {
+        let bytecode = compile_source(&format!("LOCK IN\nSAY TRANSFORM(\"{}\")\nITS OVER\n", text));
+        let err = vm::execute_with_io(&bytecode, &mut Cursor::new(Vec::new()), &mut Vec::new())
+            .expect_err(text);
+        assert_eq!(err.to_string(), "runtime: TRANSFORM: invalid number string");
+    }
+}
+
+#[test]
+fn peek_and_poke_reach_braincells_by_name() {
+    let output = run_source(
+        r#"
+TRALALERO countdown()
+  FANUMTAX steps FR 0
+  SKIBIDI peak
+    FANUMTAX left FR POKE("peak", peak 😭 1)
+    FANUMTAX steps FR steps 💀 1
+  RIZZUP
+  RETREAT steps
+TRALALA
+
+LOCK IN
+FANUMTAX aura FR "goon"
+FANUMTAX mog FR POKE(aura, "hi")
+SAY goon 💀 " " 💀 mog 💀 " " 💀 PEEK("goon")
+FANUMTAX peak FR 3
+SAY countdown() 💀 " " 💀 peak
+ITS OVER
+"#,
+        "",
+    );
+    assert_eq!(output, "hi hi hi\n3 0\n");
+
+    let err = compiler::compile_source(
+        "LOCK IN\nSAY PEEK(\"Aura\")\nITS OVER\n",
+        "<test>",
+        &Default::default(),
+    )
+    .expect_err("not a braincell");
+    assert_eq!(err.message, "PEEK: expected a braincell name like \"aura\"");
+
+    for (source, message) in [
+        (
+            "LOCK IN\nFANUMTAX aura FR \"x\"\nSAY POKE(aura, 1)\nITS OVER\n",
+            "runtime: POKE: expected a braincell name like \"aura\"",
+        ),
+        (
+            "LOCK IN\nSAY PEEK(\"sigma\")\nITS OVER\n",
+            "runtime: unset braincell: sigma",
+        ),
+    ] {
+        let bytecode = compile_source(source);
+        let err = vm::execute_with_io(&bytecode, &mut Cursor::new(Vec::new()), &mut Vec::new())
+            .expect_err(source);
+        assert_eq!(err.to_string(), message);
+    }
+}
+
+#[test]
+fn builtin_table_matches_vm_dispatch() {
+    for (idx, builtin) in builtins::BUILTINS.iter().enumerate() {
+        assert_eq!(builtin.id as usize, idx, "{} id out of order", builtin.name);
+        assert_eq!(builtins::lookup(builtin.name), Some(builtin));
+
+        // A braincell argument keeps BIG from being folded into a constant.
+        let args = vec!["aura"; builtin.arity].join(", ");
+        let bytecode = compile_source(&format!(
+            "LOCK IN\nFANUMTAX aura FR \"1\"\nSAY {}({})\nITS OVER\n",
+            builtin.name, args
+        ));
+        assert!(disasm::disassemble(&bytecode)
+            .unwrap()
+            .contains(&format!("; {}", builtin.name)));
+
+        let mut output = Vec::new();
+        if let Err(e) = vm::execute_with_io(&bytecode, &mut Cursor::new(&b""[..]), &mut output) {
+            let message = e.to_string();
+            assert!(
+                message.contains(&format!("{}: ", builtin.name)),
+                "{} is not dispatched by the VM: {}",
+                builtin.name,
+                message
+            );
+        }
+    }
+}
+
+fn function_with_locals(count: usize) -> parser::Program {
+    let body = (0..count)
+        .map(|i| parser::SpannedStatement {
+            statement: parser::Statement::Assign(
+                format!("v{}", i),
+                None,
+                parser::Expr::Number(0.0, Default::default()),
+            ),
+            span: Default::default(),
+        })
+        .collect();
+    parser::Program {
+        consts: Vec::new(),
+        functions: vec![parser::Function {
+            name: "big".to_string(),
+            params: Vec::new(),
+            body,
+            span: Default::default(),
+        }],
+        main_statements: Vec::new(),
+    }
+}
+
+#[test]
+fn compiler_accepts_max_local_count() {
+    compiler::compile(function_with_locals(65535), "<test>").expect("65535 locals should fit");
+}
+
+#[test]
+fn compiler_rejects_local_count_overflow() {
+    let err =
+        compiler::compile(function_with_locals(65536), "<test>").expect_err("too many locals");
+
+    assert_eq!(
+        err.message,
+        "too many locals in function 'big' (65536, max 65535)"
+    );
+}
+
+#[test]
+fn compiler_rejects_parameter_count_overflow() {
+    let program = parser::Program {
+        consts: Vec::new(),
+        functions: vec![parser::Function {
+            name: "wide".to_string(),
+            params: (0..65536)
+                .map(|i| parser::Param {
+                    name: format!("p{}", i),
+                    ty: None,
+                    span: Default::default(),
+                })
+                .collect(),
+            body: Vec::new(),
+            span: Default::default(),
+        }],
+        main_statements: Vec::new(),
+    };
+    let err = compiler::compile(program, "<test>").expect_err("too many parameters");
+
+    assert_eq!(
+        err.message,
+        "too many parameters in function 'wide' (65536, max 65535)"
+    );
+}
+
+#[test]
+fn flush_policy_controls_when_output_is_flushed() {
+    // Records a `|` at each flush.
+    struct Marked(std::rc::Rc<std::cell::RefCell<String>>);
+    impl std::io::Write for Marked {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0
+                .borrow_mut()
+                .push_str(std::str::from_utf8(buf).unwrap());
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            self.0.borrow_mut().push('|');
+            Ok(())
+        }
+    }
+
+    let bytecode = compile_source(
+        "LOCK IN\nSAY \"a\"\nSAY \"b\"\nFANUMTAX aura FR TOUCHY()\nSAY aura\nITS OVER\n",
+    );
+    let run = |flush: vm::FlushPolicy| {
+        let log = std::rc::Rc::new(std::cell::RefCell::new(String::new()));
+        vm::VM::builder()
+            .stdin(Cursor::new("c\n"))
+            .stdout(Marked(log.clone()))
+            .flush(flush)
+            .build()
+            .execute(&bytecode)
+            .expect("execution should succeed");
+        let marked = log.borrow().clone();
+        marked
+    };
+
+    assert_eq!(run(vm::FlushPolicy::BeforeInput), "a\nb\n|c\n|");
+    assert_eq!(run(vm::FlushPolicy::EveryPrint), "a\n|b\n||c\n||");
+}
+
+#[test]
+fn scream_goes_to_the_hosts_error_stream() {
+    #[derive(Default)]
+    struct Split {
+        out: String,
+        err: String,
+    }
+    impl VmHost for Split {
+        fn print(&mut self, s: &str) -> Result<(), String> {
+            self.out.push_str(s);
+            Ok(())
+        }
+        fn print_error(&mut self, s: &str) -> Result<(), String> {
+            self.err.push_str(s);
+            Ok(())
+        }
+        fn read_line(&mut self) -> Result<String, String> {
+            Ok(String::new())
+        }
+    }
+
+    let source = "LOCK IN\nSAY \"data\"\nSCREAM \"oops \" 💀 2\nSAY 3\nITS OVER\n";
+    let mut host = Split::default();
+    vm::execute_with_host(&compile_source(source), &mut host).expect("execution should succeed");
+    assert_eq!(host.out, "data\n3\n");
+    assert_eq!(host.err, "oops 2\n");
+
+    // Hosts without an error stream get it inline.
+    assert_eq!(run_source(source, ""), "data\noops 2\n3\n");
+}
+
+#[test]
+fn timed_touchy_gives_zero_when_no_line_arrives() {
+    // Answers the first timed read and lets every later one time out.
+    #[derive(Default)]
+    struct Slow {
+        out: String,
+        waits: Vec<u128>,
+    }
+    impl VmHost for Slow {
+        fn print(&mut self, s: &str) -> Result<(), String> {
+            self.out.push_str(s);
+            Ok(())
+        }
+        fn read_line(&mut self) -> Result<String, String> {
+            Ok(String::new())
+        }
+        fn read_line_timeout(
+            &mut self,
+            timeout: std::time::Duration,
+        ) -> Result<Option<String>, String> {
+            self.waits.push(timeout.as_millis());
+            Ok((self.waits.len() == 1).then(|| "left\n".to_string()))
+        }
+    }
+
+    let source = "LOCK IN
+FANUMTAX aura FR TOUCHY(\"a? \", 250)
+SAY TYPEOF(aura) 💀 \" \" 💀 aura
+FANUMTAX aura FR TOUCHY(\"b? \", 0)
+SAY TYPEOF(aura) 💀 \" \" 💀 aura
+ITS OVER
+";
+    let mut host = Slow::default();
+    vm::execute_with_host(&compile_source(source), &mut host).expect("execution should succeed");
+    assert_eq!(host.out, "a? string left\nb? number 0\n");
+    assert_eq!(host.waits, [250, 0]);
+
+    // Hosts that can't wait just read a line.
+    assert_eq!(run_source(source, "x\n"), "a? string x\nb? string \n");
+
+    let bytecode = compile_source("LOCK IN\nSAY TOUCHY(\"\", 0 😭 1)\nITS OVER\n");
+    let err = vm::execute_with_io(&bytecode, &mut Cursor::new(Vec::new()), &mut Vec::new())
+        .expect_err("negative timeout should fail");
+    assert_eq!(
+        err.to_string(),
+        "runtime: TOUCHY: expected a non-negative whole number of milliseconds"
+    );
 }
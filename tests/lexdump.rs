@@ -0,0 +1,21 @@
+use brvm::{lexdump, lexer};
+
+const SAMPLE: &str = include_str!("fixtures/lex/sample.br");
+
+#[test]
+fn token_listing_matches_golden_output() {
+    let tokens = lexer::tokenize_spanned(SAMPLE, "sample.br").unwrap();
+    assert_eq!(
+        lexdump::listing(&tokens),
+        include_str!("fixtures/lex/sample.tokens")
+    );
+}
+
+#[test]
+fn json_listing_with_comments_matches_golden_output() {
+    let tokens = lexer::tokenize_with_comments(SAMPLE, "sample.br").unwrap();
+    assert_eq!(
+        lexdump::json(&tokens),
+        include_str!("fixtures/lex/sample.json")
+    );
+}
@@ -0,0 +1,140 @@
+use brvm::lexer::{self, Token};
+
+fn tokens(source: &str) -> Vec<Token> {
+    lexer::tokenize(source, "<test>").expect("lexing should succeed")
+}
+
+fn lex_error(source: &str) -> String {
+    lexer::tokenize(source, "<test>")
+        .expect_err("lexing should fail")
+        .to_string()
+}
+
+#[test]
+fn trailing_line_comment_without_newline() {
+    assert_eq!(
+        tokens("SAY 1 🖕 done"),
+        vec![Token::Say, Token::Number(1.0), Token::Eof]
+    );
+    assert_eq!(
+        tokens("SAY 1🖕"),
+        vec![Token::Say, Token::Number(1.0), Token::Eof]
+    );
+}
+
+#[test]
+fn hash_starts_a_line_comment_too() {
+    let emoji = "LOCK IN\n🖕 note\nSAY 1 🖕 done\nITS OVER\n";
+    let ascii = "LOCK IN\n# note\nSAY 1# done\nITS OVER\n";
+    assert_eq!(tokens(ascii), tokens(emoji));
+
+    let comments = |source| -> Vec<Token> {
+        lexer::tokenize_with_comments(source, "<test>")
+            .unwrap()
+            .into_iter()
+            .map(|t| t.token)
+            .filter(|t| matches!(t, Token::Comment(_)))
+            .collect()
+    };
+    assert_eq!(comments(ascii), comments(emoji));
+    assert_eq!(
+        tokens("SAY \"#1\""),
+        [Token::Say, Token::String("#1".to_string()), Token::Eof]
+    );
+}
+
+#[test]
+fn block_comment_can_span_statements() {
+    let source = "LOCK IN\n🙈\nONGOD aura\n  SAY 1\nDEADASS\n🙉SAY 2\nITS OVER\n";
+    assert_eq!(
+        tokens(source),
+        vec![
+            Token::Lock,
+            Token::In,
+            Token::Say,
+            Token::Number(2.0),
+            Token::Its,
+            Token::Over,
+            Token::Eof
+        ]
+    );
+}
+
+#[test]
+fn block_comments_nest() {
+    let source = "SAY 1 🙈 outer 🙈 inner 🙉 still outer 🙉 SAY 2";
+    assert_eq!(
+        tokens(source),
+        vec![
+            Token::Say,
+            Token::Number(1.0),
+            Token::Say,
+            Token::Number(2.0),
+            Token::Eof
+        ]
+    );
+
+    let comments = lexer::tokenize_with_comments(source, "<test>").unwrap();
+    assert_eq!(
+        comments[2].token,
+        Token::Comment("outer 🙈 inner 🙉 still outer".to_string())
+    );
+}
+
+#[test]
+fn unterminated_block_comment_reports_opening_line() {
+    let err = lex_error("LOCK IN\n🙈 🙈 nested 🙉\nSAY 1\n");
+    assert!(
+        err.contains("unterminated block comment opened on line 2"),
+        "{}",
+        err
+    );
+    assert!(lex_error("SAY 1 🙉").contains("🙉 without an opening 🙈"));
+}
+
+#[test]
+fn comment_markers_inside_strings_are_literal() {
+    assert_eq!(
+        tokens("SAY \"a 🖕 b 🙈 c\""),
+        vec![
+            Token::Say,
+            Token::String("a 🖕 b 🙈 c".to_string()),
+            Token::Eof
+        ]
+    );
+}
+
+#[test]
+fn leading_shebang_line_is_skipped() {
+    let spanned = lexer::tokenize_spanned("#!/usr/bin/env -S brvm run\nSAY 1\n", "<test>").unwrap();
+    assert_eq!(spanned[0].token, Token::Say);
+    assert_eq!((spanned[0].span.line, spanned[0].span.col), (2, 1));
+
+    // Later on it is just a # comment.
+    assert_eq!(
+        tokens("SAY 1\n#!/bin/sh\n"),
+        [Token::Say, Token::Number(1.0), Token::Eof]
+    );
+}
+
+#[test]
+fn lexer_yields_tokens_on_demand() {
+    let source = "LOCK IN 🖕 start\nSAY 1 \"unterminated\nITS OVER\n";
+    let mut lexer = lexer::Lexer::new(source, "<test>");
+    let first: Vec<Token> = lexer.by_ref().take(4).map(|t| t.unwrap().token).collect();
+    assert_eq!(
+        first,
+        [Token::Lock, Token::In, Token::Say, Token::Number(1.0)]
+    );
+    assert!(lexer.next().unwrap().is_err());
+    assert!(lexer.next().is_none());
+
+    let source = "LOCK IN\n🖕 hi\nSAY 1\nITS OVER\n";
+    let streamed: Result<Vec<_>, _> = lexer::Lexer::new(source, "<test>")
+        .with_comments()
+        .collect();
+    assert_eq!(
+        streamed.unwrap(),
+        lexer::tokenize_with_comments(source, "<test>").unwrap()
+    );
+}
@@ -0,0 +1,155 @@
+use brvm::compiler::{self, CompileOptions};
+use brvm::diagnostic::Severity;
+use brvm::lint::{Level, Lint, LintLevels};
+
+const SOURCE: &str = "BASED debug FR 0
+
+TRALALERO f(n)
+  FANUMTAX aura FR n 😏 2
+  FANUMTAX tmp FR 3
+  ONGOD debug
+    SAY \"dbg\"
+  DEADASS
+  RETREAT aura
+TRALALA
+
+LOCK IN
+FANUMTAX mog FR 1
+SKIBIDI mog
+  SAY f(2)
+  FANUMTAX mog FR 0
+RIZZUP
+ITS OVER
+";
+
+fn warnings(source: &str, lints: LintLevels) -> Vec<(Lint, usize)> {
+    let options = CompileOptions {
+        lints,
+        ..Default::default()
+    };
+    let (_, warnings) = compiler::compile_source_with_warnings(source, "<test>", &options)
+        .expect("compilation should succeed");
+    warnings
+        .iter()
+        .inspect(|w| assert_eq!(w.severity, Severity::Warning))
+        .map(|w| (w.lint.unwrap(), w.line))
+        .collect()
+}
+
+#[test]
+fn lints_warn_by_default_in_source_order() {
+    assert_eq!(
+        warnings(SOURCE, LintLevels::default()),
+        [(Lint::UnusedAssignment, 5), (Lint::ConstantCondition, 6)]
+    );
+}
+
+#[test]
+fn lint_levels_can_be_changed() {
+    let mut lints = LintLevels::default();
+    lints.set(Lint::ShadowedBraincell, Level::Warn);
+    lints.set(Lint::UnusedAssignment, Level::Allow);
+    assert_eq!(
+        warnings(SOURCE, lints),
+        [(Lint::ShadowedBraincell, 4), (Lint::ConstantCondition, 6)]
+    );
+}
+
+#[test]
+fn denied_lints_fail_the_compile() {
+    let mut lints = LintLevels::default();
+    lints.deny_warnings();
+    let options = CompileOptions {
+        lints,
+        ..Default::default()
+    };
+    let err = compiler::compile_source(SOURCE, "<test>", &options).expect_err("should be denied");
+    assert_eq!(
+        err.to_string(),
+        "<test>:5:3: value assigned to 'tmp' is never read\n<test>:6:9: ONGOD condition is always false"
+    );
+    assert!(err.help.unwrap().contains("unused-assignment"));
+}
+
+#[test]
+fn clean_programs_have_no_warnings() {
+    for example in ["v1", "v2", "v3", "v4"] {
+        let path = format!(
+            "{}/examples/{}.brainrot",
+            env!("CARGO_MANIFEST_DIR"),
+            example
+        );
+        let source = std::fs::read_to_string(path).unwrap();
+        assert_eq!(warnings(&source, LintLevels::default()), [], "{}", example);
+    }
+}
+
+#[test]
+fn code_after_exits_and_in_dead_loops_is_unreachable() {
+    let source = "TRALALERO f(n)
+  ONGOD n
+    RETREAT 1
+    SAY \"never\"
+    RETREAT 2
+  DEADASS
+  RETREAT 0
+TRALALA
+
+LOCK IN
+SKIBIDI \"\"
+  SAY f(1)
+RIZZUP
+SAY f(0)
+YOUSHALLNOTPASS
+SAY \"after\"
+ITS OVER
+";
+    assert_eq!(
+        warnings(source, LintLevels::default()),
+        [
+            (Lint::UnreachableCode, 4),
+            (Lint::ConstantCondition, 11),
+            (Lint::UnreachableCode, 12),
+            (Lint::UnreachableCode, 16),
+        ]
+    );
+}
+
+#[test]
+fn reads_no_path_can_have_assigned_are_reported() {
+    let source = "TRALALERO f(n)
+  ONGOD n
+    FANUMTAX x FR 1
+    RETREAT x
+  DEADASS
+  SAY x
+  SAY goon
+  RETREAT peak
+TRALALA
+
+LOCK IN
+SAY aura
+FANUMTAX mog FR 3
+SKIBIDI mog
+  ONGOD sigma
+    SAY \"maybe set by an earlier pass\"
+  DEADASS
+  FANUMTAX sigma FR 1
+  FANUMTAX mog FR mog 😭 1
+RIZZUP
+FANUMTAX peak FR f(0)
+ITS OVER
+";
+    let options = CompileOptions::default();
+    let (_, warnings) = compiler::compile_source_with_warnings(source, "<test>", &options)
+        .expect("compilation should succeed");
+    let messages: Vec<String> = warnings.iter().map(|w| w.to_string()).collect();
+    assert_eq!(
+        messages,
+        [
+            "<test>:6:7: warning[unassigned-read]: local 'x' is read before it is assigned",
+            "<test>:7:7: warning[unassigned-read]: braincell 'goon' is read in function f but never assigned",
+            "<test>:12:5: warning[unassigned-read]: braincell 'aura' is read before it is assigned",
+        ]
+    );
+}
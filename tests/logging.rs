@@ -0,0 +1,52 @@
+use brvm::compiler;
+use brvm::host::CaptureHost;
+use std::sync::Mutex;
+
+static RECORDS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+struct Collector;
+
+impl log::Log for Collector {
+    fn enabled(&self, _: &log::Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &log::Record) {
+        let mut line = format!("{} {}", record.target(), record.args());
+        if let Some(file) = record.key_values().get("file".into()) {
+            line.push_str(&format!(" file={}", file));
+        }
+        RECORDS.lock().unwrap().push(line);
+    }
+
+    fn flush(&self) {}
+}
+
+#[test]
+fn compile_stages_and_runs_are_logged() {
+    log::set_logger(&Collector).unwrap();
+    log::set_max_level(log::LevelFilter::Debug);
+
+    let source = "LOCK IN\nSAY 1\nITS OVER\n";
+    let bytecode = compiler::compile_source(source, "one.br", &Default::default()).unwrap();
+    brvm::vm::execute_with_host(&bytecode, &mut CaptureHost::new("")).unwrap();
+
+    let records = RECORDS.lock().unwrap();
+    for expected in [
+        "brvm::compiler lexed file=one.br",
+        "brvm::compiler parsed file=one.br",
+        "brvm::compiler type checked file=one.br",
+        "brvm::compiler optimization settings",
+        "brvm::compiler generated code",
+        "brvm::compiler compiled file=one.br",
+        "brvm::vm loaded bytecode",
+        "brvm::vm finished",
+    ] {
+        assert!(
+            records.iter().any(|record| record == expected),
+            "{:?} not in {:#?}",
+            expected,
+            records
+        );
+    }
+}
@@ -0,0 +1,110 @@
+use brvm::{compiler, net, vm};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::thread;
+
+// Serves one connection with `response` and hands back the raw request.
+fn serve_once(response: &'static str) -> (String, thread::JoinHandle<String>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let handle = thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream);
+        let mut request = String::new();
+        let mut length = 0;
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            if let Some(value) = line.strip_prefix("Content-Length: ") {
+                length = value.trim().parse().unwrap();
+            }
+            request.push_str(&line);
+            if line == "\r\n" {
+                break;
+            }
+        }
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body).unwrap();
+        request.push_str(&String::from_utf8(body).unwrap());
+        reader.get_mut().write_all(response.as_bytes()).unwrap();
+        request
+    });
+    (url, handle)
+}
+
+fn run(source: &str, allow_net: bool) -> Result<String, vm::RuntimeError> {
+    let bytecode = compiler::compile_source(source, "<test>", &Default::default()).unwrap();
+    let mut output = Vec::new();
+    let mut vm = vm::VM::builder()
+        .allow_net(allow_net)
+        .stdin(&b""[..])
+        .stdout(&mut output)
+        .build();
+    vm.load(&bytecode)?;
+    vm.run()?;
+    drop(vm);
+    Ok(String::from_utf8(output).unwrap())
+}
+
+#[test]
+fn yoink_and_yeet_return_the_response_body() {
+    let (url, server) = serve_once("HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello");
+    let source = format!("LOCK IN\nSAY YOINK(\"{}/greet?x=1\")\nITS OVER\n", url);
+    assert_eq!(run(&source, true).unwrap(), "hello\n");
+    let request = server.join().unwrap();
+    assert!(
+        request.starts_with("GET /greet?x=1 HTTP/1.1\r\n"),
+        "{}",
+        request
+    );
+
+    let (url, server) = serve_once(
+        "HTTP/1.1 201 Created\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nsaw\r\n4\r\n it!\r\n0\r\n\r\n",
+    );
+    let source = format!("LOCK IN\nSAY YEET(\"{}/post\", 40 💀 2)\nITS OVER\n", url);
+    assert_eq!(run(&source, true).unwrap(), "saw it!\n");
+    let request = server.join().unwrap();
+    assert!(
+        request.starts_with("POST /post HTTP/1.1\r\n"),
+        "{}",
+        request
+    );
+    assert!(
+        request.ends_with("Content-Length: 2\r\n\r\n42"),
+        "{}",
+        request
+    );
+}
+
+#[test]
+fn network_access_needs_allow_net() {
+    let err = run(
+        "LOCK IN\nSAY YOINK(\"http://127.0.0.1:9/\")\nITS OVER\n",
+        false,
+    )
+    .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "runtime: YOINK: network access is disabled; run with --allow-net"
+    );
+}
+
+#[test]
+fn failed_requests_are_errors() {
+    let (url, server) = serve_once("HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n");
+    assert_eq!(
+        net::request(&format!("{}/missing", url), None),
+        Err("HTTP 404 Not Found".to_string())
+    );
+    server.join().unwrap();
+
+    assert_eq!(
+        net::request("https://example.com/", None),
+        Err("https is not supported; use an http:// URL".to_string())
+    );
+    let err = run("LOCK IN\nSAY YOINK(\"ftp://x\")\nITS OVER\n", true).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "runtime: YOINK: expected an http:// URL, got \"ftp://x\""
+    );
+}
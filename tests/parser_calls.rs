@@ -0,0 +1,113 @@
+use brvm::lexer;
+use brvm::parser::{self, Expr, Program, Statement};
+
+fn parse(source: &str) -> Result<Program, String> {
+    let tokens = lexer::tokenize_spanned(source, "<test>").map_err(|e| e.to_string())?;
+    parser::parse(tokens, "<test>").map_err(|e| e.to_string())
+}
+
+fn say_expr(line: &str) -> Result<Expr, String> {
+    let program = parse(&format!("LOCK IN\nSAY {}\nITS OVER\n", line))?;
+    match program
+        .main_statements
+        .into_iter()
+        .next()
+        .map(|s| s.statement)
+    {
+        Some(Statement::Print(expr)) => Ok(expr),
+        other => panic!("expected SAY, got {:?}", other),
+    }
+}
+
+fn call_args(line: &str) -> Vec<Expr> {
+    match say_expr(line).expect("call should parse") {
+        Expr::FunctionCall { args, .. } | Expr::UserFunctionCall { args, .. } => args,
+        other => panic!("expected a call, got {:?}", other),
+    }
+}
+
+#[test]
+fn builtin_calls_parse_zero_and_one_argument() {
+    assert!(call_args("TOUCHY()").is_empty());
+    assert!(matches!(
+        call_args("TOUCHY(\"name: \")").as_slice(),
+        [Expr::String(s, _)] if s == "name: "
+    ));
+    assert!(matches!(
+        call_args("TRANSFORM(\"4\" 💀 \"2\")").as_slice(),
+        [Expr::Binary { .. }]
+    ));
+}
+
+#[test]
+fn call_arguments_keep_source_order() {
+    let program = parse(
+        "TRALALERO f(a, b)\n  RETREAT a\nTRALALA\nLOCK IN\nSAY f(1, RIZZED(\"xy\"))\nITS OVER\n",
+    )
+    .unwrap();
+    let Some(Statement::Print(Expr::UserFunctionCall { args, .. })) =
+        program.main_statements.first().map(|s| &s.statement)
+    else {
+        panic!("expected a user call");
+    };
+    assert!(matches!(
+        args.as_slice(),
+        [Expr::Number(n, _), Expr::FunctionCall { name, args: inner, .. }]
+            if *n == 1.0 && name == "RIZZED" && inner.len() == 1
+    ));
+}
+
+#[test]
+fn builtin_arity_errors_name_the_builtin_and_position() {
+    let err = say_expr("TRANSFORM(\"1\", \"2\", \"3\")").unwrap_err();
+    assert!(
+        err.contains("2:5") && err.contains("TRANSFORM expects 1 argument, got 3"),
+        "{}",
+        err
+    );
+
+    let err = say_expr("RIZZED()").unwrap_err();
+    assert!(err.contains("RIZZED expects 1 argument, got 0"), "{}", err);
+
+    let err = say_expr("TOUCHY(\"a\", 1, 2)").unwrap_err();
+    assert!(
+        err.contains("TOUCHY expects 0 to 2 arguments, got 3"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn trailing_comma_is_rejected() {
+    for line in ["TRANSFORM(\"1\",)", "f(1,)", "f(1, 2,)"] {
+        let err = say_expr(line).unwrap_err();
+        assert!(err.contains("trailing comma in argument list"), "{}", err);
+    }
+}
+
+#[test]
+fn every_expression_carries_its_span() {
+    let expr = say_expr("\"a\" 💀 TYPEOF(aura) 😏 2").unwrap();
+    let at = |expr: &Expr| (expr.span().line, expr.span().col);
+    let Expr::Binary { left, right, .. } = &expr else {
+        panic!("expected 💀, got {:?}", expr);
+    };
+    assert_eq!(at(&expr), (2, 9));
+    assert_eq!(at(left), (2, 5));
+    let Expr::Binary {
+        left: call,
+        right: two,
+        ..
+    } = right.as_ref()
+    else {
+        panic!("expected 😏, got {:?}", right);
+    };
+    assert_eq!((at(right), at(call), at(two)), ((2, 24), (2, 11), (2, 26)));
+    let Expr::FunctionCall { args, .. } = call.as_ref() else {
+        panic!("expected TYPEOF, got {:?}", call);
+    };
+    assert_eq!(at(&args[0]), (2, 18));
+
+    // Spans are not part of equality.
+    assert_eq!(say_expr("aura").unwrap(), say_expr("   aura").unwrap());
+}
@@ -0,0 +1,118 @@
+use brvm::compiler::{self, CompileOptions};
+use brvm::error::CompileError;
+use brvm::{lexer, parser};
+
+fn parse_err(source: &str) -> CompileError {
+    let tokens = lexer::tokenize_spanned(source, "<test>").expect("lexing should succeed");
+    parser::parse(tokens, "<test>").expect_err("parsing should fail")
+}
+
+fn positions(err: &CompileError) -> Vec<(usize, usize, String)> {
+    std::iter::once(err)
+        .chain(&err.additional)
+        .map(|e| (e.line, e.col, e.message.clone()))
+        .collect()
+}
+
+#[test]
+fn every_bad_statement_is_reported() {
+    let err = parse_err(
+        "LOCK IN\nFANUMTAX FR 3\nSAY 1\nSAY 1 💀\nDIDDLE aura 2\nSAY \"fine\"\nITS OVER\n",
+    );
+    assert_eq!(
+        positions(&err),
+        [
+            (2, 10, "expected variable name".to_string()),
+            (5, 1, "expected expression".to_string()),
+            (5, 13, "expected FR after variable".to_string()),
+        ]
+    );
+    assert_eq!(
+        err.to_string(),
+        "<test>:2:10: expected variable name\n<test>:5:1: expected expression\n<test>:5:13: expected FR after variable"
+    );
+}
+
+#[test]
+fn recovery_resumes_inside_blocks_and_functions() {
+    let err = parse_err(
+        "TRALALERO f(a,\n  RETREAT a\nTRALALA\n\nTRALALERO g(x)\n  SAY x 😏\n  RETREAT x\nTRALALA\n\nLOCK IN\nONGOD 1 🚡\n  SAY 1\nNO\n  SAY 2\nDEADASS\nSKIBIDI aura\n  YAP\nRIZZUP\nITS OVER\n",
+    );
+    let lines: Vec<usize> = positions(&err).iter().map(|(line, _, _)| *line).collect();
+    assert_eq!(lines, [2, 7, 12, 14, 18]);
+    assert_eq!(err.additional[2].message, "expected CAP after NO");
+}
+
+#[test]
+fn unclosed_blocks_report_once() {
+    let err = parse_err("LOCK IN\nSKIBIDI aura\n  ONGOD aura\n    SAY aura\n");
+    assert_eq!(
+        err.message,
+        "unterminated ONGOD block opened on line 3: expected DEADASS"
+    );
+    assert!(err.additional.is_empty(), "{}", err);
+}
+
+#[test]
+fn duplicate_parameters_name_both_positions_and_parsing_continues() {
+    let err = parse_err("TRALALERO f(a, b, a)\n  RETREAT a\nTRALALA\nLOCK IN\nSAY\nITS OVER\n");
+    assert_eq!(
+        positions(&err),
+        [
+            (
+                1,
+                19,
+                "duplicate parameter 'a' in function f (first defined on line 1, col 13)"
+                    .to_string()
+            ),
+            (6, 1, "expected expression".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn nesting_past_the_limit_is_one_error() {
+    let deep = |n: usize| {
+        format!(
+            "LOCK IN\n{}SAY 1{}\n{}ITS OVER\n",
+            "ONGOD 1\n".repeat(n),
+            " 💀 1".repeat(n),
+            "DEADASS\n".repeat(n)
+        )
+    };
+    let tokens = lexer::tokenize_spanned(&deep(40), "<test>").unwrap();
+    assert!(parser::parse(tokens, "<test>").is_ok());
+
+    // Far past the limit, where the later passes would overflow the stack.
+    let err = parse_err(&deep(100_000));
+    assert_eq!(
+        positions(&err),
+        [(102, 1, "code nests more than 100 levels deep".to_string())]
+    );
+    let err = parse_err(&format!(
+        "LOCK IN\nSAY {}1{}\nITS OVER\n",
+        "TYPEOF(".repeat(500),
+        ")".repeat(500)
+    ));
+    assert_eq!(err.message, "code nests more than 100 levels deep");
+}
+
+#[test]
+fn operator_chains_have_their_own_limit() {
+    let chain = |blocks: usize, ops: usize| {
+        format!(
+            "LOCK IN\n{}SAY 1{}\n{}ITS OVER\n",
+            "ONGOD 1\n".repeat(blocks),
+            " 💀 1".repeat(ops),
+            "DEADASS\n".repeat(blocks)
+        )
+    };
+    let options = CompileOptions::default();
+    assert!(compiler::compile_source(&chain(0, 300), "<test>", &options).is_ok());
+    assert!(compiler::compile_source(&chain(90, 300), "<test>", &options).is_ok());
+
+    let tokens = lexer::tokenize_spanned(&chain(0, 2_000), "<test>").unwrap();
+    assert!(parser::parse(tokens, "<test>").is_ok());
+    let err = parse_err(&chain(0, 2_001));
+    assert_eq!(err.message, "expression chains more than 2000 operators");
+}
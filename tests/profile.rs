@@ -0,0 +1,128 @@
+use brvm::host::CaptureHost;
+use brvm::profile::{BranchProfile, FunctionProfile, Profile};
+use brvm::{compiler, disasm, vm};
+
+// `sq` is hot and one line; the loop runs many times and its ONGOD almost
+// never takes the then arm.
+const SOURCE: &str = r#"
+TRALALERO sq(x)
+  RETREAT x 😏 x
+TRALALA
+TRALALERO work(n)
+  FANUMTAX total FR 0
+  SKIBIDI n
+    ONGOD n 🟰 1000
+      FANUMTAX total FR total 💀 1
+    NO CAP
+      FANUMTAX total FR total 💀 sq(n)
+    DEADASS
+    FANUMTAX n FR n 😭 1
+  RIZZUP
+  RETREAT total
+TRALALA
+
+LOCK IN
+SAY work(200)
+ITS OVER
+"#;
+
+fn compile(profile: Option<Profile>) -> Vec<u8> {
+    let options = compiler::CompileOptions {
+        profile,
+        ..Default::default()
+    };
+    compiler::compile_source(SOURCE, "<test>", &options).expect("should compile")
+}
+
+fn profiled_run(bytecode: &[u8]) -> (String, vm::RunResult, Profile) {
+    let options = vm::VmOptions {
+        stats: true,
+        ..Default::default()
+    };
+    let mut host = CaptureHost::new("");
+    let result = vm::execute_with_host_options(bytecode, &mut host, &options).expect("should run");
+    let profile = Profile::from_run(bytecode, &result).unwrap();
+    (host.output, result, profile)
+}
+
+fn count_ops(bytecode: &[u8], name: &str) -> usize {
+    disasm::disassemble(bytecode)
+        .unwrap()
+        .lines()
+        .filter(|line| line.split_whitespace().nth(1) == Some(name))
+        .count()
+}
+
+#[test]
+fn runs_record_calls_and_branch_outcomes() {
+    let (_, _, profile) = profiled_run(&compile(None));
+    assert_eq!(profile.calls("sq"), 200);
+    assert_eq!(
+        profile.branches,
+        vec![
+            BranchProfile {
+                function: "work".to_string(),
+                index: 0,
+                truthy: 200,
+                falsy: 1,
+            },
+            BranchProfile {
+                function: "work".to_string(),
+                index: 1,
+                truthy: 0,
+                falsy: 200,
+            },
+        ]
+    );
+}
+
+#[test]
+fn profiles_round_trip_through_json() {
+    let profile = Profile {
+        functions: vec![FunctionProfile {
+            name: "say \"hi\"".to_string(),
+            calls: 3,
+            instructions: 12,
+        }],
+        branches: vec![BranchProfile {
+            function: "<main>".to_string(),
+            index: 2,
+            truthy: 7,
+            falsy: 0,
+        }],
+    };
+    assert_eq!(Profile::parse(&profile.to_json()).unwrap(), profile);
+    assert_eq!(
+        Profile::parse(&Profile::default().to_json()).unwrap(),
+        Profile::default()
+    );
+    assert_eq!(
+        Profile::parse("{\"functions\": []}").unwrap_err(),
+        "profile: missing \"branches\""
+    );
+}
+
+#[test]
+fn profile_guided_compile_inlines_and_biases_jumps() {
+    let plain = compile(None);
+    let (plain_output, plain_run, profile) = profiled_run(&plain);
+    let guided = compile(Some(profile.clone()));
+    let (guided_output, guided_run, _) = profiled_run(&guided);
+
+    assert_eq!(plain_output, "2686700\n");
+    assert_eq!(guided_output, plain_output);
+    assert_eq!(count_ops(&plain, "HITMEUP"), 2);
+    assert_eq!(count_ops(&guided, "HITMEUP"), 1);
+    assert_eq!(count_ops(&guided, "JUMP_IF_TRUE"), 2);
+    assert_eq!(count_ops(&guided, "JUMP_IF_FALSE"), 0);
+    assert!(guided_run.instructions < plain_run.instructions);
+
+    // Without optimizations the profile is ignored.
+    let options = compiler::CompileOptions {
+        profile: Some(profile),
+        no_optimize: true,
+        ..Default::default()
+    };
+    let unoptimized = compiler::compile_source(SOURCE, "<test>", &options).unwrap();
+    assert_eq!(count_ops(&unoptimized, "JUMP_IF_TRUE"), 0);
+}
@@ -0,0 +1,129 @@
+use brvm::host::CaptureHost;
+use brvm::repl::{self, Session};
+
+#[test]
+fn open_blocks_need_more_lines() {
+    assert!(repl::is_incomplete("ONGOD aura\n"));
+    assert!(repl::is_incomplete(
+        "SKIBIDI aura\n  ONGOD peak\n  DEADASS\n"
+    ));
+    assert!(repl::is_incomplete("TRALALERO f(n)\n  RETREAT n\n"));
+    assert!(!repl::is_incomplete(
+        "ONGOD aura\n  SAY 1\nNO CAP\n  SAY 2\nDEADASS\n"
+    ));
+    assert!(!repl::is_incomplete("SAY 1\n"));
+    // Lex errors are reported on evaluation rather than waiting forever.
+    assert!(!repl::is_incomplete("ONGOD \"unterminated\n"));
+}
+
+#[test]
+fn braincells_and_functions_carry_between_entries() {
+    let mut session = Session::new();
+    let mut host = CaptureHost::new("");
+    for entry in [
+        "FANUMTAX aura FR 3\n",
+        "TRALALERO sq(n)\n  RETREAT n 😏 n\nTRALALA\n",
+        "SKIBIDI aura\n  SAY sq(aura)\n  FANUMTAX aura FR aura 😭 1\nRIZZUP\n",
+    ] {
+        session.eval(entry, &mut host).expect("entry should run");
+    }
+    assert_eq!(host.output, "9\n4\n1\n");
+
+    // Lines in compile errors count from the start of the entry.
+    let err = session
+        .eval("SAY 1\nSAY 1 😭 \"x\"\n", &mut host)
+        .expect_err("entry should not compile");
+    assert_eq!(
+        err.to_string(),
+        "<repl>:2:7: subtraction requires both operands to be numbers"
+    );
+}
+
+#[test]
+fn meta_commands_inspect_and_reset_the_session() {
+    let mut session = Session::new();
+    let mut host = CaptureHost::new("");
+    assert_eq!(
+        session.command("dis", &mut host).unwrap_err().to_string(),
+        "nothing compiled yet"
+    );
+
+    session
+        .eval("FANUMTAX peak FR \"hi\"\n", &mut host)
+        .unwrap();
+    let vars = session.command("vars", &mut host).unwrap();
+    assert!(vars.starts_with("aura is unset\npeak = \"hi\"\n"));
+    assert!(session
+        .command("dis", &mut host)
+        .unwrap()
+        .contains("STORE_GLOBAL 1"));
+    assert!(session
+        .command("ast", &mut host)
+        .unwrap()
+        .contains("String(\n"));
+
+    let path = std::env::temp_dir().join(format!("brvm-repl-{}.brainrot", std::process::id()));
+    std::fs::write(
+        &path,
+        "TRALALERO twice(n)\n  RETREAT n 💀 n\nTRALALA\n\nLOCK IN\nSAY twice(peak)\nITS OVER\n",
+    )
+    .unwrap();
+    let load = format!("load {}", path.display());
+    session.command(&load, &mut host).unwrap();
+    session.eval("SAY twice(2)\n", &mut host).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(host.output, "hihi\n4\n");
+
+    session.command("reset", &mut host).unwrap();
+    assert!(session.eval("SAY twice(2)\n", &mut host).is_err());
+    assert!(session
+        .command("vars", &mut host)
+        .unwrap()
+        .starts_with("aura is unset\npeak is unset\n"));
+    assert!(session.command("bogus", &mut host).is_err());
+}
+
+#[test]
+fn completion_offers_keywords_braincells_and_definitions() {
+    let mut session = Session::new();
+    let mut host = CaptureHost::new("");
+    session
+        .eval(
+            "TRALALERO doubled(n)\n  RETREAT n 💀 n\nTRALALA\n",
+            &mut host,
+        )
+        .unwrap();
+    session.eval("BASED DOUGH FR 5\n", &mut host).unwrap();
+
+    assert_eq!(
+        session.complete("SAY do", 6),
+        (4, vec!["DOUGH".to_string(), "doubled".to_string()])
+    );
+    assert_eq!(
+        session.complete("fanum", 5),
+        (0, vec!["FANUMTAX".to_string()])
+    );
+    assert_eq!(
+        session.complete("SAY TYPEOF(au", 13),
+        (11, vec!["aura".to_string()])
+    );
+    assert_eq!(session.complete(":re", 3), (0, vec![":reset".to_string()]));
+}
+
+#[test]
+fn snippets_without_lock_in_are_wrapped() {
+    let run = |code: &str| {
+        let bytecode = repl::compile_snippet(code, "<eval>").expect("snippet should compile");
+        let mut host = CaptureHost::new("");
+        brvm::vm::execute_with_host(&bytecode, &mut host).unwrap();
+        host.output
+    };
+    assert_eq!(run("LOCK IN SAY \"hi\" ITS OVER"), "hi\n");
+    assert_eq!(run("FANUMTAX aura FR 2\nSAY aura 😏 3"), "6\n");
+
+    let err = repl::compile_snippet("SAY 1\nSAY 1 😭 \"x\"", "<eval>").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "<eval>:2:7: subtraction requires both operands to be numbers"
+    );
+}
@@ -0,0 +1,98 @@
+use brvm::compiler::{self, CompileOptions};
+use brvm::vm::{self, ErrorKind, Frame, RuntimeError};
+use std::io::Cursor;
+
+fn run(source: &str) -> RuntimeError {
+    let bytecode = compiler::compile_source(source, "<test>", &CompileOptions::default())
+        .expect("compilation should succeed");
+    vm::execute_with_io(&bytecode, &mut Cursor::new(Vec::new()), &mut Vec::new())
+        .expect_err("program should fail")
+}
+
+#[test]
+fn type_errors_name_the_operation_and_operands() {
+    // The compiler cannot see through the parameter, so this fails at runtime.
+    let err =
+        run("TRALALERO dec(n)\n  RETREAT n 😭 1\nTRALALA\n\nLOCK IN\nSAY dec(\"a\")\nITS OVER\n");
+    assert_eq!(
+        err.kind,
+        ErrorKind::TypeError {
+            op: "SUB",
+            lhs: "string",
+            rhs: "number"
+        }
+    );
+    assert_eq!(
+        err.to_string(),
+        "runtime: subtraction requires both operands to be numbers"
+    );
+
+    let err = run("LOCK IN\nFANUMTAX aura FR 0\nSAY 1 🚡 aura\nITS OVER\n");
+    assert_eq!(err.kind, ErrorKind::DivisionByZero);
+}
+
+#[test]
+fn errors_carry_the_failing_offset_and_active_calls() {
+    let source = "TRALALERO inner(n)
+  RETREAT mog 💀 n
+TRALALA
+
+TRALALERO outer(n)
+  RETREAT inner(n) 💀 1
+TRALALA
+
+LOCK IN
+SAY outer(1)
+ITS OVER
+";
+    let err = run(source);
+    assert_eq!(err.kind, ErrorKind::UnsetGlobal { index: 3 });
+    assert_eq!(err.to_string(), "runtime: unset braincell: mog");
+
+    // Both frames are live; the failing LOAD_GLOBAL is the first
+    // instruction of inner, which is function 0.
+    let bytecode = compiler::compile_source(source, "<test>", &CompileOptions::default()).unwrap();
+    let module = brvm::bytecode::decode(&bytecode).unwrap();
+    assert_eq!(err.ip, Some(module.functions[0].code_offset as usize));
+    assert_eq!(
+        err.call_stack
+            .iter()
+            .map(|f| f.function)
+            .collect::<Vec<_>>(),
+        [1, 0]
+    );
+    let Frame { return_address, .. } = err.call_stack[1];
+    assert!(return_address > module.functions[1].code_offset as usize);
+    assert_eq!(
+        err.backtrace,
+        [
+            "at inner+0x0",
+            "called from outer+0x3",
+            "called from <main>+0x5"
+        ]
+    );
+    assert_eq!(module.locate(return_address), "outer+0x8");
+}
+
+#[test]
+fn unknown_opcodes_report_their_offset() {
+    let mut bytecode = compiler::compile_source(
+        "LOCK IN\nSAY 1\nITS OVER\n",
+        "<test>",
+        &CompileOptions::default(),
+    )
+    .unwrap();
+    let code_start = bytecode.len() - 7; // LOAD_CONST 0, PRINT, HALT
+    bytecode[code_start + 5] = 0xEE;
+    let err = vm::execute_with_io(&bytecode, &mut Cursor::new(Vec::new()), &mut Vec::new())
+        .expect_err("bad opcode should fail");
+    assert_eq!(
+        err.kind,
+        ErrorKind::UnknownOpcode {
+            byte: 0xEE,
+            offset: 5
+        }
+    );
+    assert_eq!(err.ip, Some(5));
+    assert!(err.call_stack.is_empty());
+}
@@ -0,0 +1,38 @@
+use brvm::golden;
+use brvm::samples::{self, SAMPLES};
+
+#[test]
+fn installed_samples_pass_their_own_golden_tests() {
+    let dir = std::env::temp_dir().join(format!("brvm-samples-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+
+    let installed = samples::install(&dir, false).unwrap();
+    assert_eq!(installed.len(), SAMPLES.len());
+    assert!(installed.iter().all(|(_, written)| *written));
+    let hello = std::fs::read_to_string(dir.join("hello.br")).unwrap();
+    assert!(
+        hello.contains(&format!("brvm run {}", dir.join("hello.br").display())),
+        "{}",
+        hello
+    );
+
+    let report = golden::run_dir(&dir).unwrap();
+    assert!(report.passed(), "{}", report);
+    assert_eq!(report.cases.len(), SAMPLES.len(), "{}", report);
+
+    // A second install keeps what is there, including edits.
+    std::fs::write(dir.join("hello.br"), "LOCK IN\nITS OVER\n").unwrap();
+    let installed = samples::install(&dir, false).unwrap();
+    assert!(installed.iter().all(|(_, written)| !*written));
+    assert_eq!(
+        std::fs::read_to_string(dir.join("hello.br")).unwrap(),
+        "LOCK IN\nITS OVER\n"
+    );
+    samples::install(&dir, true).unwrap();
+    assert_eq!(
+        std::fs::read_to_string(dir.join("hello.br")).unwrap(),
+        hello
+    );
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,137 @@
+use brvm::serve::{self, Limits};
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+
+fn run(body: &str) -> (u16, String) {
+    let (status, _, json) = serve::run_request(body, &Limits::default());
+    (status, json)
+}
+
+#[test]
+fn run_returns_captured_output_as_json() {
+    let (status, json) = run(
+        r#"{"source": "LOCK IN\nSAY \"wsg \" 💀 TOUCHY()\nSAY \"tab\\there 💀\"\nITS OVER\n", "stdin": "bob\n"}"#,
+    );
+    assert_eq!(status, 200);
+    assert_eq!(
+        json,
+        r#"{"ok": true, "stdout": "wsg bob\ntab\there 💀\n", "error": null}"#
+    );
+}
+
+#[test]
+fn compile_errors_carry_their_position() {
+    let (status, json) = run(r#"{"source": "LOCK IN\nSAY 1 = 2\nITS OVER\n"}"#);
+    assert_eq!(status, 200);
+    assert!(json.contains(r#""stage": "compile""#), "{}", json);
+    assert!(json.contains(r#""line": 2, "col": 7"#), "{}", json);
+}
+
+#[test]
+fn runaway_programs_hit_the_limits() {
+    let limits = Limits {
+        max_instructions: 10_000,
+        max_string_bytes: 1024,
+        max_output_bytes: 64,
+        ..Limits::default()
+    };
+    let cases = [
+        (
+            "LOCK IN\\nFANUMTAX aura FR 1\\nSKIBIDI aura\\nRIZZUP\\nITS OVER\\n",
+            "instruction limit exceeded",
+        ),
+        (
+            "LOCK IN\\nFANUMTAX aura FR \\\"ab\\\"\\nSKIBIDI aura\\nFANUMTAX aura FR aura 💀 aura\\nRIZZUP\\nITS OVER\\n",
+            "memory limit exceeded",
+        ),
+        (
+            "LOCK IN\\nFANUMTAX aura FR 1\\nSKIBIDI aura\\nSAY \\\"spam spam spam\\\"\\nRIZZUP\\nITS OVER\\n",
+            "output limit exceeded",
+        ),
+    ];
+    for (source, message) in cases {
+        let body = format!("{{\"source\": \"{}\"}}", source);
+        let (status, _, json) = serve::run_request(&body, &limits);
+        assert_eq!(status, 200);
+        assert!(json.contains(r#""stage": "runtime""#), "{}", json);
+        assert!(json.contains(message), "{}", json);
+    }
+}
+
+#[test]
+fn malformed_requests_are_rejected() {
+    for body in [
+        "",
+        "{",
+        r#"{"source": 1}"#,
+        r#"{"stdin": "x"}"#,
+        r#"{"source": "x"} extra"#,
+    ] {
+        let (status, json) = run(body);
+        assert_eq!(status, 400, "{}", body);
+        assert!(json.contains("bad request"), "{}", json);
+    }
+}
+
+#[test]
+fn server_answers_over_http() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    std::thread::spawn(move || serve::serve(listener, Limits::default()));
+
+    let body = r#"{"source": "LOCK IN\nSAY 6 😏 7\nITS OVER\n"}"#;
+    let mut stream = TcpStream::connect(addr).unwrap();
+    write!(
+        stream,
+        "POST /run HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+        body.len(),
+        body
+    )
+    .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+    assert!(response.ends_with(r#"{"ok": true, "stdout": "42\n", "error": null}"#));
+}
+
+#[test]
+fn connections_past_the_limit_get_503() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let limits = Limits {
+        max_connections: 1,
+        ..Limits::default()
+    };
+    std::thread::spawn(move || serve::serve(listener, limits));
+
+    let get = || {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(stream, "GET / HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    };
+
+    // This connection holds the only slot until it sends its request.
+    let mut held = TcpStream::connect(addr).unwrap();
+    let response = get();
+    assert!(
+        response.starts_with("HTTP/1.1 503 Service Unavailable\r\n"),
+        "{}",
+        response
+    );
+    assert!(response.ends_with(r#"{"ok": false, "error": "server is busy, try again later"}"#));
+
+    write!(held, "GET / HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+    let mut response = String::new();
+    held.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+
+    // The slot is given back once that handler ends.
+    let freed = (0..100).any(|_| {
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        get().starts_with("HTTP/1.1 200 OK\r\n")
+    });
+    assert!(freed);
+}
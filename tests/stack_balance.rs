@@ -0,0 +1,230 @@
+use brvm::host::IoHost;
+use brvm::{bytecode, compiler, vm};
+use std::io::Cursor;
+
+fn checked() -> vm::VmOptions {
+    vm::VmOptions {
+        check_stack: true,
+        ..Default::default()
+    }
+}
+
+fn assert_balanced(source: &str, stdin: &str) {
+    let bytecode =
+        compiler::compile_source(source, "<test>", &Default::default()).expect("should compile");
+    let mut input = Cursor::new(stdin.as_bytes());
+    let mut output = Vec::new();
+    let mut host = IoHost::new(&mut input, &mut output);
+    let mut machine = vm::VM::new(&mut host, &checked());
+    machine.load(&bytecode).unwrap();
+    machine
+        .run()
+        .expect("program should finish with balanced stacks");
+    assert_eq!(machine.stack_depth(), 0);
+    assert_eq!(machine.call_depth(), 0);
+}
+
+#[test]
+fn loops_with_function_calls_leave_no_values() {
+    assert_balanced(
+        r#"
+TRALALERO twice(x)
+  RETREAT x 💀 x
+TRALALA
+
+TRALALERO shout(s)
+  YAP s
+  SAY "!"
+TRALALA
+
+LOCK IN
+FANUMTAX aura FR 3
+SKIBIDI aura
+  SAY twice(aura)
+  YAP shout("hey")
+  SAY ring yas shout("you")
+  FANUMTAX aura FR aura 😭 1
+RIZZUP
+ITS OVER
+"#,
+        "",
+    );
+}
+
+#[test]
+fn nested_ifs_with_returns_leave_no_values() {
+    assert_balanced(
+        r#"
+TRALALERO classify(n)
+  ONGOD n
+    ONGOD n 😭 1
+      RETREAT "many"
+    NO CAP
+      RETREAT "one"
+    DEADASS
+  DEADASS
+  RETREAT "none"
+TRALALA
+
+TRALALERO countdown(n, acc)
+  ONGOD n
+    RETREAT countdown(n 😭 1, acc 💀 n)
+  DEADASS
+  RETREAT acc
+TRALALA
+
+TRALALERO fact(n)
+  ONGOD n
+    RETREAT n 😏 fact(n 😭 1)
+  DEADASS
+  RETREAT 1
+TRALALA
+
+LOCK IN
+SAY classify(0) 💀 classify(1) 💀 classify(5)
+SAY countdown(10, 0) 💀 fact(5)
+DIDDLE peak FR TRANSFORM(TOUCHY("n: "))
+SAY peak 😏 peak
+ITS OVER
+"#,
+        "4\n",
+    );
+}
+
+#[test]
+fn leftover_value_at_halt_is_reported() {
+    let mut bytes = bytecode::MAGIC.to_vec();
+    bytes.extend_from_slice(&bytecode::VERSION.to_le_bytes());
+    bytes.extend_from_slice(&0u16.to_le_bytes());
+    bytes.extend_from_slice(&1u32.to_le_bytes()); // one constant
+    bytes.push(1);
+    bytes.extend_from_slice(&1.0f64.to_le_bytes());
+    bytes.extend_from_slice(&0u32.to_le_bytes()); // no functions
+    bytes.extend_from_slice(&6u32.to_le_bytes());
+    bytes.extend_from_slice(&[0x02, 0, 0, 0, 0, 0x01]); // LOAD_CONST 0; HALT
+
+    let mut output = Vec::new();
+    let mut input = Cursor::new(&b""[..]);
+    let err = vm::execute_with_options(&bytes, &mut input, &mut output, &checked())
+        .expect_err("leftover value should fail the check");
+    assert!(
+        err.to_string()
+            .contains("1 value(s) left on the operand stack at HALT"),
+        "{}",
+        err
+    );
+
+    let unchecked = vm::VmOptions {
+        check_stack: false,
+        ..Default::default()
+    };
+    vm::execute_with_options(&bytes, &mut input, &mut output, &unchecked)
+        .expect("unchecked run ignores leftovers");
+}
+
+#[test]
+fn youshallnotpass_checks_the_stack_like_halt() {
+    // LOAD_CONST 1; YOUSHALLNOTPASS, inside the call with 7 pending below.
+    let leftover = frame_test(&[0x02, 1, 0, 0, 0, 0x12]);
+    let err = vm::execute_with_options(
+        &leftover,
+        &mut Cursor::new(&b""[..]),
+        &mut Vec::new(),
+        &checked(),
+    )
+    .expect_err("the value pushed before stopping is left over");
+    assert_eq!(err.kind, vm::ErrorKind::LeftoverValues { count: 1 });
+
+    // Stopping with only the caller's values below the frame is fine.
+    let mut output = Vec::new();
+    vm::execute_with_options(
+        &frame_test(&[0x12]),
+        &mut Cursor::new(&b""[..]),
+        &mut output,
+        &checked(),
+    )
+    .expect("YOUSHALLNOTPASS inside a call");
+    assert!(output.is_empty());
+}
+
+// Main pushes 7, calls a function with `body`, and prints two values.
+// Constant 0 is 7 and constant 1 is 1.
+fn frame_test(body: &[u8]) -> Vec<u8> {
+    let mut code = vec![0x02, 0, 0, 0, 0, 0x0D, 2, 0, 0, 0, 0x09, 0x09, 0x01];
+    let offset = code.len() as u32;
+    code.extend_from_slice(body);
+    bytecode::encode(&bytecode::Module {
+        version: bytecode::VERSION,
+        flags: 0,
+        globals: vm::GLOBAL_COUNT,
+        constants: vec![
+            brvm::value::Value::Number(7.0),
+            brvm::value::Value::Number(1.0),
+            brvm::value::Value::String("f".to_string().into()),
+        ],
+        functions: vec![bytecode::FunctionEntry {
+            name_const: 2,
+            arity: 0,
+            local_count: 0,
+            code_offset: offset,
+            max_stack: 0,
+        }],
+        code,
+    })
+}
+
+#[test]
+fn leftover_values_are_dropped_when_a_function_returns() {
+    // LOAD_CONST 1; LOAD_CONST 1; UNTILWEMEETAGAIN
+    let bytes = frame_test(&[0x02, 1, 0, 0, 0, 0x02, 1, 0, 0, 0, 0x0E]);
+    let unchecked = vm::VmOptions {
+        check_stack: false,
+        ..Default::default()
+    };
+    let mut output = Vec::new();
+    vm::execute_with_options(&bytes, &mut Cursor::new(&b""[..]), &mut output, &unchecked)
+        .expect("the extra value is dropped");
+    assert_eq!(output, b"1\n7\n");
+
+    let err = vm::execute_with_options(
+        &bytes,
+        &mut Cursor::new(&b""[..]),
+        &mut Vec::new(),
+        &checked(),
+    )
+    .expect_err("checked runs report the extra value");
+    assert_eq!(
+        err.kind,
+        vm::ErrorKind::UnbalancedStack {
+            function: 0,
+            expected: 1,
+            found: 2,
+        }
+    );
+    assert_eq!(
+        err.to_string(),
+        "runtime: stack check: function left 1 value(s) on the operand stack"
+    );
+    assert_eq!(err.call_stack.len(), 1);
+}
+
+#[test]
+fn functions_cannot_consume_their_callers_values() {
+    // POOPY; LOAD_CONST 1; UNTILWEMEETAGAIN
+    let bytes = frame_test(&[0x11, 0x02, 1, 0, 0, 0, 0x0E]);
+    let unchecked = vm::VmOptions {
+        check_stack: false,
+        ..Default::default()
+    };
+    let err = vm::execute_with_options(
+        &bytes,
+        &mut Cursor::new(&b""[..]),
+        &mut Vec::new(),
+        &unchecked,
+    )
+    .expect_err("the caller's 7 is gone");
+    assert_eq!(
+        err.to_string(),
+        "runtime: stack check: function took 1 value(s) from its caller's stack"
+    );
+}
@@ -0,0 +1,81 @@
+use brvm::host::IoHost;
+use brvm::{compiler, state, vm};
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+
+fn temp_state(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("brvm-state-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+fn run_with_state(source: &str, path: &Path) -> Result<String, String> {
+    let bytecode = compiler::compile_source(source, "<test>", &Default::default())
+        .map_err(|e| e.to_string())?;
+    let mut globals = state::load(path)?;
+    let mut input = Cursor::new(&b""[..]);
+    let mut output = Vec::new();
+    vm::execute_with_globals(
+        &bytecode,
+        &mut IoHost::new(&mut input, &mut output),
+        &Default::default(),
+        &mut globals,
+    )
+    .map_err(|e| e.to_string())?;
+    state::save(path, &globals)?;
+    Ok(String::from_utf8(output).unwrap())
+}
+
+#[test]
+fn counter_advances_across_runs() {
+    let path = temp_state("counter");
+    run_with_state(
+        "LOCK IN\nFANUMTAX aura FR 0\nFANUMTAX goon FR \"x\"\nITS OVER\n",
+        &path,
+    )
+    .unwrap();
+
+    let counter = "LOCK IN\nFANUMTAX aura FR aura 💀 1\nFANUMTAX goon FR goon 💀 \"x\"\nSAY aura 💀 \" \" 💀 goon\nITS OVER\n";
+    for expected in ["1 xx\n", "2 xxx\n", "3 xxxx\n"] {
+        assert_eq!(run_with_state(counter, &path).unwrap(), expected);
+    }
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn runtime_error_keeps_previous_state() {
+    let path = temp_state("error");
+    run_with_state("LOCK IN\nFANUMTAX aura FR 5\nITS OVER\n", &path).unwrap();
+    let before = std::fs::read(&path).unwrap();
+
+    let err = run_with_state("LOCK IN\nFANUMTAX aura FR 6\nSAY 1 🚡 0\nITS OVER\n", &path)
+        .expect_err("division by zero should fail");
+    assert!(err.contains("division by zero"), "{}", err);
+    assert_eq!(std::fs::read(&path).unwrap(), before);
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn corrupted_state_is_rejected_whole() {
+    let mut globals: vm::Globals = vec![None; vm::GLOBAL_COUNT];
+    globals[0] = Some(brvm::value::Value::Number(1.0));
+    let bytes = state::encode(&globals).unwrap();
+    assert_eq!(state::decode(&bytes).unwrap(), globals);
+
+    let mut future = bytes.clone();
+    future[4] = 2;
+    assert!(state::decode(&future)
+        .unwrap_err()
+        .contains("unsupported state version 2"));
+
+    let mut bad_tag = bytes.clone();
+    bad_tag[17] = 9;
+    assert!(state::decode(&bad_tag)
+        .unwrap_err()
+        .contains("unknown value tag 9 in braincell 1"));
+
+    assert!(state::decode(&bytes[..bytes.len() - 1])
+        .unwrap_err()
+        .contains("truncated"));
+    assert!(state::decode(b"nope").is_err());
+}
@@ -0,0 +1,48 @@
+use brvm::{bytecode, compiler, strip, vm};
+use std::io::Cursor;
+
+fn run(bytecode: &[u8]) -> String {
+    let mut output = Vec::new();
+    vm::execute_with_io(bytecode, &mut Cursor::new(&b""[..]), &mut output).expect("should run");
+    String::from_utf8(output).unwrap()
+}
+
+#[test]
+fn encode_reverses_decode() {
+    let source =
+        std::fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/examples/v4.brainrot"))
+            .unwrap();
+    let bytecode = compiler::compile_source(&source, "v4.brainrot", &Default::default()).unwrap();
+    let module = bytecode::decode(&bytecode).unwrap();
+    assert_eq!(bytecode::encode(&module), bytecode);
+}
+
+#[test]
+fn strip_drops_function_names_but_not_behavior() {
+    let source = "TRALALERO shout(word)
+  RETREAT word 💀 \"!\"
+TRALALA
+
+TRALALERO greet(name)
+  RETREAT shout(\"wsg \" 💀 name)
+TRALALA
+
+LOCK IN
+SAY greet(\"greet\")
+ITS OVER
+";
+    let bytecode = compiler::compile_source(source, "<test>", &Default::default()).unwrap();
+    let stripped = strip::strip(&bytecode).unwrap();
+    assert!(stripped.len() < bytecode.len());
+    assert_eq!(run(&stripped), run(&bytecode));
+
+    // "greet" is also a string the program prints, so it stays.
+    let module = bytecode::decode(&stripped).unwrap();
+    let names: Vec<&str> = module
+        .functions
+        .iter()
+        .map(|f| module.function_name(f))
+        .collect();
+    assert_eq!(names, ["", "greet"]);
+    assert_eq!(strip::strip(&stripped).unwrap(), stripped);
+}
@@ -0,0 +1,62 @@
+use brvm::compiler;
+use brvm::trace::Tracer;
+use brvm::vm;
+
+// The name and phase of each event, in order.
+fn spans(source: &str) -> Vec<String> {
+    let bytecode = compiler::compile_source(source, "<test>", &Default::default()).unwrap();
+    let mut output = Vec::new();
+    let mut machine = vm::VM::builder().stdout(&mut output).build();
+    let tracer = Tracer::attach(&mut machine, &bytecode).unwrap();
+    let _ = machine.execute(&bytecode);
+    tracer
+        .to_json()
+        .lines()
+        .filter_map(|line| {
+            let name = line.split("\"name\": \"").nth(1)?.split('"').next()?;
+            let phase = line.split("\"ph\": \"").nth(1)?.split('"').next()?;
+            Some(format!("{} {}", phase, name))
+        })
+        .collect()
+}
+
+#[test]
+fn calls_tail_calls_and_builtins_become_spans() {
+    let source = "TRALALERO down(n)
+  ONGOD n
+    RETREAT down(n 😭 1)
+  DEADASS
+  RETREAT TRANSFORM(\"0\")
+TRALALA
+
+LOCK IN
+SAY down(1)
+ITS OVER
+";
+    assert_eq!(
+        spans(source),
+        [
+            "B <main>",
+            "B down",
+            "E down",
+            "B down",
+            "B TRANSFORM",
+            "E TRANSFORM",
+            "E down",
+            "E <main>",
+        ]
+    );
+}
+
+#[test]
+fn spans_a_runtime_error_cut_short_are_closed() {
+    let source = "TRALALERO boom(n)
+  CRASHOUT \"boom \" 💀 n
+TRALALA
+
+LOCK IN
+SAY boom(1)
+ITS OVER
+";
+    assert_eq!(spans(source), ["B <main>", "B boom", "E boom", "E <main>"]);
+}
@@ -0,0 +1,420 @@
+use brvm::profile::{BranchProfile, Profile};
+use brvm::{bytecode, compiler, transpile, vm};
+use std::io::{Cursor, Write};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+const SEED: u64 = 42;
+
+struct Run {
+    stdout: String,
+    stderr: String,
+    error: Option<String>,
+    status: i32,
+}
+
+fn compile(source: &str) -> Vec<u8> {
+    compiler::compile_source(source, "<test>", &Default::default()).expect("should compile")
+}
+
+fn run_vm(bytecode: &[u8], stdin: &str) -> Run {
+    let mut output = Vec::new();
+    let options = vm::VmOptions {
+        seed: Some(SEED),
+        check_stack: false,
+        ..Default::default()
+    };
+    let result = vm::execute_with_options(
+        bytecode,
+        &mut Cursor::new(stdin.as_bytes()),
+        &mut output,
+        &options,
+    );
+    Run {
+        stdout: String::from_utf8(output).unwrap(),
+        stderr: String::new(),
+        status: result.as_ref().map_or(1, |r| r.exit_status()),
+        error: result.err().map(|e| e.to_string()),
+    }
+}
+
+fn have_cc() -> bool {
+    Command::new("cc")
+        .arg("--version")
+        .output()
+        .is_ok_and(|o| o.status.success())
+}
+
+// Builds the transpiled program with the system C compiler; None when there
+// is no `cc` to build with.
+fn run_native(name: &str, bytecode: &[u8], stdin: &str) -> Option<Run> {
+    if !have_cc() {
+        eprintln!("skipping {}: no C compiler on PATH", name);
+        return None;
+    }
+
+    let dir = std::env::temp_dir().join(format!("brvm-transpile-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let c_path = dir.join(format!("{}.c", name));
+    let exe_path: PathBuf = dir.join(name);
+    std::fs::write(
+        &c_path,
+        transpile::to_c(bytecode).expect("should transpile"),
+    )
+    .unwrap();
+
+    let build = Command::new("cc")
+        .args([
+            "-std=c99",
+            "-Wall",
+            "-Wextra",
+            "-pedantic",
+            "-Werror",
+            "-O2",
+        ])
+        .arg(&c_path)
+        .arg("-o")
+        .arg(&exe_path)
+        .arg("-lm")
+        .output()
+        .unwrap();
+    assert!(
+        build.status.success(),
+        "cc failed:\n{}",
+        String::from_utf8_lossy(&build.stderr)
+    );
+
+    let mut child = Command::new(&exe_path)
+        .env("BRVM_SEED", SEED.to_string())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .unwrap();
+    let out = child.wait_with_output().unwrap();
+    let _ = std::fs::remove_file(&c_path);
+    let _ = std::fs::remove_file(&exe_path);
+
+    let stderr = String::from_utf8(out.stderr).unwrap();
+    Some(Run {
+        stdout: String::from_utf8(out.stdout).unwrap(),
+        error: (!out.status.success() && !stderr.is_empty()).then(|| stderr.trim_end().to_string()),
+        status: out.status.code().unwrap_or(-1),
+        stderr,
+    })
+}
+
+fn assert_matches_vm(name: &str, source: &str, stdin: &str) {
+    let bytecode = compile(source);
+    let Some(native) = run_native(name, &bytecode, stdin) else {
+        return;
+    };
+    let expected = run_vm(&bytecode, stdin);
+    assert_eq!(native.stdout, expected.stdout);
+    assert_eq!(native.error, expected.error);
+    assert_eq!(native.status, expected.status);
+}
+
+#[test]
+fn examples_match_the_vm() {
+    let stdin = "Alice\n12\n3\n5\nhello\n3\n";
+    for example in ["v1", "v2", "v3", "v4"] {
+        let path = format!(
+            "{}/examples/{}.brainrot",
+            env!("CARGO_MANIFEST_DIR"),
+            example
+        );
+        let source = std::fs::read_to_string(path).unwrap();
+        assert_matches_vm(example, &source, stdin);
+    }
+}
+
+#[test]
+fn numbers_print_like_the_vm() {
+    assert_matches_vm(
+        "numbers",
+        r#"
+LOCK IN
+SAY 0.1 💀 0.2
+SAY 1 🚡 3
+SAY 10 😏 10 😏 10 😏 10 😏 10 😏 10 😏 10 😏 10 😏 10 😏 10 😏 10 😏 10 😏 10 😏 10 😏 10 😏 10 😏 10 😏 10 😏 10 😏 10 😏 10 😏 10
+SAY 1 🚡 1024 🚡 1024 🚡 1024 🚡 1024
+SAY 0 😭 2.5
+SAY "" 💀 123456789 😏 1000
+SAY TRANSFORM("1e-7") 💀 TRANSFORM("-0")
+SAY TRANSFORM("infinity") 😭 TRANSFORM("INF")
+ITS OVER
+"#,
+        "",
+    );
+}
+
+#[test]
+fn functions_builtins_and_strings_match_the_vm() {
+    assert_matches_vm(
+        "functions",
+        r#"
+TRALALERO countdown(n, acc)
+  ONGOD n
+    RETREAT countdown(n 😭 1, acc 💀 n)
+  DEADASS
+  RETREAT acc
+TRALALA
+
+TRALALERO fact(n)
+  ONGOD n 😭 1
+    RETREAT n 😏 fact(n 😭 1)
+  DEADASS
+  RETREAT 1
+TRALALA
+
+LOCK IN
+SAY countdown(100000, 0)
+SAY fact(20)
+SAY RANDOM(1000) 💀 " " 💀 RANDOM(1000) 💀 " " 💀 RANDOM(1000)
+SAY SEED(7) 💀 " " 💀 RANDOM(100)
+SAY RIZZED("héllo 💀") 💀 TYPEOF("x") 💀 TYPEOF(1) 💀 EMPTY("") 💀 EMPTY(3)
+SAY "ab" 😏 2.9 💀 "|" 💀 3 😏 "xy" 💀 "|" 💀 "z" 😏 0
+SAY DRIP("bold green", "piped") 💀 DRIP("", 7)
+SAY SHA256("héllo " 😏 20) 💀 " " 💀 HASH(2.5) 💀 " " 💀 HASH("")
+SAY 1 🟰 1
+SAY 1 🟰 "1"
+SAY "ab" 🟰 "a" 💀 "b"
+SAY TRANSFORM(" 3,5\t") 💀 TRANSFORM("1,0000") 💀 EMPTY(TRYTRANSFORM("1,000")) 💀 TRYTRANSFORM(" 7 ") 💀 TRYTRANSFORM("x,1,2") 💀 TRYTRANSFORM(2)
+SAY POKE("npc", 5) 💀 PEEK("npc") 💀 npc
+SAY FLOORDIV(7, 2) 💀 " " 💀 FLOORDIV(0 😭 7, 2) 💀 " " 💀 TRUNC(0 😭 2.9) 💀 " " 💀 TRUNC(2.5)
+SAY BASE64("héllo 💀") 💀 " " 💀 BASE64(12) 💀 " " 💀 UNBASE64("Zm9vYg") 💀 UNBASE64("Zm8=")
+SAY FORMATTIME(0 😭 86401, "%FT%TZ %a %b %j") 💀 " " 💀 PARSETIME("29/feb/2024 12:34", "%d/%b/%Y %H:%M")
+FANUMTAX sigma FR "dup" 😏 2
+DIDDLE gyatt FR sigma
+DIDDLE gyatt FR gyatt
+SAY sigma 💀 gyatt
+YAP TOUCHY("name? ")
+SAY "!"
+ITS OVER
+"#,
+        "  sigma  \n",
+    );
+}
+
+#[test]
+fn runtime_errors_match_the_vm() {
+    let cases = [
+        ("div_zero", "LOCK IN\nSAY \"before\"\nSAY 1 🚡 0\nITS OVER\n"),
+        ("unset", "LOCK IN\nSAY mog\nITS OVER\n"),
+        ("bad_transform", "LOCK IN\nSAY TRANSFORM(\"0x10\")\nITS OVER\n"),
+        ("thousands", "LOCK IN\nSAY TRANSFORM(\"1,000\")\nITS OVER\n"),
+        ("repeat_cap", "LOCK IN\nSAY \"abc\" 😏 10000000\nITS OVER\n"),
+        ("bad_style", "LOCK IN\nSAY DRIP(\"red sparkly\", 1)\nITS OVER\n"),
+        ("bad_date", "LOCK IN\nSAY PARSETIME(\"2023-02-29\", \"%F\")\nITS OVER\n"),
+        ("floordiv_zero", "LOCK IN\nSAY FLOORDIV(1, 0)\nITS OVER\n"),
+        ("bad_base64", "LOCK IN\nSAY UNBASE64(\"/w==\")\nITS OVER\n"),
+        (
+            "crashout",
+            "TRALALERO check(n)\n  ONGOD n\n    RETREAT n\n  DEADASS\n  CRASHOUT \"bad n: \" 💀 n\nTRALALA\n\nLOCK IN\nSAY check(2)\nSAY check(0)\nITS OVER\n",
+        ),
+        (
+            "overflow",
+            "TRALALERO deep(n)\n  RETREAT 1 💀 deep(n)\nTRALALA\n\nLOCK IN\nSAY deep(1)\nITS OVER\n",
+        ),
+        (
+            "overflow_chain",
+            "TRALALERO ping(n)\n  RETREAT pong(n) 💀 0\nTRALALA\nTRALALERO pong(n)\n  RETREAT ping(n) 💀 0\nTRALALA\nTRALALERO start(n)\n  RETREAT deep(n) 💀 ping(n)\nTRALALA\nTRALALERO deep(n)\n  ONGOD n 🟰 3\n    RETREAT 0\n  DEADASS\n  RETREAT deep(n 💀 1)\nTRALALA\n\nLOCK IN\nSAY start(1)\nITS OVER\n",
+        ),
+    ];
+    for (name, source) in cases {
+        assert_matches_vm(name, source, "");
+    }
+}
+
+#[test]
+fn strict_math_matches_the_vm() {
+    let options = compiler::CompileOptions {
+        strict_math: true,
+        ..Default::default()
+    };
+    let bytecode = compiler::compile_source(
+        "LOCK IN\nFANUMTAX aura FR TRANSFORM(\"1e308\")\nSAY aura 💀 1\nSAY aura 😏 10\nITS OVER\n",
+        "<test>",
+        &options,
+    )
+    .unwrap();
+    let Some(native) = run_native("strict_math", &bytecode, "") else {
+        return;
+    };
+    let expected = run_vm(&bytecode, "");
+    assert_eq!(native.stdout, expected.stdout);
+    assert_eq!(native.error, expected.error);
+    assert_eq!(
+        native.error.as_deref(),
+        Some("runtime: strict math: multiplication gave inf")
+    );
+}
+
+#[test]
+fn declared_global_count_matches_the_vm() {
+    let mut module = bytecode::decode(&compile(
+        "LOCK IN\nFANUMTAX goon FR 1\nSAY goon\nSAY PEEK(\"mog\")\nITS OVER\n",
+    ))
+    .unwrap();
+    module.flags |= bytecode::FLAG_GLOBALS;
+    module.globals = 3;
+    let bytecode = bytecode::encode(&module);
+    let Some(native) = run_native("globals", &bytecode, "") else {
+        return;
+    };
+    let expected = run_vm(&bytecode, "");
+    assert_eq!(expected.stdout, "1\n");
+    assert_eq!(
+        expected.error.as_deref(),
+        Some("runtime: global index out of bounds")
+    );
+    assert_eq!(native.stdout, expected.stdout);
+    assert_eq!(native.error, expected.error);
+}
+
+#[test]
+fn profile_guided_jumps_match_the_vm() {
+    let branch = |index, truthy, falsy| BranchProfile {
+        function: "<main>".to_string(),
+        index,
+        truthy,
+        falsy,
+    };
+    let options = compiler::CompileOptions {
+        profile: Some(Profile {
+            functions: Vec::new(),
+            branches: vec![branch(0, 3, 1), branch(1, 0, 1)],
+        }),
+        ..Default::default()
+    };
+    let source = "LOCK IN\nFANUMTAX aura FR 3\nSKIBIDI aura\n  SAY aura\n  FANUMTAX aura FR aura 😭 1\nRIZZUP\nONGOD aura\n  SAY \"then\"\nNO CAP\n  SAY \"else\"\nDEADASS\nITS OVER\n";
+    let bytecode = compiler::compile_source(source, "<test>", &options).unwrap();
+    let Some(native) = run_native("profile_guided", &bytecode, "") else {
+        return;
+    };
+    let expected = run_vm(&bytecode, "");
+    assert_eq!(expected.stdout, "3\n2\n1\nelse\n");
+    assert_eq!(native.stdout, expected.stdout);
+    assert_eq!(native.error, expected.error);
+}
+
+#[test]
+fn unbalanced_frames_match_the_vm() {
+    // Main pushes 7, calls `f`, and prints two values. One body leaves an
+    // extra value behind, the other pops its caller's 7.
+    for (name, body) in [
+        ("leftover", &[0x02, 1, 0, 0, 0, 0x02, 1, 0, 0, 0, 0x0E][..]),
+        ("consumed", &[0x11, 0x02, 1, 0, 0, 0, 0x0E][..]),
+    ] {
+        let mut code = vec![0x02, 0, 0, 0, 0, 0x0D, 2, 0, 0, 0, 0x09, 0x09, 0x01];
+        let offset = code.len() as u32;
+        code.extend_from_slice(body);
+        let bytecode = bytecode::encode(&bytecode::Module {
+            version: bytecode::VERSION,
+            flags: 0,
+            globals: vm::GLOBAL_COUNT,
+            constants: vec![
+                brvm::value::Value::Number(7.0),
+                brvm::value::Value::Number(1.0),
+                brvm::value::Value::String("f".to_string().into()),
+            ],
+            functions: vec![bytecode::FunctionEntry {
+                name_const: 2,
+                arity: 0,
+                local_count: 0,
+                code_offset: offset,
+                max_stack: 0,
+            }],
+            code,
+        });
+        let Some(native) = run_native(name, &bytecode, "") else {
+            return;
+        };
+        let expected = run_vm(&bytecode, "");
+        assert_eq!(native.stdout, expected.stdout);
+        assert_eq!(native.error, expected.error);
+    }
+}
+
+#[test]
+fn retreat_in_main_sets_the_exit_status() {
+    let cases = [
+        ("exit_3", "LOCK IN\nSAY \"a\"\nRETREAT 3\nITS OVER\n"),
+        ("exit_9", "LOCK IN\nRETREAT 9\nITS OVER\n"),
+        ("exit_zero", "LOCK IN\nRETREAT 0\nITS OVER\n"),
+        ("exit_text", "LOCK IN\nRETREAT \"no\"\nITS OVER\n"),
+        ("exit_range", "LOCK IN\nRETREAT 256\nITS OVER\n"),
+    ];
+    for (name, source) in cases {
+        assert_matches_vm(name, source, "");
+    }
+}
+
+#[test]
+fn stack_shuffles_match_the_vm() {
+    let bytecode = brvm::asm::assemble(
+        "  LOAD_CONST \"a\"\n  LOAD_CONST \"b\"\n  LOAD_CONST \"c\"\n  ROT\n  SWAP\n  ADD\n  ADD\n  PRINT\n  SWAP\n",
+        "<test>",
+    )
+    .unwrap();
+    let Some(native) = run_native("shuffles", &bytecode, "") else {
+        return;
+    };
+    let expected = run_vm(&bytecode, "");
+    assert_eq!(native.stdout, "bac\n");
+    assert_eq!(native.stdout, expected.stdout);
+    assert_eq!(native.error, expected.error);
+
+    assert_matches_vm(
+        "reused_operand",
+        "LOCK IN\nFANUMTAX aura FR 4\nSAY aura 😏 3 😭 1 💀 aura 😏 3\nITS OVER\n",
+        "",
+    );
+}
+
+#[test]
+fn scream_writes_to_stderr() {
+    let bytecode = compile("LOCK IN\nSAY \"data\"\nSCREAM \"oops \" 💀 2\nSAY 3\nITS OVER\n");
+    let Some(native) = run_native("scream", &bytecode, "") else {
+        return;
+    };
+    assert_eq!(native.stdout, "data\n3\n");
+    assert_eq!(native.stderr, "oops 2\n");
+    assert_eq!(native.error, None);
+}
+
+#[test]
+fn big_integer_constants_are_refused() {
+    let err = transpile::to_c(&compile("LOCK IN\nSAY BIG(\"123\")\nITS OVER\n")).unwrap_err();
+    assert_eq!(err, "big integers are not supported in C output");
+}
+
+#[test]
+fn strings_are_escaped_for_c() {
+    let c = transpile::to_c(&compile(
+        "LOCK IN\nSAY \"quote \\\" back \\\\ tri??= é\"\nITS OVER\n",
+    ))
+    .unwrap();
+    assert!(
+        c.contains(r#""quote \" back \\ tri\?\?= \303\251""#),
+        "{}",
+        c
+    );
+}
+
+#[test]
+fn mid_instruction_jumps_are_rejected() {
+    let mut bytecode = compile("LOCK IN\nSAY 1\nITS OVER\n");
+    let code_start = bytecode.len() - 7;
+    // LOAD_CONST 0, PRINT, HALT -> JUMP 1 (inside its own operand), PRINT, HALT
+    bytecode[code_start..code_start + 5].copy_from_slice(&[0x0B, 1, 0, 0, 0]);
+    let err = transpile::to_c(&bytecode).unwrap_err();
+    assert!(err.contains("not an instruction boundary"), "{}", err);
+}
@@ -0,0 +1,85 @@
+use brvm::parser::{self, NodeTrivia, Statement};
+
+const SOURCE: &str = "🖕 Doubles n.
+TRALALERO double(n) 🖕 on the name line
+  RETREAT n 😏 2
+  🖕 before TRALALA
+TRALALA
+
+BASED limit FR 3 # a constant
+
+LOCK IN
+🙈 block 🙉
+ONGOD 1 🖕 condition
+  SAY 1
+
+
+  # after two blank lines
+  SAY 2
+  🖕 before NO CAP
+NO CAP
+  SAY 3
+DEADASS 🖕 end of ONGOD
+🖕 before ITS OVER
+ITS OVER
+🖕 after the program
+";
+
+fn texts(comments: &[parser::Comment]) -> Vec<&str> {
+    comments.iter().map(|c| c.text.as_str()).collect()
+}
+
+#[test]
+fn comments_and_blank_lines_attach_to_nodes() {
+    let (program, trivia) = parser::parse_with_trivia(SOURCE, "<test>").unwrap();
+    let of = |span| trivia.get(span).cloned().unwrap_or_default();
+
+    let function = of(program.functions[0].span);
+    assert_eq!(texts(&function.leading), ["Doubles n."]);
+    assert_eq!(texts(&function.trailing), ["on the name line"]);
+    assert_eq!(texts(&function.closing), ["before TRALALA"]);
+    assert_eq!(of(program.functions[0].body[0].span), NodeTrivia::default());
+
+    let constant = of(program.consts[0].span);
+    assert_eq!(constant.blank_lines, 1);
+    assert_eq!(texts(&constant.trailing), ["a constant"]);
+
+    let if_stmt = &program.main_statements[0];
+    let Statement::If {
+        then_block,
+        else_block,
+        ..
+    } = &if_stmt.statement
+    else {
+        panic!("expected ONGOD");
+    };
+    let if_trivia = of(if_stmt.span);
+    assert_eq!(texts(&if_trivia.leading), ["block"]);
+    assert!(if_trivia.leading[0].block);
+    assert_eq!(texts(&if_trivia.trailing), ["condition", "end of ONGOD"]);
+    assert_eq!(texts(&if_trivia.closing), ["before NO CAP"]);
+    let second = of(then_block[1].span);
+    assert_eq!(second.blank_lines, 2);
+    assert_eq!(texts(&second.leading), ["after two blank lines"]);
+    assert_eq!(
+        of(else_block.as_ref().unwrap()[0].span),
+        NodeTrivia::default()
+    );
+
+    assert_eq!(trivia.program.blank_lines, 1);
+    assert_eq!(
+        texts(&trivia.program.closing),
+        ["before ITS OVER", "after the program"]
+    );
+}
+
+#[test]
+fn trivia_mode_parses_the_same_program() {
+    let (program, _) = parser::parse_with_trivia(SOURCE, "<test>").unwrap();
+    let tokens = brvm::lexer::tokenize_spanned(SOURCE, "<test>").unwrap();
+    let plain = parser::parse(tokens, "<test>").unwrap();
+    assert_eq!(format!("{:?}", program), format!("{:?}", plain));
+
+    let error = parser::parse_with_trivia("LOCK IN\n🖕 hi\nSAY\nITS OVER\n", "<test>").unwrap_err();
+    assert_eq!((error.line, error.col), (4, 1));
+}
@@ -0,0 +1,107 @@
+use brvm::compiler::{self, CompileOptions};
+use brvm::vm;
+use std::io::Cursor;
+
+fn errors(source: &str, strict: bool) -> Vec<String> {
+    let options = CompileOptions {
+        strict,
+        ..Default::default()
+    };
+    match compiler::compile_source(source, "<test>", &options) {
+        Ok(_) => Vec::new(),
+        Err(e) => e.to_string().lines().map(str::to_string).collect(),
+    }
+}
+
+#[test]
+fn known_string_operands_fail_at_compile_time() {
+    let source = "LOCK IN
+FANUMTAX aura FR \"ten\"
+SAY aura 😭 1
+SAY 6 🚡 \"2\"
+SAY \"ab\" 😏 \"cd\"
+SAY \"ab\" 😏 2
+ITS OVER
+";
+    assert_eq!(
+        errors(source, false),
+        [
+            "<test>:3:10: subtraction requires both operands to be numbers",
+            "<test>:4:7: division requires both operands to be numbers",
+            "<test>:5:10: multiplication requires both operands to be numbers",
+        ]
+    );
+}
+
+#[test]
+fn unknown_types_are_left_to_the_vm() {
+    // TOUCHY input, parameters without annotations and values changed in
+    // a loop could be anything, so none of these are errors.
+    let source = "TRALALERO half(n)
+  RETREAT n 🚡 2
+TRALALA
+
+LOCK IN
+FANUMTAX aura FR 4
+SAY half(aura)
+FANUMTAX peak FR TOUCHY()
+SAY TRANSFORM(peak) 😭 1
+SKIBIDI aura
+  SAY goon 😭 1
+  FANUMTAX goon FR \"x\"
+  FANUMTAX aura FR aura 😭 1
+RIZZUP
+ITS OVER
+";
+    assert_eq!(errors(source, false), Vec::<String>::new());
+
+    let bytecode = compiler::compile_source(
+        "LOCK IN\nFANUMTAX aura: number FR 3\nSAY aura 😭 1\nITS OVER\n",
+        "<test>",
+        &CompileOptions::default(),
+    )
+    .expect("annotated program should compile");
+    let mut output = Vec::new();
+    vm::execute_with_io(&bytecode, &mut Cursor::new(Vec::new()), &mut output).unwrap();
+    assert_eq!(String::from_utf8(output).unwrap(), "2\n");
+}
+
+#[test]
+fn annotations_are_checked_on_assignment_and_calls() {
+    let source = "TRALALERO greet(name: string, times: number)
+  RETREAT name 😏 times
+TRALALA
+
+LOCK IN
+FANUMTAX aura: number FR 1
+FANUMTAX aura FR \"one\"
+SAY greet(\"hi\", \"twice\")
+FANUMTAX peak: string FR \"a\"
+FANUMTAX peak: number FR 2
+ITS OVER
+";
+    assert_eq!(
+        errors(source, false),
+        [
+            "<test>:7:1: cannot assign a string to 'aura', which is annotated number",
+            "<test>:8:17: argument 'times' of function greet is annotated number, got a string",
+            "<test>:10:1: 'peak' is annotated number but was annotated string before",
+        ]
+    );
+}
+
+#[test]
+fn strict_mode_rejects_known_mixed_add() {
+    let source = "LOCK IN\nSAY 5 💀 \"!\"\nSAY \"a\" 💀 \"b\"\nITS OVER\n";
+    assert_eq!(errors(source, false), Vec::<String>::new());
+    assert_eq!(
+        errors(source, true),
+        ["<test>:2:7: strict mode: cannot add number and string"]
+    );
+}
+
+#[test]
+fn bad_annotation_is_a_parse_error() {
+    let errors = errors("LOCK IN\nFANUMTAX aura: bool FR 1\nITS OVER\n", false);
+    assert_eq!(errors, ["<test>:2:16: unknown type 'bool'"]);
+}
@@ -0,0 +1,118 @@
+use brvm::bigint::BigInt;
+use brvm::value::{Value, MAX_REPEAT_BYTES};
+use std::rc::Rc;
+
+fn string(s: &str) -> Value {
+    Value::String(Rc::new(s.to_string()))
+}
+
+fn repeat(s: &str, n: f64) -> Result<Value, String> {
+    string(s).mul(&Value::Number(n))
+}
+
+#[test]
+fn string_repeat_works_from_either_side() {
+    assert_eq!(repeat("na", 8.0).unwrap(), string("nananananananana"));
+    assert_eq!(
+        Value::Number(3.0).mul(&string("ha")).unwrap(),
+        string("hahaha")
+    );
+}
+
+#[test]
+fn string_repeat_truncates_count_toward_zero() {
+    assert_eq!(repeat("ab", 2.9).unwrap(), string("abab"));
+    assert_eq!(repeat("ab", 0.5).unwrap(), string(""));
+    assert_eq!(repeat("ab", -0.5).unwrap(), string(""));
+}
+
+#[test]
+fn string_repeat_with_non_positive_count_is_empty() {
+    assert_eq!(repeat("ab", 0.0).unwrap(), string(""));
+    assert_eq!(repeat("ab", -3.0).unwrap(), string(""));
+    assert_eq!(repeat("ab", f64::MIN).unwrap(), string(""));
+}
+
+#[test]
+fn string_repeat_rejects_non_finite_counts() {
+    for n in [f64::INFINITY, f64::NEG_INFINITY, f64::NAN] {
+        let err = repeat("ab", n).unwrap_err();
+        assert!(err.contains("must be finite"), "{}", err);
+    }
+}
+
+#[test]
+fn string_repeat_is_capped() {
+    let at_cap = repeat("x", MAX_REPEAT_BYTES as f64).unwrap();
+    assert!(matches!(at_cap, Value::String(s) if s.len() == MAX_REPEAT_BYTES));
+
+    let err = repeat("x", MAX_REPEAT_BYTES as f64 + 1.0).unwrap_err();
+    assert!(err.contains("exceeds the 10 MB limit"), "{}", err);
+    let err = repeat("abc", 1e300).unwrap_err();
+    assert!(err.contains("exceeds the 10 MB limit"), "{}", err);
+
+    // Nothing to repeat means nothing to cap.
+    assert_eq!(repeat("", 1e300).unwrap(), string(""));
+}
+
+fn big(s: &str) -> Value {
+    Value::Big(Rc::new(BigInt::parse(s).unwrap()))
+}
+
+#[test]
+fn big_integers_stay_exact() {
+    let max_safe = big("9007199254740993");
+    assert_eq!(
+        max_safe.add(&Value::Number(1.0)).unwrap(),
+        big("9007199254740994")
+    );
+    assert_eq!(
+        big("-123456789123456789").mul(&big("1000000001")).unwrap(),
+        big("-123456789246913578123456789")
+    );
+    assert_eq!(
+        big("5").sub(&big("1000000000000")).unwrap(),
+        big("-999999999995")
+    );
+    assert_eq!(big("1000000000").sub(&big("1000000000")).unwrap(), big("0"));
+    assert_eq!(big("0").to_string(), "0");
+    assert_eq!(big("-0").to_string(), "0");
+    assert_eq!(big("000120").to_string(), "120");
+
+    // Division rounds down, like FLOORDIV.
+    let n = big("100000000000000000000000000007");
+    assert_eq!(
+        n.div(&big("1000000000000007")).unwrap(),
+        big("99999999999999")
+    );
+    assert_eq!(big("-7").div(&Value::Number(2.0)).unwrap(), big("-4"));
+    assert_eq!(big("7").div(&big("-7")).unwrap(), big("-1"));
+    assert_eq!(big("7").div(&big("0")).unwrap_err(), "division by zero");
+
+    // A fraction turns the big integer into a number.
+    assert_eq!(
+        big("3").mul(&Value::Number(0.5)).unwrap(),
+        Value::Number(1.5)
+    );
+    assert_eq!(
+        BigInt::from_f64(2f64.powi(70)).unwrap().to_string(),
+        "1180591620717411303424"
+    );
+    assert_eq!(BigInt::from_f64(1.5), None);
+    assert_eq!(BigInt::parse("12a"), None);
+    assert_eq!(BigInt::parse("-"), None);
+}
+
+#[test]
+fn big_integers_compare_and_print_like_numbers() {
+    assert!(big("3").equals(&Value::Number(3.0)));
+    assert!(!big("3").equals(&Value::Number(3.5)));
+    assert!(!big("3").equals(&string("3")));
+    assert_eq!(big("12").add(&string("!")).unwrap(), string("12!"));
+    assert_eq!(big("2").mul(&string("ab")).unwrap(), string("abab"));
+    assert_eq!(big("-5").type_name(), "bigint");
+
+    let err = big("1").add_strict(&string("x")).unwrap_err();
+    assert_eq!(err, "strict mode: cannot add bigint and string");
+    assert_eq!(big("1").add_strict(&Value::Number(2.0)).unwrap(), big("3"));
+}